@@ -0,0 +1,44 @@
+pub mod tables;
+pub use tables::*;
+
+pub mod packing;
+pub use packing::*;
+
+pub mod c;
+pub use c::*;
+
+pub mod rust;
+pub use rust::*;
+
+pub mod summary;
+pub use summary::*;
+
+use super::*;
+use crate::regex::normalizer::NormalizedDfa;
+
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+};
+
+/// Writes the scanner in the target language,
+/// on the standard output if -t/--stdout is set, in the output file otherwise
+/// (`%option outfile`, the target's output file by default).
+pub fn generate(parsing: &Parsing, tables: &Tables, config: &Config) -> io::Result<()> {
+    let file = parsing.definitions.options.outfile.as_deref()
+        .unwrap_or(config.target.output_file());
+
+    let mut out: Box<dyn Write> = if config.stdout {
+        Box::new(BufWriter::new(io::stdout().lock()))
+    } else {
+        Box::new(BufWriter::new(File::create(file)?))
+    };
+
+    match config.target {
+        TargetLanguage::C => CGenerator::new(parsing, tables).generate(&mut out)?,
+
+        TargetLanguage::Rust => RustGenerator::new(parsing, tables).generate(&mut out)?,
+    }
+
+    out.flush()
+}
@@ -0,0 +1,1050 @@
+use std::io::{self, Write};
+
+use super::*;
+use crate::parsing::definitions::{TableSizeDeclaration, TypeDeclaration};
+
+/// Size of yytext with %array, when %o is not declared
+pub const YYLMAX: usize = 8192;
+
+/// Global names of the scanner, renamed by the prefix (`%option prefix`, -P): `yy` is replaced by the prefix
+pub const C_EXPORTED_NAMES: [&str; 8] = ["yylex", "yywrap", "yyin", "yyout", "yyleng", "yytext", "yylineno", "yycolumn"];
+
+/// Declarations shared by the user code and the scanner
+const C_DECLARATIONS: &str = r#"#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+#define ECHO (void) fwrite(yytext, (size_t) yyleng, 1, yyout)
+#define BEGIN yy_start_condition =
+#define YY_START yy_start_condition
+#define yyterminate() return 0
+#define yymore() (yy_more_flag = 1)
+#define yyless(n) yy_less(n)
+
+int yylex(void);
+
+FILE *yyin = NULL;
+FILE *yyout = NULL;
+int yyleng = 0;
+
+static int yy_start_condition = 0;
+"#;
+
+/// Input buffer management
+const C_RUNTIME: &str = r#"#define YY_READ_SIZE 8192
+
+/* Input buffer: [0, yy_buf_pos) is consumed, [yy_buf_pos, yy_buf_len) is pending */
+static char *yy_buf = NULL;
+static size_t yy_buf_size = 0;
+static size_t yy_buf_len = 0;
+static size_t yy_buf_pos = 0;
+
+static int yy_eof = 0;
+static int yy_at_bol = 1;
+
+/* Position of yytext in the buffer, and if the scanner was at the beginning of a line before it */
+static size_t yy_text_pos = 0;
+static int yy_text_bol = 1;
+
+/* yymore(): the next match is appended to yytext */
+static int yy_more_flag = 0;
+static size_t yy_more_len = 0;
+
+/* Character replaced by the NUL terminator of yytext */
+static char yy_hold_char = '\0';
+static size_t yy_hold_pos = 0;
+static int yy_hold_set = 0;
+
+static void yy_fatal_error(const char *message)
+{
+	fprintf(stderr, "%s\n", message);
+	exit(2);
+}
+
+/* Makes room in the buffer for n more characters (and a NUL terminator) */
+static void yy_reserve(size_t n)
+{
+	size_t size;
+	char *buf;
+
+	if (yy_buf_len + n + 1 <= yy_buf_size)
+		return;
+	size = yy_buf_size ? yy_buf_size : YY_READ_SIZE;
+	while (size < yy_buf_len + n + 1)
+		size *= 2;
+	buf = (char *) realloc(yy_buf, size);
+	if (!buf)
+		yy_fatal_error("ft_lex: out of dynamic memory");
+	yy_buf = buf;
+	yy_buf_size = size;
+}
+
+/* Reads the input up to the next newline (a whole block if not YY_INTERACTIVE), returns 0 at the end of the input */
+static int yy_fill(void)
+{
+	size_t n = 0;
+	int c;
+
+	if (yy_eof)
+		return 0;
+	yy_reserve(YY_READ_SIZE);
+	while (n < YY_READ_SIZE && (c = getc(yyin)) != EOF) {
+		yy_buf[yy_buf_len++] = (char) c;
+		n++;
+		if (c == '\n' && YY_INTERACTIVE)
+			break;
+	}
+	if (n == 0)
+		yy_eof = 1;
+	return n > 0;
+}
+
+/* Puts back the character replaced by the NUL terminator of yytext */
+static void yy_restore_hold(void)
+{
+	if (yy_hold_set) {
+		yy_buf[yy_hold_pos] = yy_hold_char;
+		yy_hold_set = 0;
+	}
+}
+
+/* Discards the consumed part of the buffer (but the text kept by yymore()) */
+static void yy_discard(void)
+{
+	size_t n = yy_buf_pos - yy_more_len;
+
+	if (n == 0)
+		return;
+	memmove(yy_buf, yy_buf + n, yy_buf_len - n);
+	yy_buf_len -= n;
+	yy_buf_pos -= n;
+	yy_text_pos = 0;
+}
+"#;
+
+/// %pointer: yytext points to the match in the input buffer
+const C_POINTER_TEXT: &str = r#"/* NUL terminates yytext in the buffer, the replaced character is held if it is not consumed yet */
+static void yy_terminate_text(void)
+{
+	size_t end = yy_text_pos + (size_t) yyleng;
+
+	yytext = yy_buf + yy_text_pos;
+	if (end >= yy_buf_pos) {
+		yy_hold_char = yy_buf[end];
+		yy_hold_pos = end;
+		yy_hold_set = 1;
+	}
+	yy_buf[end] = '\0';
+}
+
+/* yytext is the yyleng characters at yy_text_pos */
+static void yy_set_text(void)
+{
+	yy_terminate_text();
+}
+"#;
+
+/// %array: yytext is a copy of the match, unput() and the reads of the input do not change it
+const C_ARRAY_TEXT: &str = r#"/* The copy in yytext does not depend on the buffer (a macro: unused without input() and unput()) */
+#define yy_terminate_text()
+
+/* yytext is the yyleng characters at yy_text_pos */
+static void yy_set_text(void)
+{
+	if ((size_t) yyleng >= YYLMAX)
+		yy_fatal_error("ft_lex: token too long for yytext, increase its size with %o or use %pointer");
+	memcpy(yytext, yy_buf + yy_text_pos, (size_t) yyleng);
+	yytext[yyleng] = '\0';
+}
+"#;
+
+/// input(): reads the next character of the input (0 at the end of the input)
+const C_INPUT: &str = r#"static int input(void)
+{
+	int c;
+
+	yy_restore_hold();
+	if (yy_buf_pos == yy_buf_len && !yy_fill()) {
+		yy_terminate_text();
+		return 0;
+	}
+	c = (unsigned char) yy_buf[yy_buf_pos++];
+	yy_at_bol = c == '\n';
+	YY_INPUT_POSITION(c);
+	yy_terminate_text();
+	return c;
+}
+"#;
+
+/// unput(c): pushes back a character, read by the next match (in %pointer, yytext may be overwritten)
+const C_UNPUT: &str = r#"static void unput(int c)
+{
+	yy_restore_hold();
+	if (yy_buf_pos == 0) {
+		yy_reserve(1);
+		memmove(yy_buf + 1, yy_buf, yy_buf_len);
+		yy_buf_len++;
+		yy_buf_pos++;
+		yy_text_pos++;
+	}
+	yy_buf[--yy_buf_pos] = (char) c;
+	YY_UNPUT_POSITION(c);
+	yy_terminate_text();
+}
+"#;
+
+/// yyless(n): keeps the first n characters of yytext, the others are read again by the next match
+const C_YYLESS: &str = r#"static void yy_less(int n)
+{
+	yy_restore_hold();
+	yy_buf_pos = yy_text_pos + (size_t) n;
+	yyleng = n;
+	yy_at_bol = n > 0 ? yytext[n - 1] == '\n' : yy_text_bol;
+	yy_set_text();
+	YY_LESS_POSITION(n);
+}
+"#;
+
+/// History of the states of the current match, to back up to the next best match (REJECT)
+const C_REJECT: &str = r#"/* State reached after each length of the current match */
+static int *yy_state_buf = NULL;
+static size_t yy_state_buf_size = 0;
+
+/* Length of the current match, before the removal of its trailing context */
+static size_t yy_full_len = 0;
+
+#define REJECT { yy_restore_hold(); yy_buf_pos = yy_text_pos + yy_more_len; yy_rule = yy_next_match(yy_rule); yy_match_len = yy_full_len; goto yy_find_rule; }
+
+static void yy_save_state(size_t len, int state)
+{
+	size_t size;
+	int *buf;
+
+	if (len >= yy_state_buf_size) {
+		size = yy_state_buf_size ? yy_state_buf_size : YY_READ_SIZE;
+		while (size <= len)
+			size *= 2;
+		buf = (int *) realloc(yy_state_buf, size * sizeof(int));
+		if (!buf)
+			yy_fatal_error("ft_lex: out of dynamic memory");
+		yy_state_buf = buf;
+		yy_state_buf_size = size;
+	}
+	yy_state_buf[len] = state;
+}
+
+/* First rule accepted by the state after the rule (the rules of a state are sorted) */
+static int yy_next_accept(int state, int rule)
+{
+	int i;
+
+	for (i = yy_accept_index[state]; i < yy_accept_index[state + 1]; i++)
+		if (yy_accept_list[i] > rule)
+			return yy_accept_list[i];
+	return 0;
+}
+
+/* Next best match after the rule matching yy_full_len characters:
+   a later rule matching as many characters, then the shorter matches (0 if none is left) */
+static int yy_next_match(int rule)
+{
+	int state, next, eol_next;
+	size_t cp;
+
+	for (; yy_full_len > 0; yy_full_len--, rule = 0) {
+		state = yy_state_buf[yy_full_len];
+		next = yy_next_accept(state, rule);
+
+		/* '$' rules: the next character is a newline */
+		cp = yy_buf_pos + yy_full_len;
+		if (yy_eol[state] >= 0 && cp < yy_buf_len && yy_buf[cp] == '\n') {
+			eol_next = yy_next_accept(yy_eol[state], rule);
+			if (eol_next && (!next || eol_next < next))
+				next = eol_next;
+		}
+
+		if (next)
+			return next;
+	}
+	return 0;
+}
+"#;
+
+/// Transitions of the dense tables
+const C_DENSE_NEXT_STATE: &str = "#define yy_next_state(state, class) yy_nxt[state][class]\n";
+
+/// Transitions of the packed tables (see `PackedTransitions`)
+const C_PACKED_NEXT_STATE: &str = r#"/* Next state of the state on the class, from its row or the row of its default states (-1 if none) */
+static int yy_next_state(int yy_state, int yy_class)
+{
+	while (yy_chk[yy_base[yy_state] + yy_class] != yy_state) {
+		yy_state = yy_def[yy_state];
+		if (yy_state < 0)
+			return -1;
+	}
+	return yy_nxt[yy_base[yy_state] + yy_class];
+}
+"#;
+
+/// Resolution of the trailing contexts where both parts vary
+const C_VARIABLE_HEAD: &str = r#"/* Checks if the n characters of text are accepted from the state */
+static int yy_accepts(int yy_state, const char *text, size_t n)
+{
+	size_t i;
+
+	for (i = 0; i < n; i++) {
+		yy_state = yy_next_state(yy_state, yy_ec[(unsigned char) text[i]]);
+		if (yy_state < 0)
+			return 0;
+	}
+	return yy_accept[yy_state] != 0;
+}
+
+/* Longest head of the match accepted from the state head, followed by a tail accepted from the state tail */
+static size_t yy_variable_head(size_t len, int head, int tail)
+{
+	const char *text = yy_buf + yy_buf_pos;
+	size_t split = len + 1;
+
+	while (split-- > 0)
+		if (yy_accepts(head, text, split) && yy_accepts(tail, text + split, len - split))
+			return split;
+	return len;
+}
+"#;
+
+/// Beginning of yylex(), up to the end of the input
+const C_SCANNER: &str = r#"int yylex(void)
+{
+	int yy_state, yy_next, yy_acc, yy_rule;
+	size_t yy_cp, yy_match_len;
+
+	if (!yyin)
+		yyin = stdin;
+	if (!yyout)
+		yyout = stdout;
+
+	for (;;) {
+		yy_restore_hold();
+		yy_more_len = yy_more_flag ? (size_t) yyleng : 0;
+		yy_more_flag = 0;
+		yy_discard();
+
+		if (yy_buf_pos == yy_buf_len && !yy_fill()) {
+			yy_eof = 0;
+"#;
+
+/// yywrap() at the end of the input (after the `<<EOF>>` rules),
+/// then the longest match over the tables, lowest rule id on ties (up to the state reached by a character)
+const C_SCANNER_WRAP: &str = r#"			if (yywrap())
+				return 0;
+			continue;
+		}
+
+		if (!yy_more_len)
+			yy_text_bol = yy_at_bol;
+		yy_state = yy_start_states[yy_start_condition][yy_at_bol];
+		yy_cp = yy_buf_pos;
+		yy_rule = 0;
+		yy_match_len = 0;
+
+		for (;;) {
+			if (yy_cp == yy_buf_len && !yy_fill())
+				break;
+			yy_next = yy_next_state(yy_state, yy_ec[(unsigned char) yy_buf[yy_cp]]);
+			if (yy_next < 0)
+				break;
+			yy_state = yy_next;
+			yy_cp++;
+			yy_acc = yy_accept[yy_state];
+"#;
+
+/// End of the longest match loop
+const C_SCANNER_LOOP_END: &str = r#"
+			/* '$' rules: the next character is a newline */
+			if (yy_eol[yy_state] >= 0 && (yy_cp < yy_buf_len || yy_fill()) && yy_buf[yy_cp] == '\n') {
+				yy_state = yy_eol[yy_state];
+				if (yy_accept[yy_state] && (!yy_acc || yy_accept[yy_state] < yy_acc))
+					yy_acc = yy_accept[yy_state];
+			}
+
+			if (yy_acc) {
+				yy_rule = yy_acc;
+				yy_match_len = yy_cp - yy_buf_pos;
+			}
+		}
+
+"#;
+
+/// Action of the matched rule (the match is put in yytext)
+const C_SCANNER_MATCH: &str = r#"		/* default rule: the unmatched character is copied to the output */
+		if (!yy_rule)
+			yy_match_len = 1;
+		else
+			yy_match_len = yy_head_length(yy_rule, yy_match_len);
+
+		yy_text_pos = yy_buf_pos - yy_more_len;
+		yyleng = (int) (yy_more_len + yy_match_len);
+		yy_buf_pos += yy_match_len;
+		yy_set_text();
+		if (yyleng > 0)
+			yy_at_bol = yytext[yyleng - 1] == '\n';
+"#;
+
+/// End of the actions, after the default rule (see `write_default_rule`)
+const C_SCANNER_END: &str = r#"			break;
+		}
+	}
+}
+"#;
+
+/// Line counting (`%option yylineno`): yylineno is the line of the input after yytext
+const C_LINENO: &str = r#"/* Line of yytext (for yyless()), and of the current match (for REJECT) */
+static int yy_text_lineno = 1;
+static int yy_match_lineno = 1;
+
+/* Moves the position over the character c */
+static void yy_count_char(int c)
+{
+	if (c == '\n')
+		yylineno++;
+}
+"#;
+
+/// Line and column counting (`%option yycolumn`): yylineno and yycolumn are the position of the input after yytext
+const C_COLUMN: &str = r#"/* Position of yytext (for yyless()), and of the current match (for REJECT) */
+static int yy_text_lineno = 1, yy_text_column = 1;
+static int yy_match_lineno = 1, yy_match_column = 1;
+
+/* Column of the end of the previous line, restored when unput() pushes its newline back */
+static int yy_last_column = 1;
+
+/* Moves the position over the character c (in UTF-8, the continuation bytes are not columns) */
+static void yy_count_char(int c)
+{
+	if (c == '\n') {
+		yylineno++;
+		yy_last_column = yycolumn;
+		yycolumn = 1;
+	} else if (!YY_UTF8 || (c & 0xC0) != 0x80)
+		yycolumn++;
+}
+"#;
+
+/// Counting of a text, with the `yy_count_char` of C_LINENO or C_COLUMN
+const C_COUNT: &str = r#"/* Moves the position over the n characters of text */
+static void yy_count(const char *text, size_t n)
+{
+	size_t i;
+
+	for (i = 0; i < n; i++)
+		yy_count_char((unsigned char) text[i]);
+}
+"#;
+
+/// Stack of the start conditions (`%option stack`)
+const C_STATE_STACK: &str = r#"static int *yy_start_stack = NULL;
+static size_t yy_start_stack_len = 0;
+static size_t yy_start_stack_size = 0;
+"#;
+
+/// yy_push_state(state): enters the start condition, the current one is pushed on the stack
+const C_PUSH_STATE: &str = r#"static void yy_push_state(int new_state)
+{
+	int *stack;
+
+	if (yy_start_stack_len == yy_start_stack_size) {
+		yy_start_stack_size = yy_start_stack_size ? yy_start_stack_size * 2 : 16;
+		stack = (int *) realloc(yy_start_stack, yy_start_stack_size * sizeof(int));
+		if (!stack)
+			yy_fatal_error("ft_lex: out of dynamic memory");
+		yy_start_stack = stack;
+	}
+	yy_start_stack[yy_start_stack_len++] = yy_start_condition;
+	BEGIN new_state;
+}
+"#;
+
+/// yy_pop_state(): enters the start condition on the top of the stack, and pops it
+const C_POP_STATE: &str = r#"static void yy_pop_state(void)
+{
+	if (yy_start_stack_len == 0)
+		yy_fatal_error("ft_lex: start-condition stack underflow");
+	BEGIN yy_start_stack[--yy_start_stack_len];
+}
+"#;
+
+/// yy_top_state(): the start condition on the top of the stack (the current one if the stack is empty)
+const C_TOP_STATE: &str = r#"static int yy_top_state(void)
+{
+	return yy_start_stack_len ? yy_start_stack[yy_start_stack_len - 1] : yy_start_condition;
+}
+"#;
+
+/// Writes a POSIX lex.yy.c scanner
+pub struct CGenerator<'a> {
+	parsing: &'a Parsing,
+	tables: &'a Tables,
+}
+
+impl<'a> CGenerator<'a> {
+	pub fn new(parsing: &'a Parsing, tables: &'a Tables) -> Self {
+		Self { parsing, tables }
+	}
+
+	pub fn generate(&self, out: &mut impl Write) -> io::Result<()> {
+		writeln!(out, "/* A lexical scanner generated by ft_lex */")?;
+		writeln!(out)?;
+
+		self.write_prefix(out)?;
+		out.write_all(C_DECLARATIONS.as_bytes())?;
+		self.write_yywrap(out)?;
+		self.write_yytext(out)?;
+		self.write_yylineno(out)?;
+		writeln!(out)?;
+
+		self.write_start_conditions(out)?;
+		self.write_fragments(out)?;
+		self.write_tables(out)?;
+
+		writeln!(out, "#define YY_INTERACTIVE {}", !self.parsing.definitions.options.never_interactive as u8)?;
+		out.write_all(C_RUNTIME.as_bytes())?;
+		writeln!(out)?;
+
+		match self.is_array() {
+			true => out.write_all(C_ARRAY_TEXT.as_bytes())?,
+			false => out.write_all(C_POINTER_TEXT.as_bytes())?,
+		}
+		writeln!(out)?;
+
+		self.write_position(out)?;
+		self.write_action_primitives(out)?;
+
+		self.write_head_lengths(out)?;
+
+		let reject = Rules::use_reject(&self.parsing.rules);
+
+		if reject {
+			out.write_all(C_REJECT.as_bytes())?;
+			writeln!(out)?;
+		}
+
+		out.write_all(C_SCANNER.as_bytes())?;
+		self.write_end_of_file_actions(out)?;
+		out.write_all(C_SCANNER_WRAP.as_bytes())?;
+
+		if reject {
+			writeln!(out, "\t\t\tyy_save_state(yy_cp - yy_buf_pos, yy_state);")?;
+		}
+
+		out.write_all(C_SCANNER_LOOP_END.as_bytes())?;
+		self.write_position_save(out)?;
+
+		// REJECT goes back here with the next best match
+		if reject {
+			writeln!(out, "\t\tyy_full_len = yy_match_len;")?;
+			writeln!(out, "yy_find_rule:")?;
+		}
+
+		out.write_all(C_SCANNER_MATCH.as_bytes())?;
+		self.write_position_count(out, reject)?;
+		writeln!(out)?;
+		writeln!(out, "\t\tswitch (yy_rule) {{")?;
+		self.write_actions(out)?;
+		self.write_default_rule(out)?;
+		out.write_all(C_SCANNER_END.as_bytes())?;
+
+		self.write_subroutines(out)
+	}
+
+	/// yytext is an array with %array (of %o characters, YYLMAX by default), a pointer otherwise
+	fn is_array(&self) -> bool {
+		self.parsing.definitions.type_declaration == Some(TypeDeclaration::Array)
+	}
+
+	/// With a prefix (`%option prefix`, -P), the global names are renamed (`yylex` becomes `cfglex` for `cfg`),
+	/// so several scanners can be linked together. The code of the scanner still uses the `yy` names.
+	///
+	/// With `%option noyywrap`, yywrap() is a macro of the scanner (see `write_yywrap`), it is not renamed
+	fn write_prefix(&self, out: &mut impl Write) -> io::Result<()> {
+		let options = &self.parsing.definitions.options;
+
+		let Some(prefix) = &options.prefix else {
+			return Ok(());
+		};
+
+		for name in C_EXPORTED_NAMES.into_iter().filter(|name| !(options.noyywrap && *name == "yywrap")) {
+			writeln!(out, "#define {name} {prefix}{}", &name["yy".len()..])?;
+		}
+
+		writeln!(out)
+	}
+
+	/// With `%option noyywrap`, the end of the input ends the scan (yywrap() is not called)
+	fn write_yywrap(&self, out: &mut impl Write) -> io::Result<()> {
+		if !self.parsing.definitions.options.noyywrap {
+			return writeln!(out, "int yywrap(void);");
+		}
+
+		writeln!(out, "#define yywrap() 1")?;
+		writeln!(out, "#define YY_SKIP_YYWRAP")
+	}
+
+	fn write_yytext(&self, out: &mut impl Write) -> io::Result<()> {
+		if !self.is_array() {
+			return writeln!(out, "char *yytext = NULL;");
+		}
+
+		let size = self.parsing.definitions.table_sizes
+			.get(&TableSizeDeclaration::OutputArraySize)
+			.copied()
+			.unwrap_or(YYLMAX);
+
+		writeln!(out, "#define YYLMAX {size}")?;
+		writeln!(out, "char yytext[YYLMAX];")
+	}
+
+	/// With `%option yylineno` (or `%option yycolumn`), the position of the input after yytext
+	fn write_yylineno(&self, out: &mut impl Write) -> io::Result<()> {
+		let options = &self.parsing.definitions.options;
+
+		if options.yylineno {
+			writeln!(out, "int yylineno = 1;")?;
+		}
+
+		if options.yycolumn {
+			writeln!(out, "int yycolumn = 1;")?;
+		}
+
+		Ok(())
+	}
+
+	/// The counting of the position, and its updates by input(), unput() (the character pushed back is counted again)
+	/// and yyless() (from the position of yytext). The macros are empty without `%option yylineno`
+	fn write_position(&self, out: &mut impl Write) -> io::Result<()> {
+		let definitions = &self.parsing.definitions;
+		let options = &definitions.options;
+
+		if !options.yylineno {
+			writeln!(out, "#define YY_INPUT_POSITION(c)")?;
+			writeln!(out, "#define YY_UNPUT_POSITION(c)")?;
+			writeln!(out, "#define YY_LESS_POSITION(n)")?;
+			return writeln!(out);
+		}
+
+		if options.yycolumn {
+			writeln!(out, "#define YY_UTF8 {}", definitions.utf8 as u8)?;
+			out.write_all(C_COLUMN.as_bytes())?;
+			writeln!(out)?;
+
+			writeln!(out, "#define YY_INPUT_POSITION(c) yy_count_char(c)")?;
+			writeln!(out, "#define YY_UNPUT_POSITION(c) {{ if ((c) == '\\n') {{ yylineno--; yycolumn = yy_last_column; }} else if (!YY_UTF8 || ((c) & 0xC0) != 0x80) yycolumn--; }}")?;
+			writeln!(out, "#define YY_LESS_POSITION(n) {{ yylineno = yy_text_lineno; yycolumn = yy_text_column; yy_count(yytext, (size_t) (n)); }}")?;
+		} else {
+			out.write_all(C_LINENO.as_bytes())?;
+			writeln!(out)?;
+
+			writeln!(out, "#define YY_INPUT_POSITION(c) yy_count_char(c)")?;
+			writeln!(out, "#define YY_UNPUT_POSITION(c) {{ if ((c) == '\\n') yylineno--; }}")?;
+			writeln!(out, "#define YY_LESS_POSITION(n) {{ yylineno = yy_text_lineno; yy_count(yytext, (size_t) (n)); }}")?;
+		}
+		writeln!(out)?;
+
+		out.write_all(C_COUNT.as_bytes())?;
+		writeln!(out)?;
+
+		if self.counts_every_match() {
+			return Ok(());
+		}
+
+		// the default rule (0) can match a newline
+		let can_match_eol = std::iter::once(true)
+			.chain(self.parsing.rules.iter().map(Rule::can_match_newline))
+			.map(|can_match| can_match as i64);
+
+		write_c_array(out, "char", "yy_rule_can_match_eol", can_match_eol.collect::<Vec<i64>>().into_iter())
+	}
+
+	/// In UTF-8 mode, the columns count the characters of each match (not their bytes)
+	fn counts_every_match(&self) -> bool {
+		let definitions = &self.parsing.definitions;
+
+		definitions.options.yycolumn && definitions.utf8
+	}
+
+	/// The position of yytext (kept by yymore()) and of the match, before it is counted
+	fn write_position_save(&self, out: &mut impl Write) -> io::Result<()> {
+		let options = &self.parsing.definitions.options;
+
+		if !options.yylineno {
+			return Ok(());
+		}
+
+		writeln!(out, "\t\tyy_match_lineno = yylineno;")?;
+		if options.yycolumn {
+			writeln!(out, "\t\tyy_match_column = yycolumn;")?;
+		}
+
+		writeln!(out, "\t\tif (!yy_more_len) {{")?;
+		writeln!(out, "\t\t\tyy_text_lineno = yylineno;")?;
+		if options.yycolumn {
+			writeln!(out, "\t\t\tyy_text_column = yycolumn;")?;
+		}
+		writeln!(out, "\t\t}}")
+	}
+
+	/// Counts the lines of the match, only for the rules able to match a newline (see `Rule::can_match_newline`).
+	///
+	/// The columns of the other rules are their length, unless the input is UTF-8 (its characters are then counted).
+	/// REJECT counts the next match from the start of the rejected one
+	fn write_position_count(&self, out: &mut impl Write, reject: bool) -> io::Result<()> {
+		let definitions = &self.parsing.definitions;
+
+		if !definitions.options.yylineno {
+			return Ok(());
+		}
+
+		if reject {
+			writeln!(out, "\t\tyylineno = yy_match_lineno;")?;
+			if definitions.options.yycolumn {
+				writeln!(out, "\t\tyycolumn = yy_match_column;")?;
+			}
+		}
+
+		if self.counts_every_match() {
+			return writeln!(out, "\t\tyy_count(yytext + yy_more_len, yy_match_len);");
+		}
+
+		writeln!(out, "\t\tif (yy_rule_can_match_eol[yy_rule])")?;
+		writeln!(out, "\t\t\tyy_count(yytext + yy_more_len, yy_match_len);")?;
+
+		if definitions.options.yycolumn {
+			writeln!(out, "\t\telse")?;
+			writeln!(out, "\t\t\tyycolumn += (int) yy_match_len;")?;
+		}
+
+		Ok(())
+	}
+
+	fn write_start_conditions(&self, out: &mut impl Write) -> io::Result<()> {
+		for (index, name) in self.parsing.definitions.start_conditions().iter().enumerate() {
+			writeln!(out, "#define {name} {index}")?;
+		}
+
+		writeln!(out)
+	}
+
+	/// Program fragments of the definitions section (`%{ %}` blocks and indented lines)
+	fn write_fragments(&self, out: &mut impl Write) -> io::Result<()> {
+		for fragment in &self.parsing.definitions.fragments {
+			writeln!(out, "{fragment}")?;
+		}
+
+		writeln!(out)
+	}
+
+	fn write_tables(&self, out: &mut impl Write) -> io::Result<()> {
+		let tables = self.tables;
+		let type_ = c_integer_type(tables.max_value());
+
+		writeln!(out, "#define YY_NUM_RULES {}", self.parsing.rules.len())?;
+		writeln!(out, "#define YY_NUM_STATES {}", tables.len())?;
+		writeln!(out, "#define YY_NUM_CONDITIONS {}", tables.starts.len())?;
+		writeln!(out, "#define YY_NUM_CLASSES {}", tables.character_classes())?;
+		writeln!(out)?;
+
+		// indexed by [start condition][at beginning of line]
+		writeln!(out, "static const {type_} yy_start_states[YY_NUM_CONDITIONS][2] = {{")?;
+		for (start, bol_start) in tables.starts.iter().zip(&tables.bol_starts) {
+			writeln!(out, "\t{{ {start}, {bol_start} }},")?;
+		}
+		writeln!(out, "}};")?;
+		writeln!(out)?;
+
+		let accept = tables.accept.iter().map(|rule| *rule as i64);
+		write_c_array(out, type_, "yy_accept", accept)?;
+
+		let end_of_line = tables.end_of_line.iter().map(|next| option_to_c(*next));
+		write_c_array(out, type_, "yy_eol", end_of_line)?;
+
+		// equivalence class of each input byte
+		let ec = tables.ec.iter().map(|class| *class as i64);
+		write_c_array(out, type_, "yy_ec", ec)?;
+
+		match &tables.packed {
+			Some(packed) => Self::write_packed_transitions(out, type_, packed)?,
+			None => Self::write_dense_transitions(out, type_, tables)?,
+		}
+
+		if Rules::use_reject(&self.parsing.rules) {
+			self.write_accept_lists(out)?;
+		}
+
+		Ok(())
+	}
+
+	fn write_dense_transitions(out: &mut impl Write, type_: &str, tables: &Tables) -> io::Result<()> {
+		writeln!(out, "static const {type_} yy_nxt[{}][YY_NUM_CLASSES] = {{", tables.len().max(1))?;
+		for row in &tables.next {
+			write!(out, "\t{{")?;
+			write_c_values(out, row.iter().map(|next| option_to_c(*next)), "\t\t")?;
+			writeln!(out, "\t}},")?;
+		}
+		writeln!(out, "}};")?;
+		writeln!(out)?;
+
+		out.write_all(C_DENSE_NEXT_STATE.as_bytes())?;
+		writeln!(out)
+	}
+
+	fn write_packed_transitions(out: &mut impl Write, type_: &str, packed: &PackedTransitions) -> io::Result<()> {
+		write_c_array(out, type_, "yy_base", packed.base.iter().map(|base| *base as i64))?;
+		write_c_array(out, type_, "yy_def", packed.def.iter().map(|def| option_to_c(*def)))?;
+		write_c_array(out, type_, "yy_nxt", packed.next.iter().map(|next| option_to_c(*next)))?;
+		write_c_array(out, type_, "yy_chk", packed.check.iter().map(|owner| option_to_c(*owner)))?;
+
+		out.write_all(C_PACKED_NEXT_STATE.as_bytes())?;
+		writeln!(out)
+	}
+
+	/// All the rules accepted by each state: yy_accept_list[yy_accept_index[state]..yy_accept_index[state + 1]]
+	fn write_accept_lists(&self, out: &mut impl Write) -> io::Result<()> {
+		let index = self.tables.accepts_index();
+		let type_ = c_integer_type(index.last().copied().unwrap_or(0).max(self.tables.max_value()));
+
+		write_c_array(out, type_, "yy_accept_index", index.iter().map(|index| *index as i64))?;
+
+		let list = self.tables.accepts.iter().flatten().map(|rule| *rule as i64);
+		write_c_array(out, type_, "yy_accept_list", list.collect::<Vec<i64>>().into_iter())
+	}
+
+	/// input(), unput() and yyless(), only if the user code uses them (the unused static functions are warned about),
+	/// and the functions of the start condition stack with `%option stack`
+	fn write_action_primitives(&self, out: &mut impl Write) -> io::Result<()> {
+		let primitives = [("input", C_INPUT), ("unput", C_UNPUT), ("yyless", C_YYLESS)];
+
+		for (name, primitive) in primitives {
+			if self.user_code_names(name) {
+				out.write_all(primitive.as_bytes())?;
+				writeln!(out)?;
+			}
+		}
+
+		if !self.parsing.definitions.options.stack {
+			return Ok(());
+		}
+
+		let stack_primitives: Vec<&str> = [("yy_push_state", C_PUSH_STATE), ("yy_pop_state", C_POP_STATE), ("yy_top_state", C_TOP_STATE)]
+			.into_iter()
+			.filter(|(name, _)| self.user_code_names(name))
+			.map(|(_, primitive)| primitive)
+			.collect();
+
+		if stack_primitives.is_empty() {
+			return Ok(());
+		}
+
+		out.write_all(C_STATE_STACK.as_bytes())?;
+		writeln!(out)?;
+
+		for primitive in stack_primitives {
+			out.write_all(primitive.as_bytes())?;
+			writeln!(out)?;
+		}
+
+		Ok(())
+	}
+
+	/// Checks if the identifier is in the fragments, the actions or the user subroutines
+	fn user_code_names(&self, identifier: &str) -> bool {
+		let parsing = self.parsing;
+
+		let actions = parsing.rules.iter()
+			.map(|rule| &rule.action)
+			.chain(parsing.end_of_file_rules.iter().map(|rule| &rule.action))
+			.filter_map(|action| match action {
+				RuleAction::Statement(action) => Some(action),
+				RuleAction::Or => None,
+			});
+
+		parsing.definitions.fragments.iter()
+			.chain(actions)
+			.chain(&parsing.user_subroutines)
+			.any(|code| Utils::contains_identifier(code, identifier))
+	}
+
+	/// Length of the consumed part of the rules with trailing context (`r/s`)
+	fn write_head_lengths(&self, out: &mut impl Write) -> io::Result<()> {
+		let head_lengths = &self.tables.head_lengths;
+
+		let variable = head_lengths.iter()
+			.any(|(_, head_length)| matches!(head_length, HeadLength::Variable { .. }));
+
+		if variable {
+			out.write_all(C_VARIABLE_HEAD.as_bytes())?;
+			writeln!(out)?;
+		}
+
+		writeln!(out, "static size_t yy_head_length(int rule, size_t len)")?;
+		writeln!(out, "{{")?;
+		writeln!(out, "\tswitch (rule) {{")?;
+
+		for (rule, head_length) in head_lengths {
+			writeln!(out, "\tcase {rule}:")?;
+
+			match head_length {
+				HeadLength::Fixed(length) => writeln!(out, "\t\treturn {length};")?,
+
+				HeadLength::WithoutTail(length) => writeln!(out, "\t\treturn len - {length};")?,
+
+				HeadLength::Variable { head, tail } => {
+					writeln!(out, "\t\treturn yy_variable_head(len, {head}, {tail});")?
+				},
+			}
+		}
+
+		writeln!(out, "\tdefault:")?;
+		writeln!(out, "\t\treturn len;")?;
+		writeln!(out, "\t}}")?;
+		writeln!(out, "}}")?;
+		writeln!(out)
+	}
+
+	/// One `case` per rule, the `|` actions fall through to the next rule
+	fn write_actions(&self, out: &mut impl Write) -> io::Result<()> {
+		let rules = &self.parsing.rules;
+
+		for (index, rule) in rules.iter().enumerate() {
+			writeln!(out, "\t\tcase {}:", index + 1)?;
+
+			match &rule.action {
+				RuleAction::Or if index + 1 < rules.len() => continue,
+
+				RuleAction::Or => {},
+
+				RuleAction::Statement(action) => {
+					writeln!(out, "\t\t\t{}", action.trim())?;
+				},
+			}
+
+			writeln!(out, "\t\t\tbreak;")?;
+		}
+
+		Ok(())
+	}
+
+	/// The unmatched character is copied to the output, or stops the scanner with `%option nodefault`
+	fn write_default_rule(&self, out: &mut impl Write) -> io::Result<()> {
+		writeln!(out, "\t\tdefault:")?;
+
+		match self.parsing.definitions.options.nodefault {
+			true => writeln!(out, "\t\t\tyy_fatal_error(\"ft_lex scanner jammed\");"),
+			false => writeln!(out, "\t\t\tECHO;"),
+		}
+	}
+
+	/// One `case` per start condition with a `<<EOF>>` rule, yytext is empty
+	fn write_end_of_file_actions(&self, out: &mut impl Write) -> io::Result<()> {
+		let rules = &self.parsing.end_of_file_rules;
+
+		if rules.is_empty() {
+			return Ok(());
+		}
+
+		let definitions = &self.parsing.definitions;
+		let conditions = definitions.start_conditions();
+		let by_condition = EndOfFileRule::by_condition(rules, definitions);
+
+		writeln!(out, "\t\t\tyy_text_pos = yy_buf_pos;")?;
+		writeln!(out, "\t\t\tyyleng = 0;")?;
+		writeln!(out, "\t\t\tyy_set_text();")?;
+		writeln!(out, "\t\t\tswitch (yy_start_condition) {{")?;
+
+		for (index, rule) in rules.iter().enumerate() {
+			let used_in = by_condition.iter()
+				.zip(&conditions)
+				.filter(|(used, _)| **used == Some(index));
+
+			for (_, condition) in used_in {
+				writeln!(out, "\t\t\tcase {condition}:")?;
+			}
+
+			match &rule.action {
+				RuleAction::Or if index + 1 < rules.len() => continue,
+
+				RuleAction::Or => {},
+
+				RuleAction::Statement(action) => {
+					writeln!(out, "\t\t\t\t{}", action.trim())?;
+				},
+			}
+
+			writeln!(out, "\t\t\t\tbreak;")?;
+		}
+
+		writeln!(out, "\t\t\t}}")
+	}
+
+	fn write_subroutines(&self, out: &mut impl Write) -> io::Result<()> {
+		if let Some(subroutines) = &self.parsing.user_subroutines {
+			writeln!(out)?;
+			out.write_all(subroutines.as_bytes())?;
+
+			if !subroutines.ends_with('\n') {
+				writeln!(out)?;
+			}
+		}
+
+		Ok(())
+	}
+}
+
+/// Smallest C integer type able to store the values of the tables (and -1)
+pub fn c_integer_type(max_value: usize) -> &'static str {
+	if max_value < i16::MAX as usize {
+		"short"
+	} else {
+		"int"
+	}
+}
+
+fn option_to_c(value: Option<usize>) -> i64 {
+	value.map(|value| value as i64).unwrap_or(-1)
+}
+
+pub fn write_c_array(
+	out: &mut impl Write,
+	type_: &str,
+	name: &str,
+	values: impl ExactSizeIterator<Item = i64>,
+) -> io::Result<()> {
+	// an empty array is not valid C
+	let len = values.len().max(1);
+
+	writeln!(out, "static const {type_} {name}[{len}] = {{")?;
+	write_c_values(out, values, "\t")?;
+	writeln!(out, "}};")?;
+	writeln!(out)
+}
+
+/// Writes comma separated values, 16 per line
+fn write_c_values(out: &mut impl Write, values: impl Iterator<Item = i64>, indent: &str) -> io::Result<()> {
+	let values: Vec<i64> = values.collect();
+
+	if values.is_empty() {
+		return writeln!(out, "{indent}0");
+	}
+
+	writeln!(out)?;
+
+	for line in values.chunks(16) {
+		let line = line.iter()
+			.map(|value| value.to_string())
+			.collect::<Vec<String>>()
+			.join(", ");
+
+		writeln!(out, "{indent}{line},")?;
+	}
+
+	Ok(())
+}
@@ -0,0 +1,161 @@
+/// Number of previous states tried as the default state of a state
+/// (the most recent ones, as the similar states are often built together)
+pub const DEFAULT_CANDIDATES: usize = 64;
+
+/// Comb-vector compression of a transition table (the base/def/next/check scheme of lex).
+///
+/// The rows of the states are overlaid in the same `next`/`check` vectors:
+/// the transition of `state` on `class` is `next[base[state] + class]` if `check[base[state] + class]` is `state`,
+/// the transition of its default state `def[state]` otherwise.
+///
+/// A state only stores the transitions that differ from its default state
+/// (a missing transition is stored as `None` if the default state has one).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackedTransitions {
+	/// First index of the row of each state in `next` and `check`
+	pub base: Vec<usize>,
+
+	/// State whose transitions are used when the row of a state has no entry
+	pub def: Vec<Option<usize>>,
+
+	/// Transitions of all the rows
+	pub next: Vec<Option<usize>>,
+
+	/// Owner of each entry of `next` (None for the unused entries)
+	pub check: Vec<Option<usize>>,
+}
+
+impl PackedTransitions {
+	/// Packs the rows of `next` (the transitions of each state for each class)
+	pub fn new(next: &[Vec<Option<usize>>]) -> Self {
+		let classes = next.first().map_or(0, Vec::len);
+
+		let mut packed = Self {
+			base: Vec::with_capacity(next.len()),
+			def: Vec::with_capacity(next.len()),
+			next: vec![None; classes],
+			check: vec![None; classes],
+		};
+
+		// first index where an entry may be free
+		let mut first_free = 0;
+
+		for (state, row) in next.iter().enumerate() {
+			let (def, entries) = Self::entries(next, state, row);
+
+			let base = packed.find_base(&entries, first_free);
+
+			// the whole row must be readable, even where it has no entry
+			let end = base + classes;
+			if packed.check.len() < end {
+				packed.next.resize(end, None);
+				packed.check.resize(end, None);
+			}
+
+			for (class, next) in &entries {
+				packed.next[base + class] = *next;
+				packed.check[base + class] = Some(state);
+			}
+
+			while first_free < packed.check.len() && packed.check[first_free].is_some() {
+				first_free += 1;
+			}
+
+			packed.base.push(base);
+			packed.def.push(def);
+		}
+
+		packed
+	}
+
+	/// Default state of the state `state` and the (class, next) entries of its row.
+	///
+	/// The default state is the previous state with the fewest different transitions,
+	/// only if it saves some entries
+	fn entries(next: &[Vec<Option<usize>>], state: usize, row: &[Option<usize>]) -> (Option<usize>, Vec<(usize, Option<usize>)>) {
+		let own: Vec<(usize, Option<usize>)> = row.iter()
+			.enumerate()
+			.filter(|(_, next)| next.is_some())
+			.map(|(class, next)| (class, *next))
+			.collect();
+
+		let differences = |other: &[Option<usize>]| {
+			row.iter().zip(other).filter(|(next, other)| next != other).count()
+		};
+
+		let best = (state.saturating_sub(DEFAULT_CANDIDATES)..state)
+			.rev()
+			.map(|candidate| (differences(&next[candidate]), candidate))
+			.min_by_key(|(differences, _)| *differences);
+
+		match best {
+			Some((differences, def)) if differences < own.len() => {
+				let entries = row.iter()
+					.zip(&next[def])
+					.enumerate()
+					.filter(|(_, (next, other))| next != other)
+					.map(|(class, (next, _))| (class, *next))
+					.collect();
+
+				(Some(def), entries)
+			},
+
+			_ => (None, own),
+		}
+	}
+
+	/// First base, from `first_free`, where all the entries are unused
+	fn find_base(&self, entries: &[(usize, Option<usize>)], first_free: usize) -> usize {
+		let Some((first_class, _)) = entries.first() else {
+			return 0;
+		};
+
+		// the first entry goes in a free index
+		let mut index = first_free.max(*first_class);
+
+		loop {
+			let base = index - first_class;
+
+			let fits = entries.iter()
+				.all(|(class, _)| self.check.get(base + class).is_none_or(Option::is_none));
+
+			if fits {
+				return base;
+			}
+
+			index += 1;
+		}
+	}
+
+	/// Transition of the state `state` on the class `class`, following the default states
+	pub fn next_state(&self, mut state: usize, class: usize) -> Option<usize> {
+		loop {
+			let index = self.base[state] + class;
+
+			if self.check[index] == Some(state) {
+				return self.next[index];
+			}
+
+			state = self.def[state]?;
+		}
+	}
+
+	/// Number of entries of `next` and `check`
+	pub fn len(&self) -> usize {
+		self.next.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.next.is_empty()
+	}
+
+	/// Number of transitions stored in the rows (the others come from the default states)
+	pub fn entries_count(&self) -> usize {
+		self.check.iter().filter(|owner| owner.is_some()).count()
+	}
+
+	/// Number of entries of the generated arrays (base, def, next and check)
+	pub fn output_size(&self) -> usize {
+		self.base.len() + self.def.len() + self.next.len() + self.check.len()
+	}
+}
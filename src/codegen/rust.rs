@@ -0,0 +1,859 @@
+use std::{
+	borrow::Cow,
+	io::{self, Write},
+	mem::take,
+};
+
+use super::*;
+
+/// Declarations shared by the user code and the scanner
+const RUST_DECLARATIONS: &str = r#"#![allow(dead_code, unused_variables, unused_mut, unreachable_code, unreachable_patterns, redundant_semicolons, non_upper_case_globals)]
+
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::ops::Range;
+"#;
+
+/// Input buffer management, same as the C runtime
+const RUST_RUNTIME: &str = r#"/// Scanner of the rules, reading its input from `R`
+pub struct Lexer<R: Read> {
+	input: BufReader<R>,
+
+	/// Output of ECHO and of the default rule
+	pub out: Box<dyn Write>,
+
+	/// Input buffer: [0, pos) is consumed, [pos, len) is pending
+	buf: Vec<u8>,
+	pos: usize,
+
+	/// Position of the last token in the buffer (yytext)
+	text: Range<usize>,
+
+	eof: bool,
+	at_bol: bool,
+	start_condition: usize,
+
+	/// Stack of the start conditions (`%option stack`)
+	start_stack: Vec<usize>,
+
+	/// Position of the input after yytext, from 1 (`%option yylineno`, and `%option yycolumn` for the column)
+	pub lineno: usize,
+	pub column: usize,
+}
+
+impl<R: Read> Lexer<R> {
+	/// Scanner writing its output (ECHO) on the standard output
+	pub fn new(input: R) -> Self {
+		Self::with_output(input, Box::new(io::stdout()))
+	}
+
+	pub fn with_output(input: R, out: Box<dyn Write>) -> Self {
+		Self {
+			input: BufReader::new(input),
+			out,
+			buf: Vec::new(),
+			pos: 0,
+			text: 0..0,
+			eof: false,
+			at_bol: true,
+			start_condition: INITIAL,
+			start_stack: Vec::new(),
+			lineno: 1,
+			column: 1,
+		}
+	}
+
+	/// Enters a start condition (BEGIN)
+	pub fn begin(&mut self, condition: usize) {
+		self.start_condition = condition;
+	}
+
+	/// Current start condition (YY_START)
+	pub fn start_condition(&self) -> usize {
+		self.start_condition
+	}
+
+	/// Copies the last token to the output (ECHO)
+	pub fn echo(&mut self) {
+		let text = &self.buf[self.text.clone()];
+
+		if self.out.write_all(text).is_err() {
+			panic!("ft_lex: cannot write the output");
+		}
+	}
+
+	/// Reads the input up to the next newline (a whole block if not YY_INTERACTIVE), returns false at the end of the input
+	fn fill(&mut self) -> bool {
+		if self.eof {
+			return false;
+		}
+
+		let read = match YY_INTERACTIVE {
+			true => self.input.read_until(b'\n', &mut self.buf),
+
+			false => {
+				let len = self.buf.len();
+				self.buf.resize(len + YY_READ_SIZE, 0);
+
+				let read = self.input.read(&mut self.buf[len..]);
+				self.buf.truncate(len + read.as_ref().map_or(0, |read| *read));
+
+				read
+			},
+		};
+
+		match read {
+			Ok(0) => {
+				self.eof = true;
+				false
+			},
+			Ok(_) => true,
+			Err(err) => panic!("ft_lex: cannot read the input: {}", err),
+		}
+	}
+
+	/// Discards the consumed part of the buffer
+	fn discard(&mut self) {
+		self.buf.drain(..self.pos);
+		self.pos = 0;
+		self.text = 0..0;
+	}
+"#;
+
+/// Stack of the start conditions (`%option stack`)
+const RUST_STATE_STACK: &str = r#"	/// Enters a start condition, the current one is pushed on the stack (yy_push_state)
+	pub fn push_state(&mut self, condition: usize) {
+		self.start_stack.push(self.start_condition);
+		self.start_condition = condition;
+	}
+
+	/// Enters the start condition on the top of the stack, and pops it (yy_pop_state)
+	pub fn pop_state(&mut self) {
+		match self.start_stack.pop() {
+			Some(condition) => self.start_condition = condition,
+			None => panic!("ft_lex: start-condition stack underflow"),
+		}
+	}
+
+	/// Start condition on the top of the stack, the current one if the stack is empty (yy_top_state)
+	pub fn top_state(&self) -> usize {
+		self.start_stack.last().copied().unwrap_or(self.start_condition)
+	}
+"#;
+
+/// Back up to the next best match, with the history of the states of the current match (REJECT)
+const RUST_REJECT: &str = r#"	/// First rule accepted by the state after the rule (the rules of a state are sorted)
+	fn next_accept(state: usize, rule: usize) -> usize {
+		let accepted = YY_ACCEPT_INDEX[state] as usize..YY_ACCEPT_INDEX[state + 1] as usize;
+
+		YY_ACCEPT_LIST[accepted].iter()
+			.map(|accepted| *accepted as usize)
+			.find(|accepted| *accepted > rule)
+			.unwrap_or(0)
+	}
+
+	/// Next best match after the rule matching len characters:
+	/// a later rule matching as many characters, then the shorter matches (0 if none is left)
+	fn next_match(&self, states: &[usize], len: &mut usize, mut rule: usize) -> usize {
+		while *len > 0 {
+			let state = states[*len];
+			let mut next = Self::next_accept(state, rule);
+
+			// '$' rules: the next character is a newline
+			let cp = self.pos + *len;
+			if YY_EOL[state] >= 0 && cp < self.buf.len() && self.buf[cp] == b'\n' {
+				let eol_next = Self::next_accept(YY_EOL[state] as usize, rule);
+				if eol_next != 0 && (next == 0 || eol_next < next) {
+					next = eol_next;
+				}
+			}
+
+			if next != 0 {
+				return next;
+			}
+
+			*len -= 1;
+			rule = 0;
+		}
+
+		0
+	}
+"#;
+
+/// REJECT!() runs the action of the next best match
+const RUST_REJECT_LOOP: &str = r#"			let mut full_len = match_len;
+
+			'yy_find_rule: loop {
+				macro_rules! REJECT {
+					() => {{
+						self.pos = self.text.start;
+						rule = self.next_match(&states, &mut full_len, rule);
+						match_len = full_len;
+						continue 'yy_find_rule;
+					}};
+				}
+
+"#;
+
+/// Line and column counting (`%option yylineno`), same as the C scanner
+const RUST_POSITION: &str = r#"	/// Moves the position over the bytes of the buffer (in UTF-8, the continuation bytes are not columns)
+	fn count(&mut self, bytes: Range<usize>) {
+		for index in bytes {
+			if self.buf[index] == b'\n' {
+				self.lineno += 1;
+				self.column = 1;
+			} else if YY_COLUMNS && (!YY_UTF8 || self.buf[index] & 0xC0 != 0x80) {
+				self.column += 1;
+			}
+		}
+	}
+"#;
+
+/// Transitions of the dense tables
+const RUST_DENSE_NEXT_STATE: &str = r#"/// Next state of the state on the class (-1 if none)
+#[inline]
+fn yy_next_state(state: usize, class: usize) -> isize {
+	YY_NXT[state][class] as isize
+}
+"#;
+
+/// Transitions of the packed tables (see `PackedTransitions`)
+const RUST_PACKED_NEXT_STATE: &str = r#"/// Next state of the state on the class, from its row or the row of its default states (-1 if none)
+fn yy_next_state(mut state: usize, class: usize) -> isize {
+	while YY_CHK[YY_BASE[state] as usize + class] as isize != state as isize {
+		if YY_DEF[state] < 0 {
+			return -1;
+		}
+		state = YY_DEF[state] as usize;
+	}
+
+	YY_NXT[YY_BASE[state] as usize + class] as isize
+}
+"#;
+
+/// Resolution of the trailing contexts where both parts vary
+const RUST_VARIABLE_HEAD: &str = r#"	/// Checks if the text is accepted from the state
+	fn accepts(mut state: usize, text: &[u8]) -> bool {
+		for c in text {
+			let next = yy_next_state(state, YY_EC[*c as usize] as usize);
+
+			if next < 0 {
+				return false;
+			}
+			state = next as usize;
+		}
+
+		YY_ACCEPT[state] != 0
+	}
+
+	/// Longest head of the match accepted from the state head, followed by a tail accepted from the state tail
+	fn variable_head(&self, len: usize, head: usize, tail: usize) -> usize {
+		let text = &self.buf[self.pos..self.pos + len];
+
+		(0..=len).rev()
+			.find(|split| Self::accepts(head, &text[..*split]) && Self::accepts(tail, &text[*split..]))
+			.unwrap_or(len)
+	}
+"#;
+
+/// Beginning of next_token(), up to the end of the input
+const RUST_SCANNER: &str = r#"	/// Runs the actions of the rules until one of them returns, None at the end of the input
+	pub fn next_token(&mut self) -> Option<Token> {
+		loop {
+			self.discard();
+
+			if self.pos == self.buf.len() && !self.fill() {
+				// the input can be read again on the next call
+				self.eof = false;
+"#;
+
+/// None at the end of the input (after the `<<EOF>>` rules),
+/// then the start of the longest match over the tables
+const RUST_SCANNER_WRAP: &str = r#"				return None;
+			}
+
+			let mut state = YY_START_STATES[self.start_condition][self.at_bol as usize] as usize;
+			let mut cp = self.pos;
+			let mut rule = 0;
+			let mut match_len = 0;
+"#;
+
+/// Longest match loop (lowest rule id on ties), up to the state reached by a character
+const RUST_SCANNER_LOOP: &str = r#"
+			loop {
+				if cp == self.buf.len() && !self.fill() {
+					break;
+				}
+
+				let next = yy_next_state(state, YY_EC[self.buf[cp] as usize] as usize);
+				if next < 0 {
+					break;
+				}
+				state = next as usize;
+				cp += 1;
+
+				let mut acc = YY_ACCEPT[state];
+"#;
+
+/// End of the longest match loop
+const RUST_SCANNER_LOOP_END: &str = r#"
+				// '$' rules: the next character is a newline
+				if YY_EOL[state] >= 0 && (cp < self.buf.len() || self.fill()) && self.buf[cp] == b'\n' {
+					state = YY_EOL[state] as usize;
+					if YY_ACCEPT[state] != 0 && (acc == 0 || YY_ACCEPT[state] < acc) {
+						acc = YY_ACCEPT[state];
+					}
+				}
+
+				if acc != 0 {
+					rule = acc as usize;
+					match_len = cp - self.pos;
+				}
+			}
+
+"#;
+
+/// Action of the matched rule (the match is put in yytext)
+const RUST_SCANNER_MATCH: &str = r#"			// default rule: the unmatched character is copied to the output
+			if rule == 0 {
+				match_len = 1;
+			} else {
+				match_len = self.head_length(rule, match_len);
+			}
+
+			self.text = self.pos..self.pos + match_len;
+			self.pos += match_len;
+			if match_len > 0 {
+				self.at_bol = self.buf[self.pos - 1] == b'\n';
+			}
+
+			let yytext = String::from_utf8_lossy(&self.buf[self.text.clone()]).into_owned();
+			let yytext: &str = &yytext;
+			let yyleng: usize = match_len;
+"#;
+
+
+const RUST_SCANNER_END: &str = r#"		}
+	}
+}
+"#;
+
+/// Writes a Rust module with a `Lexer` struct, same semantics as the C scanner
+pub struct RustGenerator<'a> {
+	parsing: &'a Parsing,
+	tables: &'a Tables,
+}
+
+impl<'a> RustGenerator<'a> {
+	pub fn new(parsing: &'a Parsing, tables: &'a Tables) -> Self {
+		Self { parsing, tables }
+	}
+
+	pub fn generate(&self, out: &mut impl Write) -> io::Result<()> {
+		writeln!(out, "// A lexical scanner generated by ft_lex")?;
+		writeln!(out)?;
+
+		self.write_template(out, RUST_DECLARATIONS)?;
+		writeln!(out)?;
+
+		self.write_start_conditions(out)?;
+		self.write_fragments(out)?;
+		self.write_tables(out)?;
+
+		writeln!(out, "const {}: usize = 8192;", self.item("YY_READ_SIZE"))?;
+		writeln!(out, "const {}: bool = {};", self.item("YY_INTERACTIVE"), !self.parsing.definitions.options.never_interactive)?;
+		self.write_position_constants(out)?;
+		writeln!(out)?;
+
+		self.write_template(out, RUST_RUNTIME)?;
+		writeln!(out)?;
+
+		if self.parsing.definitions.options.stack {
+			self.write_template(out, RUST_STATE_STACK)?;
+			writeln!(out)?;
+		}
+
+		if self.parsing.definitions.options.yylineno {
+			self.write_template(out, RUST_POSITION)?;
+			writeln!(out)?;
+		}
+
+		self.write_head_lengths(out)?;
+
+		let reject = Rules::use_reject(&self.parsing.rules);
+
+		if reject {
+			self.write_template(out, RUST_REJECT)?;
+			writeln!(out)?;
+		}
+
+		self.write_template(out, RUST_SCANNER)?;
+		self.write_end_of_file_actions(out)?;
+		self.write_template(out, RUST_SCANNER_WRAP)?;
+
+		if reject {
+			writeln!(out, "\t\t\tlet mut states = vec![state];")?;
+		}
+
+		self.write_template(out, RUST_SCANNER_LOOP)?;
+
+		if reject {
+			writeln!(out, "\t\t\t\tstates.push(state);")?;
+		}
+
+		self.write_template(out, RUST_SCANNER_LOOP_END)?;
+
+		// REJECT!() continues the loop with the next best match
+		if reject {
+			// REJECT counts the next match from the start of the rejected one
+			if self.parsing.definitions.options.yylineno {
+				writeln!(out, "\t\t\tlet position = (self.lineno, self.column);")?;
+			}
+
+			self.write_template(out, RUST_REJECT_LOOP)?;
+
+			write_indented(out, &self.renamed(RUST_SCANNER_MATCH), "\t")?;
+			self.write_position_count(out, "\t", reject)?;
+			self.write_actions(out, "\t")?;
+			self.write_default_rule(out, "\t")?;
+
+			writeln!(out)?;
+			writeln!(out, "\t\t\t\tbreak;")?;
+			writeln!(out, "\t\t\t}}")?;
+		} else {
+			self.write_template(out, RUST_SCANNER_MATCH)?;
+			self.write_position_count(out, "", reject)?;
+			self.write_actions(out, "")?;
+			self.write_default_rule(out, "")?;
+		}
+
+		self.write_template(out, RUST_SCANNER_END)?;
+
+		self.write_subroutines(out)
+	}
+
+	/// Name of an item of the scanner, renamed by the prefix (`%option prefix`, -P) so several scanners
+	/// can be included in the same module: for `cfg`, `Lexer` and `Token` become `CfgLexer` and `CfgToken`,
+	/// `YY_ACCEPT` becomes `CFG_ACCEPT`, `yy_next_state` becomes `cfg_next_state`, and `INITIAL` becomes `CFG_INITIAL`
+	fn item(&self, name: &str) -> String {
+		let Some(prefix) = &self.parsing.definitions.options.prefix else {
+			return name.to_string();
+		};
+
+		match name {
+			"Lexer" | "Token" => format!("{}{name}", camel_case(prefix)),
+
+			"INITIAL" => self.condition(name),
+
+			name if name.starts_with("YY_") => format!("{}{}", prefix.to_uppercase(), &name["YY".len()..]),
+
+			name if name.starts_with("yy_") => format!("{prefix}{}", &name["yy".len()..]),
+
+			name => name.to_string(),
+		}
+	}
+
+	/// Name of the constant of a start condition (`CFG_NAME` for the prefix `cfg`, see `item`)
+	fn condition(&self, name: &str) -> String {
+		match &self.parsing.definitions.options.prefix {
+			Some(prefix) => format!("{}_{name}", prefix.to_uppercase()),
+			None => name.to_string(),
+		}
+	}
+
+	/// The code `template`, its items renamed (see `item`)
+	fn renamed<'t>(&self, template: &'t str) -> Cow<'t, str> {
+		if self.parsing.definitions.options.prefix.is_none() {
+			return Cow::Borrowed(template);
+		}
+
+		let mut renamed = String::with_capacity(template.len());
+		let mut identifier = String::new();
+
+		for c in template.chars() {
+			if c.is_ascii_alphanumeric() || c == '_' {
+				identifier.push(c);
+				continue;
+			}
+
+			renamed.push_str(&self.item(&take(&mut identifier)));
+			renamed.push(c);
+		}
+
+		renamed.push_str(&self.item(&identifier));
+
+		Cow::Owned(renamed)
+	}
+
+	fn write_template(&self, out: &mut impl Write, template: &str) -> io::Result<()> {
+		out.write_all(self.renamed(template).as_bytes())
+	}
+
+	fn write_start_conditions(&self, out: &mut impl Write) -> io::Result<()> {
+		for (index, name) in self.parsing.definitions.start_conditions().iter().enumerate() {
+			writeln!(out, "pub const {}: usize = {index};", self.condition(name))?;
+		}
+
+		writeln!(out)
+	}
+
+	/// Program fragments of the definitions section (`%{ %}` blocks and indented lines).
+	///
+	/// `Token` is an `i32` (as the result of yylex) unless the user code names it (see `item` for its name)
+	fn write_fragments(&self, out: &mut impl Write) -> io::Result<()> {
+		for fragment in &self.parsing.definitions.fragments {
+			writeln!(out, "{fragment}")?;
+		}
+
+		let token = self.item("Token");
+
+		if !self.user_code_names(&token) {
+			writeln!(out, "pub type {token} = i32;")?;
+		}
+
+		writeln!(out)
+	}
+
+	/// Checks if the identifier is in the fragments or the user subroutines
+	fn user_code_names(&self, identifier: &str) -> bool {
+		let fragments = self.parsing.definitions.fragments.iter();
+
+		fragments.chain(&self.parsing.user_subroutines).any(|code| Utils::contains_identifier(code, identifier))
+	}
+
+	fn write_tables(&self, out: &mut impl Write) -> io::Result<()> {
+		let tables = self.tables;
+		let type_ = rust_integer_type(tables.max_value());
+
+		writeln!(out, "const {}: usize = {};", self.item("YY_NUM_RULES"), self.parsing.rules.len())?;
+		writeln!(out, "const {}: usize = {};", self.item("YY_NUM_STATES"), tables.len())?;
+		writeln!(out, "const {}: usize = {};", self.item("YY_NUM_CONDITIONS"), tables.starts.len())?;
+		writeln!(out, "const {}: usize = {};", self.item("YY_NUM_CLASSES"), tables.character_classes())?;
+		writeln!(out)?;
+
+		// indexed by [start condition][at beginning of line]
+		writeln!(out, "{}", self.renamed(&format!("static YY_START_STATES: [[{type_}; 2]; YY_NUM_CONDITIONS] = [")))?;
+		for (start, bol_start) in tables.starts.iter().zip(&tables.bol_starts) {
+			writeln!(out, "\t[{start}, {bol_start}],")?;
+		}
+		writeln!(out, "];")?;
+		writeln!(out)?;
+
+		let accept = tables.accept.iter().map(|rule| *rule as i64);
+		write_rust_array(out, type_, &self.item("YY_ACCEPT"), accept)?;
+
+		let end_of_line = tables.end_of_line.iter().map(|next| option_to_rust(*next));
+		write_rust_array(out, type_, &self.item("YY_EOL"), end_of_line)?;
+
+		// equivalence class of each input byte
+		let ec = tables.ec.iter().map(|class| *class as i64);
+		write_rust_array(out, type_, &self.item("YY_EC"), ec)?;
+
+		match &tables.packed {
+			Some(packed) => self.write_packed_transitions(out, type_, packed)?,
+			None => self.write_dense_transitions(out, type_, tables)?,
+		}
+
+		if Rules::use_reject(&self.parsing.rules) {
+			self.write_accept_lists(out)?;
+		}
+
+		Ok(())
+	}
+
+	fn write_dense_transitions(&self, out: &mut impl Write, type_: &str, tables: &Tables) -> io::Result<()> {
+		writeln!(out, "{}", self.renamed(&format!("static YY_NXT: [[{type_}; YY_NUM_CLASSES]; YY_NUM_STATES] = [")))?;
+		for row in &tables.next {
+			write!(out, "\t[")?;
+			write_rust_values(out, row.iter().map(|next| option_to_rust(*next)), "\t\t")?;
+			writeln!(out, "\t],")?;
+		}
+		writeln!(out, "];")?;
+		writeln!(out)?;
+
+		self.write_template(out, RUST_DENSE_NEXT_STATE)?;
+		writeln!(out)
+	}
+
+	fn write_packed_transitions(&self, out: &mut impl Write, type_: &str, packed: &PackedTransitions) -> io::Result<()> {
+		write_rust_array(out, type_, &self.item("YY_BASE"), packed.base.iter().map(|base| *base as i64))?;
+		write_rust_array(out, type_, &self.item("YY_DEF"), packed.def.iter().map(|def| option_to_rust(*def)))?;
+		write_rust_array(out, type_, &self.item("YY_NXT"), packed.next.iter().map(|next| option_to_rust(*next)))?;
+		write_rust_array(out, type_, &self.item("YY_CHK"), packed.check.iter().map(|owner| option_to_rust(*owner)))?;
+
+		self.write_template(out, RUST_PACKED_NEXT_STATE)?;
+		writeln!(out)
+	}
+
+	/// All the rules accepted by each state: YY_ACCEPT_LIST[YY_ACCEPT_INDEX[state]..YY_ACCEPT_INDEX[state + 1]]
+	fn write_accept_lists(&self, out: &mut impl Write) -> io::Result<()> {
+		let index = self.tables.accepts_index();
+		let type_ = rust_integer_type(index.last().copied().unwrap_or(0).max(self.tables.max_value()));
+
+		write_rust_array(out, type_, &self.item("YY_ACCEPT_INDEX"), index.iter().map(|index| *index as i64))?;
+
+		let list = self.tables.accepts.iter().flatten().map(|rule| *rule as i64);
+		write_rust_array(out, type_, &self.item("YY_ACCEPT_LIST"), list.collect::<Vec<i64>>().into_iter())
+	}
+
+	/// Length of the consumed part of the rules with trailing context (`r/s`)
+	fn write_head_lengths(&self, out: &mut impl Write) -> io::Result<()> {
+		let head_lengths = &self.tables.head_lengths;
+
+		let variable = head_lengths.iter()
+			.any(|(_, head_length)| matches!(head_length, HeadLength::Variable { .. }));
+
+		if variable {
+			self.write_template(out, RUST_VARIABLE_HEAD)?;
+			writeln!(out)?;
+		}
+
+		writeln!(out, "\tfn head_length(&self, rule: usize, len: usize) -> usize {{")?;
+		writeln!(out, "\t\tmatch rule {{")?;
+
+		for (rule, head_length) in head_lengths {
+			match head_length {
+				HeadLength::Fixed(length) => writeln!(out, "\t\t\t{rule} => {length},")?,
+
+				HeadLength::WithoutTail(length) => writeln!(out, "\t\t\t{rule} => len - {length},")?,
+
+				HeadLength::Variable { head, tail } => {
+					writeln!(out, "\t\t\t{rule} => self.variable_head(len, {head}, {tail}),")?
+				},
+			}
+		}
+
+		writeln!(out, "\t\t\t_ => len,")?;
+		writeln!(out, "\t\t}}")?;
+		writeln!(out, "\t}}")?;
+		writeln!(out)
+	}
+
+	/// The counting options, and the rules able to match a newline (see `Rule::can_match_newline`)
+	fn write_position_constants(&self, out: &mut impl Write) -> io::Result<()> {
+		let definitions = &self.parsing.definitions;
+
+		if !definitions.options.yylineno {
+			return Ok(());
+		}
+
+		writeln!(out, "const {}: bool = {};", self.item("YY_COLUMNS"), definitions.options.yycolumn)?;
+		writeln!(out, "const {}: bool = {};", self.item("YY_UTF8"), definitions.utf8)?;
+		writeln!(out)?;
+
+		// the default rule (0) can match a newline
+		let can_match_eol = std::iter::once(true)
+			.chain(self.parsing.rules.iter().map(Rule::can_match_newline))
+			.map(|can_match| can_match as i64);
+
+		write_rust_array(out, "u8", &self.item("YY_RULE_CAN_MATCH_EOL"), can_match_eol.collect::<Vec<i64>>().into_iter())
+	}
+
+	/// Counts the lines of the match, only for the rules able to match a newline
+	/// (the columns of the other rules are their length, unless the input is UTF-8), then starts the match of the rules
+	fn write_position_count(&self, out: &mut impl Write, indent: &str, reject: bool) -> io::Result<()> {
+		let options = &self.parsing.definitions.options;
+
+		if options.yylineno {
+			writeln!(out)?;
+
+			if reject {
+				writeln!(out, "{indent}\t\t\t(self.lineno, self.column) = position;")?;
+			}
+
+			let can_match_eol = self.item("YY_RULE_CAN_MATCH_EOL");
+
+			match (options.yycolumn, self.parsing.definitions.utf8) {
+				(true, true) => writeln!(out, "{indent}\t\t\tself.count(self.text.clone());")?,
+
+				(true, false) => {
+					writeln!(out, "{indent}\t\t\tif {can_match_eol}[rule] != 0 {{")?;
+					writeln!(out, "{indent}\t\t\t\tself.count(self.text.clone());")?;
+					writeln!(out, "{indent}\t\t\t}} else {{")?;
+					writeln!(out, "{indent}\t\t\t\tself.column += match_len;")?;
+					writeln!(out, "{indent}\t\t\t}}")?;
+				},
+
+				(false, _) => {
+					writeln!(out, "{indent}\t\t\tif {can_match_eol}[rule] != 0 {{")?;
+					writeln!(out, "{indent}\t\t\t\tself.count(self.text.clone());")?;
+					writeln!(out, "{indent}\t\t\t}}")?;
+				},
+			}
+		}
+
+		writeln!(out)?;
+		writeln!(out, "{indent}\t\t\tmatch rule {{")
+	}
+
+	/// One match arm per action, the `|` rules share the arm of the next rule
+	fn write_actions(&self, out: &mut impl Write, indent: &str) -> io::Result<()> {
+		let mut ids: Vec<String> = vec![];
+
+		for (index, rule) in self.parsing.rules.iter().enumerate() {
+			ids.push((index + 1).to_string());
+
+			match &rule.action {
+				RuleAction::Or if index + 1 < self.parsing.rules.len() => continue,
+
+				RuleAction::Or => writeln!(out, "{indent}\t\t\t\t{} => {{}},", ids.join(" | "))?,
+
+				RuleAction::Statement(action) => {
+					writeln!(out, "{indent}\t\t\t\t{} => {{", ids.join(" | "))?;
+					writeln!(out, "{indent}\t\t\t\t\t{}", action.trim())?;
+					writeln!(out, "{indent}\t\t\t\t}},")?;
+				},
+			}
+
+			ids.clear();
+		}
+
+		Ok(())
+	}
+
+	/// The unmatched character is copied to the output, or stops the scanner with `%option nodefault`
+	fn write_default_rule(&self, out: &mut impl Write, indent: &str) -> io::Result<()> {
+		match self.parsing.definitions.options.nodefault {
+			true => writeln!(out, "{indent}\t\t\t\t_ => panic!(\"ft_lex scanner jammed\"),")?,
+			false => writeln!(out, "{indent}\t\t\t\t_ => self.echo(),")?,
+		}
+
+		writeln!(out, "{indent}\t\t\t}}")
+	}
+
+	/// One match arm per `<<EOF>>` action, on the start conditions using it (yytext is empty)
+	fn write_end_of_file_actions(&self, out: &mut impl Write) -> io::Result<()> {
+		let rules = &self.parsing.end_of_file_rules;
+
+		if rules.is_empty() {
+			return Ok(());
+		}
+
+		let definitions = &self.parsing.definitions;
+		let conditions = definitions.start_conditions();
+		let by_condition = EndOfFileRule::by_condition(rules, definitions);
+
+		writeln!(out, "\t\t\t\tself.text = self.pos..self.pos;")?;
+		writeln!(out, "\t\t\t\tlet yytext: &str = \"\";")?;
+		writeln!(out, "\t\t\t\tlet yyleng: usize = 0;")?;
+		writeln!(out)?;
+		writeln!(out, "\t\t\t\tmatch self.start_condition {{")?;
+
+		// the `|` rules share the arm of the next rule
+		let mut used_in: Vec<&String> = vec![];
+
+		for (index, rule) in rules.iter().enumerate() {
+			used_in.extend(by_condition.iter()
+				.zip(&conditions)
+				.filter(|(used, _)| **used == Some(index))
+				.map(|(_, condition)| *condition));
+
+			let action = match &rule.action {
+				RuleAction::Or if index + 1 < rules.len() => continue,
+
+				RuleAction::Or => "",
+
+				RuleAction::Statement(action) => action.trim(),
+			};
+
+			if !used_in.is_empty() {
+				let patterns: Vec<String> = used_in.iter().map(|condition| self.condition(condition)).collect();
+
+				writeln!(out, "\t\t\t\t\t{} => {{", patterns.join(" | "))?;
+				writeln!(out, "\t\t\t\t\t\t{action}")?;
+				writeln!(out, "\t\t\t\t\t}},")?;
+			}
+
+			used_in.clear();
+		}
+
+		writeln!(out, "\t\t\t\t\t_ => {{}},")?;
+		writeln!(out, "\t\t\t\t}}")?;
+		writeln!(out)
+	}
+
+	fn write_subroutines(&self, out: &mut impl Write) -> io::Result<()> {
+		if let Some(subroutines) = &self.parsing.user_subroutines {
+			writeln!(out)?;
+			out.write_all(subroutines.as_bytes())?;
+
+			if !subroutines.ends_with('\n') {
+				writeln!(out)?;
+			}
+		}
+
+		Ok(())
+	}
+}
+
+/// `CalcParser` for `calc_parser`
+fn camel_case(name: &str) -> String {
+	name.split('_')
+		.map(|word| {
+			let mut chars = word.chars();
+
+			match chars.next() {
+				Some(first) => first.to_uppercase().chain(chars).collect(),
+				None => String::new(),
+			}
+		})
+		.collect()
+}
+
+/// Smallest Rust integer type able to store the values of the tables (and -1)
+pub fn rust_integer_type(max_value: usize) -> &'static str {
+	if max_value < i16::MAX as usize {
+		"i16"
+	} else {
+		"i32"
+	}
+}
+
+fn option_to_rust(value: Option<usize>) -> i64 {
+	value.map(|value| value as i64).unwrap_or(-1)
+}
+
+pub fn write_rust_array(
+	out: &mut impl Write,
+	type_: &str,
+	name: &str,
+	values: impl ExactSizeIterator<Item = i64>,
+) -> io::Result<()> {
+	writeln!(out, "static {name}: [{type_}; {}] = [", values.len())?;
+	write_rust_values(out, values, "\t")?;
+	writeln!(out, "];")?;
+	writeln!(out)
+}
+
+/// Writes the lines of the text with one more level of indentation
+fn write_indented(out: &mut impl Write, text: &str, indent: &str) -> io::Result<()> {
+	for line in text.lines() {
+		match line.is_empty() {
+			true => writeln!(out)?,
+			false => writeln!(out, "{indent}{line}")?,
+		}
+	}
+
+	Ok(())
+}
+
+/// Writes comma separated values, 16 per line
+fn write_rust_values(out: &mut impl Write, values: impl Iterator<Item = i64>, indent: &str) -> io::Result<()> {
+	let values: Vec<i64> = values.collect();
+
+	if values.is_empty() {
+		return Ok(());
+	}
+
+	writeln!(out)?;
+
+	for line in values.chunks(16) {
+		let line = line.iter()
+			.map(|value| value.to_string())
+			.collect::<Vec<String>>()
+			.join(", ");
+
+		writeln!(out, "{indent}{line},")?;
+	}
+
+	Ok(())
+}
@@ -0,0 +1,135 @@
+use std::io::{self, Write};
+
+use super::*;
+use crate::parsing::definitions::TableSizeDeclaration;
+
+/// Statistics of the generated scanner (-v), checked against the table sizes of the definitions
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Summary {
+	/// NFA states reading a character (%p)
+	pub positions: usize,
+
+	/// All the NFA states, one per node of the parse trees of the rules (%e)
+	pub parse_tree_nodes: usize,
+
+	/// DFA states (%n)
+	pub states: usize,
+
+	/// DFA states before the minimization
+	pub unminimized_states: usize,
+
+	/// Transitions between the DFA states (%a),
+	/// only the ones stored in the rows of the packed tables (not from a default state) if `packed` is set
+	pub transitions: usize,
+
+	/// Groups of characters with the same transitions (%k)
+	pub character_classes: usize,
+
+	/// Entries of the generated arrays (%o is the size of yytext, not of the tables)
+	pub output_size: usize,
+
+	/// The transitions are packed (see `Tables::pack`)
+	pub packed: bool,
+}
+
+impl Summary {
+	pub fn new(rules: &[Rule], tables: &Tables) -> Self {
+		let nfas: Vec<&Nfa> = rules.iter()
+			.flat_map(|rule| {
+				let trailing_context = rule.trailing_context.iter()
+					.flat_map(|trailing_context| [&trailing_context.head, &trailing_context.tail]);
+
+				[&rule.regex_nfa].into_iter().chain(trailing_context)
+			})
+			.collect();
+
+		let (positions, parse_tree_nodes) = count_nfa_states(&nfas);
+
+		let transitions = match &tables.packed {
+			Some(packed) => packed.entries_count(),
+			None => tables.transitions(),
+		};
+
+		Self {
+			positions,
+			parse_tree_nodes,
+			states: tables.len(),
+			unminimized_states: tables.unminimized_len,
+			transitions,
+			character_classes: tables.character_classes(),
+			output_size: tables.output_size(),
+			packed: tables.packed.is_some(),
+		}
+	}
+
+	/// Statistics and their table size declaration
+	fn entries(&self) -> [(TableSizeDeclaration, usize, &'static str); 5] {
+		let transitions = if self.packed { "packed transitions" } else { "transitions" };
+
+		[
+			(TableSizeDeclaration::Positions, self.positions, "positions"),
+			(TableSizeDeclaration::ParseTreeNodes, self.parse_tree_nodes, "parse tree nodes"),
+			(TableSizeDeclaration::States, self.states, "states"),
+			(TableSizeDeclaration::Transitions, self.transitions, transitions),
+			(TableSizeDeclaration::PackedCharacterClass, self.character_classes, "packed character classes"),
+		]
+	}
+
+	/// Returns an error for each declared table size exceeded by the scanner
+	pub fn check(&self, definitions: &Definitions) -> Result<(), Vec<ParsingError>> {
+		let errors: Vec<ParsingError> = self.entries()
+			.into_iter()
+			.filter_map(|(declaration, used, _)| {
+				let limit = *definitions.table_sizes.get(&declaration)?;
+
+				(used > limit).then(|| ParsingError::table_size_exceeded(declaration, limit, used))
+			})
+			.collect();
+
+		if errors.is_empty() {
+			Ok(())
+		} else {
+			Err(errors)
+		}
+	}
+
+	/// Writes the report, with the declared table sizes (`used/declared`)
+	pub fn write(&self, definitions: &Definitions, out: &mut impl Write) -> io::Result<()> {
+		writeln!(out, "ft_lex summary:")?;
+
+		for (declaration, used, name) in self.entries() {
+			match definitions.table_sizes.get(&declaration) {
+				Some(limit) => write!(out, "  {used}/{limit} {name} ({})", declaration.to_string())?,
+				None => write!(out, "  {used} {name} ({})", declaration.to_string())?,
+			}
+
+			if declaration == TableSizeDeclaration::States {
+				write!(out, ", {} before minimization", self.unminimized_states)?;
+			}
+
+			writeln!(out)?;
+		}
+
+		writeln!(out, "  {} table entries", self.output_size)
+	}
+}
+
+/// Counts the (positions, all states) of the NFAs
+fn count_nfa_states(nfas: &[&Nfa]) -> (usize, usize) {
+	let mut positions = 0;
+	let mut nodes = 0;
+
+	for state in nfas.iter().flat_map(|nfa| &nfa.states) {
+		match state {
+			State::Basic(_) => positions += 1,
+
+			State::NoMatch => continue,
+
+			_ => {},
+		}
+
+		nodes += 1;
+	}
+
+	(positions, nodes)
+}
@@ -0,0 +1,260 @@
+use std::collections::HashMap;
+
+use super::*;
+
+/// Number of input bytes (entries of the equivalence class table)
+pub const ALPHABET_SIZE: usize = 256;
+
+/// Dense transition tables of the scanner automaton, shared by all the target languages.
+///
+/// The tables implement the same semantics as `normalizer::simulate`:
+/// - the `^` transitions are resolved at compile time into the `bol_starts`
+/// - the `$` transitions are taken when the next input char is a newline
+/// - at the same length, the lowest rule id wins
+#[derive(Debug)]
+pub struct Tables {
+	/// Start state of each start condition, when the scanner is not at the beginning of a line
+	pub starts: Vec<usize>,
+
+	/// Start state of each start condition, when the scanner is at the beginning of a line
+	/// (the `^` rules are active)
+	pub bol_starts: Vec<usize>,
+
+	/// Rule accepted by each state (0 if the state is not accepting)
+	pub accept: Vec<usize>,
+
+	/// All the rules accepted by each state, in the order of the rules (for REJECT)
+	pub accepts: Vec<Vec<usize>>,
+
+	/// Equivalence class of each input byte: the bytes of a class have the same transitions in all the states
+	pub ec: Vec<usize>,
+
+	/// Next state of each state, for each equivalence class of the input bytes (see `ec`)
+	pub next: Vec<Vec<Option<usize>>>,
+
+	/// Comb-vector compression of `next` (see `Tables::pack`), generated instead of `next` if set
+	pub packed: Option<PackedTransitions>,
+
+	/// Next state of each state, when the next input char is a newline (`$` rules)
+	pub end_of_line: Vec<Option<usize>>,
+
+	/// Length of the consumed part of the rules with trailing context (`r/s`), by rule id
+	pub head_lengths: Vec<(usize, HeadLength)>,
+
+	/// Number of states of the automaton before its minimization
+	pub unminimized_len: usize,
+}
+
+/// How the scanner finds the length of `r`, once `rs` is matched
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeadLength {
+	/// `r` has a fixed length
+	Fixed(usize),
+
+	/// `s` has a fixed length, removed from the end of the match
+	WithoutTail(usize),
+
+	/// Both vary: the longest head accepted from the state `head`,
+	/// followed by a tail accepted from the state `tail`
+	Variable { head: usize, tail: usize },
+}
+
+impl Tables {
+	/// Builds the minimized automaton of the rules and its tables,
+	/// with the start conditions in the order of `Definitions::start_conditions`
+	pub fn new(rules: &[Rule], definitions: &Definitions) -> Self {
+		Self::from_rules_dfa(Dfa::from_rules(rules, definitions), rules, definitions)
+	}
+
+	/// Same as `new`, with an error if the automaton needs more than `limit` states (see `Dfa::try_from_rules`)
+	pub fn with_state_limit(rules: &[Rule], definitions: &Definitions, limit: usize) -> ParsingResult<Self> {
+		let dfa = Dfa::try_from_rules(rules, definitions, limit)?;
+
+		Ok(Self::from_rules_dfa(dfa, rules, definitions))
+	}
+
+	fn from_rules_dfa(mut dfa: Dfa, rules: &[Rule], definitions: &Definitions) -> Self {
+		let dfa = NormalizedDfa::from(&mut dfa);
+
+		let mut tables = Self::from(&dfa.minimize());
+		tables.unminimized_len = dfa.states.len();
+
+		// the starts of the variable trailing contexts follow the starts of the conditions
+		let conditions = definitions.start_conditions().len();
+		let mut extra_starts = tables.starts.split_off(conditions).into_iter();
+		tables.bol_starts.truncate(conditions);
+
+		for (index, rule) in rules.iter().enumerate() {
+			let Some(trailing_context) = &rule.trailing_context else {
+				continue
+			};
+
+			let head_length = match (trailing_context.head_length, trailing_context.tail_length) {
+				(Some(length), _) => HeadLength::Fixed(length),
+
+				(None, Some(length)) => HeadLength::WithoutTail(length),
+
+				(None, None) => {
+					let head = extra_starts.next().expect("missing trailing context start");
+					let tail = extra_starts.next().expect("missing trailing context start");
+
+					HeadLength::Variable { head, tail }
+				},
+			};
+
+			tables.head_lengths.push((index + 1, head_length));
+		}
+
+		tables
+	}
+
+	pub fn from(dfa: &NormalizedDfa) -> Self {
+		let len = dfa.states.len();
+
+		let mut accept = vec![0; len];
+		let mut accepts = vec![vec![]; len];
+		let mut bytes = vec![vec![None; ALPHABET_SIZE]; len];
+		let mut end_of_line = vec![None; len];
+		let mut start_of_line = vec![None; len];
+
+		for (id, state) in &dfa.states {
+			let mut matchs: Vec<usize> = state.matchs.iter().copied().collect();
+			matchs.sort();
+
+			accept[*id] = matchs.first().copied().unwrap_or(0);
+			accepts[*id] = matchs;
+
+			for (condition, next_id) in &state.next {
+				match condition {
+					InputCondition::Char(c) => Self::set_next(&mut bytes[*id], *c, *next_id),
+					InputCondition::Class(class) => {
+						let classes = dfa.classes.as_ref().expect("class transition without classes");
+
+						for c in classes.chars(*class) {
+							Self::set_next(&mut bytes[*id], *c, *next_id);
+						}
+					},
+					InputCondition::StartOfLine => start_of_line[*id] = Some(*next_id),
+					InputCondition::EndOfLine => end_of_line[*id] = Some(*next_id),
+				}
+			}
+		}
+
+		let (ec, next) = Self::compress(&bytes);
+
+		let starts = dfa.starts.clone();
+
+		let bol_starts = starts.iter()
+			.map(|start| start_of_line[*start].unwrap_or(*start))
+			.collect();
+
+		Self { starts, bol_starts, accept, accepts, ec, next, packed: None, end_of_line, head_lengths: vec![], unminimized_len: len }
+	}
+
+	fn set_next(row: &mut [Option<usize>], c: char, next: usize) {
+		// the scanner reads bytes
+		if (c as u32) < ALPHABET_SIZE as u32 {
+			row[c as usize] = Some(next);
+		}
+	}
+
+	/// Merges the bytes with the same transitions in all the states (the columns of `bytes`)
+	/// into equivalence classes, numbered in the order of their first byte.
+	///
+	/// Returns the class of each byte and the transitions of each state for each class
+	fn compress(bytes: &[Vec<Option<usize>>]) -> (Vec<usize>, Vec<Vec<Option<usize>>>) {
+		let mut classes: HashMap<Vec<Option<usize>>, usize> = HashMap::new();
+		let mut ec = Vec::with_capacity(ALPHABET_SIZE);
+		let mut next = vec![vec![]; bytes.len()];
+
+		for byte in 0..ALPHABET_SIZE {
+			let column: Vec<Option<usize>> = bytes.iter().map(|row| row[byte]).collect();
+			let len = classes.len();
+
+			let class = *classes.entry(column).or_insert_with(|| {
+				for (row, next) in bytes.iter().zip(next.iter_mut()) {
+					next.push(row[byte]);
+				}
+
+				len
+			});
+
+			ec.push(class);
+		}
+
+		(ec, next)
+	}
+
+	pub fn len(&self) -> usize {
+		self.accept.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.accept.is_empty()
+	}
+
+	/// Compresses the transitions (smaller tables, slower scanner)
+	pub fn pack(&mut self) {
+		self.packed = Some(PackedTransitions::new(&self.next));
+	}
+
+	/// Next state of the state `state` on the input byte `byte`
+	pub fn next_state(&self, state: usize, byte: u8) -> Option<usize> {
+		let class = self.ec[byte as usize];
+
+		match &self.packed {
+			Some(packed) => packed.next_state(state, class),
+			None => self.next[state][class],
+		}
+	}
+
+	/// Number of transitions between the states (including the `$` transitions)
+	pub fn transitions(&self) -> usize {
+		let next = self.next.iter()
+			.map(|row| {
+				self.ec.iter()
+					.filter(|class| row[**class].is_some())
+					.count()
+			})
+			.sum::<usize>();
+
+		next + self.end_of_line.iter().filter(|next| next.is_some()).count()
+	}
+
+	/// Number of groups of input bytes with the same transitions in all the states
+	pub fn character_classes(&self) -> usize {
+		self.ec.iter().max().map_or(0, |class| class + 1)
+	}
+
+	/// Number of entries of the generated arrays
+	pub fn output_size(&self) -> usize {
+		let start_states = self.starts.len() * 2;
+
+		let next = match &self.packed {
+			Some(packed) => packed.output_size(),
+			None => self.next.len() * self.character_classes(),
+		};
+
+		start_states + self.accept.len() + self.end_of_line.len() + ALPHABET_SIZE + next
+	}
+
+	/// First index of the rules of each state in the flattened `accepts` (and the end of the last state)
+	pub fn accepts_index(&self) -> Vec<usize> {
+		let mut index = vec![0];
+
+		for accepts in &self.accepts {
+			index.push(index.last().unwrap() + accepts.len());
+		}
+
+		index
+	}
+
+	/// Biggest value stored in the tables (used to choose the integer type of the arrays)
+	pub fn max_value(&self) -> usize {
+		let max_rule = self.accept.iter().max().copied().unwrap_or(0);
+
+		let packed = self.packed.as_ref().map_or(0, PackedTransitions::len);
+
+		max_rule.max(self.len()).max(self.character_classes()).max(packed)
+	}
+}
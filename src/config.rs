@@ -5,6 +5,7 @@ use std::{env, error::Error};
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum TargetLanguage {
     C,
+    Rust,
 }
 
 impl Default for TargetLanguage {
@@ -13,6 +14,51 @@ impl Default for TargetLanguage {
     }
 }
 
+impl TryFrom<&str> for TargetLanguage {
+    type Error = String;
+
+    fn try_from(name: &str) -> Result<Self, Self::Error> {
+        match name {
+            "c" => Ok(TargetLanguage::C),
+            "rust" => Ok(TargetLanguage::Rust),
+            _ => Err(format!("Invalid target language: {name}")),
+        }
+    }
+}
+
+impl TargetLanguage {
+    /// Name of the generated file (when -t/--stdout is not set)
+    pub fn output_file(&self) -> &'static str {
+        match self {
+            TargetLanguage::C => "lex.yy.c",
+            TargetLanguage::Rust => "lex.yy.rs",
+        }
+    }
+}
+
+/// Layout of the transition table of the generated scanner
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TableFormat {
+    /// One row of next states per state (fast)
+    #[default]
+    Dense,
+
+    /// Rows overlaid in comb vectors, with default states (small)
+    Packed,
+}
+
+impl TryFrom<&str> for TableFormat {
+    type Error = String;
+
+    fn try_from(name: &str) -> Result<Self, Self::Error> {
+        match name {
+            "dense" => Ok(TableFormat::Dense),
+            "packed" => Ok(TableFormat::Packed),
+            _ => Err(format!("Invalid table format: {name}")),
+        }
+    }
+}
+
 #[derive(Debug, Default)]
 pub struct Config {
     /// input files
@@ -29,9 +75,78 @@ pub struct Config {
     /// -n
     /// Suppress the summary of statistics usually written with the -v option. If no table sizes are specified in the lex source code and the -v option is not specified, then -n is implied
     pub no_stats_summary: bool,
+
+    /// --run
+    /// Run the rules on the standard input instead of generating a scanner (the actions are not executed)
+    pub run: bool,
+
+    /// --dump-nfa=FILE
+    /// Write the NFAs of the rules to FILE, in the Graphviz DOT format
+    pub dump_nfa: Option<String>,
+
+    /// --dump-dfa=FILE
+    /// Write the automaton of the rules (before its minimization) to FILE, in the Graphviz DOT format
+    pub dump_dfa: Option<String>,
+
+    /// --target=c|rust
+    /// Language of the generated scanner
+    pub target: TargetLanguage,
+
+    /// --tables=dense|packed
+    /// Layout of the transition table of the generated scanner
+    pub tables: TableFormat,
+
+    /// --utf8
+    /// Decode the specification as UTF-8: the patterns are Unicode, matched on the UTF-8 encoding of the input
+    pub utf8: bool,
+
+    /// -i / --case-insensitive
+    /// The letters of the patterns match both cases (see `%option case-insensitive`)
+    pub case_insensitive: bool,
+
+    /// -oFILE / --outfile=FILE
+    /// Name of the generated file (see `%option outfile`)
+    pub outfile: Option<String>,
+
+    /// -Pprefix / --prefix=prefix
+    /// Prefix of the global names of the scanner, instead of `yy` (see `%option prefix`)
+    pub prefix: Option<String>,
+
+    /// --max-dfa-states=N
+    /// Maximum number of DFA states built before the minimization (see `Definitions::dfa_state_limit` for the default)
+    pub max_dfa_states: Option<usize>,
 }
 
 impl Config {
+    /// Whether the statistics summary is written:
+    /// with -v, or when table sizes are declared, unless -n is set
+    pub fn writes_summary(&self, table_sizes_declared: bool) -> bool {
+        !self.no_stats_summary && (self.summary || table_sizes_declared)
+    }
+
+    /// The options set on the command line, replacing the `%option` lines of the specification
+    pub fn options(&self) -> Options {
+        Options {
+            case_insensitive: self.case_insensitive,
+            outfile: self.outfile.clone(),
+            prefix: self.prefix.clone(),
+            ..Default::default()
+        }
+    }
+
+    /// Maximum number of DFA states: --max-dfa-states if set, else derived from `%n`
+    pub fn dfa_state_limit(&self, definitions: &Definitions) -> usize {
+        self.max_dfa_states.unwrap_or_else(|| definitions.dfa_state_limit())
+    }
+
+    /// The prefix of -P/--prefix, if it is a valid C identifier
+    fn prefix(prefix: &str) -> Result<String, String> {
+        match Utils::is_iso_C_normed(prefix) {
+            true => Ok(prefix.to_string()),
+            false => Err(format!("Invalid prefix: {prefix}")),
+        }
+    }
+
     pub(super) fn init() -> Result<Self, String> {
         let mut args = env::args();
 
@@ -47,6 +162,37 @@ impl Config {
 
                 "-n" => config.no_stats_summary = true,
 
+                "--run" => config.run = true,
+
+                "--utf8" => config.utf8 = true,
+
+                "-i" | "--case-insensitive" => config.case_insensitive = true,
+
+                arg if arg.starts_with("--target=") => config.target = TargetLanguage::try_from(&arg["--target=".len()..])?,
+
+                arg if arg.starts_with("--tables=") => config.tables = TableFormat::try_from(&arg["--tables=".len()..])?,
+
+                arg if arg.starts_with("--max-dfa-states=") => {
+                    let limit = &arg["--max-dfa-states=".len()..];
+
+                    config.max_dfa_states = match limit.parse() {
+                        Ok(limit) if limit > 0 => Some(limit),
+                        _ => return Err(format!("Invalid number of DFA states: {limit}")),
+                    };
+                },
+
+                arg if arg.starts_with("--outfile=") => config.outfile = Some(arg["--outfile=".len()..].to_string()),
+
+                arg if arg.starts_with("-o") && arg.len() > 2 => config.outfile = Some(arg[2..].to_string()),
+
+                arg if arg.starts_with("--prefix=") => config.prefix = Some(Self::prefix(&arg["--prefix=".len()..])?),
+
+                arg if arg.starts_with("-P") && arg.len() > 2 => config.prefix = Some(Self::prefix(&arg[2..])?),
+
+                arg if arg.starts_with("--dump-nfa=") => config.dump_nfa = Some(arg["--dump-nfa=".len()..].to_string()),
+
+                arg if arg.starts_with("--dump-dfa=") => config.dump_dfa = Some(arg["--dump-dfa=".len()..].to_string()),
+
                 // stdin
                 "-" => config.args.push(None),
 
@@ -61,6 +207,10 @@ impl Config {
             config.args.push(None);
         }
 
+        if config.run && config.args.contains(&None) {
+            return Err("--run: the specification cannot be read from the standard input".to_string());
+        }
+
         Ok(config)
     }
 }
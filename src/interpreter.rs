@@ -0,0 +1,311 @@
+use std::{
+	cell::RefCell,
+	collections::{HashMap, HashSet},
+	io::{self, Write},
+};
+
+use super::*;
+use crate::regex::normalizer::*;
+use crate::regex::lazy::LazyDfa;
+
+/// Runs the rules of a specification on an input, without generating a scanner (--run).
+///
+/// The C actions cannot be executed: each token is printed with the id of its rule,
+/// and only the `BEGIN` and `REJECT` statements of the actions are honored
+pub struct Interpreter {
+	automaton: Automaton,
+
+	/// Start condition entered by the action of a rule (`BEGIN`), by rule id
+	begins: HashMap<usize, usize>,
+
+	/// Ids of the rules whose action uses REJECT
+	rejects: HashSet<usize>,
+
+	/// The unmatched input is an error (`%option nodefault`)
+	nodefault: bool,
+
+	/// The tokens are printed with the position after them (`%option yylineno`, `%option yycolumn`)
+	yylineno: bool,
+	yycolumn: bool,
+
+	/// The columns count the UTF-8 chars of the input, not its bytes (--utf8)
+	utf8: bool,
+}
+
+/// Automaton of the rules run by the interpreter
+enum Automaton {
+	/// Built at once
+	Built(NormalizedDfa),
+
+	/// Built on demand, the full automaton needing too many states (the error of its construction)
+	Lazy(Box<RefCell<LazyDfa>>, ParsingError),
+}
+
+/// Part of the input matched by a rule, or copied by the default rule
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token {
+	/// Id of the matched rule, None for the default rule
+	pub rule: Option<usize>,
+
+	/// Position of the first char of the token (from 1)
+	pub line: usize,
+	pub column: usize,
+
+	/// Position after the last char of the token: yylineno and yycolumn in its action in the generated scanner
+	pub end_line: usize,
+	pub end_column: usize,
+
+	/// The bytes of the token, decoded as UTF-8 (the invalid bytes are replaced)
+	pub text: String,
+
+	/// The bytes of the token in the input, copied as they are by the default rule
+	pub bytes: Vec<u8>,
+}
+
+impl std::fmt::Display for Token {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self.rule {
+			Some(rule) => write!(f, "rule#{rule} (line {}:{}) {:?}", self.line, self.column, self.text),
+
+			// default rule: ECHO
+			None => write!(f, "{}", self.text),
+		}
+	}
+}
+
+impl Interpreter {
+	pub fn new(parsing: &Parsing) -> Self {
+		Self::with_state_limit(parsing, parsing.definitions.dfa_state_limit())
+	}
+
+	/// Same as `new`, the automaton being built on demand if it needs more than `limit` states
+	/// (see `Dfa::try_from_rules`)
+	pub fn with_state_limit(parsing: &Parsing, limit: usize) -> Self {
+		let automaton = match Dfa::try_from_rules(&parsing.rules, &parsing.definitions, limit) {
+			Ok(mut dfa) => Automaton::Built(NormalizedDfa::from(&mut dfa)),
+
+			Err(err) => {
+				let lazy = LazyDfa::from_rules(&parsing.rules, &parsing.definitions);
+
+				Automaton::Lazy(Box::new(RefCell::new(lazy)), err)
+			},
+		};
+
+		// in the order of the start states of the automaton (INITIAL first)
+		let conditions = parsing.definitions.start_conditions();
+
+		let begins = parsing.rules.iter()
+			.enumerate()
+			.filter_map(|(index, _)| {
+				let action = Self::action_of(&parsing.rules, index)?;
+				let condition = begin_condition(action)?;
+
+				let condition = conditions.iter().position(|name| **name == condition)?;
+
+				Some((index + 1, condition))
+			})
+			.collect();
+
+		let rejects = (0..parsing.rules.len())
+			.filter(|index| {
+				Self::action_of(&parsing.rules, *index)
+					.is_some_and(|action| Utils::contains_identifier(action, REJECT))
+			})
+			.map(|index| index + 1)
+			.collect();
+
+		let options = &parsing.definitions.options;
+
+		Self {
+			automaton,
+			begins,
+			rejects,
+			nodefault: options.nodefault,
+			yylineno: options.yylineno,
+			yycolumn: options.yycolumn,
+			utf8: parsing.definitions.utf8,
+		}
+	}
+
+	/// Why the automaton is built on demand, if it is (the error of its full construction)
+	pub fn lazy_reason(&self) -> Option<&ParsingError> {
+		match &self.automaton {
+			Automaton::Built(_) => None,
+			Automaton::Lazy(_, err) => Some(err),
+		}
+	}
+
+	/// Code of the action of a rule (the action of the next rule for `|`)
+	fn action_of(rules: &[Rule], index: usize) -> Option<&str> {
+		rules[index..].iter()
+			.find_map(|rule| match &rule.action {
+				RuleAction::Statement(statement) => Some(statement.as_str()),
+				RuleAction::Or => None,
+			})
+	}
+
+	/// Splits the input into tokens, the same way as the generated scanner
+	/// (the rules match the bytes of the input, see `Regex::lower_utf8`)
+	pub fn tokens(&self, bytes: &[u8]) -> Vec<Token> {
+		let mut tokens = vec![];
+
+		let mut condition = 0;
+		let mut at_bol = true;
+
+		// the bytes of the input, a char per byte
+		let chars: String = bytes.iter().map(|byte| *byte as char).collect();
+
+		// position in `bytes` and in `chars`
+		let (mut position, mut chars_position) = (0, 0);
+		let (mut line, mut column) = (1, 1);
+
+		while position < bytes.len() {
+			let rest = &chars[chars_position..];
+
+			let matches = match &self.automaton {
+				Automaton::Built(dfa) => matches_from(rest, dfa, dfa.starts[condition], at_bol),
+
+				Automaton::Lazy(lazy, _) => {
+					let mut lazy = lazy.borrow_mut();
+					let start = lazy.dfa().starts[condition];
+
+					lazy.matches_from(rest, start, at_bol)
+				},
+			};
+			let mut matches = matches.iter().filter(|match_| match_.length() > 0);
+
+			// REJECT: the next best match is tried after the token of the rule
+			let (text, length) = loop {
+				let rule = matches.next();
+
+				// default rule: the unmatched byte is copied to the output (the unmatched char with --utf8)
+				let length = match rule {
+					Some(match_) => match_.length(),
+					None if self.utf8 => char_length(&bytes[position..]),
+					None => 1,
+				};
+
+				let text = String::from_utf8_lossy(&bytes[position..position + length]).into_owned();
+
+				let rule = rule.map(|match_| match_.id());
+
+				if let Some(begin) = rule.and_then(|rule| self.begins.get(&rule)) {
+					condition = *begin;
+				}
+
+				let (end_line, end_column) = self.advance((line, column), &bytes[position..position + length]);
+
+				tokens.push(Token {
+					rule,
+					line,
+					column,
+					end_line,
+					end_column,
+					text: text.clone(),
+					bytes: bytes[position..position + length].to_vec(),
+				});
+
+				if !rule.is_some_and(|rule| self.rejects.contains(&rule)) {
+					break (text, length);
+				}
+			};
+
+			(line, column) = self.advance((line, column), &bytes[position..position + length]);
+
+			at_bol = text.ends_with('\n');
+
+			chars_position += rest.chars().take(length).map(char::len_utf8).sum::<usize>();
+			position += length;
+		}
+
+		tokens
+	}
+
+	/// The position (line, column) after the bytes, counted as the generated scanner does:
+	/// the columns are bytes, or UTF-8 chars with --utf8 (the continuation bytes are not counted)
+	fn advance(&self, (mut line, mut column): (usize, usize), bytes: &[u8]) -> (usize, usize) {
+		for byte in bytes {
+			if *byte == b'\n' {
+				line += 1;
+				column = 1;
+			} else if !self.utf8 || byte & 0xC0 != 0x80 {
+				column += 1;
+			}
+		}
+
+		(line, column)
+	}
+
+	/// Prints the tokens of the input, one per line (the unmatched input is copied as is,
+	/// or stops the scanner with `%option nodefault`).
+	///
+	/// With `%option yylineno` (and `%option yycolumn`), the tokens are followed by the values of these variables in their action
+	pub fn run(&self, input: &[u8], out: &mut impl Write) -> io::Result<()> {
+		for token in self.tokens(input) {
+			match token.rule {
+				Some(_) => {
+					write!(out, "{token}")?;
+
+					if self.yylineno {
+						write!(out, " yylineno={}", token.end_line)?;
+					}
+
+					if self.yycolumn {
+						write!(out, " yycolumn={}", token.end_column)?;
+					}
+
+					writeln!(out)?
+				},
+
+				None if self.nodefault => {
+					out.flush()?;
+
+					return Err(io::Error::other(format!("ft_lex scanner jammed (line {}:{})", token.line, token.column)));
+				},
+
+				None => out.write_all(&token.bytes)?,
+			}
+		}
+
+		out.flush()
+	}
+}
+
+/// Length of the UTF-8 char at the start of `bytes` (1 if they do not start with a valid char)
+fn char_length(bytes: &[u8]) -> usize {
+	(1..=bytes.len().min(4))
+		.find(|length| std::str::from_utf8(&bytes[..*length]).is_ok())
+		.unwrap_or(1)
+}
+
+/// Start condition of the last `BEGIN` statement of an action (`BEGIN NAME;` or `BEGIN(NAME);`)
+fn begin_condition(action: &str) -> Option<String> {
+	let mut condition = None;
+
+	for (index, _) in action.match_indices("BEGIN") {
+		let before = action[..index].chars().next_back();
+
+		if before.is_some_and(|c| c.is_alphanumeric() || c == '_') {
+			continue;
+		}
+
+		let after = action[index + "BEGIN".len()..]
+			.trim_start_matches(|c: char| c.is_whitespace() || c == '(');
+
+		let name: String = after.chars()
+			.take_while(|c| c.is_alphanumeric() || *c == '_')
+			.collect();
+
+		if name.is_empty() {
+			continue;
+		}
+
+		// BEGIN 0 is BEGIN INITIAL
+		condition = match name.as_str() {
+			"0" => Some(DEFAULT_STATE.to_string()),
+			_ => Some(name),
+		};
+	}
+
+	condition
+}
@@ -1,6 +1,7 @@
 #![allow(dead_code, unused_imports, unreachable_code)]
 
 use std::fmt::{Debug, Formatter};
+use std::io::Read;
 
 mod tests;
 
@@ -13,30 +14,126 @@ use config::*;
 mod regex;
 use regex::*;
 
+mod codegen;
+use codegen::*;
+
+mod interpreter;
+use interpreter::*;
+
 // TODO: error if '\' or '/' in Description section
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     let config = Config::init()?;
 
-    // dbg!(&config);
-
     let mut parser = Parsing::new()?;
 
     if let Err(errors) = parser.parse_file(&config) {
-		// print errors
-        for err in errors {
-			match config.stdout {
-				// stderr if -t/--stdout is set
-				true => eprintln!("{}", err),
-
-				// stdout if -t/--stdout is not set
-				false => println!("{}", err),
-			}
+        print_errors(errors, &config);
+    }
+
+    dump(&parser, &config)?;
+
+    if config.run {
+        // the input of the rules is the standard input
+        let mut input = vec![];
+        std::io::stdin().read_to_end(&mut input)?;
+
+        let limit = config.dfa_state_limit(&parser.definitions);
+        let interpreter = Interpreter::with_state_limit(&parser, limit);
+
+        if let Some(err) = interpreter.lazy_reason() {
+            eprintln!("WARNING: {err}: the automaton is built on demand");
         }
+
+        interpreter.run(&input, &mut std::io::stdout().lock())?;
+
+        return Ok(());
     }
 
-    dbg!(parser);
+    let limit = config.dfa_state_limit(&parser.definitions);
+
+    let mut tables = match Tables::with_state_limit(&parser.rules, &parser.definitions, limit) {
+        Ok(tables) => tables,
+
+        Err(err) => {
+            print_errors(&[err], &config);
+            unreachable!("the errors exit")
+        },
+    };
+
+    if config.tables == TableFormat::Packed {
+        tables.pack();
+    }
+
+    let summary = Summary::new(&parser.rules, &tables);
+
+    if config.writes_summary(!parser.definitions.table_sizes.is_empty()) {
+        match config.stdout {
+            // stderr if -t/--stdout is set
+            true => summary.write(&parser.definitions, &mut std::io::stderr())?,
+
+            false => summary.write(&parser.definitions, &mut std::io::stdout())?,
+        }
+    }
+
+    if let Err(errors) = summary.check(&parser.definitions) {
+        print_errors(&errors, &config);
+    }
+
+    codegen::generate(&parser, &tables, &config)?;
+
+    Ok(())
+}
+
+/// Writes the automatons of the rules in the DOT format (--dump-nfa, --dump-dfa)
+fn dump(parser: &Parsing, config: &Config) -> std::io::Result<()> {
+    if let Some(file) = &config.dump_nfa {
+        let nfas: Vec<&Nfa> = parser.rules.iter()
+            .map(|rule| &rule.regex_nfa)
+            .collect();
+
+        std::fs::write(file, nfa_to_dot(&nfas))?;
+    }
+
+    if let Some(file) = &config.dump_dfa {
+        let limit = config.dfa_state_limit(&parser.definitions);
+
+        let mut dfa = match Dfa::try_from_rules(&parser.rules, &parser.definitions, limit) {
+            Ok(dfa) => dfa,
+
+            Err(err) => {
+                print_errors(&[err], config);
+                unreachable!("the errors exit")
+            },
+        };
+        let dfa = normalizer::NormalizedDfa::from(&mut dfa);
+
+        let conditions: Vec<String> = parser.definitions.start_conditions()
+            .into_iter()
+            .cloned()
+            .collect();
+
+        std::fs::write(file, dfa_to_dot(&dfa, &conditions))?;
+    }
 
     Ok(())
 }
+
+/// Prints the errors, and exits if one of them is not a warning
+fn print_errors(errors: &[ParsingError], config: &Config) {
+    for err in errors {
+        match config.stdout {
+            // stderr if -t/--stdout is set
+            true => eprintln!("{}", err),
+
+            // stdout if -t/--stdout is not set
+            false => println!("{}", err),
+        }
+    }
+
+    // warnings do not prevent the generation
+    if errors.iter().any(|err| !err.is_warning()) {
+        std::process::exit(1);
+    }
+}
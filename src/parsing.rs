@@ -1,5 +1,6 @@
 pub mod definitions;
 pub mod error;
+pub mod options;
 pub mod reader;
 mod rules;
 /// A module for parsing lexer definitions and configurations.
@@ -8,6 +9,7 @@ pub mod utils;
 use super::*;
 pub use definitions::*;
 pub use error::*;
+pub use options::*;
 pub use reader::*;
 pub use rules::*;
 pub use utils::*;
@@ -29,6 +31,9 @@ pub struct Parsing {
 	/// Collection of lexer rules
 	pub rules: Vec<Rule>,
 
+	/// Rules run at the end of the input (`<<EOF>>`)
+	pub end_of_file_rules: Vec<EndOfFileRule>,
+
 	/// Collection of user-defined subroutines
 	pub user_subroutines: Option<String>,
 
@@ -64,9 +69,12 @@ impl Section {
 impl Parsing {
     /// Creates a new parsing instance with empty definitions.
     pub fn new() -> io::Result<Self> {
+        reset_rule_id();
+
         Ok(Self {
             definitions: Definitions::new(),
             rules: Vec::new(),
+            end_of_file_rules: Vec::new(),
             user_subroutines: None,
             errors: Vec::new(),
             section: Section::Definitions,
@@ -78,6 +86,9 @@ impl Parsing {
     /// This function handles both file inputs and stdin, processing each section
     /// (definitions, rules, subroutines) in sequence.
     pub fn parse_file<'parsing>(&'parsing mut self, config: &Config) -> Result<(), &'parsing Vec<ParsingError>> {
+        self.definitions.utf8 = config.utf8;
+        self.definitions.command_line = config.options();
+
         // Create an iterator over the config arguments
         let mut args = config.args.iter().map(|arg| arg.as_ref());
 
@@ -145,7 +156,7 @@ impl Parsing {
                 }
                 Section::Rules => {
                     // Parse the rules section
-                    while let Err(err) = Rules::parse_rules(&mut self.rules, reader, &self.definitions) {
+                    while let Err(err) = Rules::parse_rules(&mut self.rules, &mut self.end_of_file_rules, reader, &self.definitions) {
                         let err = err.file(reader.filename()).line(reader.index());
 
                         self.errors.push(err);
@@ -166,10 +177,14 @@ impl Parsing {
                 Section::Subroutines => {
                     let subroutines = reader.read_all();
 
-					if subroutines.is_err() {
-						self.errors.push(ParsingError::from(subroutines.unwrap_err()).file(reader.filename()).line(reader.index()));
-					} else {
-						self.user_subroutines = subroutines.unwrap();
+					match subroutines {
+						Err(err) => {
+							self.errors.push(ParsingError::from(err).file(reader.filename()).line(reader.index()));
+						}
+						Ok(subroutines) => {
+							self.user_subroutines = subroutines
+								.map(|subroutines| self.definitions.decoded_code(subroutines));
+						}
 					}
 
 					break 'big_loop;
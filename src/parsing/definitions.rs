@@ -35,6 +35,10 @@ impl TryFrom<&str> for StateType {
     }
 }
 
+/// DFA states allowed during the subset construction for each state of the `%n` table size
+/// (see `Definitions::dfa_state_limit`)
+pub const DFA_STATES_PER_DECLARED_STATE: usize = 100;
+
 /// Collection of all lexer definitions including substitutions, fragments, and declarations.
 #[derive(Debug)]
 pub struct Definitions {
@@ -52,6 +56,18 @@ pub struct Definitions {
 
     /// Map of state names to their types
     pub states: HashMap<String, StateType>,
+
+    /// The specification is decoded as UTF-8, its patterns matching the UTF-8 encoding of the input (--utf8)
+    pub utf8: bool,
+
+    /// Options of the `%option` lines, merged with `command_line` at the end of the section
+    pub options: Options,
+
+    /// Options set on the command line, replacing the ones of the `%option` lines
+    pub command_line: Options,
+
+    /// Diagnostics of the last parsed line not returned yet, `parse` returns one of them per call
+    pending: Vec<ParsingError>,
 }
 
 impl Default for Definitions {
@@ -72,6 +88,8 @@ pub enum DefinitionType {
     TypeDeclaration(TypeDeclaration),
     /// State declaration with type and names
     StateDeclaration(StateType, Vec<String>),
+    /// Options (e.g., "%option case-insensitive")
+    Options(Vec<String>),
     /// Empty line
     Empty,
     /// End of definitions section marker ("%%")
@@ -184,6 +202,10 @@ impl Definitions {
             states: HashMap::from([("INITIAL".to_string(), StateType::Inclusive)]),
             table_sizes: HashMap::new(),
             type_declaration: None,
+            utf8: false,
+            options: Options::default(),
+            command_line: Options::default(),
+            pending: Vec::new(),
         }
     }
 
@@ -195,6 +217,7 @@ impl Definitions {
     /// - Program fragments (inline and block)
     /// - Type declarations (%array, %pointer)
     /// - State declarations (%s, %x)
+    /// - Options (%option)
     ///
     /// Returns an error if any definition is invalid or if the section delimiter is missing.
     pub fn parse<'de, R: Read>(
@@ -202,6 +225,10 @@ impl Definitions {
         reader: &mut Reader<R>,
     ) -> ParsingResult<&'de mut Self> {
         loop {
+            if let Some(err) = self.pending.pop() {
+                return Err(err);
+            }
+
             match Self::line_type(reader)? {
                 DefinitionType::TableSize(table, size) => {
                     if let Some(previous_size) = self.table_sizes.insert(table, size) {
@@ -228,7 +255,7 @@ impl Definitions {
                     }
                 }
                 DefinitionType::Fragment(fragment) => {
-                    self.fragments.push(fragment);
+                    self.fragments.push(self.decoded_code(fragment));
                 }
                 DefinitionType::TypeDeclaration(type_decla) => {
                     if self.type_declaration.is_some() && self.type_declaration != Some(type_decla)
@@ -239,8 +266,13 @@ impl Definitions {
                     }
                     self.type_declaration = Some(type_decla)
                 }
+                DefinitionType::Options(options) => self.set_options(&options)?,
                 DefinitionType::Empty => {}
-                DefinitionType::EndOfSection => return Ok(self),
+                DefinitionType::EndOfSection => {
+                    self.options = take(&mut self.options).merged(&self.command_line);
+
+                    return Ok(self);
+                }
             }
         }
     }
@@ -396,11 +428,117 @@ impl Definitions {
                     TypeDeclaration::try_from(flag).unwrap(),
                 ));
             }
+            // Options (%option followed by their names)
+            "option" => {
+                if split.len() < 2 {
+                    return ParsingError::end_of_line()
+                        .because("expected: `%option {OPTION}`")
+                        .into();
+                }
+
+                // Split again, so the quoted values may contain spaces (outfile="my scanner.c")
+                let options = &line[1..].trim_ascii_start()["option".len()..];
+
+                Ok(DefinitionType::Options(Utils::split_whitespace_quoted(options)))
+            }
             // Any other flag is an error
             _ => return ParsingError::invalid_flag(format!("%{flag}")).into(),
         }
     }
 
+    /// Returns the names of the start conditions, in the order used to number them:
+    /// `INITIAL` first, then the declared conditions in alphabetical order.
+    pub fn start_conditions(&self) -> Vec<&String> {
+        let mut names: Vec<&String> = self.states.keys()
+            .filter(|name| name.as_str() != DEFAULT_STATE)
+            .collect();
+
+        names.sort();
+
+        if let Some((initial, _)) = self.states.get_key_value(DEFAULT_STATE) {
+            names.insert(0, initial);
+        }
+
+        names
+    }
+
+    /// Applies the options of a `%option` line, warns about the unknown ones
+    ///
+    /// The whole line is processed before its diagnostics are reported.
+    fn set_options(&mut self, options: &[String]) -> ParsingResult<()> {
+        let mut errors = Vec::new();
+
+        let unknown: Vec<&String> = options.iter()
+            .filter(|option| !self.options.set(option))
+            .collect();
+
+        if !unknown.is_empty() {
+            let unknown: Vec<String> = unknown.iter().map(|option| format!("`{option}`")).collect();
+
+            errors.push(ParsingError::warning(format!("unknown option {}", unknown.join(", ")))
+                .because(format!("valid options: {}", VALID_OPTIONS.join(", "))));
+        }
+
+        if let Some(prefix) = self.options.prefix.take_if(|prefix| !Utils::is_iso_C_normed(prefix)) {
+            errors.push(ParsingError::syntax(format!("`{prefix}`"))
+                .because("the prefix must be iso-C normed"));
+        }
+
+        if options.iter().any(|option| option == "reentrant") {
+            errors.push(ParsingError::warning("%option reentrant is not supported: the scanner is not reentrant"));
+        }
+
+        // The first diagnostic is returned now, the others by the next calls of `parse`
+        errors.reverse();
+        match errors.pop() {
+            Some(err) => {
+                self.pending = errors;
+                Err(err)
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// How the patterns of the rules are compiled
+    pub fn regex_flags(&self) -> RegexFlags {
+        RegexFlags {
+            utf8: self.utf8,
+            case_insensitive: self.options.case_insensitive,
+            eight_bit: self.options.eight_bit,
+        }
+    }
+
+    /// The pattern `pattern` read from the specification, decoded in UTF-8 mode (see `Utils::decode_utf8`)
+    pub fn decoded_pattern(&self, pattern: String) -> ParsingResult<String> {
+        if !self.utf8 {
+            return Ok(pattern);
+        }
+
+        Utils::decode_utf8(&pattern)
+            .ok_or(ParsingError::unrecognized_rule().because("invalid UTF-8 in the pattern"))
+    }
+
+    /// The code `code` read from the specification, decoded in UTF-8 mode,
+    /// so it is written as it was read (kept as is if it is not valid UTF-8)
+    pub fn decoded_code(&self, code: String) -> String {
+        match self.utf8 {
+            true => Utils::decode_utf8(&code).unwrap_or(code),
+            false => code,
+        }
+    }
+
+    /// Maximum number of DFA states built before the minimization:
+    /// `DFA_STATES_PER_DECLARED_STATE` times the `%n` table size (its minimum value if not declared).
+    ///
+    /// It only stops the state explosions, the `%n` size itself is checked after the minimization
+    pub fn dfa_state_limit(&self) -> usize {
+        let declared = self.table_sizes.get(&TableSizeDeclaration::States)
+            .copied()
+            .unwrap_or(TableSizeDeclaration::States.minimum_value());
+
+        declared.saturating_mul(DFA_STATES_PER_DECLARED_STATE)
+    }
+
     /// Splits a line into parts and verifies it has the expected number of parts.
     fn split_definition(
         line: &String,
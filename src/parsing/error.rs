@@ -174,6 +174,11 @@ impl ParsingError {
         }
     }
 
+    /// Returns true if the error is only a warning (it does not prevent the generation).
+    pub fn is_warning(&self) -> bool {
+        matches!(self.type_, ParsingErrorType::Warning(_))
+    }
+
     /// Adds file context to the error.
     pub fn file(mut self, file: impl ToString) -> Self {
         self.file = Some(file.to_string());
@@ -239,6 +244,24 @@ impl ParsingError {
         ParsingError::syntax(format!("undeclared start condition: `{}`", condition.to_string()))
     }
 
+    /// Creates an error for a table bigger than its declared size (e.g. `%n 500`).
+    pub fn table_size_exceeded(declaration: impl ToString, limit: usize, used: usize) -> Self {
+        let err = format!("table size exceeded: `{} {}`", declaration.to_string(), limit);
+        ParsingError::syntax(err).because(format!("{used} needed"))
+    }
+
+    /// Creates an error for a rule whose automaton needs more than `limit` DFA states.
+    pub fn dfa_state_limit_exceeded(limit: usize, pattern: impl ToString) -> Self {
+        let err = format!("too many DFA states (more than {limit})");
+        ParsingError::syntax(err).because(format!("state explosion in the rule `{}`", pattern.to_string()))
+    }
+
+    /// Creates an error for an invalid escape sequence (`escape` being the text after the '\\').
+    pub fn invalid_escape(escape: impl ToString) -> Self {
+        let err = format!("invalid escape sequence `\\{}`", escape.to_string());
+        ParsingError::syntax(err)
+    }
+
     /// Creates an error for an invalid number format.
     pub fn invalid_number(number: impl ToString) -> Self {
         let err = format!("invalid number: `{}`", number.to_string());
@@ -0,0 +1,126 @@
+use super::*;
+
+/// Options accepted by `%option` (the `no` prefix negates the boolean ones: `yywrap`, `nostack`...)
+pub const VALID_OPTIONS: [&str; 11] = [
+    "noyywrap",
+    "yylineno",
+    "yycolumn",
+    "outfile=\"FILE\"",
+    "prefix=\"PREFIX\"",
+    "case-insensitive",
+    "never-interactive",
+    "nodefault",
+    "stack",
+    "reentrant",
+    "8bit",
+];
+
+/// Options of the scanner, set by the `%option` lines of the definitions section
+/// (and by the command line, which wins, see `Options::merged`)
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Options {
+    /// noyywrap: the end of the input ends the scan, without calling yywrap()
+    pub noyywrap: bool,
+
+    /// yylineno: the scanner counts the lines of the input in yylineno
+    pub yylineno: bool,
+
+    /// yycolumn: the scanner also counts the columns in yycolumn (implies yylineno)
+    pub yycolumn: bool,
+
+    /// outfile="FILE": name of the generated file (lex.yy.c by default)
+    pub outfile: Option<String>,
+
+    /// prefix="PREFIX": prefix of the names of the scanner, instead of `yy`
+    pub prefix: Option<String>,
+
+    /// case-insensitive (caseless): the letters of the patterns match both cases (-i)
+    pub case_insensitive: bool,
+
+    /// never-interactive: the input is read by blocks, not up to each newline
+    pub never_interactive: bool,
+
+    /// nodefault: the unmatched input is an error, instead of being copied to the output
+    pub nodefault: bool,
+
+    /// stack: the start conditions can be pushed and popped (yy_push_state(), yy_pop_state(), yy_top_state())
+    pub stack: bool,
+
+    /// reentrant: not supported, the scanner keeps its state in globals
+    pub reentrant: bool,
+
+    /// 8bit: `.` and the negated classes also match the bytes 128 to 255 (only ASCII otherwise)
+    pub eight_bit: bool,
+}
+
+impl Options {
+    /// Sets an option of a `%option` line (`name` or `name="value"`),
+    /// false if it is not one of the `VALID_OPTIONS`
+    pub fn set(&mut self, option: &str) -> bool {
+        if let Some((name, value)) = option.split_once('=') {
+            let value = value.trim_matches('"').to_string();
+
+            match name {
+                "outfile" => self.outfile = Some(value),
+                "prefix" => self.prefix = Some(value),
+                _ => return false,
+            }
+
+            return true;
+        }
+
+        match option {
+            "case-insensitive" | "caseless" => self.case_insensitive = true,
+            "case-sensitive" => self.case_insensitive = false,
+
+            "8bit" => self.eight_bit = true,
+
+            option => {
+                // the boolean options, negated by their `no` prefix
+                let (name, value) = match option.strip_prefix("no") {
+                    Some(name) => (name, false),
+                    None => (option, true),
+                };
+
+                match name {
+                    "yywrap" => self.noyywrap = !value,
+                    "yylineno" => self.yylineno = value,
+                    "yycolumn" => {
+                        self.yycolumn = value;
+                        self.yylineno |= value;
+                    },
+                    "never-interactive" => self.never_interactive = value,
+                    "default" => self.nodefault = !value,
+                    "stack" => self.stack = value,
+                    "reentrant" => self.reentrant = value,
+                    _ => return false,
+                }
+            }
+        }
+
+        true
+    }
+
+    /// The options, with the ones set in `command_line` replacing them
+    pub fn merged(mut self, command_line: &Options) -> Self {
+        self.noyywrap |= command_line.noyywrap;
+        self.yylineno |= command_line.yylineno;
+        self.yycolumn |= command_line.yycolumn;
+        self.case_insensitive |= command_line.case_insensitive;
+        self.never_interactive |= command_line.never_interactive;
+        self.nodefault |= command_line.nodefault;
+        self.stack |= command_line.stack;
+        self.reentrant |= command_line.reentrant;
+        self.eight_bit |= command_line.eight_bit;
+
+        if command_line.outfile.is_some() {
+            self.outfile = command_line.outfile.clone();
+        }
+
+        if command_line.prefix.is_some() {
+            self.prefix = command_line.prefix.clone();
+        }
+
+        self
+    }
+}
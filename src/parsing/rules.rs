@@ -2,23 +2,37 @@ use crate::regex::normalizer::NormalizedDfa;
 
 use super::*;
 
-use std::{collections::HashMap, io::Read};
+use std::{cell::Cell, collections::HashMap, io::Read};
 
 pub const DEFAULT_STATE: &str = "INITIAL";
 
-static mut RULE_ID: usize = 1;
+/// Pattern of the rules run at the end of the input
+pub const END_OF_FILE: &str = "<<EOF>>";
+
+/// Statement of the actions running the next best match instead of theirs
+pub const REJECT: &str = "REJECT";
+
+thread_local! {
+	/// Id given to the next parsed rule (rules are numbered from 1, in order of appearance)
+	static RULE_ID: Cell<usize> = const { Cell::new(1) };
+}
 
 pub fn rule_id() -> usize {
-	unsafe { RULE_ID }
+	RULE_ID.with(|id| id.get())
 }
 
 pub fn increment_rule_id() {
-	unsafe { RULE_ID += 1 }
+	RULE_ID.with(|id| id.set(id.get() + 1))
+}
+
+pub fn reset_rule_id() {
+	RULE_ID.with(|id| id.set(1))
 }
 
 #[derive(Debug)]
 pub enum LineType {
 	Rule (Rule),
+	EndOfFile (EndOfFileRule),
 
 	Empty,
 	EndOfSection,
@@ -30,28 +44,118 @@ pub enum RuleAction {
 	Statement(String)
 }
 
+impl RuleAction {
+	/// Checks if the action uses REJECT
+	pub fn rejects(&self) -> bool {
+		match self {
+			RuleAction::Or => false,
+			RuleAction::Statement(action) => Utils::contains_identifier(action, REJECT),
+		}
+	}
+
+	/// The action read from the specification, decoded in UTF-8 mode (see `Definitions::decoded_code`)
+	pub fn decoded(self, definitions: &Definitions) -> Self {
+		match self {
+			RuleAction::Statement(action) => RuleAction::Statement(definitions.decoded_code(action)),
+			RuleAction::Or => RuleAction::Or,
+		}
+	}
+}
+
 #[derive(Debug)]
 pub struct Rule {
 	pub start_conditions: Vec<String>,
 
-	pub regex_nfa: StatePtr,
-	pub following_regex_nfa: Option<StatePtr>,
+	/// False if the rule has no `<...>` prefix (`start_conditions` is then only `INITIAL`)
+	pub custom_conditions: bool,
+
+	/// NFA of the whole pattern (`rs` for a rule with trailing context `r/s`)
+	pub regex_nfa: Nfa,
+
+	pub trailing_context: Option<TrailingContext>,
+
+	pub action: RuleAction,
+
+	/// Pattern of the rule (after the substitutions), `r/s` for a trailing context
+	pub pattern: String,
+
+	/// Position of the rule in the specification, for the diagnostics
+	pub file: String,
+	pub line_index: usize,
+}
+
+impl Rule {
+	/// Returns true if the rule can match in the given start condition.
+	///
+	/// A rule without start condition is active in all the inclusive conditions.
+	pub fn is_active_in(&self, condition: &str, type_: StateType) -> bool {
+		if !self.custom_conditions {
+			return type_ == StateType::Inclusive;
+		}
+
+		self.start_conditions.iter().any(|name| name == condition)
+	}
+
+	/// Checks if the text of the rule can contain a newline, as found in its NFA:
+	/// only these rules update yylineno (`%option yylineno`)
+	pub fn can_match_newline(&self) -> bool {
+		self.regex_nfa.can_match('\n')
+	}
+}
+
+/// Rule run when the input is exhausted in one of its start conditions (`<<EOF>>`), before yywrap()
+#[derive(Debug)]
+pub struct EndOfFileRule {
+	pub start_conditions: Vec<String>,
+
+	/// False if the rule has no `<...>` prefix:
+	/// the rule is then used in all the start conditions without their own `<<EOF>>` rule
+	pub custom_conditions: bool,
 
 	pub action: RuleAction
 }
 
+impl EndOfFileRule {
+	/// Index of the `<<EOF>>` rule used in each start condition (in the order of `Definitions::start_conditions`).
+	///
+	/// The first rule naming the condition is used, the first rule without prefix otherwise
+	pub fn by_condition(rules: &[EndOfFileRule], definitions: &Definitions) -> Vec<Option<usize>> {
+		definitions.start_conditions()
+			.into_iter()
+			.map(|condition| {
+				rules.iter()
+					.position(|rule| rule.custom_conditions && rule.start_conditions.contains(condition))
+					.or_else(|| rules.iter().position(|rule| !rule.custom_conditions))
+			})
+			.collect()
+	}
+
+	/// Checks if the rule is used in the same start conditions as `other`
+	fn overlaps(&self, other: &EndOfFileRule) -> bool {
+		match (self.custom_conditions, other.custom_conditions) {
+			(true, true) => self.start_conditions.iter().any(|condition| other.start_conditions.contains(condition)),
+			(false, false) => true,
+			_ => false,
+		}
+	}
+}
+
 pub struct Rules {}
 
 impl Rules {
+	/// Checks if an action uses REJECT: the scanner then needs the history of its matches
+	pub fn use_reject(rules: &[Rule]) -> bool {
+		rules.iter().any(|rule| rule.action.rejects())
+	}
+
 	pub fn parse_rules<'rules, R: Read>(
 		rules: &'rules mut Vec<Rule>,
+		end_of_file_rules: &mut Vec<EndOfFileRule>,
         reader: &mut Reader<R>,
 		definitions: &Definitions
     ) -> ParsingResult<&'rules mut Vec<Rule>> {
 		loop {
 			let line_type = Self::line_type(reader, definitions);
-			
-			dbg!(&line_type);
 
 			if let Err(err) = line_type {
 				return Err(err);
@@ -59,8 +163,35 @@ impl Rules {
 
 			match line_type.unwrap() {
 
-				LineType::Rule( rule ) => {
+				LineType::Rule( mut rule ) => {
+					rule.action = rule.action.decoded(definitions);
+
+					let dangerous = rule.trailing_context.as_ref()
+						.is_some_and(|trailing_context| trailing_context.is_variable());
+
 					rules.push(rule);
+					increment_rule_id();
+
+					// the rule is kept, the parsing goes on after the warning
+					if dangerous {
+						return ParsingError::warning("dangerous trailing context")
+							.because("both parts of the rule have a variable length")
+							.into()
+					}
+				},
+
+				LineType::EndOfFile( mut rule ) => {
+					rule.action = rule.action.decoded(definitions);
+
+					let duplicate = end_of_file_rules.iter().any(|previous| previous.overlaps(&rule));
+
+					end_of_file_rules.push(rule);
+
+					if duplicate {
+						return ParsingError::warning("multiple <<EOF>> rules for the same start condition")
+							.because("only the first one is used")
+							.into()
+					}
 				},
 
 				LineType::Empty => {},
@@ -111,9 +242,18 @@ impl Rules {
 			}
 		}
 
+		// `<<EOF>>` without start condition
+		if first_char == '<' && second_char == &b'<' && Self::read_end_of_file(reader)? {
+			return Self::end_of_file_rule(vec![DEFAULT_STATE.to_string()], false, reader);
+		}
+
 		let custom_conditions = first_char == '<';
 		let start_conditions = Self::get_conditions(&mut first_char, reader, definitions)?;
 
+		if custom_conditions && first_char == '<' && Self::read_end_of_file(reader)? {
+			return Self::end_of_file_rule(start_conditions, true, reader);
+		}
+
 		{	// Check if the line is empty
 			if custom_conditions && first_char.is_ascii_whitespace() {
 				let _ = reader.line()?;
@@ -141,29 +281,65 @@ impl Rules {
 
 		reader.push_char(first_char);
 
+		let line_index = reader.index();
+
 		let (regex, following_regex) = Self::get_regular_expression(&definitions.substitutes, reader)?;
-		dbg!(&regex);
+
+		let regex = definitions.decoded_pattern(regex)?;
+		let following_regex = following_regex.map(|regex| definitions.decoded_pattern(regex)).transpose()?;
+
+		let pattern = match &following_regex {
+			Some(following_regex) => format!("{regex}/{following_regex}"),
+			None => regex.clone(),
+		};
 
 		let action = Self::get_action(reader)?;
 
-		let regex_nfa = Regex::new(regex, rule_id())?;
+		let (regex_nfa, trailing_context) = if let Some(following_regex) = following_regex {
+			let (regex_nfa, trailing_context) = Regex::with_trailing_context_and_flags(regex, following_regex, rule_id(), definitions.regex_flags())?;
 
-		let following_regex_nfa = if let Some(expr) = following_regex {
-			Some(Regex::new(expr, rule_id())?)
+			(regex_nfa, Some(trailing_context))
 		} else {
-			None
+			(Regex::with_flags(regex, rule_id(), definitions.regex_flags())?, None)
 		};
 
 		Ok(
 			LineType::Rule(Rule {
 				start_conditions,
+				custom_conditions,
 				regex_nfa,
-				following_regex_nfa,
-				action
+				trailing_context,
+				action,
+				pattern,
+				file: reader.filename().to_string(),
+				line_index,
 			})
 		)
 	}
 
+	/// Reads the rest of `<<EOF>>` (after its first '<'), nothing if the input is not `<<EOF>>`
+	fn read_end_of_file<R: Read>(reader: &mut Reader<R>) -> ParsingResult<bool> {
+		let marker = reader.read(END_OF_FILE.len() - 1)?.unwrap_or_default();
+
+		if marker == END_OF_FILE[1..] {
+			return Ok(true);
+		}
+
+		reader.push_str(&marker);
+
+		Ok(false)
+	}
+
+	fn end_of_file_rule<R: Read>(
+		start_conditions: Vec<String>,
+		custom_conditions: bool,
+		reader: &mut Reader<R>
+	) -> ParsingResult<LineType> {
+		let action = Self::get_action(reader)?;
+
+		Ok(LineType::EndOfFile(EndOfFileRule { start_conditions, custom_conditions, action }))
+	}
+
 	pub fn get_action<R: Read>(
 		reader: &mut Reader<R>
 	) -> ParsingResult<RuleAction> {
@@ -334,7 +510,8 @@ impl Rules {
 				} else if c == delim {
 					str.push(c);
 					break;
-				} else if c == '/' {
+				} else if c == '/' && !include_whitespaces {
+					// '/' is a litteral in a string or a class (e.g. "*/" or [/])
 					reader.push_char(c);
 					break;
 				} else {
@@ -350,6 +527,58 @@ impl Rules {
 			Ok(str)
 		};
 
+		// read until the end of a class, the `]` of its `[:alpha:]`, `[=a=]` or `[.a.]` elements included
+		let read_class = |reader: &mut Reader<R>| -> ParsingResult<String> {
+			let mut str = String::new();
+
+			// delimiter of the element being read, and its length when it was opened
+			let mut element: Option<(char, usize)> = None;
+
+			loop {
+				let c = reader.next()?
+					.ok_or(ParsingError::end_of_file().because("unclosed `[`"))?
+					as char;
+
+				str.push(c);
+
+				match (c, element) {
+					(']', Some((delimiter, start))) if str.len() > start + 1 && str[..str.len() - 1].ends_with(delimiter) => {
+						element = None;
+					},
+
+					(_, Some(_)) => {},
+
+					('\\', None) => {
+						let next = reader.next()?
+							.ok_or(ParsingError::end_of_file().because("unclosed `[`"))?
+							as char;
+
+						str.push(next);
+					},
+
+					('[', None) => {
+						let next = reader.next()?
+							.ok_or(ParsingError::end_of_file().because("unclosed `[`"))?
+							as char;
+
+						str.push(next);
+
+						if matches!(next, ':' | '=' | '.') {
+							element = Some((next, str.len()));
+						} else if next == ']' {
+							break;
+						}
+					},
+
+					(']', None) => break,
+
+					_ => {},
+				}
+			}
+
+			Ok(str)
+		};
+
 		let mut regex = String::new();
 		
 		loop {
@@ -378,11 +607,12 @@ impl Rules {
 						}
 					}
 
+					// the closing '}'
+					let _ = content.pop();
+
 					if let Some(c) = content.chars().next() {
 						if c.is_ascii_alphabetic() || c == '_' {
 
-							let _ = content.pop();
-
 							if let Some(substitute) = substitutes.get(&content) {
 								regex.push('(');
 								regex.push_str(substitute);
@@ -404,7 +634,7 @@ impl Rules {
 
 				'[' => {
 					regex.push(c);
-					regex.push_str(&read_until(']', reader, true)?);
+					regex.push_str(&read_class(reader)?);
 				},
 
 				'\\' => {
@@ -1,4 +1,4 @@
-use std::str::Chars;
+use std::{iter::Peekable, str::Chars};
 
 use super::{reader::Reader, *};
 
@@ -261,6 +261,122 @@ impl Utils {
         }
     }
 
+    /// Decodes the UTF-8 text `text` read by a `Reader` (a char per byte), None if it is not valid UTF-8
+    pub fn decode_utf8(text: &str) -> Option<String> {
+        let bytes = text.chars()
+            .map(|c| u8::try_from(c).ok())
+            .collect::<Option<Vec<u8>>>()?;
+
+        String::from_utf8(bytes).ok()
+    }
+
+    /// Decodes the escape sequence following a '\\' in `chars`:
+    /// `\\ddd` (octal), `\\xhh` (hexadecimal), `\\cX` (control character) or a single char (see `backslashed`).
+    ///
+    /// The value of a numeric escape must be a byte (at most `\\377` or `\\xff`)
+    pub fn escaped(chars: &mut Peekable<Chars>) -> ParsingResult<char> {
+        let Some(c) = chars.next() else {
+            return ParsingError::invalid_escape("").because("nothing after the '\\'").into();
+        };
+
+        let mut read_digits = |first: Option<char>, radix: u32, max_len: usize| {
+            let mut digits: String = first.into_iter().collect();
+
+            while digits.len() < max_len {
+                match chars.peek() {
+                    Some(c) if c.is_digit(radix) => digits.push(*c),
+                    _ => break,
+                }
+                chars.next();
+            }
+
+            digits
+        };
+
+        let (escape, value) = match c {
+            '0'..='7' => {
+                let digits = read_digits(Some(c), 8, 3);
+
+                (digits.clone(), u32::from_str_radix(&digits, 8).unwrap())
+            },
+
+            'x' => {
+                let digits = read_digits(None, 16, 2);
+
+                if digits.is_empty() {
+                    return ParsingError::invalid_escape("x").because("no hexadecimal digit after `\\x`").into();
+                }
+
+                (format!("x{digits}"), u32::from_str_radix(&digits, 16).unwrap())
+            },
+
+            'c' => {
+                let Some(control) = chars.next() else {
+                    return ParsingError::invalid_escape("c").because("no character after `\\c`").into();
+                };
+
+                return match control {
+                    '?' => Ok('\x7f'),
+                    '@'..='_' | 'a'..='z' => Ok(char::from(control.to_ascii_uppercase() as u8 ^ 0x40)),
+
+                    _ => ParsingError::invalid_escape(format!("c{control}"))
+                        .because("a control character is `\\c` followed by a letter or one of `@[\\]^_?`")
+                        .into(),
+                };
+            },
+
+            c => return Ok(Self::backslashed(c)),
+        };
+
+        match char::from_u32(value).filter(|_| value <= 0xff) {
+            Some(c) => Ok(c),
+
+            None => ParsingError::invalid_escape(escape)
+                .because("out of range (more than `\\377` or `\\xff`)")
+                .into(),
+        }
+    }
+
+    /// Checks if the code contains the identifier (not as a part of a longer identifier)
+    pub fn contains_identifier(code: &str, identifier: &str) -> bool {
+        let is_identifier = |c: char| c.is_alphanumeric() || c == '_';
+
+        code.match_indices(identifier).any(|(index, _)| {
+            let before = code[..index].chars().next_back();
+            let after = code[index + identifier.len()..].chars().next();
+
+            !before.is_some_and(is_identifier) && !after.is_some_and(is_identifier)
+        })
+    }
+
+    /// Splits `str` on the whitespaces outside double quotes (`name="a b"` is a single word)
+    pub fn split_whitespace_quoted(str: &str) -> Vec<String> {
+        let mut words = Vec::new();
+        let mut word = String::new();
+        let mut quoted = false;
+
+        for c in str.chars() {
+            match c {
+                '"' => {
+                    quoted = !quoted;
+                    word.push(c);
+                }
+                c if c.is_ascii_whitespace() && !quoted => {
+                    if !word.is_empty() {
+                        words.push(take(&mut word));
+                    }
+                }
+                c => word.push(c),
+            }
+        }
+
+        if !word.is_empty() {
+            words.push(word);
+        }
+
+        words
+    }
+
     pub fn split_whitespace_once(str: &str) -> Option<(&str, &str)> {
         let index = str.find(|c: char| c.is_whitespace())?;
 
@@ -16,6 +16,21 @@ pub use dfa::*;
 pub mod nfa;
 pub use nfa::*;
 
+pub mod matcher;
+pub use matcher::*;
+
+pub mod trailing_context;
+pub use trailing_context::*;
+
+pub mod dot;
+pub use dot::*;
+
+pub mod utf8;
+pub use utf8::*;
+
+pub mod case;
+pub use case::*;
+
 use std::{collections::{HashSet, VecDeque}, fmt, ops, str::Chars};
 
 use super::*;
@@ -29,12 +44,32 @@ pub struct Regex {
     output_stack: Vec<parsing::RegexType>,
 }
 
+/// How the regular expressions of a specification are compiled
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RegexFlags {
+	/// The patterns are Unicode, matched on the bytes of their UTF-8 encoding (see `Regex::lower_utf8`)
+	pub utf8: bool,
+
+	/// The letters match both cases, out of the `(?-i:...)` groups (see `Regex::case_insensitive`)
+	pub case_insensitive: bool,
+
+	/// `.` and the negated classes match the bytes 128 to 255, not only ASCII (see `CharacterClass::eight_bit`)
+	pub eight_bit: bool,
+}
+
 // 6. REGEX PARSING IMPLEMENTATION
 // ==============================
 
 impl Regex {
-    pub fn new(expr: String, id: usize) -> ParsingResult<StatePtr> {
-        let tokens = Self::tokens(&expr)?;
+    /// Compiles `expr` to the NFA of the rule `id` (`Regex` only groups the compilation steps)
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(expr: String, id: usize) -> ParsingResult<Nfa> {
+        Self::with_flags(expr, id, RegexFlags::default())
+    }
+
+    /// Same as `new`, compiled according to `flags`
+    pub fn with_flags(expr: String, id: usize, flags: RegexFlags) -> ParsingResult<Nfa> {
+        let tokens = Self::tokens_with_flags(&expr, flags)?;
 
         let tokens_with_concatenation = Self::add_concatenation(tokens);
 
@@ -44,35 +79,51 @@ impl Regex {
 
         Ok(start)
     }
+
+    /// Same as `tokens`, lowered according to `flags`
+    pub fn tokens_with_flags(expr: &str, flags: RegexFlags) -> ParsingResult<VecDeque<parsing::RegexType>> {
+        let tokens = Self::parse_tokens(expr, flags)?;
+
+        match flags.utf8 {
+            true => Ok(Self::lower_utf8(tokens)),
+
+            false if flags.eight_bit => Ok(tokens.into_iter()
+                .map(|token| match token {
+                    parsing::RegexType::CharacterClass(class) => parsing::RegexType::CharacterClass(class.eight_bit()),
+                    token => token,
+                })
+                .collect()),
+
+            false => Ok(tokens),
+        }
+    }
 }
 
 
 // Function to print NFA structure iteratively
-pub fn print_state_structure(nfa: &StatePtr, title: &str) {
+pub fn print_state_structure(nfa: &Nfa, title: &str) {
 	println!("=== {} ===", title);
 	
 	let mut stack = Vec::new();
 	let mut visited = HashSet::new();
 	
 	// Start with the root state
-	stack.push((nfa.clone(), 0, String::from("root: ")));
+	stack.push((nfa.start, 0, String::from("root: ")));
 	
 	while let Some((state, depth, path)) = stack.pop() {
-		let state_ptr = &*state.borrow() as *const State;
-		
 		// Skip if already visited
-		if visited.contains(&state_ptr) && !(State::is_match_ptr(&state) || State::is_nomatch_ptr(&state) || State::is_none_ptr(&state)) {
+		if visited.contains(&state) && !(nfa[state].is_match() || nfa[state].is_nomatch()) {
 			continue;
 		}
 		
 		// Mark as visited
-		visited.insert(state_ptr);
+		visited.insert(state);
 		
 		// Indent based on depth
 		let indent = "|  ".repeat(depth);
 		
 		// Print state information
-		match &*state.borrow() {
+		match &nfa[state] {
 			State::Basic(basic) => {
 				let char_repr = match basic.c.char() {
 					Some(c) => format!("'{}'", c),
@@ -81,32 +132,29 @@ pub fn print_state_structure(nfa: &StatePtr, title: &str) {
 				println!("{}{}Basic: {}", indent, path, char_repr);
 				
 				// Add out state to stack
-				stack.push((basic.out.borrow().clone(), depth + 1, format!("out→")));
+				stack.extend(basic.out.map(|out| (out, depth + 1, String::from("out→"))));
 			},
 			State::Split(split) => {
 				println!("{}{}Split", indent, path);
 				
 				// Add both branches to stack
-				stack.push((split.out2.borrow().clone(), depth + 1, format!("out2→")));
-				stack.push((split.out1.borrow().clone(), depth + 1, format!("out1→")));
+				stack.extend(split.out2.map(|out| (out, depth + 1, String::from("out2→"))));
+				stack.extend(split.out1.map(|out| (out, depth + 1, String::from("out1→"))));
 			},
 			State::Match { id } => {
 				println!("{}{}Match({})", indent, path, id);
 			},
 			State::StartOfLine { out } => {
 				println!("{}{}StartOfLine", indent, path);
-				stack.push((out.borrow().clone(), depth + 1, format!("out→")));
+				stack.extend(out.map(|out| (out, depth + 1, String::from("out→"))));
 			},
 			State::EndOfLine { out } => {
 				println!("{}{}EndOfLine", indent, path);
-				stack.push((out.borrow().clone(), depth + 1, format!("out→")));
+				stack.extend(out.map(|out| (out, depth + 1, String::from("out→"))));
 			},
 			State::NoMatch => {
 				println!("{}{}NoMatch", indent, path);
 			},
-			State::None => {
-				println!("{}{}None", indent, path);
-			},
 		}
 	}
 	
@@ -139,7 +187,7 @@ pub fn print_dfa_structure(dfa: &dfa::Dfa, title: &str) {
         
         // Print match information
         if state.is_match() {
-            println!("  Match State (ID: {})", state.match_id().unwrap());
+            println!("  Match State (ID: {})", state.match_id(&dfa.nfa).unwrap());
         }
         
         // Print transitions
@@ -164,8 +212,7 @@ pub fn print_dfa_structure(dfa: &dfa::Dfa, title: &str) {
         // Print NFA states contained in this DFA state
         println!("  Contains NFA states:");
         for (i, nfa_state) in state.states.enumerate() {
-            println!("  NFA State {}:", i);
-            print_state_structure(nfa_state, &format!("NFA State in DFA {}", state.id));
+            println!("  NFA State {}: {}", i, dfa.nfa[nfa_state]);
         }
         
         println!();
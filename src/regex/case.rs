@@ -0,0 +1,73 @@
+use super::*;
+
+impl Regex {
+	/// The token `token` matching both cases of its chars (`%option case-insensitive`, `-i` or `(?i:...)`):
+	/// a char with another case becomes the class of its cases, a class gets the other case of each of its chars.
+	///
+	/// Only the ASCII letters have cases, unless `unicode` is set (--utf8: the chars are then Unicode, not bytes)
+	pub fn case_insensitive(token: RegexType, unicode: bool) -> RegexType {
+		match token {
+			RegexType::Char(c) => {
+				let cases = other_cases(c, unicode);
+
+				if cases.is_empty() {
+					return RegexType::Char(c);
+				}
+
+				let mut class = CharacterClass::from_single(c);
+				class.chars.extend(cases);
+
+				RegexType::CharacterClass(class)
+			},
+
+			RegexType::CharacterClass(class) => RegexType::CharacterClass(class.case_insensitive(unicode)),
+
+			token => token,
+		}
+	}
+}
+
+impl CharacterClass {
+	/// The class with both cases of its chars (see `Regex::case_insensitive`).
+	///
+	/// A negated class gets them too, so `[^a]` matches neither `a` nor `A`
+	pub fn case_insensitive(mut self, unicode: bool) -> Self {
+		let mut chars: HashSet<char> = self.chars.iter().copied().collect();
+
+		for index in 0..self.chars.len() {
+			for c in other_cases(self.chars[index], unicode) {
+				if chars.insert(c) {
+					self.chars.push(c);
+				}
+			}
+		}
+
+		self
+	}
+}
+
+/// The other cases of `c` (its single char lowercase and uppercase, if different)
+pub fn other_cases(c: char, unicode: bool) -> Vec<char> {
+	if !unicode || c.is_ascii() {
+		return match c.is_ascii_alphabetic() {
+			true => vec![if c.is_ascii_lowercase() { c.to_ascii_uppercase() } else { c.to_ascii_lowercase() }],
+			false => vec![],
+		};
+	}
+
+	// the mappings to several chars (like `ß` to `SS`) are ignored
+	fn single(mut mapping: impl Iterator<Item = char>) -> Option<char> {
+		let c = mapping.next()?;
+		mapping.next().is_none().then_some(c)
+	}
+
+	let mut cases = vec![];
+
+	for case in [single(c.to_lowercase()), single(c.to_uppercase())].into_iter().flatten() {
+		if case != c && !cases.contains(&case) {
+			cases.push(case);
+		}
+	}
+
+	cases
+}
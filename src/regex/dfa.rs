@@ -2,6 +2,11 @@ use std::{cell::RefCell, collections::{BTreeMap, HashMap}, rc::Rc, usize};
 use super::*;
 
 pub mod normalizer;
+pub mod minimizer;
+pub mod classes;
+pub mod lazy;
+
+pub use classes::*;
 
 pub type DfaStatePtr = Rc<RefCell<DfaState>>;
 
@@ -16,9 +21,7 @@ pub fn merge_input_maps(
     for (input, state_list2) in map2 {
         if let Some(state_list1) = map1.get_mut(&input) {
             // If the key exists in both maps, merge the StateLists
-            let mut merged = state_list1.clone();
-            merged.merge(state_list2);
-            *state_list1 = merged;
+            state_list1.merge(state_list2);
         } else {
             // If the key only exists in map2, add it to the map1
             map1.insert(input, state_list2);
@@ -31,31 +34,230 @@ pub enum InputCondition {
 	StartOfLine,
 	EndOfLine,
 	Char(char),
+
+	/// Any character of an equivalence class (see `EquivalenceClasses`)
+	Class(usize),
 }
 
 #[derive(Debug)]
 pub struct Dfa {
+	/// The first start state
 	pub start: DfaStatePtr,
 
+	/// One start state per list of NFAs given to the constructor
+	pub starts: Vec<DfaStatePtr>,
+
 	pub memory: HashMap<StateList, DfaStatePtr>,
+
+	/// The states of all the NFAs given to the constructor, in a single arena
+	/// (the state lists of `memory` are ids in this arena)
+	pub nfa: Nfa,
+
+	/// Trailing contexts of the rules, by rule id
+	pub trailing_contexts: HashMap<usize, TrailingContext>,
+
+	/// Equivalence classes of the input characters, if the transitions are on classes
+	/// instead of single characters
+	pub classes: Option<EquivalenceClasses>,
 }
 
-impl Dfa {
-	pub fn new(starts: Vec<StatePtr>) -> Self {
-		let mut list = StateList::new();
+/// The subset construction needed more DFA states than its limit
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StateLimitExceeded {
+	pub limit: usize,
 
-		for state in &starts {
-			list.add_state(state);
+	/// Index of the NFA making the construction explode: the one with the most distinct
+	/// sets of its states in the DFA states built before the construction stopped
+	pub nfa: usize,
+}
+
+/// NFAs and start states of the rules (see `Dfa::from_rules`)
+pub struct RulesAutomaton<'a> {
+	/// The NFAs of the rules, followed by the heads and tails of the variable trailing contexts
+	pub nfas: Vec<&'a Nfa>,
+
+	/// Index of the rule of each NFA of `nfas`
+	pub owners: Vec<usize>,
+
+	pub starts: Vec<Vec<usize>>,
+
+	pub trailing_contexts: HashMap<usize, TrailingContext>,
+
+	pub classes: EquivalenceClasses,
+}
+
+impl<'a> RulesAutomaton<'a> {
+	pub fn new(rules: &'a [Rule], definitions: &Definitions) -> Self {
+		let mut nfas: Vec<&Nfa> = rules.iter().map(|rule| &rule.regex_nfa).collect();
+		let mut owners: Vec<usize> = (0..rules.len()).collect();
+
+		let mut starts: Vec<Vec<usize>> = definitions.start_conditions()
+			.into_iter()
+			.map(|condition| {
+				let type_ = definitions.states[condition];
+
+				(0..rules.len())
+					.filter(|index| rules[*index].is_active_in(condition, type_))
+					.collect()
+			})
+			.collect();
+
+		let mut trailing_contexts = HashMap::new();
+
+		for (index, rule) in rules.iter().enumerate() {
+			if let Some(trailing_context) = &rule.trailing_context {
+				if trailing_context.is_variable() {
+					nfas.push(&trailing_context.head);
+					starts.push(vec![nfas.len() - 1]);
+
+					nfas.push(&trailing_context.tail);
+					starts.push(vec![nfas.len() - 1]);
+
+					owners.extend([index, index]);
+				}
+
+				trailing_contexts.insert(index + 1, trailing_context.clone());
+			}
 		}
 
-		let (start, memory) = DfaState::iterative_create(list);
+		let classes = EquivalenceClasses::from_nfas(&nfas);
+
+		Self { nfas, owners, starts, trailing_contexts, classes }
+	}
+}
+
+impl Dfa {
+	pub fn new(nfas: &[&Nfa]) -> Self {
+		Self::with_starts(nfas, vec![(0..nfas.len()).collect()])
+	}
+
+	/// Builds a DFA with several start states, sharing their common states.
+	///
+	/// Each start state matches the NFAs of its list (indexes in `nfas`).
+	pub fn with_starts(nfas: &[&Nfa], starts: Vec<Vec<usize>>) -> Self {
+		Self::build(nfas, starts, None, None).expect("no state limit")
+	}
+
+	/// Same as `with_starts`, with transitions on the equivalence classes of the characters
+	/// (one `InputCondition::Class` per class instead of one `InputCondition::Char` per character)
+	pub fn with_classes(nfas: &[&Nfa], starts: Vec<Vec<usize>>, classes: EquivalenceClasses) -> Self {
+		Self::build(nfas, starts, Some(classes), None).expect("no state limit")
+	}
+
+	/// Same as `with_classes`, stopped if more than `limit` DFA states are needed
+	pub fn with_state_limit(nfas: &[&Nfa], starts: Vec<Vec<usize>>, classes: EquivalenceClasses, limit: usize) -> Result<Self, StateLimitExceeded> {
+		Self::build(nfas, starts, Some(classes), Some(limit))
+	}
+
+	fn build(nfas: &[&Nfa], starts: Vec<Vec<usize>>, classes: Option<EquivalenceClasses>, limit: Option<usize>) -> Result<Self, StateLimitExceeded> {
+		let (nfa, nfa_starts) = Nfa::merge(nfas);
+		let closures = EpsilonClosures::owned(nfa);
+
+		let lists = starts.iter()
+			.map(|indexes| {
+				let mut list = StateList::new();
+
+				for index in indexes {
+					closures.add_to(&mut list, nfa_starts[*index]);
+				}
 
-		Dfa {
-			start,
-			memory
+				list
+			})
+			.collect();
+
+		let (starts, memory) = DfaState::iterative_create_all_in(&closures, lists, classes.as_ref(), limit)
+			.map_err(|memory| StateLimitExceeded {
+				limit: limit.unwrap_or(usize::MAX),
+				nfa: Self::biggest_nfa(nfas, memory.keys()),
+			})?;
+
+		Ok(Dfa {
+			start: Rc::clone(&starts[0]),
+			starts,
+			memory,
+			nfa: closures.into_nfa(),
+			trailing_contexts: HashMap::new(),
+			classes,
+		})
+	}
+
+	/// Index of the NFA of `nfas` with the most distinct sets of its states in `lists`
+	/// (the lists being ids in the arena of the merged NFAs, see `Nfa::merge`)
+	fn biggest_nfa<'a>(nfas: &[&Nfa], lists: impl Iterator<Item = &'a StateList>) -> usize {
+		let offsets: Vec<usize> = nfas.iter()
+			.scan(0, |offset, nfa| {
+				*offset += nfa.len();
+				Some(*offset - nfa.len())
+			})
+			.collect();
+
+		let mut sets: Vec<HashSet<Vec<StateId>>> = vec![HashSet::new(); nfas.len()];
+
+		for list in lists {
+			let mut current: Option<(usize, Vec<StateId>)> = None;
+
+			// the states are in increasing order, so grouped by NFA
+			for state in list {
+				let index = offsets.partition_point(|offset| *offset <= state) - 1;
+
+				match &mut current {
+					Some((current_index, states)) if *current_index == index => states.push(state),
+
+					_ => {
+						if let Some((current_index, states)) = current.replace((index, vec![state])) {
+							sets[current_index].insert(states);
+						}
+					},
+				}
+			}
+
+			if let Some((index, states)) = current {
+				sets[index].insert(states);
+			}
 		}
+
+		// the first one on equality
+		(0..nfas.len())
+			.max_by_key(|index| (sets[*index].len(), std::cmp::Reverse(*index)))
+			.unwrap_or(0)
 	}
 
+	/// Builds the DFA of the rules, with one start state per start condition
+	/// (in the order of `Definitions::start_conditions`).
+	///
+	/// The rules without start condition are active in the inclusive conditions only.
+	///
+	/// The start states of the conditions are followed by the head and tail start states
+	/// of each rule with a variable trailing context (see `TrailingContext::is_variable`).
+	///
+	/// The transitions are on the equivalence classes of the characters of all the rules.
+	pub fn from_rules(rules: &[Rule], definitions: &Definitions) -> Self {
+		let automaton = RulesAutomaton::new(rules, definitions);
+
+		let mut dfa = Self::with_classes(&automaton.nfas, automaton.starts, automaton.classes);
+		dfa.trailing_contexts = automaton.trailing_contexts;
+
+		dfa
+	}
+
+	/// Same as `from_rules`, with an error naming the rule that makes the automaton explode
+	/// if more than `limit` DFA states are needed (see `Definitions::dfa_state_limit`)
+	pub fn try_from_rules(rules: &[Rule], definitions: &Definitions, limit: usize) -> ParsingResult<Self> {
+		let automaton = RulesAutomaton::new(rules, definitions);
+
+		let mut dfa = Self::with_state_limit(&automaton.nfas, automaton.starts, automaton.classes, limit)
+			.map_err(|exceeded| {
+				let rule = &rules[automaton.owners[exceeded.nfa]];
+
+				ParsingError::dfa_state_limit_exceeded(exceeded.limit, &rule.pattern)
+					.file(&rule.file)
+					.line(rule.line_index)
+			})?;
+
+		dfa.trailing_contexts = automaton.trailing_contexts;
+
+		Ok(dfa)
+	}
 }
 
 #[derive(Debug)]
@@ -70,11 +272,12 @@ pub struct DfaState {
 }
 
 impl DfaState {
-	pub fn new(id: usize, mut states: StateList) -> Self {
+	/// DFA state of the states `states` of `nfa` (the match states are moved to `matchs`)
+	pub fn new(id: usize, mut states: StateList, nfa: &Nfa) -> Self {
 		let mut matchs = StateList::new();
 
-		for match_ in states.remove_matchs() {
-			matchs.add_state(&match_);
+		for match_ in states.remove_matchs(nfa) {
+			matchs.push(match_);
 		}
 
 		DfaState {
@@ -87,70 +290,113 @@ impl DfaState {
 	}
 
 	#[deprecated(note="please use `iterative_create` instead")]
-	pub fn recursive_create(states: StateList, memory: &mut HashMap<StateList, DfaStatePtr>) -> DfaStatePtr {
+	pub fn recursive_create(nfa: &Nfa, states: StateList, memory: &mut HashMap<StateList, DfaStatePtr>) -> DfaStatePtr {
+		DfaState::recursive_create_in(&EpsilonClosures::new(nfa), states, memory)
+	}
+
+	fn recursive_create_in(closures: &EpsilonClosures, states: StateList, memory: &mut HashMap<StateList, DfaStatePtr>) -> DfaStatePtr {
 		if let Some(next) = memory.get(&states) {
 			return Rc::clone(next)
 		}
 
-		let mut states = DfaState::new(memory.len(), states);
+		let mut states = DfaState::new(memory.len(), states, closures.nfa());
 
-		states.compute_next();
+		states.compute_next_in(closures, None);
 
 		let states = Rc::new(RefCell::new(states));
 
 		memory.insert(states.borrow().states.clone(), Rc::clone(&states));
 
-		for (_condition, list) in &states.borrow().next {
-			DfaState::recursive_create(list.clone(), memory);
+		for list in states.borrow().next.values() {
+			DfaState::recursive_create_in(closures, list.clone(), memory);
 		}
 
 		states
 	}
 
-	pub fn iterative_create(start_states: StateList) -> (DfaStatePtr, HashMap<StateList, DfaStatePtr>) {
-		let mut memory = HashMap::new();
-		let mut work_queue = VecDeque::new();
-		
-		// Create and process the initial state
-		let start = DfaState::new(0, start_states.clone());
-		let start_ptr = Rc::new(RefCell::new(start));
+	pub fn iterative_create(nfa: &Nfa, start_states: StateList) -> (DfaStatePtr, HashMap<StateList, DfaStatePtr>) {
+		let (mut starts, memory) = DfaState::iterative_create_all(nfa, vec![start_states]);
 
-		memory.insert(start_states.clone(), Rc::clone(&start_ptr));
+		(starts.remove(0), memory)
+	}
 
-		// Add initial transitions to work queue
-		start_ptr.borrow_mut().compute_next();
-		
-		for (_, list) in &start_ptr.borrow().next {
-			if !memory.contains_key(list) && !work_queue.contains(list) {
-				work_queue.push_back(list.clone());
+	/// Same as `iterative_create`, with several start states sharing the same memory.
+	///
+	/// The start states are returned in the order of `start_lists`
+	/// (the same DFA state is returned for identical lists).
+	pub fn iterative_create_all(nfa: &Nfa, start_lists: Vec<StateList>) -> (Vec<DfaStatePtr>, HashMap<StateList, DfaStatePtr>) {
+		DfaState::iterative_create_all_in(&EpsilonClosures::new(nfa), start_lists, None, None)
+			.expect("no state limit")
+	}
+
+	/// Same as `iterative_create_all`, with transitions on the equivalence classes `classes` if any.
+	///
+	/// The NFA states are read from `closures`, so each epsilon closure is computed once.
+	/// The DFA states are numbered when they are first reached (in breadth-first order).
+	///
+	/// The construction stops if more than `limit` DFA states are needed:
+	/// the states built so far are then returned as an error.
+	#[allow(clippy::type_complexity)]
+	pub fn iterative_create_all_in(
+		closures: &EpsilonClosures,
+		start_lists: Vec<StateList>,
+		classes: Option<&EquivalenceClasses>,
+		limit: Option<usize>,
+	) -> Result<(Vec<DfaStatePtr>, HashMap<StateList, DfaStatePtr>), HashMap<StateList, DfaStatePtr>> {
+		let nfa = closures.nfa();
+		let mut memory: HashMap<StateList, DfaStatePtr> = HashMap::new();
+		let mut work_queue = VecDeque::new();
+
+		// Create the start states first, so they have the lowest ids
+		for start_states in &start_lists {
+			if memory.contains_key(start_states) {
+				continue;
 			}
+
+			let start = DfaState::new(memory.len(), start_states.clone(), nfa);
+			let start_ptr = Rc::new(RefCell::new(start));
+
+			memory.insert(start_states.clone(), Rc::clone(&start_ptr));
+			work_queue.push_back(start_ptr);
 		}
-		
-		// Process work queue iteratively
-		while let Some(state_list) = work_queue.pop_front() {
-			let dfa_state = DfaState::new(memory.len(), state_list.clone());
-			let state_ptr = Rc::new(RefCell::new(dfa_state));
-			
-			memory.insert(state_list, Rc::clone(&state_ptr));
-			state_ptr.borrow_mut().compute_next();
-			
-			// Add new states to work queue
-			for (_, list) in &state_ptr.borrow().next {
-				if !memory.contains_key(list) && !work_queue.contains(list) {
-					work_queue.push_back(list.clone());
+
+		// Process work queue iteratively, each state list being looked up once per transition
+		while let Some(state_ptr) = work_queue.pop_front() {
+			state_ptr.borrow_mut().compute_next_in(closures, classes);
+
+			for list in state_ptr.borrow().next.values() {
+				if memory.contains_key(list) {
+					continue;
 				}
+
+				if limit.is_some_and(|limit| memory.len() >= limit) {
+					return Err(memory);
+				}
+
+				let dfa_state = DfaState::new(memory.len(), list.clone(), nfa);
+				let next_ptr = Rc::new(RefCell::new(dfa_state));
+
+				memory.insert(list.clone(), Rc::clone(&next_ptr));
+				work_queue.push_back(next_ptr);
 			}
 		}
 		
-		// Return the start state
-		(Rc::clone(&memory[&start_states]), memory)
+		// Return the start states
+		let starts = start_lists.iter()
+			.map(|start_states| Rc::clone(&memory[start_states]))
+			.collect();
+
+		Ok((starts, memory))
+	}
+
+	pub fn compute_next(&mut self, nfa: &Nfa) {
+		self.compute_next_in(&EpsilonClosures::new(nfa), None)
 	}
 
-	pub fn compute_next(&mut self) {
+	/// Same as `compute_next`, with transitions on the equivalence classes `classes` if any
+	pub fn compute_next_in(&mut self, closures: &EpsilonClosures, classes: Option<&EquivalenceClasses>) {
 		for state in &self.states {
-			let (next_states, matchs) = DfaState::find_next(state, &self.states);
-			merge_input_maps(&mut self.next, next_states);
-			self.matchs.merge(matchs);
+			DfaState::add_next_in(closures, state, &self.states, classes, &mut self.next, &mut self.matchs);
 		}
 	}
 
@@ -161,94 +407,105 @@ impl DfaState {
 	///
 	/// # Arguments
 	///
-	/// * `state` - A reference to the current state pointer to analyze
+	/// * `nfa` - The arena of the states
+	/// * `state` - The id of the state to analyze
 	///
 	/// # Returns
 	///
 	/// A tuple containing:
 	/// * A HashMap mapping input conditions to the states reachable under those conditions
 	/// * A StateList containing any match states encountered
-	pub fn find_next(state: &StatePtr, current_states: &StateList) -> (HashMap<InputCondition, StateList>, StateList) {
+	pub fn find_next(nfa: &Nfa, state: StateId, current_states: &StateList) -> (HashMap<InputCondition, StateList>, StateList) {
+		DfaState::find_next_in(&EpsilonClosures::new(nfa), state, current_states, None)
+	}
+
+	/// Same as `find_next`, with transitions on the equivalence classes `classes` if any
+	/// (the NFA states and their epsilon closures are read from `closures`)
+	pub fn find_next_in(closures: &EpsilonClosures, state: StateId, current_states: &StateList, classes: Option<&EquivalenceClasses>) -> (HashMap<InputCondition, StateList>, StateList) {
 		let mut next_states: HashMap<InputCondition, StateList> = HashMap::new();
 		let mut matchs: StateList = StateList::new();
 
-		match &*state.borrow() {
+		DfaState::add_next_in(closures, state, current_states, classes, &mut next_states, &mut matchs);
+
+		(next_states, matchs)
+	}
+
+	/// Same as `find_next_in`, adding the transitions to `next_states` and the match states to `matchs`
+	fn add_next_in(
+		closures: &EpsilonClosures,
+		state: StateId,
+		current_states: &StateList,
+		classes: Option<&EquivalenceClasses>,
+		next_states: &mut HashMap<InputCondition, StateList>,
+		matchs: &mut StateList,
+	) {
+		let nfa = closures.nfa();
+
+		match &nfa[state] {
 			State::Basic(basic) => {
-				if !State::is_none_var_ptr(&basic.out) && !State::is_nomatch_var_ptr(&basic.out) {
-					if let RegexType::CharacterClass(class) = &basic.c {
-						let next_state = basic.out.borrow();
-						for c in class.chars() {
-							let condition = InputCondition::Char(c);
-							let list = next_states.entry(condition).or_insert_with(|| StateList::new());
-							list.add_state(&next_state);
-						}
-					} else {
-						let condition = InputCondition::Char(basic.c.char().expect("Basic state should have a char"));
-						let list = next_states.entry(condition).or_insert_with(|| StateList::new());
-						list.add_state(&basic.out.borrow());
+				if let Some(out) = basic.out.filter(|out| !nfa[*out].is_nomatch()) {
+					let conditions: Vec<InputCondition> = match (classes, &basic.c) {
+						(Some(classes), _) => classes.of_state(state).iter().copied().map(InputCondition::Class).collect(),
+						(None, RegexType::CharacterClass(class)) => class.chars().into_iter().map(InputCondition::Char).collect(),
+						(None, c) => vec![InputCondition::Char(c.char().expect("Basic state should have a char"))],
+					};
+
+					for condition in conditions {
+						let list = next_states.entry(condition).or_default();
+						closures.add_to(list, out);
 					}
 				}
 			},
 
 			State::Split(split) => {
-				let (next_states_1, matchs_1) = DfaState::find_next(&*State::deref_var_ptr(&split.out1), current_states);
-				let (next_states_2, matchs_2) = DfaState::find_next(&*State::deref_var_ptr(&split.out2), current_states);
-
-				matchs.merge(matchs_1);
-				matchs.merge(matchs_2);
-
-				for (condition, next_list) in next_states_1 {
-					let list = next_states.entry(condition).or_insert_with(|| StateList::new());
-					
-					list.merge(next_list);
-				}
-
-				for (condition, next_list) in next_states_2 {
-					let list = next_states.entry(condition).or_insert_with(|| StateList::new());
-					
-					list.merge(next_list);
+				for out in [split.out1, split.out2].into_iter().flatten() {
+					DfaState::add_next_in(closures, out, current_states, classes, next_states, matchs);
 				}
-				
 			},
 
 			State::StartOfLine { out } => {
-				let list = next_states.entry(InputCondition::StartOfLine).or_insert_with(|| StateList::new());
-				list.add_state(&*out.borrow());
+				let list = next_states.entry(InputCondition::StartOfLine).or_default();
+
+				if let Some(out) = out {
+					closures.add_to(list, *out);
+				}
 
 				current_states.iter().for_each(|state|
-					if !State::is_end_of_line_ptr(state) {
-						list.add_state(state)
+					if !nfa[state].is_end_of_line() {
+						closures.add_to(list, state)
 					}
 				);
 			},
 
 			State::EndOfLine { out } => {
-				let list = next_states.entry(InputCondition::EndOfLine).or_insert_with(|| StateList::new());
-				list.add_state(&*out.borrow());
+				let list = next_states.entry(InputCondition::EndOfLine).or_default();
+
+				if let Some(out) = out {
+					closures.add_to(list, *out);
+				}
 
 				// if end of line, states
 				current_states.iter().for_each(|state|
-					if !State::is_start_of_line_ptr(state) {
-						list.add_state(state)
+					if !nfa[state].is_start_of_line() {
+						closures.add_to(list, state)
 					}
 				);
 			},
 
 			State::Match {..} => {
-				matchs.add_state(state);
+				matchs.push(state);
 			},
 
-			_ => { eprintln!("Unhandled state: {:?}", state); }
+			State::NoMatch => {},
 		}
-
-		(next_states, matchs)
 	}
 
 	pub fn is_match(&self) -> bool {
 		self.matchs.is_empty() == false
 	}
 
-	pub fn match_id(&self) -> Option<usize> {
+	/// Id of the matched rule (the lowest one), the match states being in `nfa`
+	pub fn match_id(&self, nfa: &Nfa) -> Option<usize> {
 
 		if self.matchs.is_empty() {
 			return  None;
@@ -257,7 +514,7 @@ impl DfaState {
 		let mut match_ = usize::MAX;
 
 		for state in &self.matchs {
-			match &*state.borrow() {
+			match &nfa[state] {
 
 				State::Match { id } => {
 					if *id < match_ {
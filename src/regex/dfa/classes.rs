@@ -0,0 +1,134 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use super::*;
+
+/// Partition of the input characters in equivalence classes:
+/// two characters are in the same class if every character class and literal of the rules
+/// contains both of them or none of them, so the automaton cannot tell them apart.
+///
+/// The characters used by no rule are in no class.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EquivalenceClasses {
+	/// Characters of each class, sorted (the classes are sorted by their first character)
+	classes: Vec<Vec<char>>,
+
+	/// Class of each character
+	class_of: HashMap<char, usize>,
+
+	/// Classes read by each state, by id in the arena of the merged NFAs (see `Nfa::merge`):
+	/// the construction of the DFA reads them instead of the characters of the states
+	state_classes: Vec<Vec<usize>>,
+}
+
+impl EquivalenceClasses {
+	/// Computes the classes of the characters of all the states of `nfas`
+	///
+	/// The DFA must be built from the same `nfas`, in the same order (see `EquivalenceClasses::of_state`)
+	pub fn from_nfas(nfas: &[&Nfa]) -> Self {
+		let state_sets = Self::state_sets(nfas);
+		let sets = Self::character_sets(&state_sets);
+
+		// the sets containing each character
+		let mut signatures: BTreeMap<char, Vec<usize>> = BTreeMap::new();
+
+		for (index, set) in sets.iter().enumerate() {
+			for c in *set {
+				signatures.entry(*c).or_default().push(index);
+			}
+		}
+
+		let mut classes: Vec<Vec<char>> = vec![];
+		let mut by_signature: HashMap<Vec<usize>, usize> = HashMap::new();
+		let mut class_of = HashMap::with_capacity(signatures.len());
+
+		// in the order of the characters, so the classes are sorted by their first character
+		for (c, signature) in signatures {
+			let class = *by_signature.entry(signature).or_insert_with(|| {
+				classes.push(vec![]);
+				classes.len() - 1
+			});
+
+			classes[class].push(c);
+			class_of.insert(c, class);
+		}
+
+		let mut equivalence_classes = Self { classes, class_of, state_classes: vec![] };
+
+		// the classes of each set are computed once, whatever the number of states reading it
+		let set_classes: HashMap<&[char], Vec<usize>> = sets.iter()
+			.map(|set| (*set, equivalence_classes.classes_of(set)))
+			.collect();
+
+		equivalence_classes.state_classes = state_sets.iter()
+			.map(|set| set.as_deref().map(|set| set_classes[set].clone()).unwrap_or_default())
+			.collect();
+
+		equivalence_classes
+	}
+
+	/// The set of characters read by each state of `nfas` (sorted, None if the state reads nothing),
+	/// in the order of the arena of the merged NFAs
+	fn state_sets(nfas: &[&Nfa]) -> Vec<Option<Vec<char>>> {
+		nfas.iter()
+			.flat_map(|nfa| &nfa.states)
+			.map(|state| {
+				let basic = state.into_basic()?;
+
+				let mut set = match &basic.c {
+					RegexType::CharacterClass(class) => class.chars(),
+					c => vec![c.char().expect("Basic state should have a char")],
+				};
+
+				set.sort();
+				set.dedup();
+
+				Some(set)
+			})
+			.collect()
+	}
+
+	/// The different sets of characters of `state_sets`, sorted
+	fn character_sets(state_sets: &[Option<Vec<char>>]) -> Vec<&[char]> {
+		let sets: HashSet<&[char]> = state_sets.iter().flatten().map(Vec::as_slice).collect();
+
+		let mut sets: Vec<&[char]> = sets.into_iter().collect();
+		sets.sort();
+
+		sets
+	}
+
+	/// Class of the character `c` (None if no rule reads it)
+	pub fn class_of(&self, c: char) -> Option<usize> {
+		self.class_of.get(&c).copied()
+	}
+
+	/// Characters of the class `class`
+	pub fn chars(&self, class: usize) -> &[char] {
+		&self.classes[class]
+	}
+
+	/// Classes of the characters of `chars` (each one once, sorted)
+	pub fn classes_of(&self, chars: &[char]) -> Vec<usize> {
+		let mut classes: Vec<usize> = chars.iter()
+			.filter_map(|c| self.class_of(*c))
+			.collect();
+
+		classes.sort();
+		classes.dedup();
+
+		classes
+	}
+
+	/// Classes read by the state `state` of the merged NFAs (each one once, sorted)
+	pub fn of_state(&self, state: StateId) -> &[usize] {
+		&self.state_classes[state]
+	}
+
+	pub fn len(&self) -> usize {
+		self.classes.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.classes.is_empty()
+	}
+}
@@ -0,0 +1,155 @@
+use super::*;
+use normalizer::*;
+
+/// DFA of the rules built on demand, for the automatons too big to be built at once
+/// (see `Dfa::try_from_rules`).
+///
+/// A state is built the first time it is reached by an input: its transitions are then
+/// computed and cached, the states they lead to being only numbered until they are reached.
+/// The states built so far are a `NormalizedDfa`, so they are run by `matches_from`
+pub struct LazyDfa {
+	closures: EpsilonClosures<'static>,
+
+	/// Number of each state list reached so far (the full lists, with their match states)
+	ids: HashMap<StateList, usize>,
+
+	/// State lists by number
+	lists: Vec<StateList>,
+
+	/// The states whose transitions are computed
+	dfa: NormalizedDfa,
+}
+
+impl LazyDfa {
+	/// Same start states as `Dfa::from_rules`, without any other state
+	pub fn from_rules(rules: &[Rule], definitions: &Definitions) -> Self {
+		let automaton = RulesAutomaton::new(rules, definitions);
+		let (nfa, nfa_starts) = Nfa::merge(&automaton.nfas);
+
+		let mut lazy = LazyDfa {
+			closures: EpsilonClosures::owned(nfa),
+			ids: HashMap::new(),
+			lists: vec![],
+			dfa: NormalizedDfa {
+				start_id: 0,
+				starts: vec![],
+				states: HashMap::new(),
+				matchs: HashMap::new(),
+				trailing_contexts: automaton.trailing_contexts,
+				classes: Some(automaton.classes),
+			},
+		};
+
+		for indexes in &automaton.starts {
+			let mut list = StateList::new();
+
+			for index in indexes {
+				lazy.closures.add_to(&mut list, nfa_starts[*index]);
+			}
+
+			let id = lazy.id_of(list);
+			lazy.dfa.starts.push(id);
+		}
+
+		lazy.dfa.start_id = lazy.dfa.starts[0];
+
+		lazy
+	}
+
+	/// The states built so far
+	pub fn dfa(&self) -> &NormalizedDfa {
+		&self.dfa
+	}
+
+	/// Number of states built so far (the states only numbered are not counted)
+	pub fn len(&self) -> usize {
+		self.dfa.states.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.dfa.states.is_empty()
+	}
+
+	/// Same as `normalizer::matches_from`, the states reached by `str` being built first
+	pub fn matches_from(&mut self, str: &str, start: usize, at_bol: bool) -> Vec<Match> {
+		self.build_along(str, start, at_bol);
+
+		matches_from(str, &self.dfa, start, at_bol)
+	}
+
+	/// Builds the states reached by `str` from `start`, following the same path as `matches_from`
+	fn build_along(&mut self, str: &str, start: usize, at_bol: bool) {
+		let mut current = self.build(start);
+
+		if at_bol {
+			current = self.follow(current, InputCondition::StartOfLine).unwrap_or(current);
+		}
+
+		let mut chars = str.chars().peekable();
+
+		while let Some(c) = chars.next() {
+			let Some(input) = self.dfa.input_of(c) else {
+				break
+			};
+
+			current = match self.follow(current, input) {
+				Some(next) => next,
+				None => break,
+			};
+
+			if chars.peek() == Some(&'\n') {
+				current = self.follow(current, InputCondition::EndOfLine).unwrap_or(current);
+			}
+		}
+	}
+
+	/// Builds the state reached from the state `id` (already built) on `input`, if any
+	fn follow(&mut self, id: usize, input: InputCondition) -> Option<usize> {
+		let next = *self.dfa.states[&id].next.get(&input)?;
+
+		Some(self.build(next))
+	}
+
+	/// Computes the transitions of the state `id` if they are not yet
+	fn build(&mut self, id: usize) -> usize {
+		if self.dfa.states.contains_key(&id) {
+			return id;
+		}
+
+		let nfa = self.closures.nfa();
+
+		let mut state = DfaState::new(id, self.lists[id].clone(), nfa);
+		state.compute_next_in(&self.closures, self.dfa.classes.as_ref());
+
+		let mut matchs = HashSet::new();
+
+		for match_ in &state.matchs {
+			if let State::Match { id } = &nfa[match_] {
+				matchs.insert(*id);
+				self.dfa.matchs.entry(*id).or_insert(match_);
+			}
+		}
+
+		let next = state.next.into_iter()
+			.map(|(condition, list)| (condition, self.id_of(list)))
+			.collect();
+
+		self.dfa.states.insert(id, NormalizedState::new(id, matchs, next));
+
+		id
+	}
+
+	/// Number of the state list `list`, numbered now if it was never reached
+	fn id_of(&mut self, list: StateList) -> usize {
+		if let Some(id) = self.ids.get(&list) {
+			return *id;
+		}
+
+		let id = self.lists.len();
+
+		self.ids.insert(list.clone(), id);
+		self.lists.push(list);
+
+		id
+	}
+}
@@ -0,0 +1,205 @@
+use std::collections::{HashMap, HashSet};
+
+use super::*;
+use super::normalizer::*;
+
+/// Partition of the states of the automaton (Hopcroft's algorithm)
+struct Partition {
+	blocks: Vec<Vec<usize>>,
+
+	/// Block of each state
+	block_of: Vec<usize>,
+}
+
+impl Partition {
+	fn new(blocks: Vec<Vec<usize>>, len: usize) -> Self {
+		let mut block_of = vec![0; len];
+
+		for (index, block) in blocks.iter().enumerate() {
+			for state in block {
+				block_of[*state] = index;
+			}
+		}
+
+		Self { blocks, block_of }
+	}
+
+	/// Splits the blocks containing some of the `marked` states (and some others).
+	///
+	/// Returns the (kept, new) indexes of the split blocks
+	fn split(&mut self, marked: &HashSet<usize>) -> Vec<(usize, usize)> {
+		let mut touched: HashMap<usize, Vec<usize>> = HashMap::new();
+
+		for state in marked {
+			touched.entry(self.block_of[*state]).or_default().push(*state);
+		}
+
+		let mut splits = vec![];
+
+		for (index, inside) in touched {
+			if inside.len() == self.blocks[index].len() {
+				continue;
+			}
+
+			let outside: Vec<usize> = self.blocks[index].iter()
+				.copied()
+				.filter(|state| !marked.contains(state))
+				.collect();
+
+			let new_index = self.blocks.len();
+
+			for state in &inside {
+				self.block_of[*state] = new_index;
+			}
+
+			self.blocks[index] = outside;
+			self.blocks.push(inside);
+
+			splits.push((index, new_index));
+		}
+
+		splits
+	}
+}
+
+impl NormalizedDfa {
+	/// Returns the minimal automaton recognizing the same rules (Hopcroft's algorithm).
+	///
+	/// Only the states with the same set of matched rules can be merged,
+	/// and the states from which no rule can be matched are removed (except the start states).
+	pub fn minimize(&self) -> NormalizedDfa {
+		let len = self.states.len();
+
+		// every missing transition goes to a dead state
+		let dead = len;
+
+		let alphabet: Vec<InputCondition> = self.states.values()
+			.flat_map(|state| state.next.keys().cloned())
+			.collect::<HashSet<InputCondition>>()
+			.into_iter()
+			.collect();
+
+		// reverse transitions, for each input of the alphabet
+		let mut previous: Vec<Vec<Vec<usize>>> = vec![vec![vec![]; len + 1]; alphabet.len()];
+
+		for (input, condition) in alphabet.iter().enumerate() {
+			for id in 0..len {
+				let next = self.states[&id].next.get(condition).copied().unwrap_or(dead);
+
+				previous[input][next].push(id);
+			}
+
+			previous[input][dead].push(dead);
+		}
+
+		// initial partition: by set of matched rules
+		let mut by_matchs: HashMap<Vec<usize>, Vec<usize>> = HashMap::new();
+
+		for id in 0..=len {
+			let mut matchs: Vec<usize> = self.states.get(&id)
+				.map(|state| state.matchs.iter().copied().collect())
+				.unwrap_or_default();
+
+			matchs.sort();
+
+			by_matchs.entry(matchs).or_default().push(id);
+		}
+
+		let mut blocks: Vec<Vec<usize>> = by_matchs.into_values().collect();
+		blocks.sort();
+
+		let mut partition = Partition::new(blocks, len + 1);
+
+		let mut work_list: Vec<usize> = (0..partition.blocks.len()).collect();
+		let mut in_work_list = vec![true; partition.blocks.len()];
+
+		while let Some(splitter) = work_list.pop() {
+			in_work_list[splitter] = false;
+
+			// the splitter itself can be split while it is used
+			let splitter = partition.blocks[splitter].clone();
+
+			for previous in &previous {
+				let marked: HashSet<usize> = splitter.iter()
+					.flat_map(|state| previous[*state].iter().copied())
+					.collect();
+
+				for (kept, new) in partition.split(&marked) {
+					in_work_list.push(false);
+
+					// both parts are needed if the block was waiting, the smallest one is enough otherwise
+					let added = if in_work_list[kept] || partition.blocks[new].len() <= partition.blocks[kept].len() {
+						new
+					} else {
+						kept
+					};
+
+					if !in_work_list[added] {
+						in_work_list[added] = true;
+						work_list.push(added);
+					}
+				}
+			}
+		}
+
+		self.rebuild(&partition, dead)
+	}
+
+	/// Builds the automaton with one state per block of the partition
+	fn rebuild(&self, partition: &Partition, dead: usize) -> NormalizedDfa {
+		let dead_block = partition.block_of[dead];
+
+		let start_blocks: HashSet<usize> = self.starts.iter()
+			.chain([&self.start_id])
+			.map(|start| partition.block_of[*start])
+			.collect();
+
+		// new ids in the order of the lowest old id of each block
+		let mut kept: Vec<(usize, usize)> = partition.blocks.iter()
+			.enumerate()
+			.filter(|(index, _)| *index != dead_block || start_blocks.contains(index))
+			.map(|(index, block)| (*block.iter().min().unwrap(), index))
+			.collect();
+
+		kept.sort();
+
+		let new_ids: HashMap<usize, usize> = kept.iter()
+			.enumerate()
+			.map(|(new_id, (_, index))| (*index, new_id))
+			.collect();
+
+		let new_id = |old: usize| new_ids[&partition.block_of[old]];
+
+		let mut states = HashMap::with_capacity(kept.len());
+
+		for (id, (_, index)) in kept.iter().enumerate() {
+			let representative = partition.blocks[*index].iter()
+				.copied()
+				.find(|state| *state != dead);
+
+			let (matchs, next) = match representative.map(|state| &self.states[&state]) {
+				Some(state) => {
+					let next = state.next.iter()
+						.filter(|(_, next)| partition.block_of[**next] != dead_block)
+						.map(|(condition, next)| (condition.clone(), new_id(*next)))
+						.collect();
+
+					(state.matchs.clone(), next)
+				},
+
+				None => (HashSet::new(), HashMap::new()),
+			};
+
+			states.insert(id, NormalizedState::new(id, matchs, next));
+		}
+
+		NormalizedDfa {
+			start_id: new_id(self.start_id),
+			starts: self.starts.iter().map(|start| new_id(*start)).collect(),
+			states,
+			matchs: self.matchs.clone(),
+			trailing_contexts: self.trailing_contexts.clone(),
+			classes: self.classes.clone(),
+		}
+	}
+}
@@ -1,10 +1,10 @@
-use std::{collections::HashMap, rc::Rc};
+use std::collections::HashMap;
 
 use super::*;
 
 pub struct NormalizedMatch {}
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct NormalizedState {
 	pub id: usize,
 	pub matchs: HashSet<usize>,
@@ -21,9 +21,19 @@ impl NormalizedState {
 pub struct NormalizedDfa {
 	pub start_id: usize,
 
+	/// Ids of the start states (see `Dfa::starts`)
+	pub starts: Vec<usize>,
+
 	pub states: HashMap<usize, NormalizedState>,
 
-	pub matchs: HashMap<usize, StatePtr>,
+	/// Match state of each rule (in the arena `Dfa::nfa`)
+	pub matchs: HashMap<usize, StateId>,
+
+	/// Trailing contexts of the rules, by rule id (see `Dfa::trailing_contexts`)
+	pub trailing_contexts: HashMap<usize, TrailingContext>,
+
+	/// Equivalence classes of the input characters (see `Dfa::classes`)
+	pub classes: Option<EquivalenceClasses>,
 }
 
 impl NormalizedDfa {
@@ -33,25 +43,40 @@ impl NormalizedDfa {
 		let mut normalized_states = HashMap::new();
 
 		for state in dfa.memory.values() {
-			let normalized = Self::normalize_state(state, &dfa.memory, &mut match_memory);
+			let normalized = Self::normalize_state(state, &dfa.memory, &dfa.nfa, &mut match_memory);
 			normalized_states.insert(state.borrow().id, normalized);
 		}
 
 		let start_id = dfa.start.borrow().id;
 
-		Self { start_id, states: normalized_states, matchs: match_memory }
+		let starts = dfa.starts.iter()
+			.map(|start| start.borrow().id)
+			.collect();
+
+		let trailing_contexts = dfa.trailing_contexts.clone();
+		let classes = dfa.classes.clone();
+
+		Self { start_id, starts, states: normalized_states, matchs: match_memory, trailing_contexts, classes }
+	}
+
+	/// Transition condition of the character `c` (None if no rule reads it)
+	pub fn input_of(&self, c: char) -> Option<InputCondition> {
+		match &self.classes {
+			Some(classes) => classes.class_of(c).map(InputCondition::Class),
+			None => Some(InputCondition::Char(c)),
+		}
 	}
 
-	pub fn normalize_state(state: &DfaStatePtr, memory: &HashMap<StateList, DfaStatePtr>, match_memory: &mut HashMap<usize, StatePtr>) -> NormalizedState {
+	pub fn normalize_state(state: &DfaStatePtr, memory: &HashMap<StateList, DfaStatePtr>, nfa: &Nfa, match_memory: &mut HashMap<usize, StateId>) -> NormalizedState {
 		let next = Self::normalize_hashmap(&state.borrow().next, memory);
 
 		let mut matchs = HashSet::new();
 
 		for state in &state.borrow().matchs {
-			if let State::Match { id } = &*state.borrow() {
+			if let State::Match { id } = &nfa[state] {
 				matchs.insert(*id);
 				if !match_memory.contains_key(id) {
-					match_memory.insert(*id, Rc::clone(state));
+					match_memory.insert(*id, state);
 				}
 			}
 		}
@@ -80,7 +105,8 @@ impl NormalizedDfa {
 }
 
 pub struct Match {
-	state: StatePtr,
+	state: StateId,
+	id: usize,
 	length: usize
 }
 
@@ -88,15 +114,34 @@ impl Match {
 	pub fn length(&self) -> usize {
 		self.length
 	}
+
+	/// Id of the matched rule
+	pub fn id(&self) -> usize {
+		self.id
+	}
 }
 
 pub fn simulate(str: &str, dfa: &NormalizedDfa) -> Option<Match> {
+	simulate_from(str, dfa, dfa.start_id, true)
+}
 
-	let mut matchs: HashMap<usize, usize> = HashMap::new();
+/// Same as `simulate`, from the start state `start` (see `NormalizedDfa::starts`).
+///
+/// The `^` rules can only match if `at_bol` is set
+pub fn simulate_from(str: &str, dfa: &NormalizedDfa, start: usize, at_bol: bool) -> Option<Match> {
+	matches_from(str, dfa, start, at_bol).into_iter().next()
+}
 
-	let mut current = match dfa.states.get(&dfa.start_id) {
+/// All the matches of the rules on the prefixes of `str`, from the best one:
+/// the longest first, the lowest rule id first at the same length (the order followed by REJECT)
+pub fn matches_from(str: &str, dfa: &NormalizedDfa, start: usize, at_bol: bool) -> Vec<Match> {
+
+	// (rule id, length) of every match
+	let mut matchs: Vec<(usize, usize)> = vec![];
+
+	let mut current = match dfa.states.get(&start) {
 		Some(state) => state,
-		None => return None,
+		None => return vec![],
 	};
 
 	add_matchs(0, current, &mut matchs);
@@ -104,13 +149,19 @@ pub fn simulate(str: &str, dfa: &NormalizedDfa) -> Option<Match> {
 	let mut readed = 0;
 	let mut chars = str.chars().peekable();
 
-	current = if_start_of_line(readed, current, &dfa.states, &mut matchs);
+	if at_bol {
+		current = if_start_of_line(readed, current, &dfa.states, &mut matchs);
+	}
 
 	while let Some(c) = chars.next() {
 		readed += 1;
 		let end_of_line = chars.peek() == Some(&'\n');
 
-		current = match step(c, readed, current, &dfa.states, &mut matchs) {
+		let Some(input) = dfa.input_of(c) else {
+			break
+		};
+
+		current = match step(input, readed, current, &dfa.states, &mut matchs) {
 			Some(state) => state,
 			None => break
 		};
@@ -120,59 +171,59 @@ pub fn simulate(str: &str, dfa: &NormalizedDfa) -> Option<Match> {
 		}
 	}
 
-	if matchs.is_empty() {
-		return None;
-	}
-
-	let mut match_id = usize::MAX;
-	let mut match_length = 0;
+	sorted_matches(str, matchs, &dfa.matchs, &dfa.trailing_contexts)
+}
 
-	matchs.iter().for_each(|(id, length)| {
-		if match_length < *length {
-			match_id = *id;
-			match_length = *length;
-		} else if match_length == *length && match_id > *id {
-			// same length -> first match added (lower id)
-			match_id = *id;
-		}
+/// The (rule id, length) matches `matchs` of the prefixes of `str`, from the best one (see `matches_from`),
+/// `match_states` being the match state of each rule
+pub fn sorted_matches(
+	str: &str,
+	mut matchs: Vec<(usize, usize)>,
+	match_states: &HashMap<usize, StateId>,
+	trailing_contexts: &HashMap<usize, TrailingContext>,
+) -> Vec<Match> {
+	matchs.sort_by(|(id, length), (other_id, other_length)| {
+		other_length.cmp(length).then(id.cmp(other_id))
 	});
+	matchs.dedup();
 
-	let match_state = match dfa.matchs.get(&match_id) {
-		Some(state) => state,
-		None => return None
-	};
+	matchs.into_iter()
+		.filter_map(|(id, mut length)| {
+			let match_state = match_states.get(&id)?;
+
+			// only the head of a `r/s` rule is matched
+			if let Some(trailing_context) = trailing_contexts.get(&id) {
+				let text: Vec<char> = str.chars().take(length).collect();
+
+				length = trailing_context.head_length_in(&text);
+			}
 
-	Some(Match {
-		state: Rc::clone(match_state),
-		length: match_length
-	})
+			Some(Match {
+				state: *match_state,
+				id,
+				length
+			})
+		})
+		.collect()
 }
 
 fn add_matchs(
 	readed: usize,
 	current: &NormalizedState,
-	matchs: &mut HashMap<usize, usize>
+	matchs: &mut Vec<(usize, usize)>
 ) {
-	current.matchs.iter().for_each(|match_id| {
-		// if already added
-		if let Some(previous_readed) = matchs.insert(*match_id, readed) {
-			// if previous match was longer
-			if previous_readed > readed {
-				matchs.insert(*match_id, previous_readed);
-			}
-		}
-	});
+	matchs.extend(current.matchs.iter().map(|match_id| (*match_id, readed)));
 }
 
 fn step<'a>(
-	c: char,
+	input: InputCondition,
 	readed: usize,
 	mut current: &'a NormalizedState,
 	states: &'a HashMap<usize, NormalizedState>,
-	matchs: &mut HashMap<usize, usize>
+	matchs: &mut Vec<(usize, usize)>
 ) -> Option<&'a NormalizedState> {
 
-	current = match get_next(current, InputCondition::Char(c)) {
+	current = match get_next(current, input) {
 		Some(next) => {
 			match states.get(&next) {
 				Some(state) => state,
@@ -191,7 +242,7 @@ fn if_start_of_line<'a>(
 	readed: usize,
 	mut current: &'a NormalizedState,
 	states: &'a HashMap<usize, NormalizedState>,
-	matchs: &mut HashMap<usize, usize>
+	matchs: &mut Vec<(usize, usize)>
 ) -> &'a NormalizedState {
 
 	current = match get_next(current, InputCondition::StartOfLine) {
@@ -208,7 +259,7 @@ fn if_end_of_line<'a>(
 	readed: usize,
 	mut current: &'a NormalizedState,
 	states: &'a HashMap<usize, NormalizedState>,
-	matchs: &mut HashMap<usize, usize>
+	matchs: &mut Vec<(usize, usize)>
 ) -> &'a NormalizedState {
 
 	current = match get_next(current, InputCondition::EndOfLine) {
@@ -0,0 +1,228 @@
+use std::{
+	collections::{BTreeMap, HashMap, VecDeque},
+	fmt::Write,
+};
+
+use super::*;
+use super::normalizer::*;
+
+/// Graphviz description of the NFAs of the rules (Thompson's construction, `--dump-nfa`).
+///
+/// The accepting states are labeled with their rule id, the `^` and `$` conditions are dashed edges
+pub fn nfa_to_dot(nfas: &[&Nfa]) -> String {
+	let mut dot = String::from("digraph nfa {\n\trankdir=LR;\n\tnode [shape=circle];\n\tstart [shape=point];\n");
+
+	// (nfa, state) of each id
+	let mut ids: HashMap<(usize, StateId), usize> = HashMap::new();
+	let mut queue: VecDeque<(usize, StateId)> = VecDeque::new();
+
+	// same id for the same state, in the order of discovery
+	let mut id_of = |state: (usize, StateId), queue: &mut VecDeque<(usize, StateId)>| -> usize {
+		let len = ids.len();
+
+		*ids.entry(state).or_insert_with(|| {
+			queue.push_back(state);
+			len
+		})
+	};
+
+	for (index, nfa) in nfas.iter().enumerate() {
+		let id = id_of((index, nfa.start), &mut queue);
+		let _ = writeln!(dot, "\tstart -> {id};");
+	}
+
+	while let Some((index, state)) = queue.pop_front() {
+		let id = id_of((index, state), &mut queue);
+		let nfa = nfas[index];
+
+		let mut id_of = |out: &Out| id_of((index, out.expect("The NFA should be complete")), &mut queue);
+
+		match &nfa[state] {
+			State::Basic(basic) => {
+				let out = id_of(&basic.out);
+
+				let label = match &basic.c {
+					RegexType::CharacterClass(class) => class_label(&class.chars()),
+					c => class_label(&[c.char().expect("Basic state should have a char")]),
+				};
+
+				let _ = writeln!(dot, "\t{id} [label=\"\"];");
+				let _ = writeln!(dot, "\t{id} -> {out} [label=\"{}\"];", escape(&label));
+			},
+
+			State::Split(split) => {
+				let out1 = id_of(&split.out1);
+				let out2 = id_of(&split.out2);
+
+				let _ = writeln!(dot, "\t{id} [label=\"\"];");
+				let _ = writeln!(dot, "\t{id} -> {out1} [label=\"ε\"];");
+				let _ = writeln!(dot, "\t{id} -> {out2} [label=\"ε\"];");
+			},
+
+			State::StartOfLine { out } => {
+				let out = id_of(out);
+
+				let _ = writeln!(dot, "\t{id} [label=\"\"];");
+				let _ = writeln!(dot, "\t{id} -> {out} [label=\"^\", style=dashed];");
+			},
+
+			State::EndOfLine { out } => {
+				let out = id_of(out);
+
+				let _ = writeln!(dot, "\t{id} [label=\"\"];");
+				let _ = writeln!(dot, "\t{id} -> {out} [label=\"$\", style=dashed];");
+			},
+
+			State::Match { id: rule } => {
+				let _ = writeln!(dot, "\t{id} [shape=doublecircle, label=\"rule {rule}\"];");
+			},
+
+			State::NoMatch => {
+				let _ = writeln!(dot, "\t{id} [shape=point];");
+			},
+		}
+	}
+
+	dot.push_str("}\n");
+	dot
+}
+
+/// Graphviz description of the automaton (`--dump-dfa`).
+///
+/// The start states are labeled with their name in `starts` (their index if there is no name),
+/// the accepting states with the rule they accept (the lowest rule id)
+pub fn dfa_to_dot(dfa: &NormalizedDfa, starts: &[String]) -> String {
+	let mut dot = String::from("digraph dfa {\n\trankdir=LR;\n\tnode [shape=circle];\n");
+
+	for (index, start) in dfa.starts.iter().enumerate() {
+		let name = starts.get(index).cloned().unwrap_or_else(|| index.to_string());
+
+		let _ = writeln!(dot, "\tstart{index} [shape=plaintext, label=\"{}\"];", escape(&name));
+		let _ = writeln!(dot, "\tstart{index} -> {start};");
+	}
+
+	let mut ids: Vec<&usize> = dfa.states.keys().collect();
+	ids.sort();
+
+	for id in ids {
+		let state = &dfa.states[id];
+
+		match state.matchs.iter().min() {
+			Some(rule) => {
+				let _ = writeln!(dot, "\t{id} [shape=doublecircle, label=\"{id}\\nrule {rule}\"];");
+			},
+
+			None => {
+				let _ = writeln!(dot, "\t{id};");
+			},
+		}
+
+		// one edge per next state, with all the chars leading to it
+		let mut chars: BTreeMap<usize, Vec<char>> = BTreeMap::new();
+		let mut conditions: Vec<(&str, usize)> = vec![];
+
+		for (condition, next) in &state.next {
+			match condition {
+				InputCondition::Char(c) => chars.entry(*next).or_default().push(*c),
+				InputCondition::Class(class) => {
+					let class = dfa.classes.as_ref().expect("class transition without classes").chars(*class);
+
+					chars.entry(*next).or_default().extend_from_slice(class);
+				},
+				InputCondition::StartOfLine => conditions.push(("^", *next)),
+				InputCondition::EndOfLine => conditions.push(("$", *next)),
+			}
+		}
+
+		for (next, chars) in chars {
+			let _ = writeln!(dot, "\t{id} -> {next} [label=\"{}\"];", escape(&class_label(&chars)));
+		}
+
+		conditions.sort();
+
+		for (condition, next) in conditions {
+			let _ = writeln!(dot, "\t{id} -> {next} [label=\"{condition}\", style=dashed];");
+		}
+	}
+
+	dot.push_str("}\n");
+	dot
+}
+
+/// Label of a set of chars, with the consecutive chars collapsed into ranges (e.g. `[0-9a-f]`).
+///
+/// The big sets of ascii chars are written as a negated class (e.g. `[^\n]`)
+pub fn class_label(chars: &[char]) -> String {
+	let mut chars = chars.to_vec();
+	chars.sort();
+	chars.dedup();
+
+	if let [c] = chars[..] {
+		return match c {
+			' ' => "' '".to_string(),
+			'\\' | ']' | '-' | '^' => c.to_string(),
+			c => escape_char(c),
+		};
+	}
+
+	if chars.len() > 64 && chars.iter().all(char::is_ascii) {
+		let others: Vec<char> = (0..=127_u8)
+			.map(char::from)
+			.filter(|c| chars.binary_search(c).is_err())
+			.collect();
+
+		if !others.is_empty() {
+			return format!("[^{}]", ranges(&others));
+		}
+	}
+
+	format!("[{}]", ranges(&chars))
+}
+
+/// Sorted chars, with the runs of 3 chars or more written `first-last`
+fn ranges(chars: &[char]) -> String {
+	let mut label = String::new();
+	let mut index = 0;
+
+	while index < chars.len() {
+		let first = chars[index];
+		let mut last = index;
+
+		while last + 1 < chars.len() && chars[last + 1] as u32 == chars[last] as u32 + 1 {
+			last += 1;
+		}
+
+		match last - index {
+			0 => label.push_str(&escape_char(first)),
+			1 => {
+				label.push_str(&escape_char(first));
+				label.push_str(&escape_char(chars[last]));
+			},
+			_ => {
+				label.push_str(&escape_char(first));
+				label.push('-');
+				label.push_str(&escape_char(chars[last]));
+			},
+		}
+
+		index = last + 1;
+	}
+
+	label
+}
+
+/// A char as written in a character class
+fn escape_char(c: char) -> String {
+	match c {
+		'\n' => "\\n".to_string(),
+		'\t' => "\\t".to_string(),
+		'\\' | ']' | '-' | '^' => format!("\\{c}"),
+		c if c.is_control() => format!("\\x{:02x}", c as u32),
+		c => c.to_string(),
+	}
+}
+
+/// Escapes a label for a DOT string
+fn escape(label: &str) -> String {
+	label.replace('\\', "\\\\").replace('"', "\\\"")
+}
@@ -0,0 +1,41 @@
+use super::*;
+use dfa::normalizer::{self, Match, NormalizedDfa};
+
+/// Common interface of the engines running the rules (the DFA and the NFA simulation),
+/// so they can be checked against each other
+pub trait Matcher {
+	/// Number of start conditions
+	fn conditions(&self) -> usize;
+
+	/// All the matches on the prefixes of `str` from the start condition of index `condition`,
+	/// from the best one (see `normalizer::matches_from`)
+	fn matches(&self, str: &str, condition: usize, at_bol: bool) -> Vec<Match>;
+
+	/// The best match on the prefixes of `str` (the longest, then the lowest rule id)
+	fn longest_match(&self, str: &str, condition: usize, at_bol: bool) -> Option<Match> {
+		self.matches(str, condition, at_bol).into_iter().next()
+	}
+}
+
+impl Matcher for NormalizedDfa {
+	fn conditions(&self) -> usize {
+		self.starts.len()
+	}
+
+	fn matches(&self, str: &str, condition: usize, at_bol: bool) -> Vec<Match> {
+		match self.starts.get(condition) {
+			Some(start) => normalizer::matches_from(str, self, *start, at_bol),
+			None => vec![],
+		}
+	}
+}
+
+impl Matcher for NfaSimulation {
+	fn conditions(&self) -> usize {
+		self.starts_len()
+	}
+
+	fn matches(&self, str: &str, condition: usize, at_bol: bool) -> Vec<Match> {
+		self.matches_from(str, condition, at_bol)
+	}
+}
@@ -1,168 +1,172 @@
-// use super::*;
-
-// /// Represents the status of the NFA simulation
-// pub enum NfaStatus {
-// 	Match(usize),
-// 	NoMatch,
-// 	Pending
-// }
-
-// /// Main simulation controller for NFA-based regex matching
-// #[derive(Debug)]
-// pub struct NfaSimulation<'a> {
-// 	/// If the current input is at the start of a line
-// 	start_of_line: bool,
-
-// 	/// The current number of characters readed
-// 	readed: usize,
-
-// 	/// The number of characters read until match (if matched)
-// 	pub longest_match: Option<usize>,
-
-//     /// NFA to use for matching
-//     nfa: &'a Nfa,
-
-//     /// All active validation paths
-//     current_states: StateList,
-
-// 	/// Next validation paths that have successfully matched
-//     next_states: StateList,
-// }
-
-// impl<'a> NfaSimulation<'a> {
-// 	pub fn new(nfa: &'a Nfa) -> Self {
-
-// 		let readed = 0;
-// 		let longest_match = None;
-
-// 		let current_states = StateList::from(&nfa.start);
-// 		let next_states = StateList::new();
-
-// 		NfaSimulation {
-// 			start_of_line: false,
-// 			readed,
-// 			longest_match,
-// 			nfa,
-// 			current_states,
-// 			next_states
-// 		}
-// 	}
-
-// 	/// Current states are now next states, and next states are cleared
-// 	pub fn switch_to_next_states(&mut self) {
-// 		std::mem::swap(&mut self.current_states, &mut self.next_states);
-
-// 		self.next_states.clear();
-// 	}
-
-// 	/// Check if the start of line matches the NFA's start of line condition
-// 	pub fn check_start_of_line(&self) -> bool {
-// 		self.nfa.start_of_line == false || self.start_of_line == true
-// 	}
-// 	/// Check if the end of line matches the NFA's end of line condition
-// 	pub fn check_end_of_line(&self, end_of_line: bool) -> bool {
-// 		self.nfa.end_of_line == false || end_of_line == true
-// 	}
-
-// 	pub fn status(&self) -> NfaStatus {
-// 		if self.check_start_of_line() == false {
-// 			return NfaStatus::NoMatch
-// 		}
-
-// 		if self.current_states.is_empty() == false {
-// 			return NfaStatus::Pending
-// 		}
-
-// 		if self.longest_match.is_none() {
-// 			return NfaStatus::NoMatch
-// 		}
-
-// 		NfaStatus::Match(self.longest_match.unwrap())
-// 	}
-
-// 	/// Step the simulation forward by one character.
-// 	/// 
-// 	/// - c :  The current character
-// 	/// 
-// 	/// - end_of_line :  If the current character is at the end of a line
-// 	pub fn step(&mut self, c: &char, end_of_line: bool) -> NfaStatus {
-
-// 		if self.check_start_of_line() == false || self.current_states.is_empty() {
-// 			return self.status()
-// 		}
-
-// 		self.readed += 1;
-
-// 		for state in &self.current_states {
-// 			// The states should be basic states
-// 			if State::is_basic_ptr(state) == false {
-// 				continue;
-// 			}
-
-// 			let borrowed_state = state.borrow();
-
-// 			// Check if the state matches the current character
-// 			if borrowed_state.matche_with(c) {
-// 				let out = &borrowed_state.basic_out().unwrap();
-// 				let next_state = out.borrow();
-
-// 				self.next_states.add_state(&next_state);
-// 			}
-// 		}
-
-// 		// Check if the next states have a match
-// 		if self.next_states.is_matched() {
-// 			if self.check_end_of_line(end_of_line) {
-// 				self.longest_match = Some(self.readed);
-// 			}
-// 			self.next_states.remove_matchs();
-// 		}
-
-// 		// remove the matchs, to only keep active states in the next states
-// 		self.switch_to_next_states();
-// 		return self.status()
-// 	}
-
-// 	pub fn start(&mut self, start_of_line: bool) {
-// 		self.readed = 0;
-// 		self.longest_match = None;
-// 		self.current_states.clear();
-// 		self.current_states.add_state(&self.nfa.start);
-// 		self.next_states.clear();
-// 		self.start_of_line = start_of_line;
-// 	}
-// }
-
-// /// Implements a traditional NFA simulation where we track all possible states simultaneously. \
-// /// The algorithm maintains two SETS of states (current_states and next_states) and follows all possible
-// /// paths through the NFA in parallel. This approach handles nondeterminism by exploring all possible
-// /// transitions for each input character, which is the defining characteristic of Thompson's NFA simulation.
-// pub fn input_match(nfa: &Nfa, input: &str) -> bool {
-//     let mut simulation = NfaSimulation::new(nfa);
-
-// 	let mut chars = input.chars().peekable();
-
-// 	let start_of_line = true;
-
-// 	simulation.start(start_of_line);
-
-// 	// Check if the next states have a match
-// 	if simulation.current_states.is_matched() {
-// 		return simulation.nfa.end_of_line == false || input.is_empty();
-// 	}	
-
-// 	while let Some(c) = chars.next() {
-// 		let peek = chars.peek();
-// 		// check if the next character is the end of a line
-// 		let end_of_line = peek == None || peek == Some(&'\n');
-
-// 		match simulation.step(&c, end_of_line) {
-// 			NfaStatus::Pending => continue,
-
-// 			// finished
-// 			_ => break,
-// 		}
-// 	}
-
-// 	simulation.longest_match.is_some()
-// }
+use std::collections::HashMap;
+
+use super::*;
+use normalizer::*;
+
+/// Thompson simulation of the NFAs of the rules: the set of all the NFA states reached by
+/// the input is followed, without building any DFA state.
+///
+/// It finds the same matches as the DFA (see `normalizer::matches_from`), so both are
+/// checked against each other (see `Matcher`)
+pub struct NfaSimulation {
+	closures: EpsilonClosures<'static>,
+
+	/// Start states of each start condition (see `Dfa::starts`)
+	starts: Vec<StateList>,
+
+	/// Match state of each rule, in the arena of the merged NFAs
+	matchs: HashMap<usize, StateId>,
+
+	/// Trailing contexts of the rules, by rule id
+	trailing_contexts: HashMap<usize, TrailingContext>,
+}
+
+impl NfaSimulation {
+	pub fn new(nfas: &[&Nfa]) -> Self {
+		Self::with_starts(nfas, vec![(0..nfas.len()).collect()])
+	}
+
+	/// Simulation with several start states, each one matching the NFAs of its list (indexes in `nfas`)
+	pub fn with_starts(nfas: &[&Nfa], starts: Vec<Vec<usize>>) -> Self {
+		let (nfa, nfa_starts) = Nfa::merge(nfas);
+		let closures = EpsilonClosures::owned(nfa);
+
+		let starts = starts.iter()
+			.map(|indexes| {
+				let mut list = StateList::new();
+
+				for index in indexes {
+					closures.add_to(&mut list, nfa_starts[*index]);
+				}
+
+				list
+			})
+			.collect();
+
+		let mut matchs = HashMap::new();
+
+		for state in 0..closures.nfa().len() {
+			if let Some(id) = closures.nfa()[state].match_id() {
+				matchs.entry(id).or_insert(state);
+			}
+		}
+
+		Self { closures, starts, matchs, trailing_contexts: HashMap::new() }
+	}
+
+	/// Same start states as `Dfa::from_rules`
+	pub fn from_rules(rules: &[Rule], definitions: &Definitions) -> Self {
+		let automaton = RulesAutomaton::new(rules, definitions);
+
+		let mut simulation = Self::with_starts(&automaton.nfas, automaton.starts);
+		simulation.trailing_contexts = automaton.trailing_contexts;
+
+		simulation
+	}
+
+	/// Number of start states
+	pub fn starts_len(&self) -> usize {
+		self.starts.len()
+	}
+
+	/// Same as `normalizer::matches_from`, from the start state of index `start`
+	pub fn matches_from(&self, str: &str, start: usize, at_bol: bool) -> Vec<Match> {
+		// (rule id, length) of every match
+		let mut matchs: Vec<(usize, usize)> = vec![];
+
+		let mut current = match self.starts.get(start) {
+			Some(list) => list.clone(),
+			None => return vec![],
+		};
+
+		self.add_matchs(0, &current, &mut matchs);
+
+		let mut readed = 0;
+		let mut chars = str.chars().peekable();
+
+		if at_bol {
+			current = self.follow_anchors(readed, current, true, &mut matchs);
+		}
+
+		while let Some(c) = chars.next() {
+			readed += 1;
+
+			current = match self.step(c, &current) {
+				Some(next) => next,
+				None => break,
+			};
+
+			self.add_matchs(readed, &current, &mut matchs);
+
+			if chars.peek() == Some(&'\n') {
+				current = self.follow_anchors(readed, current, false, &mut matchs);
+			}
+		}
+
+		sorted_matches(str, matchs, &self.matchs, &self.trailing_contexts)
+	}
+
+	/// States reached from the states `current` by reading `c`, None if no state reads it
+	fn step(&self, c: char, current: &StateList) -> Option<StateList> {
+		let nfa = self.closures.nfa();
+
+		let mut next = StateList::new();
+		let mut read = false;
+
+		for state in current {
+			let Some(out) = nfa[state].out().filter(|out| !nfa[*out].is_nomatch()) else {
+				continue
+			};
+
+			if nfa[state].matche_with(&c) {
+				self.closures.add_to(&mut next, out);
+				read = true;
+			}
+		}
+
+		read.then_some(next)
+	}
+
+	/// Follows the `^` states of `current` (`$` states if `start_of_line` is false):
+	/// the other states are kept, except the opposite anchors and the match states.
+	///
+	/// `current` is returned as is if it has no such state
+	fn follow_anchors(&self, readed: usize, current: StateList, start_of_line: bool, matchs: &mut Vec<(usize, usize)>) -> StateList {
+		let nfa = self.closures.nfa();
+
+		let is_anchor = |state: &State| match start_of_line {
+			true => state.is_start_of_line(),
+			false => state.is_end_of_line(),
+		};
+
+		if !current.iter().any(|state| is_anchor(&nfa[state])) {
+			return current;
+		}
+
+		let mut next = StateList::new();
+
+		for state in &current {
+			let state_ = &nfa[state];
+
+			if is_anchor(state_) {
+				if let Some(out) = state_.out() {
+					self.closures.add_to(&mut next, out);
+				}
+
+				next.push(state);
+			} else if !(state_.is_match() || state_.is_start_of_line() || state_.is_end_of_line()) {
+				next.push(state);
+			}
+		}
+
+		self.add_matchs(readed, &next, matchs);
+
+		next
+	}
+
+	fn add_matchs(&self, readed: usize, current: &StateList, matchs: &mut Vec<(usize, usize)>) {
+		let nfa = self.closures.nfa();
+
+		matchs.extend(current.iter().filter_map(|state| Some((nfa[state].match_id()?, readed))));
+	}
+}
@@ -106,6 +106,7 @@ impl RegexType {
     pub fn match_(&self, c: &char) -> bool {
         match self {
             RegexType::Char(char) => char == c,
+            RegexType::CharacterClass(class) => class.contains(c),
             _ => todo!(),
         }
     }
@@ -291,9 +292,9 @@ impl CharacterClass {
 
     pub fn add_range(&mut self, start: char, end: char) -> ParsingResult<()> {
         if start <= end {
-            for c in start..=end {
-                self.add_char(c);
-            }
+            let chars: HashSet<char> = self.chars.iter().copied().collect();
+
+            self.chars.extend((start..=end).filter(|c| !chars.contains(c)));
 
             Ok(())
         } else {
@@ -326,6 +327,15 @@ impl CharacterClass {
                                 class.add_char('-');
                                 return Ok(class);
                             } else {
+                                let end = match (end, chars.peek()) {
+                                    ('[', Some('.')) => {
+                                        chars.next();
+                                        Self::parse_single_element(chars, '.')?
+                                    },
+                                    ('\\', Some(_)) => Utils::escaped(chars)?,
+                                    _ => end,
+                                };
+
                                 class.remove_char(start); // Remove the start char as it's now part of a range
                                 class.add_range(start, end)?;
                                 prev_char = None;
@@ -342,8 +352,8 @@ impl CharacterClass {
                 }
                 '\\' => {
                     // Handle escape sequences
-                    if let Some(next_c) = chars.next() {
-                        let interpreted = Utils::backslashed(next_c);
+                    if chars.peek().is_some() {
+                        let interpreted = Utils::escaped(chars)?;
                         class.add_char(interpreted);
                         prev_char = Some(interpreted);
                     } else {
@@ -352,6 +362,36 @@ impl CharacterClass {
                         .into();                    }
                 }
 
+                '[' if matches!(chars.peek(), Some(':' | '=' | '.')) => {
+                    let delimiter = chars.next().unwrap();
+
+                    match delimiter {
+                        // `[:alpha:]`
+                        ':' => {
+                            let name = Self::parse_bracket_element(chars, delimiter)?;
+
+                            for c in Self::posix(&name)?.chars {
+                                class.add_char(c);
+                            }
+                            prev_char = None;
+                        },
+
+                        // `[=a=]`, which cannot start a range
+                        '=' => {
+                            class.add_char(Self::parse_single_element(chars, delimiter)?);
+                            prev_char = None;
+                        },
+
+                        // `[.a.]`
+                        _ => {
+                            let c = Self::parse_single_element(chars, delimiter)?;
+
+                            class.add_char(c);
+                            prev_char = Some(c);
+                        },
+                    }
+                }
+
                 c => {
                     class.add_char(c);
                     prev_char = Some(c);
@@ -364,6 +404,39 @@ impl CharacterClass {
             .into()
     }
 
+    /// Reads the content of a bracket element (e.g. `alpha` in `[:alpha:]`), after its opening `[` and `delimiter`
+    fn parse_bracket_element(chars: &mut Peekable<Chars>, delimiter: char) -> ParsingResult<String> {
+        let mut content = String::new();
+
+        while let Some(c) = chars.next() {
+            if c == delimiter && chars.peek() == Some(&']') {
+                chars.next();
+                return Ok(content);
+            }
+
+            content.push(c);
+        }
+
+        ParsingError::unrecognized_rule()
+            .because(format!("Unclosed `[{delimiter}` in character class"))
+            .into()
+    }
+
+    /// Same as `parse_bracket_element`, for the equivalence classes and collating symbols:
+    /// only the single characters are supported
+    fn parse_single_element(chars: &mut Peekable<Chars>, delimiter: char) -> ParsingResult<char> {
+        let content = Self::parse_bracket_element(chars, delimiter)?;
+        let mut content_chars = content.chars();
+
+        match (content_chars.next(), content_chars.next()) {
+            (Some(c), None) => Ok(c),
+
+            _ => ParsingError::unrecognized_rule()
+                .because(format!("unsupported collating element `[{delimiter}{content}{delimiter}]` (only single characters are supported)"))
+                .into(),
+        }
+    }
+
     // Compatibility methods to create instances
     pub fn from_single(c: char) -> Self {
         let mut class = Self::new();
@@ -439,15 +512,61 @@ impl CharacterClass {
         Self::whitespace().negated()
     }
 
+    /// The POSIX class `[:name:]` (of the ASCII characters)
+    pub fn posix(name: &str) -> ParsingResult<Self> {
+        let predicate: fn(&char) -> bool = match name {
+            "alnum" => char::is_ascii_alphanumeric,
+            "alpha" => char::is_ascii_alphabetic,
+            "blank" => |c| c == &' ' || c == &'\t',
+            "cntrl" => char::is_ascii_control,
+            "digit" => char::is_ascii_digit,
+            "graph" => char::is_ascii_graphic,
+            "lower" => char::is_ascii_lowercase,
+            "print" => |c| c == &' ' || c.is_ascii_graphic(),
+            "punct" => char::is_ascii_punctuation,
+            "space" => |c| c == &'\x0b' || c.is_ascii_whitespace(),
+            "upper" => char::is_ascii_uppercase,
+            "xdigit" => char::is_ascii_hexdigit,
+
+            _ => return ParsingError::unrecognized_rule()
+                .because(format!("Unknown POSIX class `[:{name}:]`"))
+                .into(),
+        };
+
+        let mut class = Self::new();
+
+        for c in (0..=127_u8).map(char::from).filter(predicate) {
+            class.add_char(c);
+        }
+
+        Ok(class)
+    }
+
     // Check if a character matches this character class
+    // (a negated class only matches ASCII characters, like `chars`)
     pub fn contains(&self, c: &char) -> bool {
         if self.negated {
-            !self.chars.contains(c)
+            c.is_ascii() && !self.chars.contains(c)
         } else {
             self.chars.contains(c)
         }
     }
 
+    /// The class over the 256 bytes (`%option 8bit`): a negated class becomes the bytes it does not contain,
+    /// the ones from 128 to 255 included
+    pub fn eight_bit(self) -> Self {
+        if !self.negated {
+            return self;
+        }
+
+        let chars = (0..=255_u8)
+            .map(char::from)
+            .filter(|c| !self.chars.contains(c))
+            .collect();
+
+        Self { chars, negated: false }
+    }
+
 	pub fn len(&self) -> usize {
 		if self.negated {
 			128 - self.chars.len()
@@ -522,11 +641,40 @@ impl Regex {
     }
 
     pub fn tokens(input: &str) -> ParsingResult<VecDeque<RegexType>> {
+        Self::parse_tokens(input, RegexFlags::default())
+    }
+
+    /// Same as `tokens`, the letters matching both cases if `flags.case_insensitive` is set,
+    /// or inside a `(?i:...)` group (`(?-i:...)` restores the exact case)
+    pub fn parse_tokens(input: &str, flags: RegexFlags) -> ParsingResult<VecDeque<RegexType>> {
         let mut tokens = VecDeque::with_capacity(input.len());
         let mut chars = input.chars().peekable();
 
+        let mut case_insensitive = flags.case_insensitive;
+
+        // mode to restore at the end of each open group
+        let mut groups: Vec<bool> = vec![];
+
         while let Some(c) = chars.next() {
+            let first_token = tokens.len();
+
             match c {
+				'(' => {
+					groups.push(case_insensitive);
+
+					if chars.peek() == Some(&'?') {
+						chars.next();
+						case_insensitive = Self::group_mode(&mut chars)?;
+					}
+
+					tokens.push_back(RegexType::OpenParenthesis);
+				},
+
+				')' => {
+					case_insensitive = groups.pop().unwrap_or(case_insensitive);
+					tokens.push_back(RegexType::CloseParenthesis);
+				},
+
                 '"' => Self::add_string(&mut tokens, &mut chars)?,
 
 				'[' => Self::add_character_class(&mut tokens, &mut chars)?,
@@ -534,7 +682,7 @@ impl Regex {
 				'{' => Self::add_quantifier(&mut tokens, &mut chars)?,
 
 
-				'\\' => Self::add_backslash(&mut tokens, &mut chars),
+				'\\' => Self::add_backslash(&mut tokens, &mut chars)?,
 
 				'.' => tokens.push_back(RegexType::CharacterClass(CharacterClass::from_single('\n').negated())),
 
@@ -558,29 +706,58 @@ impl Regex {
 
                 c => tokens.push_back(Self::into_type(c)),
             }
+
+            if case_insensitive {
+                let folded: Vec<RegexType> = tokens.drain(first_token..)
+                    .map(|token| Self::case_insensitive(token, flags.utf8))
+                    .collect();
+
+                tokens.extend(folded);
+            }
         }
 
         Ok(tokens)
     }
 
+    /// Reads the flags of a `(?i:` or `(?-i:` group (after the `(?`), returns its case insensitivity
+    fn group_mode(chars: &mut Peekable<Chars<'_>>) -> ParsingResult<bool> {
+        let mut flags = String::new();
+
+        for c in chars.by_ref() {
+            if c == ':' {
+                return match flags.as_str() {
+                    "i" => Ok(true),
+                    "-i" => Ok(false),
+                    _ => ParsingError::unrecognized_rule().because(format!("unknown group flags `(?{flags}:`")).into(),
+                };
+            }
+
+            flags.push(c);
+        }
+
+        ParsingError::unrecognized_rule().because("Unclosed group flags").into()
+    }
+
     pub fn add_backslash(
         tokens: &mut VecDeque<RegexType>,
         chars: &mut Peekable<Chars<'_>>,
-    ) {
-        let next_c = chars.next().unwrap_or('\\');
-
+    ) -> ParsingResult<()> {
         // Check if it's a shorthand character class
-        match next_c {
-            'd' | 'D' | 'w' | 'W' | 's' | 'S' => {
-                if let Ok(class) = CharacterClass::from_shorthand(next_c) {
-                    tokens.push_back(RegexType::CharacterClass(class));
-                } else {
-                    tokens.push_back(RegexType::Char(Utils::backslashed(next_c)));
-                }
+        match chars.peek() {
+            Some(c @ ('d' | 'D' | 'w' | 'W' | 's' | 'S')) => {
+                let class = CharacterClass::from_shorthand(*c)?;
+                chars.next();
+
+                tokens.push_back(RegexType::CharacterClass(class));
             }
+            // A '\\' at the end is a literal
+            None => tokens.push_back(RegexType::Char('\\')),
+
             // Handle other escape sequences
-            _ => tokens.push_back(RegexType::Char(Utils::backslashed(next_c))),
+            _ => tokens.push_back(RegexType::Char(Utils::escaped(chars)?)),
         }
+
+        Ok(())
     }
 
     /// Handling litterals (trick: transform litterals into parenthesis of chars)
@@ -594,9 +771,12 @@ impl Regex {
         while let Some(c) = chars.next() {
             match c {
                 '\\' => {
-                    let c = chars.next().unwrap_or('\\');
+                    let c = match chars.peek() {
+                        Some(_) => Utils::escaped(chars)?,
+                        None => '\\',
+                    };
 
-                    tokens.push_back(RegexType::Char(Utils::backslashed(c)));
+                    tokens.push_back(RegexType::Char(c));
                 }
 
                 '\"' => {
@@ -1,284 +1,296 @@
-use std::{collections::HashSet, hash::Hash, iter::{Enumerate, Peekable}, ops::Deref, rc::Rc};
+use std::{borrow::Cow, cell::OnceCell, collections::HashSet, hash::Hash, iter::Enumerate};
 
 use super::*;
 
+const WORD_BITS: usize = u64::BITS as usize;
+
 // ===================================
 // 1. DATA STRUCTURES FOR NFA SIMULATION
 // ===================================
 
-/// Represents a list of NFA states during simulation
-#[derive(Debug)]
+/// Represents a set of NFA states during simulation
+///
+/// The states are ids in the arena of an NFA (see `Nfa`), stored as a dense bitset:
+/// the bit `id` is set if the state `id` is in the list. Only the words between the
+/// first and the last non-empty ones are kept (starting at the word `offset`),
+/// so two lists with the same states are equal and have the same hash
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
 pub struct StateList {
-    states: Vec<StatePtr>,
+    offset: usize,
+    words: Vec<u64>,
 }
 
-impl<'a> IntoIterator for &'a StateList {
-    type Item = &'a StatePtr;
-    type IntoIter = std::slice::Iter<'a, StatePtr>;
-    
-    fn into_iter(self) -> Self::IntoIter {
-        self.states.iter()
-    }
+/// Iterator over the states of a `StateList`, in increasing order
+pub struct Iter<'a> {
+    offset: usize,
+    words: &'a [u64],
+    index: usize,
+    current: u64,
 }
 
-impl<'a> IntoIterator for &'a mut StateList {
-    type Item = &'a mut StatePtr;
-    type IntoIter = std::slice::IterMut<'a, StatePtr>;
-    
-    fn into_iter(self) -> Self::IntoIter {
-        self.states.iter_mut()
+impl Iterator for Iter<'_> {
+    type Item = StateId;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.current == 0 {
+            self.index += 1;
+            self.current = *self.words.get(self.index)?;
+        }
+
+        let bit = self.current.trailing_zeros() as usize;
+
+        // clear the lowest set bit
+        self.current &= self.current - 1;
+
+        Some((self.offset + self.index) * WORD_BITS + bit)
     }
 }
 
-impl IntoIterator for StateList {
-    type Item = StatePtr;
-    type IntoIter = std::vec::IntoIter<Self::Item>;
-    
+impl<'a> IntoIterator for &'a StateList {
+    type Item = StateId;
+    type IntoIter = Iter<'a>;
+
     fn into_iter(self) -> Self::IntoIter {
-        self.states.into_iter()
+        self.iter()
     }
 }
 
 impl std::fmt::Display for StateList {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "StateList[")?;
-        for (i, state) in self.states.iter().enumerate() {
+        for (i, state) in self.enumerate() {
             if i > 0 {
                 write!(f, ", ")?;
             }
-            match &*state.borrow() {
-                State::Basic(basic) => {
-                    let char_repr = match basic.c.char() {
-                        Some(c) => format!("'{}'", c),
-                        None => format!("{:?}", basic.c),
-                    };
-                    write!(f, "Basic({})", char_repr)?;
-                },
-                State::Split(_) => write!(f, "Split")?,
-                State::Match { id } => write!(f, "Match({})", id)?,
-                State::StartOfLine { .. } => write!(f, "StartOfLine")?,
-                State::EndOfLine { .. } => write!(f, "EndOfLine")?,
-                State::NoMatch => write!(f, "NoMatch")?,
-                State::None => write!(f, "None")?,
-            }
+            write!(f, "{state}")?;
         }
         write!(f, "]")
     }
 }
 
-impl std::ops::Index<usize> for StateList {
-    type Output = StatePtr;
-
-    fn index(&self, index: usize) -> &Self::Output {
-        &self.states[index]
-    }
-}
-
-
-impl Clone for StateList {
-	fn clone(&self) -> Self {
-		let cloned_states = self.states.iter().map(|state| Rc::clone(state)).collect();
-
-		StateList {
-			states: cloned_states
-		}
-	}
-}
-
-impl Eq for StateList {}
-impl PartialEq for StateList {
-	fn eq(&self, other: &Self) -> bool {
-		
-		if self.states.len() != other.states.len() {
-			return false
-		}
-
-		for other_state in &other.states {
-			let contained = self.states.iter().any(|state|
-				Rc::ptr_eq(state, other_state)
-			);
-
-			if contained == false {
-				return false
-			}
-		}
-
-		true
-	}
-}
-
-impl Hash for StateList {
-    fn hash<H: std::hash::Hasher>(&self, hasher: &mut H) {
-        // Hash the number of states
-        self.states.len().hash(hasher);
-
-        for state_ptr in &self.states {
-            let raw_ptr = state_ptr.borrow().deref() as *const State;
-            raw_ptr.hash(hasher);
-        }
-    }
-}
-
 
 impl StateList {
 	pub fn new() -> Self {
-		StateList { states: Vec::with_capacity(1) }
+		StateList { offset: 0, words: Vec::new() }
 	}
 
-	pub fn from(state: &StatePtr) -> Self {
+	pub fn from(nfa: &Nfa, state: StateId) -> Self {
 		let mut list = StateList::new();
 
-		list.add_state(state);
+		list.add_state(nfa, state);
 
 		list
 	}
 
-    pub fn add_state(&mut self, state: &StatePtr) {
-        self.add_state_with_memo(state, &mut HashSet::new());
+    /// Adds the state `state` of `nfa`, or the states reached by its epsilon transitions if it is a split
+    pub fn add_state(&mut self, nfa: &Nfa, state: StateId) {
+        self.add_state_with_memo(nfa, state, &mut HashSet::new());
     }
 
-    pub fn add_state_with_memo(&mut self, state: &StatePtr, visited: &mut HashSet<*const State>) {
-        let state_ptr = state.borrow().deref() as *const State;
-
-        if visited.insert(state_ptr) == false {
-            return;
-        }
+	pub fn add_state_with_memo(&mut self, nfa: &Nfa, state: StateId, visited: &mut HashSet<StateId>) {
+		let mut work_stack = vec![state];
 
-        if self.contains(state) {
-            return;
-        }
-
-		if State::is_split_ptr(&state) {
-            let borrowed_state = state.borrow();
-            let split = borrowed_state.into_split().unwrap();
-
-            // out1
-            self.add_state_with_memo(&split.out1.borrow(), visited);
-            // out2
-            self.add_state_with_memo(&split.out2.borrow(), visited);
-        } else {
-            self.push(state);
-        }
-    }
-
-	pub fn add_state_with_memo_iterative(&mut self, state: &StatePtr, visited: &mut HashSet<*const State>) {
-		let mut work_stack = Vec::new();
-		work_stack.push(Rc::clone(state));
-		
 		while let Some(current) = work_stack.pop() {
-			let state_ptr = current.borrow().deref() as *const State;
-			
-			if visited.insert(state_ptr) == false {
+			if !visited.insert(current) {
 				continue;
 			}
-			
-			if self.contains(&current) {
+
+			if self.contains(current) {
 				continue;
 			}
 
-			if State::is_split_ptr(&current) {
-				let borrowed_state = current.borrow();
-				let split = borrowed_state.into_split().unwrap();
-				
-				work_stack.push(Rc::clone(&split.out1.borrow()));
-				work_stack.push(Rc::clone(&split.out2.borrow()));
-			} else {
-				self.push(&current);
+			match &nfa[current] {
+				State::Split(split) => {
+					// out1 first
+					work_stack.extend(split.out2);
+					work_stack.extend(split.out1);
+				},
+
+				_ => self.push(current),
 			}
 		}
 	}
 
-	pub fn contains(&self, to_find: &StatePtr) -> bool {
-		self.states
-			.iter()
-			.any(|state|
-				Rc::ptr_eq(to_find, state)
-			)
+	pub fn contains(&self, to_find: StateId) -> bool {
+		(to_find / WORD_BITS).checked_sub(self.offset)
+			.and_then(|word| self.words.get(word))
+			.is_some_and(|word| word & (1 << (to_find % WORD_BITS)) != 0)
 	}
 
-	pub fn is_matched(&self) -> bool {
-		self.states
-		.iter()
+	pub fn is_matched(&self, nfa: &Nfa) -> bool {
+		self.iter()
 		.any(|state|
-			State::is_match_ptr(state)
+			nfa[state].is_match()
 		)
 	}
 
-	pub fn remove_matchs(&mut self) -> Vec<StatePtr> {
-		let mut indexes = vec![];
-
-		self.states.iter().enumerate().for_each(|(index, state)|
-			if State::is_match_ptr(state) {
-				indexes.push(index);
-			}
-		);
-
-		let mut removed = 0;
-		let mut matchs = vec![];
-
-		for index in indexes {
-			let match_ = self.states.remove(index - removed);
-			matchs.push(match_);
+	pub fn remove_matchs(&mut self, nfa: &Nfa) -> Vec<StateId> {
+		let matchs: Vec<StateId> = self.iter()
+			.filter(|state| nfa[*state].is_match())
+			.collect();
 
-			removed += 1;
+		for state in &matchs {
+			self.words[state / WORD_BITS - self.offset] &= !(1 << (state % WORD_BITS));
 		}
 
+		self.trim();
+
 		matchs
 	}
 
-	pub fn push(&mut self, state: &StatePtr) {
-		self.states.push(Rc::clone(state));
+	/// Adds the state `state` itself (even a split)
+	pub fn push(&mut self, state: StateId) {
+		let word = state / WORD_BITS;
+
+		self.reserve(word, word + 1);
+		self.words[word - self.offset] |= 1 << (state % WORD_BITS);
 	}
-	
+
 	pub fn clear(&mut self) {
-		self.states.clear()
+		self.offset = 0;
+		self.words.clear()
 	}
-	
+
 	pub fn is_empty(&self) -> bool {
-		self.states.is_empty()
+		self.words.is_empty()
 	}
 
 	pub fn merge(&mut self, other: StateList) {
-		for state in other.states {
-			if !self.contains(&state) {
-				self.states.push(state);
-			}
+		if self.words.is_empty() {
+			*self = other;
+			return;
+		}
+
+		self.union(&other);
+	}
+
+	/// Adds all the states of `other`
+	pub fn union(&mut self, other: &StateList) {
+		if other.words.is_empty() {
+			return;
+		}
+
+		self.reserve(other.offset, other.offset + other.words.len());
+
+		let start = other.offset - self.offset;
+
+		for (word, other) in self.words[start..].iter_mut().zip(&other.words) {
+			*word |= other;
 		}
 	}
-	
+
 	pub fn len(&self) -> usize {
-		self.states.len()
+		self.words.iter().map(|word| word.count_ones() as usize).sum()
 	}
-	
+
 	pub fn hash_code(&self) -> u64 {
-		use std::hash::{Hash, Hasher};
+		use std::hash::Hasher;
 		use std::collections::hash_map::DefaultHasher;
-		
+
 		let mut hasher = DefaultHasher::new();
 		self.hash(&mut hasher);
 		hasher.finish()
 	}
 
-	pub fn enumerate(&self) -> Enumerate<std::slice::Iter<'_, StatePtr>> {
-		self.states.iter().enumerate()
+	pub fn enumerate(&self) -> Enumerate<Iter<'_>> {
+		self.iter().enumerate()
 	}
 
-	pub fn match_(&self, c: char) -> bool {
+	pub fn match_(&self, nfa: &Nfa, c: char) -> bool {
 
 		for state in self {
-			if state.borrow().matche_with(&c) {
+			if nfa[state].matche_with(&c) {
 				return true
 			}
 		}
 
 		false
 	}
-	
-	pub fn iter(&self) -> std::slice::Iter<'_, StatePtr> {
-		self.states.iter()
+
+	pub fn iter(&self) -> Iter<'_> {
+		Iter {
+			offset: self.offset,
+			words: &self.words,
+			index: 0,
+			current: self.words.first().copied().unwrap_or(0),
+		}
+	}
+
+	/// Makes room for the words from `start` to `end` (excluded)
+	fn reserve(&mut self, start: usize, end: usize) {
+		if self.words.is_empty() {
+			self.offset = start;
+			self.words.resize(end - start, 0);
+			return;
+		}
+
+		if start < self.offset {
+			self.words.splice(0..0, std::iter::repeat_n(0, self.offset - start));
+			self.offset = start;
+		}
+
+		if end > self.offset + self.words.len() {
+			self.words.resize(end - self.offset, 0);
+		}
+	}
+
+	/// Removes the leading and trailing empty words, so equal lists have the same words
+	fn trim(&mut self) {
+		while self.words.last() == Some(&0) {
+			self.words.pop();
+		}
+
+		let leading = self.words.iter().take_while(|word| **word == 0).count();
+
+		self.words.drain(..leading);
+		self.offset = if self.words.is_empty() { 0 } else { self.offset + leading };
+	}
+}
+
+// ===================================
+// 2. EPSILON CLOSURES
+// ===================================
+
+/// Epsilon closures of the states of an NFA, computed once per state on first use
+///
+/// The NFA is borrowed, or owned by the closures built with `EpsilonClosures::owned`
+pub struct EpsilonClosures<'a> {
+	nfa: Cow<'a, Nfa>,
+	closures: Vec<OnceCell<StateList>>,
+}
+
+impl<'a> EpsilonClosures<'a> {
+	pub fn new(nfa: &'a Nfa) -> Self {
+		Self::with_nfa(Cow::Borrowed(nfa))
 	}
-	
-	pub fn iter_mut(&mut self) -> std::slice::IterMut<'_, StatePtr> {
-		self.states.iter_mut()
+
+	/// Closures owning their NFA, for the automatons keeping them (see `NfaSimulation`, `LazyDfa`)
+	pub fn owned(nfa: Nfa) -> EpsilonClosures<'static> {
+		EpsilonClosures::with_nfa(Cow::Owned(nfa))
+	}
+
+	fn with_nfa(nfa: Cow<'a, Nfa>) -> Self {
+		let closures = (0..nfa.len()).map(|_| OnceCell::new()).collect();
+
+		EpsilonClosures { nfa, closures }
+	}
+
+	pub fn nfa(&self) -> &Nfa {
+		&self.nfa
+	}
+
+	/// Gives back the NFA, without its closures
+	pub fn into_nfa(self) -> Nfa {
+		self.nfa.into_owned()
+	}
+
+	/// States reached from `state` by its epsilon transitions (see `StateList::add_state`)
+	pub fn of(&self, state: StateId) -> &StateList {
+		self.closures[state].get_or_init(|| StateList::from(&self.nfa, state))
+	}
+
+	/// Adds the epsilon closure of `state` to `list`
+	pub fn add_to(&self, list: &mut StateList, state: StateId) {
+		list.union(self.of(state));
 	}
 }
@@ -0,0 +1,182 @@
+use std::{cell::OnceCell, collections::HashMap};
+
+use super::*;
+use super::normalizer::{NormalizedDfa, NormalizedState};
+
+/// Trailing context of a rule (`r/s`): the rule matches `rs`, but only `r` is consumed,
+/// `s` is pushed back into the input
+#[derive(Debug, Clone)]
+pub struct TrailingContext {
+	/// NFA of `r` alone (without `^`)
+	pub head: Nfa,
+
+	/// NFA of `s` alone (without `$`)
+	pub tail: Nfa,
+
+	/// Length of `r`, if all its matches have the same length
+	pub head_length: Option<usize>,
+
+	/// Length of `s`, if all its matches have the same length
+	pub tail_length: Option<usize>,
+
+	/// DFAs of `r` and `s`, built by the first `head_length_in` needing them
+	matchers: OnceCell<Box<(FullMatcher, FullMatcher)>>,
+}
+
+impl TrailingContext {
+	/// Returns true if both `r` and `s` have a variable length ("dangerous" trailing context)
+	pub fn is_variable(&self) -> bool {
+		self.head_length.is_none() && self.tail_length.is_none()
+	}
+
+	/// Length of `r` in `text`, a match of `rs`.
+	///
+	/// When both parts vary, `r` is the longest head of `text` followed by a match of `s`
+	pub fn head_length_in(&self, text: &[char]) -> usize {
+		if let Some(length) = self.head_length {
+			return length;
+		}
+
+		if let Some(length) = self.tail_length {
+			return text.len().saturating_sub(length);
+		}
+
+		let (head, tail) = self.matchers
+			.get_or_init(|| Box::new((FullMatcher::new(&self.head), FullMatcher::new(&self.tail))))
+			.as_ref();
+
+		(0..=text.len()).rev()
+			.find(|split| head.matches(&text[..*split]) && tail.matches(&text[*split..]))
+			.unwrap_or(text.len())
+	}
+}
+
+/// DFA checking if a whole text is matched by an NFA
+#[derive(Debug, Clone)]
+struct FullMatcher {
+	start_id: usize,
+	states: HashMap<usize, NormalizedState>,
+}
+
+impl FullMatcher {
+	fn new(nfa: &Nfa) -> Self {
+		let mut dfa = Dfa::new(&[nfa]);
+		let dfa = NormalizedDfa::from(&mut dfa);
+
+		Self { start_id: dfa.start_id, states: dfa.states }
+	}
+
+	/// Returns true if the whole `text` is matched
+	fn matches(&self, text: &[char]) -> bool {
+		let mut id = self.start_id;
+
+		for c in text {
+			id = match self.states[&id].next.get(&InputCondition::Char(*c)) {
+				Some(next) => *next,
+				None => return false,
+			};
+		}
+
+		!self.states[&id].matchs.is_empty()
+	}
+}
+
+impl Regex {
+	/// Builds the NFA of `expr/following` (matching `expr` followed by `following`), and its trailing context
+	pub fn with_trailing_context(expr: String, following: String, id: usize) -> ParsingResult<(Nfa, TrailingContext)> {
+		Self::with_trailing_context_and_flags(expr, following, id, RegexFlags::default())
+	}
+
+	/// Same as `with_trailing_context`, compiled according to `flags`
+	pub fn with_trailing_context_and_flags(expr: String, following: String, id: usize, flags: RegexFlags) -> ParsingResult<(Nfa, TrailingContext)> {
+		let mut head = Self::tokens_with_flags(&expr, flags)?;
+		let mut tail = Self::tokens_with_flags(&following, flags)?;
+
+		// '^' and '$' apply to the whole rule
+		let start_of_line = head.front() == Some(&RegexType::LineStart);
+		if start_of_line {
+			head.pop_front();
+		}
+
+		let end_of_line = tail.back() == Some(&RegexType::LineEnd);
+		if end_of_line {
+			tail.pop_back();
+		}
+
+		if head.is_empty() || tail.is_empty() {
+			return ParsingError::unrecognized_rule().because("empty trailing context part").into()
+		}
+
+		// ^(r)(s)$
+		let mut tokens = VecDeque::with_capacity(head.len() + tail.len() + 6);
+
+		if start_of_line {
+			tokens.push_back(RegexType::LineStart);
+		}
+
+		tokens.push_back(RegexType::OpenParenthesis);
+		tokens.extend(head.iter().cloned());
+		tokens.push_back(RegexType::CloseParenthesis);
+		tokens.push_back(RegexType::OpenParenthesis);
+		tokens.extend(tail.iter().cloned());
+		tokens.push_back(RegexType::CloseParenthesis);
+
+		if end_of_line {
+			tokens.push_back(RegexType::LineEnd);
+		}
+
+		let start = post2nfa(re2post(Self::add_concatenation(tokens))?, id)?;
+
+		let head = re2post(Self::add_concatenation(head))?;
+		let tail = re2post(Self::add_concatenation(tail))?;
+
+		let trailing_context = TrailingContext {
+			head_length: Self::length(&head),
+			tail_length: Self::length(&tail),
+			head: post2nfa(head, id)?,
+			tail: post2nfa(tail, id)?,
+			matchers: OnceCell::new(),
+		};
+
+		Ok((start, trailing_context))
+	}
+
+	/// Number of chars matched by a postfix expression, if all its matches have the same length
+	pub fn length(postfix: &VecDeque<TokenType>) -> Option<usize> {
+		let mut lengths: Vec<Option<usize>> = Vec::with_capacity(postfix.len());
+
+		for token in postfix {
+			match token.into_inner() {
+				RegexType::Concatenation => {
+					let e2 = lengths.pop().flatten();
+					let e1 = lengths.pop().flatten();
+
+					lengths.push(e1.zip(e2).map(|(e1, e2)| e1 + e2));
+				},
+
+				RegexType::Or => {
+					let e2 = lengths.pop().flatten();
+					let e1 = lengths.pop().flatten();
+
+					lengths.push(if e1 == e2 { e1 } else { None });
+				},
+
+				RegexType::Quant(quantifier) => {
+					let e = lengths.pop().flatten();
+
+					lengths.push(match quantifier {
+						Quantifier::Exact(n) => e.map(|e| e * n),
+						Quantifier::Range(n, m) if n == m => e.map(|e| e * n),
+						_ => e.filter(|e| *e == 0),
+					});
+				},
+
+				RegexType::LineStart | RegexType::LineEnd => {},
+
+				_ => lengths.push(Some(1)),
+			}
+		}
+
+		lengths.pop().flatten()
+	}
+}
@@ -0,0 +1,199 @@
+use super::*;
+
+/// Highest Unicode scalar value
+const MAX_SCALAR: u32 = 0x10ffff;
+
+/// The surrogates, which are not scalar values (and have no UTF-8 encoding)
+const SURROGATES: (u32, u32) = (0xd800, 0xdfff);
+
+/// Highest scalar value encoded in 1, 2 and 3 bytes
+const ENCODING_LIMITS: [u32; 3] = [0x7f, 0x7ff, 0xffff];
+
+impl Regex {
+	/// Lowers the tokens of a Unicode regex into tokens on the bytes of its UTF-8 encoding (--utf8):
+	/// the chars are lowered into their byte sequences and the classes into alternations of byte sequences.
+	///
+	/// A byte `b` is the char `b as char`, like the input read by the scanner, so the automaton still runs on bytes.
+	/// The ASCII chars and classes are kept as they are
+	pub fn lower_utf8(tokens: VecDeque<RegexType>) -> VecDeque<RegexType> {
+		let mut lowered = VecDeque::with_capacity(tokens.len());
+
+		for token in tokens {
+			match token {
+				RegexType::Char(c) if !c.is_ascii() => {
+					let mut buffer = [0; 4];
+
+					lowered.push_back(RegexType::OpenParenthesis);
+					lowered.extend(c.encode_utf8(&mut buffer).bytes().map(|byte| RegexType::Char(byte as char)));
+					lowered.push_back(RegexType::CloseParenthesis);
+				},
+
+				RegexType::CharacterClass(class) if class.negated || !class.chars.iter().all(char::is_ascii) => {
+					lowered.extend(Self::lower_class(&class));
+				},
+
+				token => lowered.push_back(token),
+			}
+		}
+
+		lowered
+	}
+
+	/// Alternation of the byte sequences of the scalar values of `class`
+	/// (all of them but its chars if it is negated)
+	fn lower_class(class: &CharacterClass) -> VecDeque<RegexType> {
+		let mut ranges = scalar_ranges(&class.chars);
+
+		if class.negated {
+			ranges.push(SURROGATES);
+			ranges = complement(&merged(ranges));
+		}
+
+		let sequences = utf8_sequences(&ranges);
+
+		// the single bytes in one class
+		let mut bytes = CharacterClass::new();
+		let mut alternatives: Vec<Vec<RegexType>> = vec![];
+
+		for sequence in sequences {
+			match sequence.as_slice() {
+				[(start, end)] => {
+					let _ = bytes.add_range(*start as char, *end as char);
+				},
+
+				_ => alternatives.push(sequence.iter().map(|range| byte_range(*range)).collect()),
+			}
+		}
+
+		if !bytes.chars.is_empty() || alternatives.is_empty() {
+			alternatives.insert(0, vec![RegexType::CharacterClass(bytes)]);
+		}
+
+		let mut tokens = VecDeque::new();
+
+		tokens.push_back(RegexType::OpenParenthesis);
+
+		for (index, alternative) in alternatives.into_iter().enumerate() {
+			if index > 0 {
+				tokens.push_back(RegexType::Or);
+			}
+
+			tokens.extend(alternative);
+		}
+
+		tokens.push_back(RegexType::CloseParenthesis);
+
+		tokens
+	}
+}
+
+/// Token of the bytes from `start` to `end`
+fn byte_range((start, end): (u8, u8)) -> RegexType {
+	if start == end {
+		RegexType::Char(start as char)
+	} else {
+		let mut class = CharacterClass::new();
+		let _ = class.add_range(start as char, end as char);
+
+		RegexType::CharacterClass(class)
+	}
+}
+
+/// The chars `chars` as sorted, disjoint and non adjacent ranges of scalar values
+fn scalar_ranges(chars: &[char]) -> Vec<(u32, u32)> {
+	merged(chars.iter().map(|c| (*c as u32, *c as u32)).collect())
+}
+
+/// Sorts the ranges, and merges the overlapping or adjacent ones
+fn merged(mut ranges: Vec<(u32, u32)>) -> Vec<(u32, u32)> {
+	ranges.sort();
+
+	let mut merged: Vec<(u32, u32)> = Vec::with_capacity(ranges.len());
+
+	for (start, end) in ranges {
+		match merged.last_mut() {
+			Some(last) if start <= last.1 + 1 => last.1 = last.1.max(end),
+			_ => merged.push((start, end)),
+		}
+	}
+
+	merged
+}
+
+/// The scalar values in none of the ranges (sorted and disjoint)
+fn complement(ranges: &[(u32, u32)]) -> Vec<(u32, u32)> {
+	let mut complement = vec![];
+	let mut next = 0;
+
+	for (start, end) in ranges {
+		if *start > next {
+			complement.push((next, start - 1));
+		}
+
+		next = end + 1;
+	}
+
+	if next <= MAX_SCALAR {
+		complement.push((next, MAX_SCALAR));
+	}
+
+	complement
+}
+
+/// The UTF-8 encodings of the scalar values of `ranges`, as sequences of byte ranges:
+/// a scalar value is in the ranges if and only if its encoding is matched by one of the sequences.
+///
+/// A range is split until its first and last values have the same encoding length,
+/// and all the bytes after the first differing one cover their whole continuation range
+/// (the sequence is then the byte ranges between the encodings of its bounds)
+pub fn utf8_sequences(ranges: &[(u32, u32)]) -> Vec<Vec<(u8, u8)>> {
+	let mut sequences = vec![];
+	let mut stack: Vec<(u32, u32)> = ranges.iter().rev().copied().collect();
+
+	while let Some((start, end)) = stack.pop() {
+		// different encoding lengths
+		if let Some(limit) = ENCODING_LIMITS.into_iter().find(|limit| start <= *limit && *limit < end) {
+			stack.push((limit + 1, end));
+			stack.push((start, limit));
+			continue;
+		}
+
+		let split = (1..4).find_map(|continuations| {
+			let mask = (1_u32 << (6 * continuations)) - 1;
+
+			if start & !mask == end & !mask {
+				return None;
+			}
+
+			if start & mask != 0 {
+				return Some(start | mask);
+			}
+
+			if end & mask != mask {
+				return Some((end & !mask) - 1);
+			}
+
+			None
+		});
+
+		if let Some(split) = split.filter(|_| start > 0x7f) {
+			stack.push((split + 1, end));
+			stack.push((start, split));
+			continue;
+		}
+
+		let (mut start_bytes, mut end_bytes) = ([0; 4], [0; 4]);
+		let start_bytes = encode(start, &mut start_bytes);
+		let end_bytes = encode(end, &mut end_bytes);
+
+		sequences.push(start_bytes.iter().copied().zip(end_bytes.iter().copied()).collect());
+	}
+
+	sequences
+}
+
+fn encode(scalar: u32, buffer: &mut [u8; 4]) -> &[u8] {
+	let c = char::from_u32(scalar).expect("the surrogates are not in the ranges");
+
+	c.encode_utf8(buffer).as_bytes()
+}
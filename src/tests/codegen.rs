@@ -0,0 +1,28 @@
+mod c;
+mod packing;
+mod rust;
+mod summary;
+mod tables;
+
+use std::io::Cursor;
+
+use crate::parsing::{Parsing, Reader};
+
+/// Parses a complete lex specification (warnings are allowed)
+fn parse(spec: &str) -> Parsing {
+	parse_with(spec, false)
+}
+
+/// Same as `parse`, the specification being decoded as UTF-8 if `utf8` is set (--utf8)
+fn parse_with(spec: &str, utf8: bool) -> Parsing {
+	let mut parsing = Parsing::new().unwrap();
+	parsing.definitions.utf8 = utf8;
+
+	let mut reader = Reader::new(Cursor::new(spec.as_bytes().to_vec()), "<test>").unwrap();
+
+	let _ = parsing.parse_sections(&mut reader);
+
+	assert!(parsing.errors.iter().all(|err| err.is_warning()), "invalid test specification: {:?}", parsing.errors);
+
+	parsing
+}
@@ -0,0 +1,792 @@
+use std::{
+	fs,
+	io::Write,
+	process::{Command, Output, Stdio},
+	sync::atomic::{AtomicUsize, Ordering},
+};
+
+use crate::{codegen::*, interpreter::Interpreter, parsing::Parsing};
+
+use super::{parse, parse_with};
+
+fn generate(spec: &str) -> String {
+	generate_tables(spec, false)
+}
+
+/// Same as `generate`, with packed transitions if `packed` is set
+fn generate_tables(spec: &str, packed: bool) -> String {
+	generate_parsing(&parse(spec), packed)
+}
+
+/// Same as `generate_tables`, from the parsed specification
+fn generate_parsing(parsing: &Parsing, packed: bool) -> String {
+	let mut tables = Tables::new(&parsing.rules, &parsing.definitions);
+
+	if packed {
+		tables.pack();
+	}
+
+	let mut out = Vec::new();
+	CGenerator::new(parsing, &tables).generate(&mut out).unwrap();
+
+	String::from_utf8(out).unwrap()
+}
+
+/// Compiles the scanner of the rules (with a main() running yylex()) and runs it on the input,
+/// returns its standard output.
+///
+/// None if there is no C compiler
+fn compile_and_run(spec: &str, input: &str) -> Option<String> {
+	let output = compile_and_run_output(spec, input)?;
+
+	Some(String::from_utf8(output.stdout).unwrap())
+}
+
+/// Same as `compile_and_run`, with the exit status and the error output of the scanner
+fn compile_and_run_output(spec: &str, input: &str) -> Option<Output> {
+	compile_and_run_tables(spec, input, false)
+}
+
+/// Same as `compile_and_run_output`, with packed transitions if `packed` is set
+fn compile_and_run_tables(spec: &str, input: &str, packed: bool) -> Option<Output> {
+	compile_and_run_code(generate_tables(&with_main(spec), packed), input)
+}
+
+/// Same as `compile_and_run`, the specification being decoded as UTF-8 (--utf8)
+fn compile_and_run_utf8(spec: &str, input: &str) -> Option<String> {
+	let output = compile_and_run_code(generate_parsing(&parse_with(&with_main(spec), true), false), input)?;
+
+	Some(String::from_utf8(output.stdout).unwrap())
+}
+
+/// The specification `spec` (without user subroutines), with a main() running yylex()
+fn with_main(spec: &str) -> String {
+	format!("{spec}%%\nint yywrap(void) {{ return 1; }}\nint main(void) {{ return yylex(); }}\n")
+}
+
+/// Compiles the scanner `code` and runs it on the input
+fn compile_and_run_code(code: String, input: &str) -> Option<Output> {
+	static SCANNERS: AtomicUsize = AtomicUsize::new(0);
+
+	let dir = std::env::temp_dir().join(format!("ft_lex_{}_{}", std::process::id(), SCANNERS.fetch_add(1, Ordering::Relaxed)));
+	fs::create_dir_all(&dir).unwrap();
+	fs::write(dir.join("lex.yy.c"), code).unwrap();
+
+	let compiled = Command::new("cc")
+		.current_dir(&dir)
+		.args(["-o", "scanner", "lex.yy.c"])
+		.status()
+		.ok()?;
+	assert!(compiled.success(), "the scanner does not compile");
+
+	let mut scanner = Command::new(dir.join("scanner"))
+		.stdin(Stdio::piped())
+		.stdout(Stdio::piped())
+		.stderr(Stdio::piped())
+		.spawn()
+		.unwrap();
+
+	// the scanner may stop before reading all its input
+	let _ = scanner.stdin.take().unwrap().write_all(input.as_bytes());
+	let output = scanner.wait_with_output().unwrap();
+
+	let _ = fs::remove_dir_all(&dir);
+
+	Some(output)
+}
+
+#[test]
+fn test_generate_posix_interface() {
+	let code = generate("%%\na\tECHO;\n");
+
+	for declaration in ["int yylex(void)", "char *yytext", "int yyleng", "FILE *yyin", "FILE *yyout", "int yywrap(void);"] {
+		assert!(code.contains(declaration), "missing `{declaration}`");
+	}
+
+	assert!(code.contains("#define ECHO"));
+	assert!(code.contains("#define BEGIN"));
+}
+
+#[test]
+fn test_generate_fragments_before_scanner() {
+	let code = generate("%{\nint counter = 0;\n%}\n%%\na\tcounter++;\n");
+
+	let fragment = code.find("int counter = 0;").expect("missing fragment");
+	let scanner = code.find("int yylex(void)\n{").unwrap();
+
+	assert!(fragment < scanner);
+}
+
+#[test]
+fn test_generate_actions() {
+	let code = generate("%%\na\t{ return 1; }\nb\tprintf(\"b\");\n");
+
+	assert!(code.contains("case 1:\n\t\t\t{ return 1; }\n\t\t\tbreak;"));
+	assert!(code.contains("case 2:\n\t\t\tprintf(\"b\");\n\t\t\tbreak;"));
+	assert!(code.contains("#define YY_NUM_RULES 2"));
+}
+
+#[test]
+fn test_generate_or_actions_fall_through() {
+	let code = generate("%%\na\t|\nb\t|\nc\treturn 3;\n");
+
+	assert!(code.contains("case 1:\n\t\tcase 2:\n\t\tcase 3:\n\t\t\treturn 3;\n\t\t\tbreak;"));
+}
+
+#[test]
+fn test_generate_reject() {
+	let code = generate("%%\nfrob\t{ special(); REJECT; }\n[a-z]+\tword();\n");
+
+	assert!(code.contains("#define REJECT"));
+	assert!(code.contains("static const short yy_accept_index["));
+	assert!(code.contains("static const short yy_accept_list["));
+	assert!(code.contains("\t\t\tyy_save_state(yy_cp - yy_buf_pos, yy_state);\n"));
+
+	// REJECT goes back before the computation of yytext
+	let label = code.find("yy_find_rule:\n").expect("missing yy_find_rule");
+	assert!(label < code.find("\t\tyy_set_text();\n").unwrap());
+}
+
+#[test]
+fn test_generate_without_reject() {
+	let code = generate("%%\nfrob\tspecial();\n[a-z]+\tword();\n");
+
+	assert!(!code.contains("REJECT"));
+	assert!(!code.contains("yy_accept_list"));
+	assert!(!code.contains("yy_save_state"));
+	assert!(!code.contains("yy_find_rule"));
+}
+
+#[test]
+fn test_generate_end_of_file_actions() {
+	let code = generate("%x STR\n%%\na\tECHO;\n<STR><<EOF>>\t{ error(); return 0; }\n");
+
+	assert!(code.contains("\t\t\tswitch (yy_start_condition) {\n\t\t\tcase STR:\n\t\t\t\t{ error(); return 0; }\n\t\t\t\tbreak;\n\t\t\t}"));
+	assert!(!code.contains("case INITIAL:"));
+
+	// run before yywrap()
+	assert!(code.find("switch (yy_start_condition)").unwrap() < code.find("if (yywrap())").unwrap());
+}
+
+#[test]
+fn test_generate_without_end_of_file_rules() {
+	let code = generate("%%\na\tECHO;\n");
+
+	assert!(!code.contains("switch (yy_start_condition)"));
+}
+
+#[test]
+fn test_generate_default_end_of_file_action() {
+	let code = generate("%x STR\n%%\n<<EOF>>\treturn 0;\n<STR><<EOF>>\treturn 1;\n");
+
+	assert!(code.contains("\t\t\tcase INITIAL:\n\t\t\t\treturn 0;\n"));
+	assert!(code.contains("\t\t\tcase STR:\n\t\t\t\treturn 1;\n"));
+}
+
+#[test]
+fn test_generate_or_end_of_file_actions_fall_through() {
+	let code = generate("%x A B\n%%\n<A><<EOF>>\t|\n<B><<EOF>>\treturn 2;\n");
+
+	assert!(code.contains("\t\t\tcase A:\n\t\t\tcase B:\n\t\t\t\treturn 2;\n\t\t\t\tbreak;\n"));
+}
+
+#[test]
+fn test_generate_subroutines_at_the_end() {
+	let code = generate("%%\na\tECHO;\n%%\nint yywrap(void) { return 1; }");
+
+	assert!(code.trim_end().ends_with("int yywrap(void) { return 1; }"));
+}
+
+#[test]
+fn test_generate_start_conditions() {
+	let code = generate("%s B A\n%x C\n%%\na\tBEGIN A;\n");
+
+	assert!(code.contains("#define INITIAL 0\n#define A 1\n#define B 2\n#define C 3\n"));
+}
+
+#[test]
+fn test_generate_start_states() {
+	let code = generate("%x C\n%%\na\tBEGIN C;\n<C>b\tBEGIN INITIAL;\n");
+
+	assert!(code.contains("#define YY_NUM_CONDITIONS 2\n"));
+	assert!(code.contains("static const short yy_start_states[YY_NUM_CONDITIONS][2] = {\n\t{ 0, 0 },\n\t{ 1, 1 },\n};"));
+	assert!(code.contains("yy_state = yy_start_states[yy_start_condition][yy_at_bol];"));
+}
+
+#[test]
+fn test_generate_head_lengths() {
+	let code = generate("%%\nab/c+\tone();\n[a-z]+/\"!\"\ttwo();\n");
+
+	assert!(code.contains("static size_t yy_head_length(int rule, size_t len)"));
+	assert!(code.contains("\tcase 1:\n\t\treturn 2;\n\tcase 2:\n\t\treturn len - 1;\n\tdefault:\n\t\treturn len;\n"));
+	assert!(code.contains("yy_match_len = yy_head_length(yy_rule, yy_match_len);"));
+
+	// only needed by the variable trailing contexts
+	assert!(!code.contains("yy_variable_head"));
+}
+
+#[test]
+fn test_generate_variable_head_length() {
+	let code = generate("%%\nx+/y+z\tone();\n");
+
+	assert!(code.contains("static size_t yy_variable_head(size_t len, int head, int tail)"));
+	assert!(code.contains("\tcase 1:\n\t\treturn yy_variable_head(len, "));
+}
+
+#[test]
+fn test_generate_without_trailing_context() {
+	let code = generate("%%\na\tECHO;\n");
+
+	assert!(code.contains("\tswitch (rule) {\n\tdefault:\n\t\treturn len;\n\t}"));
+}
+
+#[test]
+fn test_generate_tables() {
+	let code = generate("%%\nab\tECHO;\n");
+
+	assert!(code.contains("static const short yy_accept["));
+	assert!(code.contains("static const short yy_eol["));
+	assert!(code.contains("static const short yy_ec[256] = {"));
+	assert!(code.contains("static const short yy_nxt[3][YY_NUM_CLASSES] = {"));
+	assert!(code.contains("#define YY_NUM_CLASSES 3\n"));
+	assert!(code.contains("#define yy_next_state(state, class) yy_nxt[state][class]\n"));
+	assert!(code.contains("yy_next_state(yy_state, yy_ec[(unsigned char) yy_buf[yy_cp]])"));
+}
+
+#[test]
+fn test_generate_packed_tables() {
+	let code = generate_tables("%%\nab\tECHO;\n[a-z]+\tECHO;\n", true);
+
+	for array in ["yy_base", "yy_def", "yy_nxt", "yy_chk"] {
+		assert!(code.contains(&format!("static const short {array}[")), "missing `{array}`");
+	}
+
+	assert!(code.contains("static int yy_next_state(int yy_state, int yy_class)"));
+	assert!(!code.contains("yy_nxt[state][class]"));
+}
+
+#[test]
+fn test_generate_action_primitives_only_if_used() {
+	let code = generate("%%\na\tECHO;\n");
+
+	assert!(code.contains("#define yymore() (yy_more_flag = 1)"));
+	assert!(code.contains("#define yyless(n) yy_less(n)"));
+	assert!(!code.contains("static int input(void)"));
+	assert!(!code.contains("static void unput(int c)"));
+	assert!(!code.contains("static void yy_less(int n)"));
+
+	let code = generate("%%\na\t{ unput(input()); yyless(0); }\n");
+
+	assert!(code.contains("static int input(void)"));
+	assert!(code.contains("static void unput(int c)"));
+	assert!(code.contains("static void yy_less(int n)"));
+}
+
+#[test]
+fn test_generate_primitives_in_subroutines() {
+	let code = generate("%%\na\tskip();\n%%\nvoid skip(void) { input(); }\n");
+
+	assert!(code.contains("static int input(void)"));
+	assert!(!code.contains("static void unput(int c)"));
+}
+
+#[test]
+fn test_scanner_echo() {
+	let Some(output) = compile_and_run("%%\n[0-9]+\tECHO;\n.\t;\n", "a12b3\n") else {
+		return;
+	};
+
+	assert_eq!(output, "123\n");
+}
+
+#[test]
+fn test_scanner_equivalence_classes() {
+	let spec = "%%\n[^a-z\\n]+\tprintf(\"<%s>\", yytext);\n[a-z]+\tprintf(\"(%s)\", yytext);\n";
+
+	let Some(output) = compile_and_run(spec, "ab12écd\n") else {
+		return;
+	};
+
+	// the bytes above 127 are read by no rule
+	assert_eq!(output, "(ab)<12>é(cd)\n");
+}
+
+#[test]
+fn test_scanner_utf8() {
+	let spec = "%%\n[α-ωΑ-Ω_][α-ωΑ-Ω0-9_]*\tprintf(\"<%s>\", yytext);\n[^α-ω\\n]\tprintf(\"«%s»\", yytext);\n";
+
+	let Some(output) = compile_and_run_utf8(spec, "αβ_2 x€ ω😀\n") else {
+		return;
+	};
+
+	// each char is matched whole, the actions being written as they are
+	assert_eq!(output, "<αβ_2>« »«x»«€»« »<ω>«😀»\n");
+}
+
+#[test]
+fn test_scanner_packed_tables() {
+	let spec = "%%
+if|else\tprintf(\"<%s>\", yytext);
+[a-z]+\tprintf(\"(%s)\", yytext);
+[0-9]+/[a-z]+[.]\tprintf(\"[%s]\", yytext);
+^#.*\tprintf(\"{%s}\", yytext);
+[a-z]+$\tprintf(\"$%s\", yytext);
+";
+	let input = "if iffy 12ab. else 34\n#define x\nend\n";
+
+	let Some(dense) = compile_and_run_tables(spec, input, false) else {
+		return;
+	};
+	let packed = compile_and_run_tables(spec, input, true).unwrap();
+
+	assert_eq!(String::from_utf8(packed.stdout).unwrap(), String::from_utf8(dense.stdout).unwrap());
+}
+
+#[test]
+fn test_scanner_packed_tables_reject() {
+	let spec = "%%\n[a-z]+\t{ printf(\"(%s)\", yytext); REJECT; }\nab\tprintf(\"<%s>\", yytext);\n";
+
+	let Some(output) = compile_and_run_tables(spec, "abc\n", true) else {
+		return;
+	};
+
+	assert_eq!(String::from_utf8(output.stdout).unwrap(), "(abc)(ab)<ab>(c)c\n");
+}
+
+#[test]
+fn test_scanner_yymore() {
+	let spec = "%%\nmore\t{ yymore(); }\nover\t{ printf(\"[%s:%d]\", yytext, yyleng); }\n";
+
+	let Some(output) = compile_and_run(spec, "moreover over") else {
+		return;
+	};
+
+	assert_eq!(output, "[moreover:8] [over:4]");
+}
+
+#[test]
+fn test_scanner_yyless() {
+	let spec = "%%\nfoobar\t{ yyless(3); printf(\"[%s]\", yytext); }\nbar\tprintf(\"(bar)\");\n";
+
+	let Some(output) = compile_and_run(spec, "foobar") else {
+		return;
+	};
+
+	assert_eq!(output, "[foo](bar)");
+}
+
+#[test]
+fn test_scanner_yyless_to_a_line_start() {
+	let spec = "%%\n^#[a-z]+\tprintf(\"[directive %s]\", yytext);\na\\n#[a-z]+\t{ yyless(2); printf(\"[a]\"); }\n#\tprintf(\"[hash]\");\n";
+
+	let Some(output) = compile_and_run(spec, "a\n#include") else {
+		return;
+	};
+
+	// the pushed back text starts a line: the ^ rule matches it
+	assert_eq!(output, "[a][directive #include]");
+}
+
+#[test]
+fn test_scanner_yyless_zero_at_a_line_start() {
+	let spec = "%s DIRECTIVE\n%%\n<INITIAL>^#[a-z]+\t{ BEGIN DIRECTIVE; yyless(0); }\n<DIRECTIVE>^#[a-z]+\t{ BEGIN INITIAL; printf(\"[%s]\", yytext); }\n#\tprintf(\"[hash]\");\n";
+
+	let Some(output) = compile_and_run(spec, "#define\n") else {
+		return;
+	};
+
+	assert_eq!(output, "[#define]\n");
+}
+
+#[test]
+fn test_scanner_yyless_in_the_middle_of_a_line() {
+	let spec = "%%\n^#[a-z]+\tprintf(\"[directive]\");\nx#[a-z]+\t{ yyless(1); printf(\"[x]\"); }\n#\tprintf(\"[hash]\");\n";
+
+	let Some(output) = compile_and_run(spec, "x#a") else {
+		return;
+	};
+
+	assert_eq!(output, "[x][hash]a");
+}
+
+#[test]
+fn test_scanner_input() {
+	let spec = "%%\n\"/*\"\t{ int c, last = 0; while ((c = input()) != 0 && !(last == '*' && c == '/')) last = c; printf(\"[comment]\"); }\n";
+
+	let Some(output) = compile_and_run(spec, "a/* b\n */c") else {
+		return;
+	};
+
+	assert_eq!(output, "a[comment]c");
+}
+
+#[test]
+fn test_scanner_input_at_the_end_of_the_input() {
+	let spec = "%%\na\t{ printf(\"[%d]\", input()); }\n";
+
+	let Some(output) = compile_and_run(spec, "a") else {
+		return;
+	};
+
+	assert_eq!(output, "[0]");
+}
+
+#[test]
+fn test_scanner_unput() {
+	let spec = "%%\nu\t{ unput('b'); unput('a'); }\nab\tprintf(\"[ab]\");\n";
+
+	let Some(output) = compile_and_run(spec, "uu.") else {
+		return;
+	};
+
+	assert_eq!(output, "[ab][ab].");
+}
+
+#[test]
+fn test_scanner_unput_newline_starts_a_line() {
+	let spec = "%%\nx\t{ unput('#'); unput('\\n'); }\n^#\tprintf(\"[bol]\");\n#\tprintf(\"[hash]\");\n";
+
+	let Some(output) = compile_and_run(spec, "x") else {
+		return;
+	};
+
+	assert_eq!(output, "\n[bol]");
+}
+
+#[test]
+fn test_generate_yytext_pointer() {
+	for spec in ["%%\na\tECHO;\n", "%pointer\n%%\na\tECHO;\n"] {
+		let code = generate(spec);
+
+		assert!(code.contains("\nchar *yytext = NULL;\n"));
+		assert!(code.contains("yytext = yy_buf + yy_text_pos;"));
+		assert!(!code.contains("YYLMAX"));
+	}
+}
+
+#[test]
+fn test_generate_yytext_array() {
+	let code = generate("%array\n%%\na\tECHO;\n");
+
+	assert!(code.contains(&format!("\n#define YYLMAX {YYLMAX}\nchar yytext[YYLMAX];\n")));
+	assert!(code.contains("memcpy(yytext, yy_buf + yy_text_pos, (size_t) yyleng);"));
+	assert!(!code.contains("yytext = yy_buf"));
+}
+
+#[test]
+fn test_generate_yytext_array_size() {
+	let code = generate("%array\n%o 4000\n%%\na\tECHO;\n");
+
+	assert!(code.contains("\n#define YYLMAX 4000\n"));
+
+	// the size of the array without %array
+	let code = generate("%o 4000\n%%\na\tECHO;\n");
+
+	assert!(!code.contains("YYLMAX"));
+}
+
+#[test]
+fn test_scanner_array_yytext_is_kept_by_unput() {
+	let spec = "%array\n%%\n[a-z]+\t{ int i; for (i = yyleng - 1; i >= 0; i--) unput(yytext[i] - 'a' + 'A'); printf(\"[%s]\", yytext); }\n[A-Z]+\tprintf(\"(%s)\", yytext);\n";
+
+	let Some(output) = compile_and_run(spec, "abc") else {
+		return;
+	};
+
+	assert_eq!(output, "[abc](ABC)");
+}
+
+#[test]
+fn test_scanner_array_primitives() {
+	let spec = "%array\n%%\nmore\tyymore();\nover\tprintf(\"[%s]\", yytext);\nfoobar\t{ yyless(3); printf(\"[%s]\", yytext); }\nbar\tprintf(\"(bar)\");\n";
+
+	let Some(output) = compile_and_run(spec, "moreover foobar") else {
+		return;
+	};
+
+	assert_eq!(output, "[moreover] [foo](bar)");
+}
+
+#[test]
+fn test_scanner_array_token_too_long() {
+	let Some(output) = compile_and_run_output("%array\n%o 3000\n%%\n[a-z]+\tECHO;\n", &"a".repeat(5000)) else {
+		return;
+	};
+
+	assert_eq!(output.status.code(), Some(2));
+	assert!(String::from_utf8(output.stderr).unwrap().contains("token too long for yytext"));
+}
+
+#[test]
+fn test_generate_noyywrap() {
+	let code = generate("%option noyywrap\n%%\na\tECHO;\n");
+
+	assert!(code.contains("#define yywrap() 1\n"));
+	assert!(!code.contains("int yywrap(void);"));
+
+	// the specification has no yywrap()
+	let Some(output) = compile_and_run_code(code.replace("%%", "") + "int main(void) { return yylex(); }\n", "ab") else {
+		return;
+	};
+
+	assert_eq!(String::from_utf8(output.stdout).unwrap(), "ab");
+}
+
+#[test]
+fn test_scanner_nodefault() {
+	let spec = "%option nodefault\n%%\n[a-z]+\tprintf(\"[%s]\", yytext);\n";
+
+	assert!(generate(spec).contains("\t\tdefault:\n\t\t\tyy_fatal_error(\"ft_lex scanner jammed\");\n"));
+
+	let Some(output) = compile_and_run_output(spec, "ab1") else {
+		return;
+	};
+
+	assert_eq!(output.status.code(), Some(2));
+	assert_eq!(String::from_utf8(output.stdout).unwrap(), "[ab]");
+	assert!(String::from_utf8(output.stderr).unwrap().contains("ft_lex scanner jammed"));
+}
+
+#[test]
+fn test_scanner_stack() {
+	let spec = "%option stack\n%x COMMENT STRING\n%%\n\"/*\"\tyy_push_state(COMMENT);\n<COMMENT,STRING>\\\"\t{ if (YY_START == STRING) yy_pop_state(); else yy_push_state(STRING); }\n<COMMENT>\"*/\"\tyy_pop_state();\n<COMMENT,STRING>.\tprintf(\"%d%d\", YY_START, yy_top_state());\n";
+
+	let Some(output) = compile_and_run(spec, "a/*b\"*/\"c*/d") else {
+		return;
+	};
+
+	// the start condition and the top of the stack for each commented char
+	assert_eq!(output, "a10212110d");
+}
+
+#[test]
+fn test_generate_stack_primitives_only_if_used() {
+	let code = generate("%option stack\n%x A\n%%\na\tyy_push_state(A);\n");
+
+	assert!(code.contains("static void yy_push_state(int new_state)"));
+	assert!(!code.contains("yy_pop_state"));
+
+	// without %option stack, yy_push_state() is the user's
+	assert!(!generate("%x A\n%%\na\tyy_push_state(A);\n").contains("yy_start_stack"));
+}
+
+#[test]
+fn test_scanner_never_interactive() {
+	let spec = "%option never-interactive\n%%\n[a-z]+\\n\tprintf(\"[%s]\", yytext);\n";
+
+	assert!(generate(spec).contains("#define YY_INTERACTIVE 0\n"));
+	assert!(generate("%%\na\tECHO;\n").contains("#define YY_INTERACTIVE 1\n"));
+
+	let Some(output) = compile_and_run(spec, "ab\ncd\n") else {
+		return;
+	};
+
+	assert_eq!(output, "[ab\n][cd\n]");
+}
+
+#[test]
+fn test_scanner_eight_bit() {
+	let rules = "%%\n.\tprintf(\"[%02x]\", (unsigned char) yytext[0]);\n[^a]\tprintf(\"(%02x)\", (unsigned char) yytext[0]);\n";
+
+	let Some(output) = compile_and_run(&format!("%option 8bit\n{rules}"), "é\n") else {
+		return;
+	};
+
+	// `.` and the negated classes match the bytes 128 to 255
+	assert_eq!(output, "[c3][a9](0a)");
+
+	let Some(output) = compile_and_run(rules, "é\n") else {
+		return;
+	};
+
+	assert_eq!(output, "é(0a)");
+}
+
+#[test]
+fn test_generate_prefix() {
+	let code = generate("%option prefix=\"cfg\"\n%%\na\tECHO;\n");
+
+	for name in ["lex", "wrap", "in", "out", "leng", "text", "lineno", "column"] {
+		assert!(code.contains(&format!("#define yy{name} cfg{name}\n")), "missing `cfg{name}`");
+	}
+
+	assert!(!generate("%%\na\tECHO;\n").contains("#define yylex"));
+}
+
+#[test]
+fn test_generate_prefix_without_yywrap() {
+	let code = generate("%option prefix=\"cfg\" noyywrap\n%%\na\tECHO;\n");
+
+	assert!(code.contains("#define yylex cfglex\n"));
+	assert!(!code.contains("#define yywrap cfgwrap"));
+	assert!(code.contains("#define yywrap() 1\n"));
+
+	let dir = std::env::temp_dir().join(format!("ft_lex_{}_prefix_noyywrap", std::process::id()));
+	fs::create_dir_all(&dir).unwrap();
+	fs::write(dir.join("cfg.c"), code).unwrap();
+
+	// %array without input() nor unput(): yy_terminate_text is not an unused function
+	fs::write(dir.join("array.c"), generate("%array\n%%\na\tECHO;\n")).unwrap();
+
+	// yywrap is not redefined
+	let compiled = Command::new("cc").current_dir(&dir).args(["-Wall", "-Werror", "-c", "cfg.c", "array.c"]).status();
+	let _ = fs::remove_dir_all(&dir);
+
+	let compiled = compiled.expect("this test needs a C compiler (cc)");
+
+	assert!(compiled.success(), "the scanners do not compile without warnings");
+}
+
+#[test]
+fn test_scanners_with_prefixes_link_together() {
+	let scanner = |prefix: &str, rule: &str| generate(&format!("%option prefix=\"{prefix}\" noyywrap\n%%\n{rule}\n.|\\n\t;\n"));
+
+	let dir = std::env::temp_dir().join(format!("ft_lex_{}_prefixes", std::process::id()));
+	fs::create_dir_all(&dir).unwrap();
+
+	fs::write(dir.join("cfg.c"), scanner("cfg", "[a-z]+\tprintf(\"cfg(%s)\", yytext);")).unwrap();
+	fs::write(dir.join("query.c"), scanner("query", "[0-9]+\tprintf(\"query(%s:%d)\", yytext, yyleng);")).unwrap();
+	fs::write(dir.join("main.c"), concat!(
+		"#include <stdio.h>\n",
+		"extern FILE *cfgin, *queryin;\n",
+		"int cfglex(void);\n",
+		"int querylex(void);\n",
+		"int main(void) {\n",
+		"\tcfgin = fmemopen(\"ab 12\", 5, \"r\");\n",
+		"\tqueryin = fmemopen(\"cd 34\", 5, \"r\");\n",
+		"\tcfglex();\n",
+		"\treturn querylex();\n",
+		"}\n",
+	)).unwrap();
+
+	// without a C compiler, the test fails instead of passing without linking anything
+	let compiled = Command::new("cc").current_dir(&dir).args(["-o", "scanners", "cfg.c", "query.c", "main.c"]).status();
+	if compiled.is_err() {
+		let _ = fs::remove_dir_all(&dir);
+	}
+
+	let compiled = compiled.expect("this test needs a C compiler (cc)");
+	assert!(compiled.success(), "the scanners do not link together");
+
+	let output = Command::new(dir.join("scanners")).output().unwrap();
+	let _ = fs::remove_dir_all(&dir);
+
+	assert_eq!(String::from_utf8(output.stdout).unwrap(), "cfg(ab)query(34:2)");
+}
+
+#[test]
+fn test_generate_yylineno_only_for_newline_rules() {
+	let spec = "%option yylineno\n%%\n[a-z]+\tECHO;\n\\n\tECHO;\n\"/*\"(.|\\n)*\"*/\"\t;\n[^a-z]\tECHO;\n";
+	let code = generate(spec);
+
+	assert!(code.contains("int yylineno = 1;"));
+	assert!(!code.contains("int yycolumn"));
+
+	// the default rule, then the rules in order
+	assert!(code.contains("static const char yy_rule_can_match_eol[5] = {"));
+	assert!(code.contains("\t1, 0, 1, 1, 1,\n};"));
+	assert!(code.contains("if (yy_rule_can_match_eol[yy_rule])"));
+
+	let code = generate("%%\n[a-z]+\tECHO;\n");
+
+	assert!(!code.contains("yylineno"));
+	assert!(code.contains("#define YY_LESS_POSITION(n)\n"));
+}
+
+#[test]
+fn test_scanner_yylineno() {
+	let spec = "%option yylineno\n%%\n[a-z]+\tprintf(\"[%s:%d]\", yytext, yylineno);\n\"/*\"[^*]*\"*/\"\tprintf(\"[comment:%d]\", yylineno);\n";
+
+	let Some(output) = compile_and_run(spec, "a\nb /*\n\n*/ c\n") else {
+		return;
+	};
+
+	// the lines of the match are counted before its action
+	assert_eq!(output, "[a:1]\n[b:2] [comment:4] [c:4]\n");
+}
+
+#[test]
+fn test_scanner_yylineno_primitives() {
+	let spec = concat!(
+		"%option yylineno\n%%\n",
+		"ab\\n\\ncd\t{ yyless(2); printf(\"[%s:%d]\", yytext, yylineno); }\n",
+		"x\\n\t{ unput('\\n'); printf(\"[x:%d]\", yylineno); }\n",
+		"y\t{ input(); printf(\"[y:%d]\", yylineno); }\n",
+		"\\n\tprintf(\"[nl:%d]\", yylineno);\n",
+		"[a-z]+\tprintf(\"[%s:%d]\", yytext, yylineno);\n",
+	);
+
+	let Some(output) = compile_and_run(spec, "ab\n\ncd x\ny\nz") else {
+		return;
+	};
+
+	// the newlines given back by yyless() and unput() are counted when read again, the ones of input() are counted
+	assert_eq!(output, "[ab:1][nl:2][nl:3][cd:3] [x:3][nl:4][y:5][z:5]");
+}
+
+#[test]
+fn test_scanner_yycolumn() {
+	let spec = "%option yycolumn\n%%\n[a-z]+\tprintf(\"[%s:%d:%d]\", yytext, yylineno, yycolumn);\n";
+
+	let Some(output) = compile_and_run(spec, "ab cd\n  ef") else {
+		return;
+	};
+
+	assert_eq!(output, "[ab:1:3] [cd:1:6]\n  [ef:2:5]");
+
+	// REJECT counts the next match from the start of the rejected one
+	let spec = "%option yycolumn\n%%\nabc\t{ printf(\"[%d]\", yycolumn); REJECT; }\nab\tprintf(\"(%d)\", yycolumn);\n";
+
+	let Some(output) = compile_and_run(spec, "abc") else {
+		return;
+	};
+
+	assert_eq!(output, "[4](3)c");
+
+	// unput() of a newline goes back to the end of the previous line
+	let spec = "%option yycolumn\n%%\nab\\n\t{ unput('\\n'); printf(\"[%d:%d]\", yylineno, yycolumn); }\n\\n\tprintf(\"(%d:%d)\", yylineno, yycolumn);\n";
+
+	let Some(output) = compile_and_run(spec, "ab\n") else {
+		return;
+	};
+
+	assert_eq!(output, "[1:3](2:1)");
+}
+
+#[test]
+fn test_scanner_yycolumn_utf8() {
+	let spec = "%option yycolumn\n%%\n[α-ω]+\tprintf(\"[%d]\", yycolumn);\n";
+
+	let Some(output) = compile_and_run_utf8(spec, "αβ γ é") else {
+		return;
+	};
+
+	// the columns count the characters, not their bytes
+	assert_eq!(output, "[3] [5] é");
+
+	let Some(output) = compile_and_run("%option yycolumn\n%%\n[a-z]\tprintf(\"[%d]\", yycolumn);\n", "éa") else {
+		return;
+	};
+
+	assert_eq!(output, "é[4]");
+}
+
+#[test]
+fn test_scanner_positions_match_the_interpreter() {
+	let rules = "%option yycolumn\n%%\n[a-z]+\t|\n\"/*\"([^*]|\\*+[^*/])*\\*+\"/\"\t|\n[ \\t\\n]+\t|\n.\tprintf(\"%d:%d\\n\", yylineno, yycolumn);\n";
+	let input = "int main /* a\n * b */\n\n\treturn é;\n";
+
+	let Some(output) = compile_and_run(rules, input) else {
+		return;
+	};
+
+	// the bytes of `é` are not matched by `.`: they are copied by the default rule
+	let interpreter = Interpreter::new(&parse(rules));
+	let positions: String = interpreter.tokens(input.as_bytes())
+		.into_iter()
+		.filter(|token| token.rule.is_some())
+		.map(|token| format!("{}:{}\n", token.end_line, token.end_column))
+		.collect();
+
+	assert_eq!(output.replace('é', ""), positions);
+}
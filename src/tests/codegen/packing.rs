@@ -0,0 +1,126 @@
+use crate::codegen::*;
+use crate::parsing::definitions::Definitions;
+
+use super::parse;
+
+fn tables(spec: &str) -> Tables {
+	let parsing = parse(spec);
+
+	Tables::new(&parsing.rules, &parsing.definitions)
+}
+
+/// Checks that every transition of the packed tables is the one of the dense tables
+fn assert_same_transitions(next: &[Vec<Option<usize>>], packed: &PackedTransitions) {
+	for (state, row) in next.iter().enumerate() {
+		for (class, expected) in row.iter().enumerate() {
+			assert_eq!(packed.next_state(state, class), *expected, "state {state}, class {class}");
+		}
+	}
+}
+
+#[test]
+fn test_pack_keeps_transitions() {
+	for spec in [
+		"%%\nab\tone();\nc\ttwo();\n",
+		"%%\nif|else|while\tone();\n[a-z]+\ttwo();\n[0-9]+(\\.[0-9]+)?\tthree();\n.\tfour();\n",
+		"%x STR\n%%\n\\\"\tBEGIN STR;\n<STR>[^\"\\n]*\tone();\n<STR>\\\"\tBEGIN INITIAL;\n",
+		"%%\n^a+$\tone();\nx+/y+z\ttwo();\n",
+	] {
+		let tables = tables(spec);
+		let packed = PackedTransitions::new(&tables.next);
+
+		assert_eq!(packed.base.len(), tables.len());
+		assert_eq!(packed.def.len(), tables.len());
+		assert_same_transitions(&tables.next, &packed);
+	}
+}
+
+#[test]
+fn test_pack_uses_default_states() {
+	// the states after the keywords prefixes only differ from the identifier state on one letter
+	let tables = tables("%%\nabstract|assert|boolean|break|byte\tone();\n[a-z]+\ttwo();\n");
+	let packed = PackedTransitions::new(&tables.next);
+
+	assert!(packed.def.iter().any(Option::is_some));
+	assert!(packed.entries_count() < tables.transitions());
+	assert_same_transitions(&tables.next, &packed);
+}
+
+#[test]
+fn test_pack_stores_missing_transitions_of_default_states() {
+	let next = vec![
+		vec![Some(1), Some(1), Some(1), Some(1)],
+		vec![Some(1), Some(1), Some(1), None],
+	];
+
+	let packed = PackedTransitions::new(&next);
+
+	assert_eq!(packed.def, vec![None, Some(0)]);
+	assert_eq!(packed.entries_count(), 5);
+	assert_eq!(packed.next_state(1, 3), None);
+	assert_same_transitions(&next, &packed);
+}
+
+#[test]
+fn test_pack_chains_default_states() {
+	let next = vec![
+		vec![Some(0), Some(0), Some(0), Some(0), Some(0)],
+		vec![Some(1), Some(0), Some(0), Some(0), Some(0)],
+		vec![Some(1), Some(2), Some(0), Some(0), Some(0)],
+	];
+
+	let packed = PackedTransitions::new(&next);
+
+	// the third state only stores its second transition
+	assert_eq!(packed.def[2], Some(1));
+	assert_eq!(packed.def[1], Some(0));
+	assert_eq!(packed.entries_count(), 7);
+	assert_same_transitions(&next, &packed);
+}
+
+#[test]
+fn test_pack_overlays_rows() {
+	let next = vec![
+		vec![Some(1), None, None, None],
+		vec![None, Some(0), None, None],
+		vec![None, None, None, Some(2)],
+	];
+
+	let packed = PackedTransitions::new(&next);
+
+	// the rows fit in the holes of each other
+	assert_eq!(packed.len(), 4);
+	assert_eq!(packed.base, vec![0, 0, 0]);
+	assert_same_transitions(&next, &packed);
+}
+
+#[test]
+fn test_pack_without_transitions() {
+	let tables = Tables::new(&[], &Definitions::new());
+	let packed = PackedTransitions::new(&tables.next);
+
+	assert_eq!(packed.entries_count(), 0);
+	assert_eq!(packed.len(), tables.character_classes());
+	assert_eq!(packed.next_state(0, 0), None);
+
+	let packed = PackedTransitions::new(&[]);
+
+	assert!(packed.is_empty());
+	assert!(packed.base.is_empty());
+}
+
+#[test]
+fn test_pack_is_smaller() {
+	let tables = tables("%%
+auto|break|case|char|const|continue|default|do|double|else|enum|extern\tone();
+float|for|goto|if|int|long|register|return|short|signed|sizeof|static\ttwo();
+struct|switch|typedef|union|unsigned|void|volatile|while\tthree();
+[a-zA-Z_][a-zA-Z0-9_]*\tfour();
+[0-9]+\tfive();
+\"/*\"([^*]|\"*\"+[^*/])*\"*\"+\"/\"\tsix();
+");
+	let packed = PackedTransitions::new(&tables.next);
+
+	assert!(packed.output_size() * 2 < tables.len() * tables.character_classes());
+	assert_same_transitions(&tables.next, &packed);
+}
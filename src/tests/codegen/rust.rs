@@ -0,0 +1,329 @@
+use std::{fs, process::Command};
+
+use crate::codegen::*;
+
+use super::parse;
+
+fn generate(spec: &str) -> String {
+	let parsing = parse(spec);
+	let tables = Tables::new(&parsing.rules, &parsing.definitions);
+
+	let mut out = Vec::new();
+	RustGenerator::new(&parsing, &tables).generate(&mut out).unwrap();
+
+	String::from_utf8(out).unwrap()
+}
+
+#[test]
+fn test_generate_lexer_interface() {
+	let code = generate("%%\na\tself.echo();\n");
+
+	for declaration in [
+		"pub struct Lexer<R: Read>",
+		"pub fn new(input: R) -> Self",
+		"pub fn with_output(input: R, out: Box<dyn Write>) -> Self",
+		"pub fn next_token(&mut self) -> Option<Token>",
+		"pub fn begin(&mut self, condition: usize)",
+		"pub fn start_condition(&self) -> usize",
+		"pub fn echo(&mut self)",
+	] {
+		assert!(code.contains(declaration), "missing `{declaration}`");
+	}
+}
+
+#[test]
+fn test_generate_yytext_in_scope() {
+	let code = generate("%%\na\tself.echo();\n");
+
+	let yytext = code.find("let yytext: &str = &yytext;").expect("missing yytext");
+	let yyleng = code.find("let yyleng: usize = match_len;").expect("missing yyleng");
+	let actions = code.find("\t\t\tmatch rule {").unwrap();
+
+	assert!(yytext < actions && yyleng < actions);
+}
+
+#[test]
+fn test_generate_default_token_type() {
+	let code = generate("%%\na\treturn Some(1);\n");
+
+	assert!(code.contains("pub type Token = i32;"));
+}
+
+#[test]
+fn test_generate_user_token_type() {
+	let code = generate("%{\npub enum Token { A }\n%}\n%%\na\treturn Some(Token::A);\n");
+
+	assert!(code.contains("pub enum Token { A }"));
+	assert!(!code.contains("pub type Token"));
+}
+
+#[test]
+fn test_generate_token_in_subroutines() {
+	let code = generate("%%\na\treturn Some(Token::A);\n%%\npub enum Token { A }\n");
+
+	assert!(!code.contains("pub type Token"));
+}
+
+#[test]
+fn test_generate_token_as_part_of_an_identifier() {
+	let code = generate("%{\nstruct MyToken;\nconst Token_COUNT: usize = 0;\n%}\n%%\na\treturn Some(1);\n");
+
+	assert!(code.contains("pub type Token = i32;"));
+}
+
+#[test]
+fn test_generate_fragments_before_lexer() {
+	let code = generate("%{\nuse std::collections::HashMap;\n%}\n%%\na\tself.echo();\n");
+
+	let fragment = code.find("use std::collections::HashMap;").expect("missing fragment");
+	let lexer = code.find("pub struct Lexer").unwrap();
+
+	assert!(fragment < lexer);
+}
+
+#[test]
+fn test_generate_actions() {
+	let code = generate("%%\na\t{ return Some(1); }\nb\tprintln!(\"b\");\n");
+
+	assert!(code.contains("\t\t\t\t1 => {\n\t\t\t\t\t{ return Some(1); }\n\t\t\t\t},\n"));
+	assert!(code.contains("\t\t\t\t2 => {\n\t\t\t\t\tprintln!(\"b\");\n\t\t\t\t},\n"));
+	assert!(code.contains("\t\t\t\t_ => self.echo(),\n"));
+	assert!(code.contains("const YY_NUM_RULES: usize = 2;"));
+}
+
+#[test]
+fn test_generate_or_actions_share_an_arm() {
+	let code = generate("%%\na\t|\nb\t|\nc\treturn Some(3);\nd\tself.echo();\n");
+
+	assert!(code.contains("\t\t\t\t1 | 2 | 3 => {\n\t\t\t\t\treturn Some(3);\n\t\t\t\t},\n"));
+	assert!(code.contains("\t\t\t\t4 => {\n"));
+}
+
+#[test]
+fn test_generate_last_or_action() {
+	let code = generate("%%\na\tself.echo();\nb\t|\n");
+
+	assert!(code.contains("\t\t\t\t2 => {},\n"));
+}
+
+#[test]
+fn test_generate_reject() {
+	let code = generate("%%\nfrob\t{ special(); REJECT!(); }\n[a-z]+\tword();\n");
+
+	assert!(code.contains("macro_rules! REJECT {"));
+	assert!(code.contains("static YY_ACCEPT_INDEX: [i16; "));
+	assert!(code.contains("static YY_ACCEPT_LIST: [i16; "));
+	assert!(code.contains("fn next_match(&self, states: &[usize], len: &mut usize, mut rule: usize) -> usize"));
+	assert!(code.contains("\t\t\t\tstates.push(state);\n"));
+
+	// the actions are in the loop of REJECT!()
+	assert!(code.contains("\t\t\t'yy_find_rule: loop {\n"));
+	assert!(code.contains("\t\t\t\t\t1 => {\n\t\t\t\t\t\t{ special(); REJECT!(); }\n\t\t\t\t\t},\n"));
+	assert!(code.contains("\t\t\t\t\t_ => self.echo(),\n\t\t\t\t}\n\n\t\t\t\tbreak;\n\t\t\t}\n"));
+}
+
+#[test]
+fn test_generate_without_reject() {
+	let code = generate("%%\nfrob\tspecial();\n[a-z]+\tword();\n");
+
+	assert!(!code.contains("REJECT"));
+	assert!(!code.contains("YY_ACCEPT_LIST"));
+	assert!(!code.contains("states.push(state);"));
+}
+
+#[test]
+fn test_generate_end_of_file_actions() {
+	let code = generate("%x A B\n%%\n<A><<EOF>>\t|\n<B><<EOF>>\treturn Some(2);\n<<EOF>>\treturn Some(0);\n");
+
+	assert!(code.contains("\t\t\t\tmatch self.start_condition {\n"));
+	assert!(code.contains("\t\t\t\t\tA | B => {\n\t\t\t\t\t\treturn Some(2);\n\t\t\t\t\t},\n"));
+	assert!(code.contains("\t\t\t\t\tINITIAL => {\n\t\t\t\t\t\treturn Some(0);\n\t\t\t\t\t},\n"));
+	assert!(code.contains("let yytext: &str = \"\";"));
+}
+
+#[test]
+fn test_generate_without_end_of_file_rules() {
+	let code = generate("%%\na\tself.echo();\n");
+
+	assert!(!code.contains("match self.start_condition {"));
+}
+
+#[test]
+fn test_generate_subroutines_at_the_end() {
+	let code = generate("%%\na\tself.echo();\n%%\npub fn helper() {}");
+
+	assert!(code.trim_end().ends_with("pub fn helper() {}"));
+}
+
+#[test]
+fn test_generate_start_conditions() {
+	let code = generate("%s B A\n%x C\n%%\na\tself.begin(A);\n");
+
+	assert!(code.contains("pub const INITIAL: usize = 0;\npub const A: usize = 1;\npub const B: usize = 2;\npub const C: usize = 3;\n"));
+}
+
+#[test]
+fn test_generate_start_states() {
+	let code = generate("%x C\n%%\na\tself.begin(C);\n<C>b\tself.begin(INITIAL);\n");
+
+	assert!(code.contains("const YY_NUM_CONDITIONS: usize = 2;\n"));
+	assert!(code.contains("static YY_START_STATES: [[i16; 2]; YY_NUM_CONDITIONS] = [\n\t[0, 0],\n\t[1, 1],\n];"));
+	assert!(code.contains("YY_START_STATES[self.start_condition][self.at_bol as usize]"));
+}
+
+#[test]
+fn test_generate_head_lengths() {
+	let code = generate("%%\nab/c+\tone();\n[a-z]+/\"!\"\ttwo();\n");
+
+	assert!(code.contains("\tfn head_length(&self, rule: usize, len: usize) -> usize {\n\t\tmatch rule {\n\t\t\t1 => 2,\n\t\t\t2 => len - 1,\n\t\t\t_ => len,\n"));
+	assert!(code.contains("match_len = self.head_length(rule, match_len);"));
+
+	// only needed by the variable trailing contexts
+	assert!(!code.contains("fn variable_head"));
+}
+
+#[test]
+fn test_generate_variable_head_length() {
+	let code = generate("%%\nx+/y+z\tone();\n");
+
+	assert!(code.contains("fn variable_head(&self, len: usize, head: usize, tail: usize) -> usize"));
+	assert!(code.contains("\t\t\t1 => self.variable_head(len, "));
+}
+
+#[test]
+fn test_generate_tables() {
+	let code = generate("%%\nab\tself.echo();\n");
+	let parsing = parse("%%\nab\tself.echo();\n");
+	let tables = Tables::new(&parsing.rules, &parsing.definitions);
+
+	assert!(code.contains(&format!("static YY_ACCEPT: [i16; {}] = [", tables.len())));
+	assert!(code.contains(&format!("static YY_EOL: [i16; {}] = [", tables.len())));
+	assert!(code.contains("static YY_EC: [i16; 256] = ["));
+	assert!(code.contains("static YY_NXT: [[i16; YY_NUM_CLASSES]; YY_NUM_STATES] = ["));
+	assert!(code.contains(&format!("const YY_NUM_CLASSES: usize = {};", tables.character_classes())));
+	assert!(code.contains(&format!("const YY_NUM_STATES: usize = {};", tables.len())));
+}
+
+#[test]
+fn test_generate_packed_tables() {
+	let parsing = parse("%%\nab\tself.echo();\n[a-z]+\tself.echo();\n");
+	let mut tables = Tables::new(&parsing.rules, &parsing.definitions);
+	tables.pack();
+
+	let mut out = Vec::new();
+	RustGenerator::new(&parsing, &tables).generate(&mut out).unwrap();
+	let code = String::from_utf8(out).unwrap();
+
+	for array in ["YY_BASE", "YY_DEF", "YY_NXT", "YY_CHK"] {
+		assert!(code.contains(&format!("static {array}: [i16; ")), "missing `{array}`");
+	}
+
+	assert!(code.contains("fn yy_next_state(mut state: usize, class: usize) -> isize"));
+	assert!(!code.contains("YY_NXT[state][class]"));
+}
+
+#[test]
+fn test_rust_integer_type() {
+	assert_eq!(rust_integer_type(0), "i16");
+	assert_eq!(rust_integer_type(32766), "i16");
+	assert_eq!(rust_integer_type(32767), "i32");
+}
+
+#[test]
+fn test_generate_options() {
+	let code = generate("%option stack nodefault never-interactive\n%%\na\tself.echo();\n");
+
+	assert!(code.contains("pub fn push_state(&mut self, condition: usize)"));
+	assert!(code.contains("pub fn pop_state(&mut self)"));
+	assert!(code.contains("pub fn top_state(&self) -> usize"));
+	assert!(code.contains("_ => panic!(\"ft_lex scanner jammed\"),"));
+	assert!(code.contains("const YY_INTERACTIVE: bool = false;"));
+
+	let code = generate("%%\na\tself.echo();\n");
+
+	assert!(!code.contains("fn push_state"));
+	assert!(code.contains("_ => self.echo(),"));
+	assert!(code.contains("const YY_INTERACTIVE: bool = true;"));
+}
+
+#[test]
+fn test_generate_prefix() {
+	let code = generate("%option prefix=\"calc_expr\"\n%x STRING\n%%\n<STRING>a\tself.begin(CALC_EXPR_INITIAL);\n<<EOF>>\treturn None;\n");
+
+	for item in [
+		"pub struct CalcExprLexer<R: Read>",
+		"pub type CalcExprToken = i32;",
+		"pub const CALC_EXPR_INITIAL: usize = 0;",
+		"pub const CALC_EXPR_STRING: usize = 1;",
+		"static CALC_EXPR_ACCEPT:",
+		"const CALC_EXPR_NUM_RULES: usize = 1;",
+		"fn calc_expr_next_state(state: usize, class: usize) -> isize",
+		"start_condition: CALC_EXPR_INITIAL,",
+		"pub fn next_token(&mut self) -> Option<CalcExprToken>",
+		"CALC_EXPR_INITIAL | CALC_EXPR_STRING => {",
+	] {
+		assert!(code.contains(item), "missing `{item}`");
+	}
+
+	// the names of the scanner are all renamed, not the ones of the actions
+	for name in ["Lexer<", "Token>", "YY_", "yy_next_state", "INITIAL,"] {
+		assert!(!code.contains(&format!(" {name}")), "`{name}` is not renamed");
+	}
+
+	assert!(code.contains("let yytext: &str"));
+}
+
+#[test]
+fn test_generate_yylineno() {
+	let code = generate("%option yycolumn\n%%\n[a-z]+\tself.echo();\n\\n\tself.echo();\n");
+
+	assert!(code.contains("pub lineno: usize,"));
+	assert!(code.contains("fn count(&mut self, bytes: Range<usize>)"));
+	assert!(code.contains("const YY_COLUMNS: bool = true;"));
+	assert!(code.contains("\t1, 0, 1,\n];"));
+	assert!(code.contains("if YY_RULE_CAN_MATCH_EOL[rule] != 0 {"));
+	assert!(code.contains("self.column += match_len;"));
+
+	// REJECT counts the next match from the start of the rejected one
+	let code = generate("%option yylineno\n%%\nab\tREJECT!();\n[a-z]+\tself.echo();\n");
+
+	assert!(code.contains("let position = (self.lineno, self.column);"));
+	assert!(code.contains("(self.lineno, self.column) = position;"));
+	assert!(!code.contains("self.column += match_len;"));
+
+	let code = generate("%%\n[a-z]+\tself.echo();\n");
+
+	assert!(!code.contains("fn count("));
+	assert!(!code.contains("YY_RULE_CAN_MATCH_EOL"));
+}
+
+#[test]
+fn test_generated_lexer_compiles_in_edition_2015() {
+	let code = generate(concat!(
+		"%option yylineno\n",
+		"%%\n",
+		"ab\tREJECT!();\n",
+		"[a-z]+/[0-9]*x\tprintln!(\"word {}\", yytext);\n",
+		".|\\n\t{}\n",
+		"%%\n",
+		"fn main() {\n",
+		"\tlet mut lexer = Lexer::new(std::io::stdin());\n",
+		"\twhile lexer.next_token().is_some() {}\n",
+		"}\n",
+	));
+
+	let dir = std::env::temp_dir().join(format!("ft_lex_{}_edition_2015", std::process::id()));
+	fs::create_dir_all(&dir).unwrap();
+	fs::write(dir.join("lex.rs"), code).unwrap();
+
+	// in the 2015 edition, `panic!("{err}")` prints `{err}`: the panic messages must use positional arguments
+	let compiled = Command::new("rustc")
+		.current_dir(&dir)
+		.args(["--edition", "2015", "-D", "non-fmt-panics", "-o", "lex", "lex.rs"])
+		.status();
+	let _ = fs::remove_dir_all(&dir);
+
+	let compiled = compiled.expect("this test needs a Rust compiler (rustc)");
+
+	assert!(compiled.success(), "the lexer does not compile in the 2015 edition");
+}
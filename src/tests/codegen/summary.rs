@@ -0,0 +1,188 @@
+use crate::codegen::*;
+use crate::parsing::definitions::*;
+use crate::parsing::Parsing;
+
+use super::parse;
+
+fn summary(parsing: &Parsing) -> Summary {
+	let tables = Tables::new(&parsing.rules, &parsing.definitions);
+
+	Summary::new(&parsing.rules, &tables)
+}
+
+fn report(summary: &Summary, definitions: &Definitions) -> String {
+	let mut out = vec![];
+
+	summary.write(definitions, &mut out).unwrap();
+
+	String::from_utf8(out).unwrap()
+}
+
+#[test]
+fn test_summary_counts() {
+	let parsing = parse("%%\nab\tone();\nc\ttwo();\n");
+	let tables = Tables::new(&parsing.rules, &parsing.definitions);
+	let summary = Summary::new(&parsing.rules, &tables);
+
+	// 'a', 'b' and 'c' read a character
+	assert_eq!(summary.positions, 3);
+	assert!(summary.parse_tree_nodes > summary.positions);
+
+	assert_eq!(summary.states, tables.len());
+	assert_eq!(summary.unminimized_states, tables.unminimized_len);
+
+	// start -a-> . -b-> accept, start -c-> accept (in the two start states of INITIAL)
+	assert_eq!(summary.transitions, tables.transitions());
+	assert!(summary.transitions >= 3);
+
+	// 'a', 'b', 'c' and all the other bytes
+	assert_eq!(summary.character_classes, 4);
+
+	assert_eq!(summary.output_size, tables.output_size());
+	assert!(summary.output_size >= tables.len() * 4 + ALPHABET_SIZE);
+}
+
+#[test]
+fn test_summary_loops_are_counted_once() {
+	let parsing = parse("%%\na*\tone();\n(ab)*c\ttwo();\n");
+
+	let summary = summary(&parsing);
+
+	// the states of a loop are visited once
+	assert_eq!(summary.positions, 4);
+}
+
+#[test]
+fn test_summary_counts_trailing_contexts() {
+	let without = summary(&parse("%%\nab\tone();\n"));
+	let with = summary(&parse("%%\na/b\tone();\n"));
+
+	// the parts of the trailing context are counted as well as the whole pattern
+	assert!(with.positions > without.positions);
+}
+
+#[test]
+fn test_check_without_declarations() {
+	let parsing = parse("%%\n[a-z]+\tone();\n[0-9]+\ttwo();\n");
+
+	assert!(summary(&parsing).check(&parsing.definitions).is_ok());
+}
+
+#[test]
+fn test_check_declared_sizes() {
+	let parsing = parse("%p 2500\n%n 500\n%o 100000\n%%\n[a-z]+\tone();\n");
+
+	assert!(summary(&parsing).check(&parsing.definitions).is_ok());
+}
+
+#[test]
+fn test_check_exceeded_size() {
+	// one state per character
+	let parsing = parse(&format!("%n 500\n%%\n{}\tone();\n", "a".repeat(600)));
+
+	let errors = summary(&parsing).check(&parsing.definitions).unwrap_err();
+
+	assert_eq!(errors.len(), 1);
+	assert!(!errors[0].is_warning());
+	assert!(errors[0].to_string().contains("%n 500"));
+}
+
+#[test]
+fn test_check_output_array_size_is_not_a_table_size() {
+	// %o is the size of yytext (%array): the tables can be bigger
+	let parsing = parse("%o 500\n%%\nabcdefghijklmnop\tone();\n");
+
+	assert!(summary(&parsing).output_size > 500);
+	assert!(summary(&parsing).check(&parsing.definitions).is_ok());
+}
+
+#[test]
+fn test_check_every_exceeded_size() {
+	let summary = Summary {
+		positions: 10,
+		parse_tree_nodes: 10,
+		states: 10,
+		unminimized_states: 10,
+		transitions: 10,
+		character_classes: 10,
+		output_size: 10,
+		packed: false,
+	};
+
+	let mut definitions = Definitions::new();
+	definitions.table_sizes.insert(TableSizeDeclaration::States, 9);
+	definitions.table_sizes.insert(TableSizeDeclaration::Transitions, 10);
+	definitions.table_sizes.insert(TableSizeDeclaration::Positions, 1);
+
+	let errors = summary.check(&definitions).unwrap_err();
+
+	// a limit can be reached, not exceeded
+	assert_eq!(errors.len(), 2);
+}
+
+#[test]
+fn test_write_without_declarations() {
+	let summary = Summary {
+		positions: 1,
+		parse_tree_nodes: 2,
+		states: 3,
+		unminimized_states: 4,
+		transitions: 5,
+		character_classes: 6,
+		output_size: 7,
+		packed: false,
+	};
+
+	let expected = "\
+ft_lex summary:
+  1 positions (%p)
+  2 parse tree nodes (%e)
+  3 states (%n), 4 before minimization
+  5 transitions (%a)
+  6 packed character classes (%k)
+  7 table entries
+";
+
+	assert_eq!(report(&summary, &Definitions::new()), expected);
+}
+
+#[test]
+fn test_write_with_declarations() {
+	let parsing = parse("%n 600\n%a 3000\n%%\na\tone();\n");
+	let summary = summary(&parsing);
+
+	let report = report(&summary, &parsing.definitions);
+
+	assert!(report.contains(&format!("  {}/600 states (%n)", summary.states)));
+	assert!(report.contains(&format!("  {}/3000 transitions (%a)", summary.transitions)));
+	assert!(report.contains(&format!("  {} positions (%p)", summary.positions)));
+}
+
+#[test]
+fn test_summary_of_packed_tables() {
+	let parsing = parse("%%\nbreak|byte|boolean\tone();\n[a-z]+\ttwo();\n");
+
+	let mut tables = Tables::new(&parsing.rules, &parsing.definitions);
+	let dense = Summary::new(&parsing.rules, &tables);
+
+	tables.pack();
+	let packed = Summary::new(&parsing.rules, &tables);
+
+	assert!(packed.packed);
+	assert_eq!(packed.transitions, tables.packed.as_ref().unwrap().entries_count());
+	assert!(packed.transitions < dense.transitions);
+	assert!(packed.output_size < dense.output_size);
+	assert_eq!(packed.character_classes, dense.character_classes);
+
+	// checked against %a
+	let mut definitions = Definitions::new();
+	definitions.table_sizes.insert(TableSizeDeclaration::Transitions, 100);
+
+	assert!(dense.check(&definitions).is_err());
+	assert!(packed.check(&definitions).is_ok());
+
+	let report = report(&packed, &definitions);
+
+	assert!(report.contains(&format!("  {}/100 packed transitions (%a)", packed.transitions)));
+	assert!(report.contains(&format!("  {} packed character classes (%k)", packed.character_classes)));
+}
@@ -0,0 +1,365 @@
+use crate::codegen::*;
+use crate::parsing::Rule;
+use crate::parsing::definitions::Definitions;
+use crate::regex::*;
+use crate::regex::dfa::normalizer::*;
+
+use super::parse;
+
+/// Runs the tables the same way as the generated scanner: returns the (rule, length) of the first token
+fn scan(tables: &Tables, input: &str, at_bol: bool) -> Option<(usize, usize)> {
+	scan_in(tables, 0, input, at_bol)
+}
+
+/// Same as `scan`, in the start condition of index `condition`
+fn scan_in(tables: &Tables, condition: usize, input: &str, at_bol: bool) -> Option<(usize, usize)> {
+	let start = if at_bol { tables.bol_starts[condition] } else { tables.starts[condition] };
+
+	scan_from(tables, start, input)
+}
+
+/// Same as `scan`, from any state
+fn scan_from(tables: &Tables, start: usize, input: &str) -> Option<(usize, usize)> {
+	let bytes = input.as_bytes();
+
+	let mut state = start;
+	let mut best = None;
+	let mut cp = 0;
+
+	while cp < bytes.len() {
+		state = match tables.next_state(state, bytes[cp]) {
+			Some(next) => next,
+			None => break,
+		};
+		cp += 1;
+
+		let mut accept = tables.accept[state];
+
+		if let (Some(eol), Some(b'\n')) = (tables.end_of_line[state], bytes.get(cp)) {
+			state = eol;
+
+			let eol_accept = tables.accept[state];
+			if eol_accept != 0 && (accept == 0 || eol_accept < accept) {
+				accept = eol_accept;
+			}
+		}
+
+		if accept != 0 {
+			best = Some((accept, cp));
+		}
+	}
+
+	best
+}
+
+fn rules_tables(rules: &[Rule]) -> Tables {
+	Tables::new(rules, &Definitions::new())
+}
+
+#[test]
+fn test_rules_are_numbered_in_order() {
+	let parsing = parse("%%\na\tone();\nb\ttwo();\nc\tthree();\n");
+
+	let ids: Vec<Option<usize>> = parsing.rules.iter()
+		.map(|rule| {
+			let nfa = &rule.regex_nfa;
+			let out = nfa[nfa.start].out().unwrap();
+			nfa[out].match_id()
+		})
+		.collect();
+
+	assert_eq!(ids, vec![Some(1), Some(2), Some(3)]);
+}
+
+#[test]
+fn test_tables_dimensions() {
+	let parsing = parse("%%\nab\tone();\na\ttwo();\n");
+	let tables = rules_tables(&parsing.rules);
+
+	assert_eq!(tables.next.len(), tables.len());
+	assert_eq!(tables.end_of_line.len(), tables.len());
+	assert_eq!(tables.ec.len(), ALPHABET_SIZE);
+	assert!(tables.next.iter().all(|row| row.len() == tables.character_classes()));
+	assert!(tables.max_value() >= tables.len());
+}
+
+#[test]
+fn test_tables_accept_lowest_rule() {
+	let parsing = parse("%%\nif\tone();\n[a-z]+\ttwo();\n");
+	let tables = rules_tables(&parsing.rules);
+
+	assert_eq!(scan(&tables, "if", false), Some((1, 2)));
+	assert_eq!(scan(&tables, "ifx", false), Some((2, 3)));
+	assert_eq!(scan(&tables, "i", false), Some((2, 1)));
+	assert_eq!(scan(&tables, "IF", false), None);
+}
+
+#[test]
+fn test_tables_accepts_all_rules() {
+	let parsing = parse("%%
+if\tone();
+[a-z]+\ttwo();
+i[a-z]\tthree();
+");
+	let tables = rules_tables(&parsing.rules);
+
+	// the state reached by "if" accepts the three rules
+	let mut state = tables.starts[0];
+	for c in "if".bytes() {
+		state = tables.next_state(state, c).unwrap();
+	}
+
+	assert_eq!(tables.accepts[state], vec![1, 2, 3]);
+	assert_eq!(tables.accept[state], 1);
+}
+
+#[test]
+fn test_tables_accepts_index() {
+	let parsing = parse("%%
+if\tone();
+[a-z]+\ttwo();
+");
+	let tables = rules_tables(&parsing.rules);
+
+	let index = tables.accepts_index();
+
+	assert_eq!(index.len(), tables.len() + 1);
+	assert_eq!(*index.last().unwrap(), tables.accepts.iter().map(|accepts| accepts.len()).sum::<usize>());
+
+	for (state, accepts) in tables.accepts.iter().enumerate() {
+		assert_eq!(index[state + 1] - index[state], accepts.len());
+	}
+}
+
+#[test]
+fn test_tables_start_of_line() {
+	let parsing = parse("%%\n^a\tone();\na\ttwo();\n");
+	let tables = rules_tables(&parsing.rules);
+
+	assert_ne!(tables.starts[0], tables.bol_starts[0]);
+	assert_eq!(scan(&tables, "a", true), Some((1, 1)));
+	assert_eq!(scan(&tables, "a", false), Some((2, 1)));
+}
+
+#[test]
+fn test_tables_end_of_line() {
+	let parsing = parse("%%\nab$\tone();\n[a-z]+\ttwo();\n");
+	let tables = rules_tables(&parsing.rules);
+
+	assert_eq!(scan(&tables, "ab\n", false), Some((1, 2)));
+	assert_eq!(scan(&tables, "ab", false), Some((2, 2)));
+	assert_eq!(scan(&tables, "abc\n", false), Some((2, 3)));
+}
+
+#[test]
+fn test_tables_one_start_per_condition() {
+	// conditions: INITIAL 0, EXC 1, INC 2
+	let parsing = parse("%s INC\n%x EXC\n%%\na\tone();\n<INC>b\ttwo();\n<EXC>c\tthree();\n<INITIAL,EXC>d\tfour();\n");
+	let tables = Tables::new(&parsing.rules, &parsing.definitions);
+
+	assert_eq!(tables.starts.len(), 3);
+	assert_eq!(tables.bol_starts.len(), 3);
+
+	assert_eq!(scan_in(&tables, 0, "a", false), Some((1, 1)));
+	assert_eq!(scan_in(&tables, 0, "b", false), None);
+	assert_eq!(scan_in(&tables, 0, "d", false), Some((4, 1)));
+
+	// inclusive: the rules without start condition are active
+	assert_eq!(scan_in(&tables, 2, "a", false), Some((1, 1)));
+	assert_eq!(scan_in(&tables, 2, "b", false), Some((2, 1)));
+	assert_eq!(scan_in(&tables, 2, "d", false), None);
+
+	// exclusive: only the rules of the condition are active
+	assert_eq!(scan_in(&tables, 1, "a", false), None);
+	assert_eq!(scan_in(&tables, 1, "c", false), Some((3, 1)));
+	assert_eq!(scan_in(&tables, 1, "d", false), Some((4, 1)));
+}
+
+#[test]
+fn test_tables_start_of_line_per_condition() {
+	let parsing = parse("%x EXC\n%%\n^a\tone();\n<EXC>^b\ttwo();\n<EXC>b\tthree();\n");
+	let tables = Tables::new(&parsing.rules, &parsing.definitions);
+
+	assert_eq!(scan_in(&tables, 0, "a", true), Some((1, 1)));
+	assert_eq!(scan_in(&tables, 0, "a", false), None);
+
+	assert_eq!(scan_in(&tables, 1, "b", true), Some((2, 1)));
+	assert_eq!(scan_in(&tables, 1, "b", false), Some((3, 1)));
+	assert_eq!(scan_in(&tables, 1, "a", true), None);
+}
+
+#[test]
+fn test_tables_identical_conditions_share_start() {
+	let parsing = parse("%s A B\n%%\n[a-z]+\tone();\n");
+	let tables = Tables::new(&parsing.rules, &parsing.definitions);
+
+	assert_eq!(tables.starts, vec![tables.starts[0]; 3]);
+}
+
+#[test]
+fn test_tables_head_lengths() {
+	let parsing = parse("%%\nab/c+\tone();\n[a-z]+/\"!\"\ttwo();\nx+/y+z\tthree();\n[a-z]+\tfour();\n");
+	let tables = Tables::new(&parsing.rules, &parsing.definitions);
+
+	assert_eq!(tables.starts.len(), 1);
+	assert_eq!(tables.head_lengths.len(), 3);
+	assert_eq!(tables.head_lengths[0], (1, HeadLength::Fixed(2)));
+	assert_eq!(tables.head_lengths[1], (2, HeadLength::WithoutTail(1)));
+
+	let (rule, HeadLength::Variable { head, tail }) = tables.head_lengths[2] else {
+		panic!("expected a variable trailing context");
+	};
+
+	assert_eq!(rule, 3);
+	assert_ne!(head, tail);
+	assert!(!tables.starts.contains(&head));
+
+	// the head and tail states match `x+` and `y+z` alone
+	assert_eq!(scan_from(&tables, head, "xxx"), Some((3, 3)));
+	assert_eq!(scan_from(&tables, tail, "yyz"), Some((3, 3)));
+	assert_eq!(scan_from(&tables, tail, "xyz"), None);
+}
+
+#[test]
+fn test_tables_whole_trailing_context_match() {
+	let parsing = parse("%%\nab/cd\tone();\n[a-z]+\ttwo();\n");
+	let tables = Tables::new(&parsing.rules, &parsing.definitions);
+
+	// `abcd` is matched by both rules, the first one wins
+	assert_eq!(scan(&tables, "abcd", false), Some((1, 4)));
+	assert_eq!(scan(&tables, "abcde", false), Some((2, 5)));
+}
+
+#[test]
+fn test_tables_without_rules() {
+	let tables = rules_tables(&[]);
+
+	assert_eq!(tables.len(), 1);
+	assert_eq!(scan(&tables, "abc", true), None);
+}
+
+#[test]
+fn test_tables_equivalence_classes() {
+	let parsing = parse("%%\n[a-z]+\tone();\n[0-9]+\ttwo();\n.\tthree();\n");
+	let tables = rules_tables(&parsing.rules);
+
+	// the letters, the digits, the newline and the other bytes
+	assert_eq!(tables.character_classes(), 4);
+	assert_eq!(tables.ec[b'a' as usize], tables.ec[b'z' as usize]);
+	assert_eq!(tables.ec[b'0' as usize], tables.ec[b'9' as usize]);
+	assert_ne!(tables.ec[b'a' as usize], tables.ec[b'0' as usize]);
+	assert_ne!(tables.ec[b'\n' as usize], tables.ec[b'+' as usize]);
+
+	// the bytes read by no rule are with the newline
+	assert_eq!(tables.ec[b'\n' as usize], tables.ec[200]);
+	assert_eq!(tables.ec[0], 0);
+
+	assert_eq!(scan(&tables, "abc1", false), Some((1, 3)));
+	assert_eq!(scan(&tables, "42", false), Some((2, 2)));
+	assert_eq!(scan(&tables, "+", false), Some((3, 1)));
+	assert_eq!(scan(&tables, "\n", false), None);
+}
+
+#[test]
+fn test_tables_classes_merge_after_minimization() {
+	// the classes of the automaton tell 'a' and 'b' apart, but both lead to the same states
+	let parsing = parse("%%\na|b\tone();\n[ab]c\ttwo();\n");
+	let tables = rules_tables(&parsing.rules);
+
+	assert_eq!(tables.ec[b'a' as usize], tables.ec[b'b' as usize]);
+	assert_eq!(tables.character_classes(), 3);
+}
+
+#[test]
+fn test_tables_output_size_with_any_char() {
+	let parsing = parse("%%\n.*\tone();\n\\n\ttwo();\n");
+	let tables = rules_tables(&parsing.rules);
+
+	assert_eq!(tables.character_classes(), 3);
+	assert!(tables.output_size() < tables.len() * ALPHABET_SIZE + ALPHABET_SIZE);
+	assert_eq!(tables.next_state(tables.starts[0], b'x'), tables.next_state(tables.starts[0], b'%'));
+}
+
+#[test]
+fn test_packed_tables_scan_the_same() {
+	let parsing = parse("%%\nif|int\tone();\n[a-z]+\ttwo();\n[0-9]+/x\tthree();\nend$\tfour();\n");
+	let dense = rules_tables(&parsing.rules);
+
+	let mut packed = rules_tables(&parsing.rules);
+	packed.pack();
+
+	assert!(packed.output_size() < dense.output_size());
+	assert!(packed.max_value() >= packed.packed.as_ref().unwrap().len());
+
+	for input in ["if", "int", "into", "12x", "12", "end\n", "end", "x"] {
+		assert_eq!(scan(&packed, input, false), scan(&dense, input, false), "input {input:?}");
+	}
+}
+
+#[test]
+fn test_tables_agree_with_simulate() {
+	let patterns = ["[a-z]+", "[0-9]+(\\.[0-9]+)?", "\"if\"", "a|ab|abc", "x(yz)*", "^begin", "end$"];
+	let inputs = ["abc def", "12.5x", "if", "abcd", "xyzyzy", "begin", "end\n", "end", "9.", "xyz\n"];
+
+	for pattern in patterns {
+		let nfa = Regex::new(pattern.to_string(), 1).unwrap();
+		let mut dfa = Dfa::new(&[&nfa]);
+		let normalized = NormalizedDfa::from(&mut dfa);
+		let tables = Tables::from(&normalized);
+
+		for input in inputs {
+			// the scanner ignores empty matches
+			let expected = simulate(input, &normalized)
+				.map(|match_| match_.length())
+				.filter(|length| *length > 0);
+
+			let found = scan(&tables, input, true).map(|(_, length)| length);
+
+			assert_eq!(found, expected, "pattern `{pattern}` on input {input:?}");
+		}
+	}
+}
+
+#[test]
+fn test_tables_keywords_benchmark() {
+	let parsing = parse(include_str!("../../../examples/keywords.lex"));
+	assert_eq!(parsing.rules.len(), 304);
+
+	// structural bounds instead of timings: the construction work grows with the number of
+	// DFA states times the number of classes, both bounded by the size of the specification
+	let dfa = Dfa::from_rules(&parsing.rules, &parsing.definitions);
+	let classes = dfa.classes.as_ref().expect("the DFA of the rules is built on classes");
+
+	// at most one state per character of the patterns (the keywords share their prefixes)
+	let pattern_chars: usize = parsing.rules.iter().map(|rule| rule.pattern.chars().count()).sum();
+	assert!(dfa.memory.len() <= pattern_chars + 1, "{} DFA states for {} pattern characters", dfa.memory.len(), pattern_chars);
+
+	// at most one transition per class (and per anchor) from each state, not one per character
+	assert!(dfa.memory.values().all(|state| state.borrow().next.len() <= classes.len() + 2));
+
+	let tables = Tables::new(&parsing.rules, &parsing.definitions);
+	assert!(tables.len() <= dfa.memory.len());
+
+	// the keywords are the first rules, then the identifiers, the numbers and the blanks
+	assert_eq!(scan(&tables, "auto", true), Some((1, 4)));
+	assert_eq!(scan(&tables, "owner", true), Some((300, 5)));
+	assert_eq!(scan(&tables, "automobile", true), Some((301, 10)));
+	assert_eq!(scan(&tables, "auto2 ", true), Some((301, 5)));
+	assert_eq!(scan(&tables, "42", true), Some((302, 2)));
+	assert_eq!(scan(&tables, "+", true), Some((304, 1)));
+}
+
+#[test]
+fn test_tables_state_limit() {
+	let parsing = parse("%%\nif\tone();\n\n(a|b)*a(a|b){9}\ttwo();\n[a-z]+\tthree();\n");
+
+	assert!(Tables::with_state_limit(&parsing.rules, &parsing.definitions, 10_000).is_ok());
+
+	let err = Tables::with_state_limit(&parsing.rules, &parsing.definitions, 300).unwrap_err();
+
+	assert!(!err.is_warning());
+	assert_eq!(
+		err.to_string(),
+		"<test>:4: : too many DFA states (more than 300): state explosion in the rule `(a|b)*a(a|b){9}`"
+	);
+}
@@ -1,7 +1,9 @@
 use crate::parsing::definitions::{
     DefinitionType, Definitions, StateType, TableSizeDeclaration, TypeDeclaration,
+    DFA_STATES_PER_DECLARED_STATE,
 };
 use crate::parsing::error::ParsingResult;
+use crate::parsing::options::Options;
 use crate::parsing::reader::Reader;
 use std::io::Cursor;
 
@@ -169,6 +171,18 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_dfa_state_limit() -> ParsingResult<()> {
+        let defs = Definitions::new();
+        assert_eq!(defs.dfa_state_limit(), 500 * DFA_STATES_PER_DECLARED_STATE);
+
+        let mut defs = Definitions::new();
+        defs.parse(&mut reader_from_str("%n 2000\n%%\n"))?;
+        assert_eq!(defs.dfa_state_limit(), 2000 * DFA_STATES_PER_DECLARED_STATE);
+
+        Ok(())
+    }
+
     #[test]
     fn test_parse_invalid_table_size() {
         let mut defs = Definitions::new();
@@ -265,6 +279,175 @@ ALPHANUM    [a-zA-Z0-9]
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_parse_case_insensitive_option() -> ParsingResult<()> {
+        let mut defs = Definitions::new();
+        let mut reader = reader_from_str("%option case-insensitive\n%%\n");
+
+        defs.parse(&mut reader)?;
+        assert!(defs.options.case_insensitive);
+        assert!(defs.regex_flags().case_insensitive);
+
+        // the last option wins
+        let mut defs = Definitions::new();
+        let mut reader = reader_from_str("%option caseless\n%option case-sensitive\n%%\n");
+
+        defs.parse(&mut reader)?;
+        assert!(!defs.options.case_insensitive);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_invalid_option() {
+        let mut defs = Definitions::new();
+        let mut reader = reader_from_str("%option\n%%\n");
+        assert!(defs.parse(&mut reader).is_err());
+    }
+
+    #[test]
+    fn test_parse_options() -> ParsingResult<()> {
+        let mut defs = Definitions::new();
+        let input = "%option noyywrap yylineno outfile=\"scanner.c\"\n%option prefix=\"calc\" never-interactive\n%option nodefault stack 8bit\n%%\n";
+        let mut reader = reader_from_str(input);
+
+        defs.parse(&mut reader)?;
+
+        assert_eq!(defs.options, Options {
+            noyywrap: true,
+            yylineno: true,
+            yycolumn: false,
+            outfile: Some("scanner.c".to_string()),
+            prefix: Some("calc".to_string()),
+            case_insensitive: false,
+            never_interactive: true,
+            nodefault: true,
+            stack: true,
+            reentrant: false,
+            eight_bit: true,
+        });
+        assert!(defs.regex_flags().eight_bit);
+
+        // the `no` prefix negates the boolean options
+        let mut defs = Definitions::new();
+        let mut reader = reader_from_str("%option nostack\n%option yywrap default\n%%\n");
+
+        defs.parse(&mut reader)?;
+        assert_eq!(defs.options, Options::default());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_yycolumn_option() -> ParsingResult<()> {
+        // the column is counted with the line
+        let mut defs = Definitions::new();
+        let mut reader = reader_from_str("%option yycolumn
+%%
+");
+
+        defs.parse(&mut reader)?;
+        assert!(defs.options.yycolumn);
+        assert!(defs.options.yylineno);
+
+        let mut defs = Definitions::new();
+        let mut reader = reader_from_str("%option yylineno noyycolumn
+%%
+");
+
+        defs.parse(&mut reader)?;
+        assert!(!defs.options.yycolumn);
+        assert!(defs.options.yylineno);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_unknown_options() {
+        let mut defs = Definitions::new();
+        let mut reader = reader_from_str("%option yywarp stack bison-bridge\n%%\n");
+
+        // a warning listing the valid options, the other options are set
+        let err = defs.parse(&mut reader).unwrap_err();
+        assert!(err.is_warning());
+        assert!(err.to_string().contains("unknown option `yywarp`, `bison-bridge`"), "{err}");
+        assert!(err.to_string().contains("valid options: noyywrap, yylineno"), "{err}");
+        assert!(defs.options.stack);
+
+        // the parsing goes on after the warning
+        assert!(defs.parse(&mut reader).is_ok());
+
+        let mut defs = Definitions::new();
+        let mut reader = reader_from_str("%option reentrant\n%%\n");
+        assert!(defs.parse(&mut reader).unwrap_err().is_warning());
+    }
+
+    #[test]
+    fn test_parse_quoted_option_values() -> ParsingResult<()> {
+        let mut defs = Definitions::new();
+        let mut reader = reader_from_str("%option  outfile=\"my scanner.c\"\tnoyywrap prefix=\"cfg\"\n%%\n");
+
+        defs.parse(&mut reader)?;
+
+        assert_eq!(defs.options.outfile.as_deref(), Some("my scanner.c"));
+        assert_eq!(defs.options.prefix.as_deref(), Some("cfg"));
+        assert!(defs.options.noyywrap);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_invalid_prefix() {
+        let mut defs = Definitions::new();
+        let mut reader = reader_from_str("%option prefix=\"2fast\"\n%%\n");
+
+        let err = defs.parse(&mut reader).unwrap_err();
+        assert!(err.to_string().contains("the prefix must be iso-C normed"), "{err}");
+        assert_eq!(defs.options.prefix, None);
+    }
+
+    #[test]
+    fn test_parse_options_line_diagnostics() {
+        let mut defs = Definitions::new();
+        let mut reader = reader_from_str("%option yywarp prefix=\"1x\" reentrant yylineno\n%%\n");
+
+        // every diagnostic of the line is reported, in order, and the line is fully applied
+        let unknown = defs.parse(&mut reader).unwrap_err();
+        assert!(unknown.is_warning());
+        assert!(unknown.to_string().contains("unknown option `yywarp`"), "{unknown}");
+
+        let prefix = defs.parse(&mut reader).unwrap_err();
+        assert!(!prefix.is_warning());
+        assert!(prefix.to_string().contains("the prefix must be iso-C normed"), "{prefix}");
+
+        let reentrant = defs.parse(&mut reader).unwrap_err();
+        assert!(reentrant.is_warning());
+        assert!(reentrant.to_string().contains("%option reentrant is not supported"), "{reentrant}");
+
+        assert!(defs.parse(&mut reader).is_ok());
+        assert_eq!(defs.options.prefix, None);
+        assert!(defs.options.yylineno);
+    }
+
+    #[test]
+    fn test_command_line_options_win() -> ParsingResult<()> {
+        let mut defs = Definitions::new();
+        defs.command_line = Options {
+            case_insensitive: true,
+            outfile: Some("cli.c".to_string()),
+            ..Default::default()
+        };
+
+        let mut reader = reader_from_str("%option case-sensitive outfile=\"spec.c\" prefix=\"spec\"\n%%\n");
+        defs.parse(&mut reader)?;
+
+        assert!(defs.options.case_insensitive);
+        assert_eq!(defs.options.outfile.as_deref(), Some("cli.c"));
+        assert_eq!(defs.options.prefix.as_deref(), Some("spec"));
+
+        Ok(())
+    }
+
     #[test]
     fn test_check_split_size() {
         // Testing the check_split_size utility function
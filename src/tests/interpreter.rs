@@ -0,0 +1,353 @@
+use std::io::Cursor;
+
+use crate::interpreter::*;
+use crate::parsing::{Parsing, Reader};
+
+fn interpreter(spec: &str) -> Interpreter {
+	interpreter_with(spec, false)
+}
+
+/// Same as `interpreter`, the specification being decoded as UTF-8 if `utf8` is set (--utf8)
+fn interpreter_with(spec: &str, utf8: bool) -> Interpreter {
+	let mut parsing = Parsing::new().unwrap();
+	parsing.definitions.utf8 = utf8;
+
+	let mut reader = Reader::new(Cursor::new(spec.as_bytes().to_vec()), "<test>").unwrap();
+
+	let _ = parsing.parse_sections(&mut reader);
+
+	assert!(parsing.errors.iter().all(|err| err.is_warning()), "invalid test specification: {:?}", parsing.errors);
+
+	Interpreter::new(&parsing)
+}
+
+/// (rule, text) of each token
+fn tokens(spec: &str, input: &str) -> Vec<(Option<usize>, String)> {
+	interpreter(spec).tokens(input.as_bytes())
+		.into_iter()
+		.map(|token| (token.rule, token.text))
+		.collect()
+}
+
+fn run(spec: &str, input: &str) -> String {
+	let mut out = vec![];
+
+	interpreter(spec).run(input.as_bytes(), &mut out).unwrap();
+
+	String::from_utf8(out).unwrap()
+}
+
+fn token(rule: usize, text: &str) -> (Option<usize>, String) {
+	(Some(rule), text.to_string())
+}
+
+fn echo(text: &str) -> (Option<usize>, String) {
+	(None, text.to_string())
+}
+
+#[test]
+fn test_longest_match() {
+	let spec = "%%\n\"if\"\tkeyword();\n[a-z]+\tword();\n";
+
+	assert_eq!(tokens(spec, "if"), vec![token(1, "if")]);
+	assert_eq!(tokens(spec, "iff"), vec![token(2, "iff")]);
+	assert_eq!(tokens(spec, "i"), vec![token(2, "i")]);
+}
+
+#[test]
+fn test_unmatched_input_is_echoed() {
+	let spec = "%%\n[0-9]+\tnumber();\n";
+
+	assert_eq!(
+		tokens(spec, "a12 3"),
+		vec![echo("a"), token(1, "12"), echo(" "), token(1, "3")]
+	);
+}
+
+#[test]
+fn test_empty_match_is_not_a_token() {
+	// a* matches the empty string before 'b': the default rule is used instead
+	assert_eq!(tokens("%%\na*\tas();\n", "aab"), vec![token(1, "aa"), echo("b")]);
+}
+
+#[test]
+fn test_positions() {
+	let tokens = interpreter("%%\n[a-z]+\tword();\n").tokens(b"ab cd\n  ef");
+
+	let positions: Vec<(usize, usize)> = tokens.iter()
+		.filter(|token| token.rule.is_some())
+		.map(|token| (token.line, token.column))
+		.collect();
+
+	assert_eq!(positions, vec![(1, 1), (1, 4), (2, 3)]);
+}
+
+#[test]
+fn test_start_of_line() {
+	let spec = "%%\n^a\tfirst();\na\tother();\n";
+
+	assert_eq!(
+		tokens(spec, "aa\na"),
+		vec![token(1, "a"), token(2, "a"), echo("\n"), token(1, "a")]
+	);
+}
+
+#[test]
+fn test_end_of_line() {
+	let spec = "%%\na$\tlast();\na\tother();\n";
+
+	assert_eq!(
+		tokens(spec, "aa\n"),
+		vec![token(2, "a"), token(1, "a"), echo("\n")]
+	);
+}
+
+#[test]
+fn test_trailing_context() {
+	let spec = "%%\n[a-z]+/\"(\"\tcall();\n[a-z]+\tword();\n";
+
+	assert_eq!(
+		tokens(spec, "f(x)"),
+		vec![token(1, "f"), echo("("), token(2, "x"), echo(")")]
+	);
+}
+
+#[test]
+fn test_begin_exclusive() {
+	let spec = "%x COMMENT\n%%\n\"/*\"\tBEGIN COMMENT;\n<COMMENT>\"*/\"\tBEGIN INITIAL;\n<COMMENT>.\t;\n[a-z]+\tword();\n";
+
+	assert_eq!(
+		tokens(spec, "a/*b*/c"),
+		vec![token(4, "a"), token(1, "/*"), token(3, "b"), token(2, "*/"), token(4, "c")]
+	);
+}
+
+#[test]
+fn test_begin_inclusive() {
+	let spec = "%s WORD\n%%\n\"#\"\tBEGIN(WORD);\n<WORD>[a-z]+\t{ word(); BEGIN 0; }\n[a-z]+\tother();\n";
+
+	assert_eq!(
+		tokens(spec, "ab#cd ef"),
+		vec![token(3, "ab"), token(1, "#"), token(2, "cd"), echo(" "), token(3, "ef")]
+	);
+}
+
+#[test]
+fn test_begin_of_or_action() {
+	let spec = "%x STRING\n%%\n\"'\"\t|\n\"\\\"\"\tBEGIN STRING;\n<STRING>.\t;\n";
+
+	assert_eq!(
+		tokens(spec, "'a"),
+		vec![token(1, "'"), token(3, "a")]
+	);
+}
+
+#[test]
+fn test_begin_in_an_identifier_is_ignored() {
+	let spec = "%x X\n%%\na\tNOBEGIN X;\nb\tb();\n";
+
+	assert_eq!(tokens(spec, "ab"), vec![token(1, "a"), token(2, "b")]);
+}
+
+#[test]
+fn test_reject_same_length() {
+	let spec = "%%\nfrob\t{ special(); REJECT; }\n[a-z]+\tword();\n";
+
+	assert_eq!(tokens(spec, "frob"), vec![token(1, "frob"), token(2, "frob")]);
+}
+
+#[test]
+fn test_reject_shorter_match() {
+	let spec = "%%\n[a-z]+\t{ word(); REJECT; }\na\tletter();\n";
+
+	assert_eq!(
+		tokens(spec, "ab"),
+		vec![token(1, "ab"), token(1, "a"), token(2, "a"), token(1, "b"), echo("b")]
+	);
+}
+
+#[test]
+fn test_reject_in_an_identifier_is_ignored() {
+	let spec = "%%\nab\tNOT_REJECT;\n[a-z]+\tword();\n";
+
+	assert_eq!(tokens(spec, "ab"), vec![token(1, "ab")]);
+}
+
+#[test]
+fn test_run_output() {
+	let spec = "%%\n[a-z]+\tword();\n";
+
+	assert_eq!(
+		run(spec, "ab, \"c\"\n"),
+		"rule#1 (line 1:1) \"ab\"\n, \"rule#1 (line 1:6) \"c\"\n\"\n"
+	);
+}
+
+#[test]
+fn test_lazy_automaton_past_the_state_limit() {
+	let spec = "%%\n(a|b)*a(a|b){20}\tlong();\n[a-z]+\tword();\n";
+	let input = "ab bbababbbababaabababbbab\nabab";
+
+	let mut parsing = Parsing::new().unwrap();
+	let mut reader = Reader::new(Cursor::new(spec.as_bytes().to_vec()), "<test>").unwrap();
+	let _ = parsing.parse_sections(&mut reader);
+
+	let lazy = Interpreter::with_state_limit(&parsing, 50);
+
+	let reason = lazy.lazy_reason().expect("the automaton should be built on demand");
+	assert!(reason.to_string().contains("(a|b)*a(a|b){20}"));
+
+	let tokens: Vec<(Option<usize>, String)> = lazy.tokens(input.as_bytes())
+		.into_iter()
+		.map(|token| (token.rule, token.text))
+		.collect();
+
+	assert_eq!(
+		tokens,
+		vec![
+			token(2, "ab"),
+			echo(" "),
+			token(1, "bbababbbababaabababbbab"),
+			echo("\n"),
+			token(2, "abab"),
+		]
+	);
+}
+
+#[test]
+fn test_no_lazy_automaton_below_the_state_limit() {
+	assert!(interpreter("%%\n[a-z]+\tword();\n").lazy_reason().is_none());
+}
+
+#[test]
+fn test_numeric_escapes() {
+	let spec = "%%\n\\x41+\tone();\n\"\\142\\x63\"\ttwo();\n[\\60-\\x39]+\tthree();\n";
+
+	assert_eq!(
+		tokens(spec, "AAbc42c"),
+		vec![token(1, "AA"), token(2, "bc"), token(3, "42"), echo("c")]
+	);
+}
+
+#[test]
+fn test_run_invalid_utf8_input() {
+	let spec = "%%\n\\xe9+\tone();\n";
+	let mut out = vec![];
+
+	// the bytes are matched as they are, like in the generated scanner
+	interpreter(spec).run(b"ab\xe9\xe9cd\xff", &mut out).unwrap();
+
+	let mut expected = "abrule#1 (line 1:3) \"\u{fffd}\u{fffd}\"\ncd".as_bytes().to_vec();
+	expected.push(0xff);
+
+	assert_eq!(out, expected);
+}
+
+#[test]
+fn test_utf8_tokens() {
+	let spec = "%%\n[α-ωΑ-Ω_][α-ωΑ-Ω0-9_]*\tident();\n[^α-ω \\n]+\tother();\n";
+
+	let tokens: Vec<(Option<usize>, String, usize)> = interpreter_with(spec, true).tokens("αβ_2 x€ ω\nλ".as_bytes())
+		.into_iter()
+		.map(|token| (token.rule, token.text, token.column))
+		.collect();
+
+	assert_eq!(tokens, vec![
+		(Some(1), "αβ_2".to_string(), 1),
+		(None, " ".to_string(), 5),
+		(Some(2), "x€".to_string(), 6),
+		(None, " ".to_string(), 8),
+		(Some(1), "ω".to_string(), 9),
+		(None, "\n".to_string(), 10),
+		(Some(1), "λ".to_string(), 1),
+	]);
+}
+
+#[test]
+fn test_bytes_without_utf8() {
+	// the pattern is read byte per byte, like the input: `+` only repeats the last byte of 'é'
+	let spec = "%%\né+\tone();\n";
+
+	assert_eq!(tokens(spec, "éé!"), vec![token(1, "é"), token(1, "é"), echo("!")]);
+
+	let texts: Vec<String> = interpreter_with(spec, true).tokens("éé!".as_bytes())
+		.into_iter()
+		.map(|token| token.text)
+		.collect();
+	assert_eq!(texts, vec!["éé", "!"]);
+
+	// the unmatched bytes are copied one by one: a rule can match from the middle of a char
+	assert_eq!(run(spec, "€"), "€");
+
+	let spec = "%%\n\\xa9x\tone();\n";
+	assert_eq!(tokens(spec, "éx"), vec![echo("\u{fffd}"), token(1, "\u{fffd}x")]);
+
+	// with --utf8, the unmatched chars are copied whole
+	let texts: Vec<String> = interpreter_with("%%\nx\tone();\n", true).tokens("éx".as_bytes())
+		.into_iter()
+		.map(|token| token.text)
+		.collect();
+	assert_eq!(texts, vec!["é", "x"]);
+}
+
+#[test]
+fn test_case_insensitive_keywords() {
+	let spec = "%option case-insensitive\n%%\nselect|from\tkeyword();\nwhere\twhere();\n(?-i:[A-Z])[a-z]*\tname();\n[a-z]+\tword();\n[ \\n]\tspace();\n";
+
+	assert_eq!(tokens(spec, "SELECT name FROM t Where x"), vec![
+		token(1, "SELECT"), token(5, " "), token(4, "name"), token(5, " "), token(1, "FROM"), token(5, " "),
+		token(4, "t"), token(5, " "), token(2, "Where"), token(5, " "), token(4, "x"),
+	]);
+
+	// without the option, only the scoped groups are case-insensitive
+	let spec = "%%\n(?i:select)\tkeyword();\n[A-Za-z]+\tword();\n";
+
+	assert_eq!(tokens(spec, "sElEcT"), vec![token(1, "sElEcT")]);
+	assert_eq!(tokens(spec, "sElEcTs"), vec![token(2, "sElEcTs")]);
+}
+
+#[test]
+fn test_nodefault() {
+	let spec = "%option nodefault\n%%\n[a-z]+\tword();\n\\n\tnewline();\n";
+	let mut out = vec![];
+
+	let err = interpreter(spec).run(b"ab\nc1d", &mut out).unwrap_err();
+
+	assert_eq!(err.to_string(), "ft_lex scanner jammed (line 2:2)");
+	assert_eq!(String::from_utf8(out).unwrap(), "rule#1 (line 1:1) \"ab\"\nrule#2 (line 1:3) \"\\n\"\nrule#1 (line 2:1) \"c\"\n");
+}
+
+#[test]
+fn test_yylineno_output() {
+	let spec = "%option yycolumn\n%%\n[a-z]+\tword();\n\\n\tnewline();\n";
+
+	assert_eq!(run(spec, "ab\ncd"), concat!(
+		"rule#1 (line 1:1) \"ab\" yylineno=1 yycolumn=3\n",
+		"rule#2 (line 1:3) \"\\n\" yylineno=2 yycolumn=1\n",
+		"rule#1 (line 2:1) \"cd\" yylineno=2 yycolumn=3\n",
+	));
+
+	let spec = "%option yylineno\n%%\n[a-z]+\\n\tword();\n";
+	assert_eq!(run(spec, "ab\n"), "rule#1 (line 1:1) \"ab\\n\" yylineno=2\n");
+
+	// REJECT: the position after each match
+	let spec = "%option yycolumn\n%%\nabc\tREJECT;\nab\tword();\n";
+	assert_eq!(run(spec, "abc"), "rule#1 (line 1:1) \"abc\" yylineno=1 yycolumn=4\nrule#2 (line 1:1) \"ab\" yylineno=1 yycolumn=3\nc");
+}
+
+#[test]
+fn test_columns_count_bytes_without_utf8() {
+	let spec = "%%\n[a-z]\tletter();\n";
+
+	let columns = |utf8: bool| -> Vec<usize> {
+		interpreter_with(spec, utf8).tokens("é a".as_bytes())
+			.into_iter()
+			.filter(|token| token.rule.is_some())
+			.map(|token| token.column)
+			.collect()
+	};
+
+	// like the generated scanner, which reads bytes
+	assert_eq!(columns(false), vec![4]);
+	assert_eq!(columns(true), vec![3]);
+}
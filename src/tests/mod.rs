@@ -3,4 +3,6 @@ use super::*;
 mod definitions;
 mod rules;
 mod parsing;
-mod regex;
\ No newline at end of file
+mod regex;
+mod codegen;
+mod interpreter;
@@ -1,5 +1,5 @@
 use std::io::{Cursor, Read};
-use crate::parsing::{Parsing, Reader, Section, ParsingError};
+use crate::parsing::{Parsing, Reader, RuleAction, Section, ParsingError};
 
 /// Create a `Reader` from a string for testing purposes
 fn create_reader(content: &str) -> Reader<Cursor<Vec<u8>>> {
@@ -51,13 +51,13 @@ fn test_parse_sections_with_rules() {
     
     // Verify rules were parsed
     assert_eq!(parsing.rules.len(), 1);
-    assert!(parsing.rules[0].regex_nfa.borrow().to_string().contains("[0123456789]"));
+    let nfa = &parsing.rules[0].regex_nfa;
+    assert!(nfa[nfa.start].to_string().contains("[0123456789]"));
 	
-	let split_out_1 = parsing.rules[0].regex_nfa.borrow().basic_out().unwrap().borrow().borrow().split_out().unwrap().0.borrow().borrow().to_string();
-    assert!(split_out_1.contains("[0123456789]"));
+	let (split_out_1, split_out_2) = nfa[nfa[nfa.start].out().unwrap()].split_out().unwrap();
+    assert!(nfa[split_out_1.unwrap()].to_string().contains("[0123456789]"));
 
-	let split_out_2 = parsing.rules[0].regex_nfa.borrow().basic_out().unwrap().borrow().borrow().split_out().unwrap().1.borrow().borrow().to_string();
-	assert!(split_out_2.contains("Match"));
+	assert!(nfa[split_out_2.unwrap()].to_string().contains("Match"));
 }
 
 #[test]
@@ -87,3 +87,47 @@ fn test_parse_sections_invalid_rule() {
     assert!(result.is_err());
     assert_eq!(parsing.errors.len(), 1);
 }
+
+#[test]
+fn test_parse_sections_invalid_escape() {
+    let mut parsing = Parsing::new().unwrap();
+    let content = "%%\n[a-z]+ { return WORD; }\n\\x{41} { return A; }\n";
+    let mut reader = create_reader(content);
+
+    let result = parsing.parse_sections(&mut reader);
+    assert!(result.is_err());
+    assert_eq!(parsing.errors.len(), 1);
+    assert!(parsing.errors[0].to_string().contains(":3: : invalid escape sequence `\\x`"), "{}", parsing.errors[0]);
+}
+
+#[test]
+fn test_parse_sections_utf8() {
+    let mut parsing = Parsing::new().unwrap();
+    parsing.definitions.utf8 = true;
+
+    let content = "GREEK [α-ω]\n%%\n{GREEK}+ { puts(\"λ\"); }\n";
+    let mut reader = create_reader(content);
+
+    assert!(parsing.parse_sections(&mut reader).is_ok());
+    assert_eq!(parsing.rules.len(), 1);
+    assert_eq!(parsing.rules[0].pattern, "([α-ω])+");
+    assert!(matches!(&parsing.rules[0].action, RuleAction::Statement(action) if action.contains("puts(\"λ\");")));
+}
+
+#[test]
+fn test_parse_sections_invalid_utf8() {
+    let mut parsing = Parsing::new().unwrap();
+    parsing.definitions.utf8 = true;
+
+    let content = b"%%\nab\xe9 { return A; }\n".to_vec();
+    let mut reader = Reader::new(Cursor::new(content.clone()), "<test>").unwrap();
+
+    assert!(parsing.parse_sections(&mut reader).is_err());
+    assert!(parsing.errors[0].to_string().contains("invalid UTF-8 in the pattern"), "{}", parsing.errors[0]);
+
+    // the same bytes are a Latin-1 pattern without --utf8
+    let mut parsing = Parsing::new().unwrap();
+    let mut reader = Reader::new(Cursor::new(content), "<test>").unwrap();
+
+    assert!(parsing.parse_sections(&mut reader).is_ok());
+}
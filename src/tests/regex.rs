@@ -3,3 +3,8 @@ mod parsing;
 mod post2nfa;
 mod re2post;
 mod dfa;
+mod trailing_context;
+mod dot;
+mod differential;
+mod utf8;
+mod case;
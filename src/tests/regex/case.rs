@@ -0,0 +1,157 @@
+use crate::regex::*;
+use crate::regex::dfa::normalizer::*;
+
+fn case_dfa(patterns: &[&str], flags: RegexFlags) -> NormalizedDfa {
+	let nfas: Vec<Nfa> = patterns.iter()
+		.enumerate()
+		.map(|(index, pattern)| Regex::with_flags(pattern.to_string(), index + 1, flags).unwrap())
+		.collect();
+
+	NormalizedDfa::from(&mut Dfa::new(&nfas.iter().collect::<Vec<_>>()))
+}
+
+fn insensitive() -> RegexFlags {
+	RegexFlags { case_insensitive: true, ..Default::default() }
+}
+
+/// (rule, length) of the best match of `text`
+fn best(dfa: &NormalizedDfa, text: &str) -> Option<(usize, usize)> {
+	simulate(text, dfa).map(|match_| (match_.id(), match_.length()))
+}
+
+fn sorted(mut chars: Vec<char>) -> Vec<char> {
+	chars.sort();
+	chars
+}
+
+// ==============================
+// Case Folding Tests
+// ==============================
+
+#[test]
+fn test_other_cases() {
+	assert_eq!(other_cases('a', false), vec!['A']);
+	assert_eq!(other_cases('Z', false), vec!['z']);
+	assert!(other_cases('1', false).is_empty());
+	assert!(other_cases('_', true).is_empty());
+
+	// bytes have no case
+	assert!(other_cases('é', false).is_empty());
+
+	assert_eq!(other_cases('é', true), vec!['É']);
+	assert_eq!(other_cases('Ω', true), vec!['ω']);
+
+	// `ß` is uppercased to `SS`
+	assert!(other_cases('ß', true).is_empty());
+}
+
+#[test]
+fn test_case_insensitive_char() {
+	let RegexType::CharacterClass(class) = Regex::case_insensitive(RegexType::Char('k'), false) else {
+		panic!("expected a class");
+	};
+
+	assert!(!class.negated);
+	assert_eq!(sorted(class.chars), vec!['K', 'k']);
+
+	assert_eq!(Regex::case_insensitive(RegexType::Char('+'), false), RegexType::Char('+'));
+	assert_eq!(Regex::case_insensitive(RegexType::Or, false), RegexType::Or);
+}
+
+#[test]
+fn test_case_insensitive_class() {
+	let class = CharacterClass::from_range('a', 'c').unwrap().case_insensitive(false);
+	assert_eq!(sorted(class.chars), vec!['A', 'B', 'C', 'a', 'b', 'c']);
+
+	// no duplicate when both cases are already there
+	let class = CharacterClass::from_range('A', 'z').unwrap().case_insensitive(false);
+	assert_eq!(class.chars.len(), ('A'..='z').count());
+
+	let class = CharacterClass::from_single('x').negated().case_insensitive(false);
+	assert!(!class.contains(&'x'));
+	assert!(!class.contains(&'X'));
+	assert!(class.contains(&'y'));
+}
+
+// ==============================
+// Scoped Groups Tests
+// ==============================
+
+#[test]
+fn test_scoped_group_tokens() {
+	let tokens = Regex::tokens("(?i:ab)c").unwrap();
+
+	assert_eq!(tokens.len(), 5);
+	assert_eq!(tokens[0], RegexType::OpenParenthesis);
+	assert!(matches!(&tokens[1], RegexType::CharacterClass(class) if sorted(class.chars.clone()) == vec!['A', 'a']));
+	assert!(matches!(&tokens[2], RegexType::CharacterClass(class) if sorted(class.chars.clone()) == vec!['B', 'b']));
+	assert_eq!(tokens[3], RegexType::CloseParenthesis);
+	assert_eq!(tokens[4], RegexType::Char('c'));
+}
+
+#[test]
+fn test_scoped_group_matching() {
+	let dfa = case_dfa(&["(?i:select)", "[a-z]+"], RegexFlags::default());
+
+	assert_eq!(best(&dfa, "SeLeCt"), Some((1, 6)));
+	assert_eq!(best(&dfa, "select"), Some((1, 6)));
+	assert_eq!(best(&dfa, "selects"), Some((2, 7)));
+	assert_eq!(best(&dfa, "SELECTS"), Some((1, 6)));
+}
+
+#[test]
+fn test_nested_groups() {
+	let dfa = case_dfa(&["(?i:a(?-i:b)(c))d"], RegexFlags::default());
+
+	assert_eq!(best(&dfa, "AbCd"), Some((1, 4)));
+	assert_eq!(best(&dfa, "abcd"), Some((1, 4)));
+	assert_eq!(best(&dfa, "aBcd"), None);
+	assert_eq!(best(&dfa, "abcD"), None);
+}
+
+#[test]
+fn test_global_case_insensitive() {
+	let dfa = case_dfa(&["if", "(?-i:ID)", "[a-c]+"], insensitive());
+
+	assert_eq!(best(&dfa, "IF"), Some((1, 2)));
+	assert_eq!(best(&dfa, "iD"), None);
+	assert_eq!(best(&dfa, "ID"), Some((2, 2)));
+	assert_eq!(best(&dfa, "AbC"), Some((3, 3)));
+}
+
+#[test]
+fn test_case_insensitive_strings() {
+	let dfa = case_dfa(&["\"a+b\"", "(?-i:\"x\")"], insensitive());
+
+	assert_eq!(best(&dfa, "A+B"), Some((1, 3)));
+	assert_eq!(best(&dfa, "AAB"), None);
+	assert_eq!(best(&dfa, "X"), None);
+
+	let dfa = case_dfa(&["(?i:\"where\")"], RegexFlags::default());
+	assert_eq!(best(&dfa, "WHERE"), Some((1, 5)));
+}
+
+#[test]
+fn test_case_insensitive_negated_class() {
+	let dfa = case_dfa(&["[^q]+"], insensitive());
+
+	assert_eq!(best(&dfa, "abQ"), Some((1, 2)));
+	assert_eq!(best(&dfa, "abq"), Some((1, 2)));
+}
+
+#[test]
+fn test_case_insensitive_utf8() {
+	let flags = RegexFlags { utf8: true, case_insensitive: true, ..Default::default() };
+	let dfa = case_dfa(&["ωé"], flags);
+
+	let bytes = |text: &str| -> String { text.bytes().map(|byte| byte as char).collect() };
+
+	assert_eq!(best(&dfa, &bytes("ΩÉ")), Some((1, 4)));
+	assert_eq!(best(&dfa, &bytes("ωé")), Some((1, 4)));
+}
+
+#[test]
+fn test_invalid_group_flags() {
+	assert!(Regex::tokens("(?x:a)").is_err());
+	assert!(Regex::tokens("(?i").is_err());
+}
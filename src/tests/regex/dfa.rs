@@ -6,6 +6,9 @@ use std::rc::Rc;
 use std::time::Instant;
 
 mod normalizer;
+mod minimizer;
+mod classes;
+mod lazy;
 
 fn into_postfix(str: &str) -> VecDeque<TokenType> {
 	re2post(Regex::add_concatenation(Regex::tokens(str).unwrap())).unwrap()
@@ -16,85 +19,90 @@ fn into_postfix(str: &str) -> VecDeque<TokenType> {
 // ==============================
 
 /// Creates a simple basic state for a specific character
-fn create_basic_state(c: char) -> StatePtr {
-    State::basic(RegexType::Char(c))
+fn create_basic_state(nfa: &mut Nfa, c: char) -> StateId {
+    nfa.push(State::basic(RegexType::Char(c)))
+}
+
+/// Connects the transition of `from` to `to`
+fn connect(nfa: &mut Nfa, from: StateId, to: StateId) {
+    nfa.patch(OutPtr::first(from), to);
 }
 
 /// Creates a basic state for a character with a transition to a match state
-fn create_basic_to_match(c: char, id: usize) -> StatePtr {
-    let state = State::basic(RegexType::Char(c));
-    let match_state = State::match_(id);
+fn create_basic_to_match(nfa: &mut Nfa, c: char, id: usize) -> StateId {
+    let state = create_basic_state(nfa, c);
+    let match_state = nfa.push(State::match_(id));
     
     // Connect the basic state to the match state
-    state.borrow_mut().into_basic().unwrap().out.replace(match_state);
+    connect(nfa, state, match_state);
     
     state
 }
 
 /// Creates a state with a transition to a match state
-fn create_state_to_match(state_type: &str, id: usize) -> StatePtr {
-    let match_state = State::match_(id);
-    
-    match state_type {
-        "basic" => {
-            let state = State::basic(RegexType::Char('a'));
-            state.borrow_mut().into_basic().unwrap().out.replace(match_state);
-            state
-        },
-        "startofline" => {
-            let state = State::start_of_line();
-            state.borrow().start_of_line_out().unwrap().replace(match_state);
-            state
-        },
-        "endofline" => {
-            let state = State::end_of_line();
-            state.borrow().end_of_line_out().unwrap().replace(match_state);
-            state
-        },
-        _ => State::none()
-    }
+fn create_state_to_match(nfa: &mut Nfa, state_type: &str, id: usize) -> StateId {
+    let state = match state_type {
+        "basic" => State::basic(RegexType::Char('a')),
+        "startofline" => State::start_of_line(),
+        "endofline" => State::end_of_line(),
+        _ => return nfa.push(State::no_match())
+    };
+
+    let state = nfa.push(state);
+    let match_state = nfa.push(State::match_(id));
+
+    connect(nfa, state, match_state);
+
+    state
 }
 
 /// Creates a basic state for a character class
-fn create_class_state(chars: &[char]) -> StatePtr {
+fn create_class_state(nfa: &mut Nfa, chars: &[char]) -> StateId {
     let mut class = CharacterClass::new();
     for &c in chars {
         class.add_char(c);
     }
-    State::basic(RegexType::CharacterClass(class))
+    nfa.push(State::basic(RegexType::CharacterClass(class)))
 }
 
 /// Creates a state list from a vector of states
-fn create_state_list(states: Vec<StatePtr>) -> StateList {
+fn create_state_list(nfa: &Nfa, states: Vec<StateId>) -> StateList {
     let mut list = StateList::new();
     for state in states {
-        list.add_state(&state);
+        list.add_state(nfa, state);
     }
     list
 }
 
 /// Creates a cycle in the state machine (a -> b -> c -> a)
-fn create_cycle() -> Vec<StatePtr> {
-    let a = create_basic_state('a');
-    let b = create_basic_state('b');
-    let c = create_basic_state('c');
+fn create_cycle(nfa: &mut Nfa) -> Vec<StateId> {
+    let a = create_basic_state(nfa, 'a');
+    let b = create_basic_state(nfa, 'b');
+    let c = create_basic_state(nfa, 'c');
     
     // Connect a -> b -> c -> a
-    a.borrow_mut().into_basic().unwrap().out.replace(b.clone());
-    b.borrow_mut().into_basic().unwrap().out.replace(c.clone());
-    c.borrow_mut().into_basic().unwrap().out.replace(a.clone());
+    connect(nfa, a, b);
+    connect(nfa, b, c);
+    connect(nfa, c, a);
     
     vec![a, b, c]
 }
 
+/// The NFA `nfa` starting at `start`
+fn starting_at(nfa: &Nfa, start: StateId) -> Nfa {
+    let mut nfa = nfa.clone();
+    nfa.start = start;
+    nfa
+}
+
 /// Create an input map with specified conditions and states
-fn create_input_map(entries: Vec<(InputCondition, Vec<StatePtr>)>) -> HashMap<InputCondition, StateList> {
+fn create_input_map(nfa: &Nfa, entries: Vec<(InputCondition, Vec<StateId>)>) -> HashMap<InputCondition, StateList> {
     let mut map = HashMap::new();
     
     for (condition, states) in entries {
         let mut state_list = StateList::new();
         for state in states {
-            state_list.add_state(&state);
+            state_list.add_state(nfa, state);
         }
         map.insert(condition, state_list);
     }
@@ -182,16 +190,23 @@ fn test_hashing_of_input_conditions() {
 // 2. merge_input_maps Tests
 // ==============================
 
+
 #[test]
 fn test_merge_maps_no_overlapping_keys() {
-    let mut map1 = create_input_map(vec![
-        (InputCondition::Char('a'), vec![create_basic_state('x')]),
-        (InputCondition::Char('b'), vec![create_basic_state('y')])
+    let mut nfa = Nfa::new();
+    let x = create_basic_state(&mut nfa, 'x');
+    let y = create_basic_state(&mut nfa, 'y');
+    let z = create_basic_state(&mut nfa, 'z');
+    let w = create_basic_state(&mut nfa, 'w');
+
+    let mut map1 = create_input_map(&nfa, vec![
+        (InputCondition::Char('a'), vec![x]),
+        (InputCondition::Char('b'), vec![y])
     ]);
     
-    let map2 = create_input_map(vec![
-        (InputCondition::Char('c'), vec![create_basic_state('z')]),
-        (InputCondition::Char('d'), vec![create_basic_state('w')])
+    let map2 = create_input_map(&nfa, vec![
+        (InputCondition::Char('c'), vec![z]),
+        (InputCondition::Char('d'), vec![w])
     ]);
     
     let map1_len = map1.len();
@@ -208,19 +223,20 @@ fn test_merge_maps_no_overlapping_keys() {
 
 #[test]
 fn test_merge_maps_with_overlapping_keys() {
-    let state_a1 = create_basic_state('a');
-    let state_a2 = create_basic_state('b');
-    let state_b1 = create_basic_state('c');
-    let state_b2 = create_basic_state('d');
-    
-    let mut map1 = create_input_map(vec![
-        (InputCondition::Char('x'), vec![state_a1.clone()]),
-        (InputCondition::Char('y'), vec![state_b1.clone()])
+    let mut nfa = Nfa::new();
+    let state_a1 = create_basic_state(&mut nfa, 'a');
+    let state_a2 = create_basic_state(&mut nfa, 'b');
+    let state_b1 = create_basic_state(&mut nfa, 'c');
+    let state_b2 = create_basic_state(&mut nfa, 'd');
+    
+    let mut map1 = create_input_map(&nfa, vec![
+        (InputCondition::Char('x'), vec![state_a1]),
+        (InputCondition::Char('y'), vec![state_b1])
     ]);
     
-    let map2 = create_input_map(vec![
-        (InputCondition::Char('x'), vec![state_a2.clone()]),
-        (InputCondition::Char('z'), vec![state_b2.clone()])
+    let map2 = create_input_map(&nfa, vec![
+        (InputCondition::Char('x'), vec![state_a2]),
+        (InputCondition::Char('z'), vec![state_b2])
     ]);
     
     merge_input_maps(&mut map1, map2);
@@ -229,28 +245,21 @@ fn test_merge_maps_with_overlapping_keys() {
     
     // Check that x contains both states
     let x_list = map1.get(&InputCondition::Char('x')).unwrap();
-    let mut found_a1 = false;
-    let mut found_a2 = false;
-    
-    for state in x_list {
-        if Rc::ptr_eq(state, &state_a1) {
-            found_a1 = true;
-        }
-        if Rc::ptr_eq(state, &state_a2) {
-            found_a2 = true;
-        }
-    }
     
-    assert!(found_a1 && found_a2);
+    assert!(x_list.contains(state_a1) && x_list.contains(state_a2));
 }
 
 #[test]
 fn test_merge_with_empty_first_map() {
+    let mut nfa = Nfa::new();
+    let x = create_basic_state(&mut nfa, 'x');
+    let y = create_basic_state(&mut nfa, 'y');
+
     let mut map1 = HashMap::new();
     
-    let map2 = create_input_map(vec![
-        (InputCondition::Char('a'), vec![create_basic_state('x')]),
-        (InputCondition::Char('b'), vec![create_basic_state('y')])
+    let map2 = create_input_map(&nfa, vec![
+        (InputCondition::Char('a'), vec![x]),
+        (InputCondition::Char('b'), vec![y])
     ]);
     
     let map2_len = map2.len();
@@ -264,9 +273,13 @@ fn test_merge_with_empty_first_map() {
 
 #[test]
 fn test_merge_with_empty_second_map() {
-    let mut map1 = create_input_map(vec![
-        (InputCondition::Char('a'), vec![create_basic_state('x')]),
-        (InputCondition::Char('b'), vec![create_basic_state('y')])
+    let mut nfa = Nfa::new();
+    let x = create_basic_state(&mut nfa, 'x');
+    let y = create_basic_state(&mut nfa, 'y');
+
+    let mut map1 = create_input_map(&nfa, vec![
+        (InputCondition::Char('a'), vec![x]),
+        (InputCondition::Char('b'), vec![y])
     ]);
     
     let map2: HashMap<InputCondition, StateList> = HashMap::new();
@@ -292,15 +305,16 @@ fn test_merge_with_both_maps_empty() {
 
 #[test]
 fn test_merge_maps_with_same_keys_different_state_lists() {
-    let state_a1 = create_basic_state('a');
-    let state_a2 = create_basic_state('b');
+    let mut nfa = Nfa::new();
+    let state_a1 = create_basic_state(&mut nfa, 'a');
+    let state_a2 = create_basic_state(&mut nfa, 'b');
     
-    let mut map1 = create_input_map(vec![
-        (InputCondition::Char('x'), vec![state_a1.clone()])
+    let mut map1 = create_input_map(&nfa, vec![
+        (InputCondition::Char('x'), vec![state_a1])
     ]);
     
-    let map2 = create_input_map(vec![
-        (InputCondition::Char('x'), vec![state_a2.clone()])
+    let map2 = create_input_map(&nfa, vec![
+        (InputCondition::Char('x'), vec![state_a2])
     ]);
     
     merge_input_maps(&mut map1, map2);
@@ -312,23 +326,22 @@ fn test_merge_maps_with_same_keys_different_state_lists() {
 
 #[test]
 fn test_merge_large_maps() {
+    let mut nfa = Nfa::new();
     let mut map1 = HashMap::new();
     let mut map2 = HashMap::new();
 
     // Create 100 entries in map1
     for i in 0..50_u8 {
         let c = i as char;
-        let mut list = StateList::new();
-        list.add_state(&create_basic_state(c));
-        map1.insert(InputCondition::Char(c), list);
+        let state = create_basic_state(&mut nfa, c);
+        map1.insert(InputCondition::Char(c), StateList::from(&nfa, state));
     }
 
     // Create 100 different entries in map2
     for i in 50..100_u8 {
         let c = i as char;
-        let mut list = StateList::new();
-        list.add_state(&create_basic_state(c));
-        map2.insert(InputCondition::Char(c), list);
+        let state = create_basic_state(&mut nfa, c);
+        map2.insert(InputCondition::Char(c), StateList::from(&nfa, state));
     }
 
     let start = Instant::now();
@@ -341,21 +354,22 @@ fn test_merge_large_maps() {
 
 #[test]
 fn test_merged_state_lists_have_correct_contents() {
-    let state_a = create_basic_state('a');
-    let state_b = create_basic_state('b');
-    let state_c = create_basic_state('c');
-    let state_d = create_basic_state('d');
+    let mut nfa = Nfa::new();
+    let state_a = create_basic_state(&mut nfa, 'a');
+    let state_b = create_basic_state(&mut nfa, 'b');
+    let state_c = create_basic_state(&mut nfa, 'c');
+    let state_d = create_basic_state(&mut nfa, 'd');
     
     let mut map1 = HashMap::new();
     let mut list1 = StateList::new();
-    list1.add_state(&state_a);
-    list1.add_state(&state_b);
+    list1.add_state(&nfa, state_a);
+    list1.add_state(&nfa, state_b);
     map1.insert(InputCondition::Char('x'), list1);
     
     let mut map2 = HashMap::new();
     let mut list2 = StateList::new();
-    list2.add_state(&state_c);
-    list2.add_state(&state_d);
+    list2.add_state(&nfa, state_c);
+    list2.add_state(&nfa, state_d);
     map2.insert(InputCondition::Char('x'), list2);
     
     merge_input_maps(&mut map1, map2);
@@ -366,19 +380,9 @@ fn test_merged_state_lists_have_correct_contents() {
     assert_eq!(merged_list.len(), 4);
     
     // Check if all states are in the merged list
-    let mut found_a = false;
-    let mut found_b = false;
-    let mut found_c = false;
-    let mut found_d = false;
-    
-    for state in merged_list {
-        if Rc::ptr_eq(state, &state_a) { found_a = true; }
-        else if Rc::ptr_eq(state, &state_b) { found_b = true; }
-        else if Rc::ptr_eq(state, &state_c) { found_c = true; }
-        else if Rc::ptr_eq(state, &state_d) { found_d = true; }
+    for state in [state_a, state_b, state_c, state_d] {
+        assert!(merged_list.contains(state));
     }
-    
-    assert!(found_a && found_b && found_c && found_d);
 }
 
 // ==============================
@@ -387,8 +391,9 @@ fn test_merged_state_lists_have_correct_contents() {
 
 #[test]
 fn test_create_dfa_with_single_start_state() {
-    let start_state = create_basic_to_match('a', 0);
-    let dfa = Dfa::new(vec![start_state]);
+    let mut nfa = Nfa::new();
+    nfa.start = create_basic_to_match(&mut nfa, 'a', 0);
+    let dfa = Dfa::new(&[&nfa]);
     
     // The DFA should have a start state
     assert!(dfa.start.borrow().next.len() > 0);
@@ -398,15 +403,16 @@ fn test_create_dfa_with_single_start_state() {
 
 	let borrow = dfa.start.borrow();
 	let next = borrow.next.get(&InputCondition::Char('a')).unwrap();
-	assert!(next.is_matched());
+	assert!(next.is_matched(&dfa.nfa));
 }
 
 #[test]
 fn test_create_dfa_with_multiple_start_states() {
-    let state1 = create_basic_to_match('a', 1);
-    let state2 = create_basic_to_match('b', 2);
+    let mut nfa = Nfa::new();
+    let state1 = create_basic_to_match(&mut nfa, 'a', 1);
+    let state2 = create_basic_to_match(&mut nfa, 'b', 2);
     
-    let dfa = Dfa::new(vec![state1, state2]);
+    let dfa = Dfa::new(&[&starting_at(&nfa, state1), &starting_at(&nfa, state2)]);
     
     // The DFA should have a start state
     assert!(dfa.start.borrow().states.len() > 0);
@@ -417,8 +423,9 @@ fn test_create_dfa_with_multiple_start_states() {
 
 #[test]
 fn test_create_dfa_with_no_outgoing_transitions() {
-    let start_state = State::match_(0); // Match state with no outgoing transitions
-    let dfa = Dfa::new(vec![start_state]);
+    let mut nfa = Nfa::new();
+    nfa.start = nfa.push(State::match_(0)); // Match state with no outgoing transitions
+    let dfa = Dfa::new(&[&nfa]);
     
     // The DFA should have a start state
     assert!(dfa.start.borrow().is_match());
@@ -429,8 +436,10 @@ fn test_create_dfa_with_no_outgoing_transitions() {
 
 #[test]
 fn test_create_dfa_with_cycle() {
-    let cycle_states = create_cycle();
-    let dfa = Dfa::new(cycle_states);
+    let mut nfa = Nfa::new();
+    let cycle_states = create_cycle(&mut nfa);
+    let nfas: Vec<Nfa> = cycle_states.iter().map(|state| starting_at(&nfa, *state)).collect();
+    let dfa = Dfa::new(&nfas.iter().collect::<Vec<_>>());
     
     // The DFA should have a start state with transitions
     assert!(dfa.start.borrow().next.len() > 0);
@@ -443,7 +452,7 @@ fn test_create_dfa_with_cycle() {
 fn test_create_dfa_with_complex_pattern() {
     // Create an NFA for a(b|c)*d
     let nfa = post2nfa(into_postfix("a(b|c)*d"), 0).unwrap();
-    let dfa = Dfa::new(vec![nfa]);
+    let dfa = Dfa::new(&[&nfa]);
     
     // Check that the DFA was created correctly
     assert!(dfa.memory.len() > 0);
@@ -453,15 +462,15 @@ fn test_create_dfa_with_complex_pattern() {
 fn test_create_dfa_for_common_patterns() {
     // Test "starts with a"
     let starts_with_a = post2nfa(into_postfix("^a.*"), 0).unwrap();
-    let dfa1 = Dfa::new(vec![starts_with_a]);
+    let dfa1 = Dfa::new(&[&starts_with_a]);
     
     // Test "ends with b"
     let ends_with_b = post2nfa(into_postfix(".*b$"), 1).unwrap();
-    let dfa2 = Dfa::new(vec![ends_with_b]);
+    let dfa2 = Dfa::new(&[&ends_with_b]);
     
     // Test "contains c"
     let contains_c = post2nfa(into_postfix(".*c.*"), 2).unwrap();
-    let dfa3 = Dfa::new(vec![contains_c]);
+    let dfa3 = Dfa::new(&[&contains_c]);
     
     // All DFAs should have been created
     assert!(dfa1.memory.len() > 0);
@@ -472,7 +481,7 @@ fn test_create_dfa_for_common_patterns() {
 // This test is expected to return an empty DFA
 #[test]
 fn test_create_dfa_from_empty_state_list() {
-    let dfa = Dfa::new(vec![]);
+    let dfa = Dfa::new(&[]);
     
     // The start state should exist but have an empty state list
     assert_eq!(dfa.start.borrow().states.len(), 0);
@@ -481,33 +490,52 @@ fn test_create_dfa_from_empty_state_list() {
     assert_eq!(dfa.memory.len(), 1);
 }
 
+#[test]
+fn test_create_dfa_merges_the_nfas() {
+    let nfa_a = post2nfa(into_postfix("ab"), 1).unwrap();
+    let nfa_b = post2nfa(into_postfix("c"), 2).unwrap();
+
+    let dfa = Dfa::new(&[&nfa_a, &nfa_b]);
+
+    // The DFA keeps the states of both NFAs
+    assert_eq!(dfa.nfa.len(), nfa_a.len() + nfa_b.len());
+
+    let borrow = dfa.start.borrow();
+    let next = borrow.next.get(&InputCondition::Char('c')).unwrap();
+    let next = dfa.memory.get(next).unwrap();
+
+    assert_eq!(next.borrow().match_id(&dfa.nfa), Some(2));
+}
+
 // ==============================
 // 4. DfaState Basic Tests
 // ==============================
 
 #[test]
 fn test_create_dfastate_with_valid_id_and_states() {
-    let state = create_basic_to_match('a', 0);
+    let mut nfa = Nfa::new();
+    let state = create_basic_to_match(&mut nfa, 'a', 0);
     let mut list = StateList::new();
-    list.add_state(&state);
+    list.add_state(&nfa, state);
     
-    let mut dfa_state = DfaState::new(42, list);
+    let mut dfa_state = DfaState::new(42, list, &nfa);
     
     assert_eq!(dfa_state.id, 42);
     assert_eq!(dfa_state.states.len(), 1);
 
-	dfa_state.compute_next();
+	dfa_state.compute_next(&nfa);
 
 	assert_eq!(dfa_state.next.len(), 1);
 
 	let next = dfa_state.next.get(&InputCondition::Char('a')).unwrap();
-	assert!(next.is_matched());
+	assert!(next.is_matched(&nfa));
 }
 
 #[test]
 fn test_create_dfastate_with_empty_state_list() {
+    let nfa = Nfa::new();
     let list = StateList::new();
-    let dfa_state = DfaState::new(1, list);
+    let dfa_state = DfaState::new(1, list, &nfa);
     
     assert_eq!(dfa_state.id, 1);
     assert_eq!(dfa_state.states.len(), 0);
@@ -518,15 +546,16 @@ fn test_create_dfastate_with_empty_state_list() {
 #[test]
 fn test_matchs_extraction_works_correctly() {
     // Create a state list with a match state
-    let match_state = State::match_(5);
-    let basic_state = create_basic_state('a');
+    let mut nfa = Nfa::new();
+    let match_state = nfa.push(State::match_(5));
+    let basic_state = create_basic_state(&mut nfa, 'a');
     
     let mut list = StateList::new();
-    list.add_state(&match_state);
-    list.add_state(&basic_state);
+    list.add_state(&nfa, match_state);
+    list.add_state(&nfa, basic_state);
     
     // Create a DFA state
-    let dfa_state = DfaState::new(1, list);
+    let dfa_state = DfaState::new(1, list, &nfa);
     
     // The match should be extracted to the matchs list
     assert_eq!(dfa_state.states.len(), 1); // Only basic_state remains
@@ -537,43 +566,45 @@ fn test_matchs_extraction_works_correctly() {
 #[test]
 fn test_dfastate_creation_with_match_states() {
     // Create a chain: basic -> match
-    let basic = create_basic_state('a');
-    let match_state = State::match_(7);
-    basic.borrow_mut().into_basic().unwrap().out.replace(match_state);
+    let mut nfa = Nfa::new();
+    let basic = create_basic_state(&mut nfa, 'a');
+    let match_state = nfa.push(State::match_(7));
+    connect(&mut nfa, basic, match_state);
     
     let mut list = StateList::new();
-    list.add_state(&basic);
+    list.add_state(&nfa, basic);
     
     // Create a DFA state
-    let mut dfa_state = DfaState::new(1, list);
+    let mut dfa_state = DfaState::new(1, list, &nfa);
     
     // Initially no match state in the DFA state
     assert_eq!(dfa_state.matchs.len(), 0);
     
     // Compute next states
-    dfa_state.compute_next();
+    dfa_state.compute_next(&nfa);
     
     // Now there should be a transition to a state that has a match
     let next_list = dfa_state.next.get(&InputCondition::Char('a')).unwrap();
-    assert!(next_list.is_matched());
+    assert!(next_list.is_matched(&nfa));
 }
 
 #[test]
 fn test_create_dfastate_with_large_state_list() {
     // Create a large number of states (1000)
+    let mut nfa = Nfa::new();
     let mut states = Vec::with_capacity(1000);
     for i in 0..1000 {
         let c = (i % 26 + 'a' as usize) as u8 as char;
-        states.push(create_basic_state(c));
+        states.push(create_basic_state(&mut nfa, c));
     }
     
     let mut list = StateList::new();
     for state in &states {
-        list.add_state(state);
+        list.add_state(&nfa, *state);
     }
     
     let start_time = Instant::now();
-    let dfa_state = DfaState::new(1, list);
+    let dfa_state = DfaState::new(1, list, &nfa);
     let create_time = start_time.elapsed();
     
     println!("Large DfaState creation took: {:?}", create_time);
@@ -588,12 +619,13 @@ fn test_create_dfastate_with_large_state_list() {
 
 #[test]
 fn test_compute_next_for_single_character_transition() {
-    let state = create_basic_to_match('a', 0);
+    let mut nfa = Nfa::new();
+    let state = create_basic_to_match(&mut nfa, 'a', 0);
     let mut list = StateList::new();
-    list.add_state(&state);
+    list.add_state(&nfa, state);
     
-    let mut dfa_state = DfaState::new(1, list);
-    dfa_state.compute_next();
+    let mut dfa_state = DfaState::new(1, list, &nfa);
+    dfa_state.compute_next(&nfa);
     
     // Should have only one transition for character 'a'
     assert_eq!(dfa_state.next.len(), 1);
@@ -601,23 +633,24 @@ fn test_compute_next_for_single_character_transition() {
     
     // The transition should lead to a match state
     let next_list = dfa_state.next.get(&InputCondition::Char('a')).unwrap();
-    assert!(next_list.is_matched());
+    assert!(next_list.is_matched(&nfa));
 }
 
 #[test]
 fn test_compute_next_for_multiple_character_transitions() {
     // Create states for 'a', 'b', and 'c'
-    let state_a = create_basic_to_match('a', 1);
-    let state_b = create_basic_to_match('b', 2);
-    let state_c = create_basic_to_match('c', 3);
+    let mut nfa = Nfa::new();
+    let state_a = create_basic_to_match(&mut nfa, 'a', 1);
+    let state_b = create_basic_to_match(&mut nfa, 'b', 2);
+    let state_c = create_basic_to_match(&mut nfa, 'c', 3);
     
     let mut list = StateList::new();
-    list.add_state(&state_a);
-    list.add_state(&state_b);
-    list.add_state(&state_c);
+    list.add_state(&nfa, state_a);
+    list.add_state(&nfa, state_b);
+    list.add_state(&nfa, state_c);
     
-    let mut dfa_state = DfaState::new(1, list);
-    dfa_state.compute_next();
+    let mut dfa_state = DfaState::new(1, list, &nfa);
+    dfa_state.compute_next(&nfa);
     
     // Should have three transitions
     assert_eq!(dfa_state.next.len(), 3);
@@ -628,22 +661,23 @@ fn test_compute_next_for_multiple_character_transitions() {
     // Each transition should lead to a match state
     for c in ['a', 'b', 'c'] {
         let next_list = dfa_state.next.get(&InputCondition::Char(c)).unwrap();
-        assert!(next_list.is_matched());
+        assert!(next_list.is_matched(&nfa));
     }
 }
 
 #[test]
 fn test_compute_next_for_character_class_transitions() {
     // Create a state with a character class [abc]
-    let class_state = create_class_state(&['a', 'b', 'c']);
-    let match_state = State::match_(0);
-    class_state.borrow_mut().into_basic().unwrap().out.replace(match_state);
+    let mut nfa = Nfa::new();
+    let class_state = create_class_state(&mut nfa, &['a', 'b', 'c']);
+    let match_state = nfa.push(State::match_(0));
+    connect(&mut nfa, class_state, match_state);
     
     let mut list = StateList::new();
-    list.add_state(&class_state);
+    list.add_state(&nfa, class_state);
     
-    let mut dfa_state = DfaState::new(1, list);
-    dfa_state.compute_next();
+    let mut dfa_state = DfaState::new(1, list, &nfa);
+    dfa_state.compute_next(&nfa);
     
     // Should have three transitions (one for each character in the class)
     assert_eq!(dfa_state.next.len(), 3);
@@ -654,21 +688,20 @@ fn test_compute_next_for_character_class_transitions() {
     // Each transition should lead to a match state
     for c in ['a', 'b', 'c'] {
         let next_list = dfa_state.next.get(&InputCondition::Char(c)).unwrap();
-        assert!(next_list.is_matched());
+        assert!(next_list.is_matched(&nfa));
     }
 }
 
 #[test]
 fn test_compute_next_for_line_start_transition() {
-    let state = State::start_of_line();
-    let match_state = State::match_(0);
-    state.borrow().start_of_line_out().unwrap().replace(match_state);
+    let mut nfa = Nfa::new();
+    let state = create_state_to_match(&mut nfa, "startofline", 0);
     
     let mut list = StateList::new();
-    list.add_state(&state);
+    list.add_state(&nfa, state);
     
-    let mut dfa_state = DfaState::new(1, list);
-    dfa_state.compute_next();
+    let mut dfa_state = DfaState::new(1, list, &nfa);
+    dfa_state.compute_next(&nfa);
     
     // Should have a start-of-line transition
     assert_eq!(dfa_state.next.len(), 1);
@@ -676,20 +709,19 @@ fn test_compute_next_for_line_start_transition() {
     
     // The transition should lead to a match state
     let next_list = dfa_state.next.get(&InputCondition::StartOfLine).unwrap();
-    assert!(next_list.is_matched());
+    assert!(next_list.is_matched(&nfa));
 }
 
 #[test]
 fn test_compute_next_for_line_end_transition() {
-    let state = State::end_of_line();
-    let match_state = State::match_(0);
-    state.borrow().end_of_line_out().unwrap().replace(match_state);
+    let mut nfa = Nfa::new();
+    let state = create_state_to_match(&mut nfa, "endofline", 0);
     
     let mut list = StateList::new();
-    list.add_state(&state);
+    list.add_state(&nfa, state);
     
-    let mut dfa_state = DfaState::new(1, list);
-    dfa_state.compute_next();
+    let mut dfa_state = DfaState::new(1, list, &nfa);
+    dfa_state.compute_next(&nfa);
     
     // Should have an end-of-line transition
     assert_eq!(dfa_state.next.len(), 1);
@@ -697,19 +729,20 @@ fn test_compute_next_for_line_end_transition() {
     
     // The transition should lead to a match state
     let next_list = dfa_state.next.get(&InputCondition::EndOfLine).unwrap();
-    assert!(next_list.is_matched());
+    assert!(next_list.is_matched(&nfa));
 }
 
 #[test]
 fn test_compute_next_with_no_valid_transitions() {
     // Create a state with no outgoing transition
-    let state = State::none();
+    let mut nfa = Nfa::new();
+    let state = create_basic_state(&mut nfa, 'a');
     
     let mut list = StateList::new();
-    list.add_state(&state);
+    list.add_state(&nfa, state);
     
-    let mut dfa_state = DfaState::new(1, list);
-    dfa_state.compute_next();
+    let mut dfa_state = DfaState::new(1, list, &nfa);
+    dfa_state.compute_next(&nfa);
     
     // Should have no transitions
     assert_eq!(dfa_state.next.len(), 0);
@@ -718,14 +751,15 @@ fn test_compute_next_with_no_valid_transitions() {
 #[test]
 fn test_compute_next_for_state_with_self_loop() {
     // Create a state that transitions to itself: a -> a
-    let state = create_basic_state('a');
-    state.borrow_mut().into_basic().unwrap().out.replace(state.clone());
+    let mut nfa = Nfa::new();
+    let state = create_basic_state(&mut nfa, 'a');
+    connect(&mut nfa, state, state);
     
     let mut list = StateList::new();
-    list.add_state(&state);
+    list.add_state(&nfa, state);
     
-    let mut dfa_state = DfaState::new(1, list);
-    dfa_state.compute_next();
+    let mut dfa_state = DfaState::new(1, list, &nfa);
+    dfa_state.compute_next(&nfa);
     
     // Should have one transition
     assert_eq!(dfa_state.next.len(), 1);
@@ -733,34 +767,27 @@ fn test_compute_next_for_state_with_self_loop() {
     
     // The transition should contain the original state (self-loop)
     let next_list = dfa_state.next.get(&InputCondition::Char('a')).unwrap();
-    let mut found_self = false;
-    
-    for next_state in next_list {
-        if Rc::ptr_eq(next_state, &state) {
-            found_self = true;
-            break;
-        }
-    }
     
-    assert!(found_self, "Self-loop not found in next states");
+    assert!(next_list.contains(state), "Self-loop not found in next states");
 }
 
 #[test]
 fn test_compute_next_for_multiple_transitions_to_same_target() {
     // Create multiple states that all go to the same target
-    let target = State::match_(0);
+    let mut nfa = Nfa::new();
+    let target = nfa.push(State::match_(0));
     
-    let state_a = create_basic_state('a');
-    let state_b = create_basic_state('b');
-    state_a.borrow_mut().into_basic().unwrap().out.replace(target.clone());
-    state_b.borrow_mut().into_basic().unwrap().out.replace(target.clone());
+    let state_a = create_basic_state(&mut nfa, 'a');
+    let state_b = create_basic_state(&mut nfa, 'b');
+    connect(&mut nfa, state_a, target);
+    connect(&mut nfa, state_b, target);
     
     let mut list = StateList::new();
-    list.add_state(&state_a);
-    list.add_state(&state_b);
+    list.add_state(&nfa, state_a);
+    list.add_state(&nfa, state_b);
     
-    let mut dfa_state = DfaState::new(1, list);
-    dfa_state.compute_next();
+    let mut dfa_state = DfaState::new(1, list, &nfa);
+    dfa_state.compute_next(&nfa);
     
     // Should have two transitions
     assert_eq!(dfa_state.next.len(), 2);
@@ -770,35 +797,37 @@ fn test_compute_next_for_multiple_transitions_to_same_target() {
     // Both transitions should lead to the same match state
     for c in ['a', 'b'] {
         let next_list = dfa_state.next.get(&InputCondition::Char(c)).unwrap();
-        assert!(next_list.is_matched());
+        assert!(next_list.is_matched(&nfa));
+        assert!(next_list.contains(target));
     }
 }
 
 #[test]
 fn test_matchs_correctly_propagated_during_compute_next() {
     // Create a chain: a -> Match(1)
-    let state_a = create_basic_state('a');
-    let match_state = State::match_(1);
-    state_a.borrow_mut().into_basic().unwrap().out.replace(match_state);
+    let mut nfa = Nfa::new();
+    let state_a = create_basic_state(&mut nfa, 'a');
+    let match_state = nfa.push(State::match_(1));
+    connect(&mut nfa, state_a, match_state);
     
     let mut list = StateList::new();
-    list.add_state(&state_a);
+    list.add_state(&nfa, state_a);
     
-    let mut dfa_state = DfaState::new(1, list);
+    let mut dfa_state = DfaState::new(1, list, &nfa);
     
     // Initially, no match states in the DFA state
     assert_eq!(dfa_state.matchs.len(), 0);
     
     // Compute next states
-    dfa_state.compute_next();
+    dfa_state.compute_next(&nfa);
     
     // Now the matchs should include the match state
     let next_list = dfa_state.next.get(&InputCondition::Char('a')).unwrap();
-    assert!(next_list.is_matched());
+    assert!(next_list.is_matched(&nfa));
     
     // The match ID should match what we defined
     for state in next_list {
-        if let Some(id) = state.borrow().match_id() {
+        if let Some(id) = nfa[state].match_id() {
             assert_eq!(id, 1);
         }
     }
@@ -807,24 +836,20 @@ fn test_matchs_correctly_propagated_during_compute_next() {
 #[test]
 fn test_compute_next_with_nested_state_structures() {
     // Create a more complex structure: a -> (b -> Match(1) | c -> Match(2))
-    let state_a = create_basic_state('a');
-    let state_b = create_basic_state('b');
-    let state_c = create_basic_state('c');
-    let match1 = State::match_(1);
-    let match2 = State::match_(2);
-    
-    state_b.borrow_mut().into_basic().unwrap().out.replace(match1);
-    state_c.borrow_mut().into_basic().unwrap().out.replace(match2);
+    let mut nfa = Nfa::new();
+    let state_a = create_basic_state(&mut nfa, 'a');
+    let state_b = create_basic_to_match(&mut nfa, 'b', 1);
+    let state_c = create_basic_to_match(&mut nfa, 'c', 2);
     
     // Create a split state that branches to b and c
-    let split = State::split(state_b, state_c);
-    state_a.borrow_mut().into_basic().unwrap().out.replace(split);
+    let split = nfa.push(State::split(Some(state_b), Some(state_c)));
+    connect(&mut nfa, state_a, split);
     
     let mut list = StateList::new();
-    list.add_state(&state_a);
+    list.add_state(&nfa, state_a);
     
-    let mut dfa_state = DfaState::new(1, list);
-    dfa_state.compute_next();
+    let mut dfa_state = DfaState::new(1, list, &nfa);
+    dfa_state.compute_next(&nfa);
     
     // Should have one transition for 'a'
     assert_eq!(dfa_state.next.len(), 1);
@@ -834,8 +859,8 @@ fn test_compute_next_with_nested_state_structures() {
     let a_list = dfa_state.next.get(&InputCondition::Char('a')).unwrap();
     
     // We need to create a new DFA state from the 'a' transition to test its next transitions
-    let mut next_dfa = DfaState::new(2, a_list.clone());
-    next_dfa.compute_next();
+    let mut next_dfa = DfaState::new(2, a_list.clone(), &nfa);
+    next_dfa.compute_next(&nfa);
     
     // The next state should have two transitions
     assert_eq!(next_dfa.next.len(), 2);
@@ -845,7 +870,7 @@ fn test_compute_next_with_nested_state_structures() {
     // Both transitions should lead to match states
     for c in ['b', 'c'] {
         let next_list = next_dfa.next.get(&InputCondition::Char(c)).unwrap();
-        assert!(next_list.is_matched());
+        assert!(next_list.is_matched(&nfa));
     }
 }
 
@@ -855,15 +880,14 @@ fn test_compute_next_with_nested_state_structures() {
 
 #[test]
 fn test_find_next_for_basic_state_with_single_character() {
-    let match_state = State::match_(0);
-    let basic = create_basic_state('a');
-    basic.borrow_mut().into_basic().unwrap().out.replace(match_state);
+    let mut nfa = Nfa::new();
+    let basic = create_basic_to_match(&mut nfa, 'a', 0);
     
     // Create current states (empty for this test)
     let current_states = StateList::new();
     
     // Call find_next directly
-    let (next_map, match_list) = DfaState::find_next(&basic, &current_states);
+    let (next_map, match_list) = DfaState::find_next(&nfa, basic, &current_states);
     
     // Should have one transition for 'a'
     assert_eq!(next_map.len(), 1);
@@ -871,7 +895,7 @@ fn test_find_next_for_basic_state_with_single_character() {
     
     // The transition should lead to a match state
     let next_list = next_map.get(&InputCondition::Char('a')).unwrap();
-    assert!(next_list.is_matched());
+    assert!(next_list.is_matched(&nfa));
     
     // We didn't have a match state in the input, so match_list should be empty
     assert_eq!(match_list.len(), 0);
@@ -879,15 +903,16 @@ fn test_find_next_for_basic_state_with_single_character() {
 
 #[test]
 fn test_find_next_for_basic_state_with_character_class() {
-    let match_state = State::match_(0);
-    let class_state = create_class_state(&['a', 'b', 'c']);
-    class_state.borrow_mut().into_basic().unwrap().out.replace(match_state);
+    let mut nfa = Nfa::new();
+    let match_state = nfa.push(State::match_(0));
+    let class_state = create_class_state(&mut nfa, &['a', 'b', 'c']);
+    connect(&mut nfa, class_state, match_state);
     
     // Create current states (empty for this test)
     let current_states = StateList::new();
     
     // Call find_next directly
-    let (next_map, _match_list) = DfaState::find_next(&class_state, &current_states);
+    let (next_map, _match_list) = DfaState::find_next(&nfa, class_state, &current_states);
     
     // Should have three transitions (one for each character in the class)
     assert_eq!(next_map.len(), 3);
@@ -898,45 +923,45 @@ fn test_find_next_for_basic_state_with_character_class() {
     // Each transition should lead to the same match state
     for c in ['a', 'b', 'c'] {
         let next_list = next_map.get(&InputCondition::Char(c)).unwrap();
-        assert!(next_list.is_matched());
+        assert!(next_list.contains(match_state));
     }
 }
 
 #[test]
 fn test_find_next_for_split_state() {
-    let match1 = State::match_(1);
-    let match2 = State::match_(2);
+    let mut nfa = Nfa::new();
+    let match1 = nfa.push(State::match_(1));
+    let match2 = nfa.push(State::match_(2));
     
     // Create a split state that branches to two match states
-    let split = State::split(match1, match2);
+    let split = nfa.push(State::split(Some(match1), Some(match2)));
     
     // Create current states (empty for this test)
     let current_states = StateList::new();
     
     // Call find_next directly
-    let (next_map, match_list) = DfaState::find_next(&split, &current_states);
+    let (next_map, match_list) = DfaState::find_next(&nfa, split, &current_states);
     
     // A split state with two match states should have no transitions
     assert_eq!(next_map.len(), 0);
     
     // But it should collect both match states
     assert_eq!(match_list.len(), 2);
-    assert!(match_list.is_matched());
+    assert!(match_list.is_matched(&nfa));
 }
 
 #[test]
 fn test_find_next_for_start_of_line_state() {
-    let match_state = State::match_(0);
-    let start_line = State::start_of_line();
-    start_line.borrow().start_of_line_out().unwrap().replace(match_state);
+    let mut nfa = Nfa::new();
+    let start_line = create_state_to_match(&mut nfa, "startofline", 0);
     
     // Create some current states
     let mut current_states = StateList::new();
-    let basic = create_basic_state('a');
-    current_states.add_state(&basic);
+    let basic = create_basic_state(&mut nfa, 'a');
+    current_states.add_state(&nfa, basic);
     
     // Call find_next directly
-    let (next_map, _match_list) = DfaState::find_next(&start_line, &current_states);
+    let (next_map, _match_list) = DfaState::find_next(&nfa, start_line, &current_states);
     
     // Should have one transition for StartOfLine
     assert_eq!(next_map.len(), 1);
@@ -945,22 +970,22 @@ fn test_find_next_for_start_of_line_state() {
     // The transition should lead to a match state and include the current states
     let next_list = next_map.get(&InputCondition::StartOfLine).unwrap();
     assert!(next_list.len() >= 2); // Match state plus current states
-    assert!(next_list.is_matched());
+    assert!(next_list.is_matched(&nfa));
+    assert!(next_list.contains(basic));
 }
 
 #[test]
 fn test_find_next_for_end_of_line_state() {
-    let match_state = State::match_(0);
-    let end_line = State::end_of_line();
-    end_line.borrow().end_of_line_out().unwrap().replace(match_state);
+    let mut nfa = Nfa::new();
+    let end_line = create_state_to_match(&mut nfa, "endofline", 0);
     
     // Create some current states
     let mut current_states = StateList::new();
-    let basic = create_basic_state('a');
-    current_states.add_state(&basic);
+    let basic = create_basic_state(&mut nfa, 'a');
+    current_states.add_state(&nfa, basic);
     
     // Call find_next directly
-    let (next_map, _match_list) = DfaState::find_next(&end_line, &current_states);
+    let (next_map, _match_list) = DfaState::find_next(&nfa, end_line, &current_states);
     
     // Should have one transition for EndOfLine
     assert_eq!(next_map.len(), 1);
@@ -969,72 +994,91 @@ fn test_find_next_for_end_of_line_state() {
     // The transition should lead to a match state and include the current states
     let next_list = next_map.get(&InputCondition::EndOfLine).unwrap();
     assert!(next_list.len() >= 2); // Match state plus current states
-    assert!(next_list.is_matched());
+    assert!(next_list.is_matched(&nfa));
+    assert!(next_list.contains(basic));
 }
 
 #[test]
 fn test_find_next_for_match_state() {
-    let match_state = State::match_(0);
+    let mut nfa = Nfa::new();
+    let match_state = nfa.push(State::match_(0));
     
     // Create current states (empty for this test)
     let current_states = StateList::new();
     
     // Call find_next directly
-    let (next_map, match_list) = DfaState::find_next(&match_state, &current_states);
+    let (next_map, match_list) = DfaState::find_next(&nfa, match_state, &current_states);
     
     // A match state should have no transitions
     assert_eq!(next_map.len(), 0);
     
     // But it should be collected in the match_list
     assert_eq!(match_list.len(), 1);
-    assert!(match_list.is_matched());
+    assert!(match_list.is_matched(&nfa));
 }
 
 #[test]
 fn test_find_next_for_unhandled_state_types() {
-    let none_state = State::none();
-    let nomatch_state = State::no_match();
+    let mut nfa = Nfa::new();
+    let unconnected_state = create_basic_state(&mut nfa, 'a');
+    let nomatch_state = nfa.push(State::no_match());
     
     // Create current states (empty for this test)
     let current_states = StateList::new();
     
-    // Call find_next on None state
-    let (next_map1, match_list1) = DfaState::find_next(&none_state, &current_states);
+    // Call find_next on the unconnected state
+    let (next_map1, match_list1) = DfaState::find_next(&nfa, unconnected_state, &current_states);
     
-    // A None state should have no transitions and no matches
+    // An unconnected state should have no transitions and no matches
     assert_eq!(next_map1.len(), 0);
     assert_eq!(match_list1.len(), 0);
     
     // Call find_next on NoMatch state
-    let (next_map2, match_list2) = DfaState::find_next(&nomatch_state, &current_states);
+    let (next_map2, match_list2) = DfaState::find_next(&nfa, nomatch_state, &current_states);
     
     // A NoMatch state should have no transitions and no matches
     assert_eq!(next_map2.len(), 0);
     assert_eq!(match_list2.len(), 0);
 }
 
+#[test]
+fn test_find_next_for_basic_state_to_no_match() {
+    // a{0}: the 'a' state leads to a NoMatch state
+    let mut nfa = Nfa::new();
+    let basic = create_basic_state(&mut nfa, 'a');
+    let nomatch_state = nfa.push(State::no_match());
+    connect(&mut nfa, basic, nomatch_state);
+
+    let (next_map, match_list) = DfaState::find_next(&nfa, basic, &StateList::new());
+
+    // The transition is never taken
+    assert_eq!(next_map.len(), 0);
+    assert_eq!(match_list.len(), 0);
+}
+
 #[test]
 fn test_match_states_correctly_collected() {
-    let match1 = State::match_(1);
-    let match2 = State::match_(2);
-    let basic = create_basic_state('a');
+    let mut nfa = Nfa::new();
+    let match1 = nfa.push(State::match_(1));
+    let match2 = nfa.push(State::match_(2));
+    let basic = create_basic_state(&mut nfa, 'a');
     
     // Create split state pointing to both match states
-    let split = State::split(match1.clone(), match2.clone());
+    let split = nfa.push(State::split(Some(match1), Some(match2)));
     
     // Create current states including another match state
     let mut current_states = StateList::new();
-    current_states.add_state(&match1); // Add match1 directly to current states
+    current_states.add_state(&nfa, match1); // Add match1 directly to current states
     
     // Call find_next on the split state
-    let (_, match_list) = DfaState::find_next(&split, &current_states);
+    let (_, match_list) = DfaState::find_next(&nfa, split, &current_states);
     
     // Should collect both match states from the split
     assert_eq!(match_list.len(), 2);
-    assert!(match_list.is_matched());
+    assert!(match_list.is_matched(&nfa));
     
     // Call find_next on the basic state
-    let (_, match_list2) = DfaState::find_next(&basic, &current_states);
+    let (_, match_list2) = DfaState::find_next(&nfa, basic, &current_states);
     
     // Should have no matches from the basic state
     assert_eq!(match_list2.len(), 0);
@@ -1042,14 +1086,17 @@ fn test_match_states_correctly_collected() {
 
 #[test]
 fn test_character_class_handling() {
+    let mut nfa = Nfa::new();
+    let match_state = nfa.push(State::match_(0));
+
     // Test with a regular character class
     let mut class1 = CharacterClass::new();
     for c in 'a'..='z' {
         class1.add_char(c);
     }
     
-    let class_state1 = State::basic(RegexType::CharacterClass(class1));
-    class_state1.borrow_mut().into_basic().unwrap().out.replace(State::match_(0));
+    let class_state1 = nfa.push(State::basic(RegexType::CharacterClass(class1)));
+    connect(&mut nfa, class_state1, match_state);
     
     // Test with a negated character class
     let mut class2 = CharacterClass::new();
@@ -1058,19 +1105,19 @@ fn test_character_class_handling() {
     class2.add_char('2');
     let class2 = class2.negated();
     
-    let class_state2 = State::basic(RegexType::CharacterClass(class2));
-    class_state2.borrow_mut().into_basic().unwrap().out.replace(State::match_(0));
+    let class_state2 = nfa.push(State::basic(RegexType::CharacterClass(class2)));
+    connect(&mut nfa, class_state2, match_state);
     
     let current_states = StateList::new();
     
     // Call find_next on the regular class state
-    let (next_map1, _) = DfaState::find_next(&class_state1, &current_states);
+    let (next_map1, _) = DfaState::find_next(&nfa, class_state1, &current_states);
     
     // Should have 26 transitions (a-z)
     assert_eq!(next_map1.len(), 26);
     
     // Call find_next on the negated class state
-    let (next_map2, _) = DfaState::find_next(&class_state2, &current_states);
+    let (next_map2, _) = DfaState::find_next(&nfa, class_state2, &current_states);
     
     // Should have many transitions (all ASCII chars except 0, 1, 2)
     assert!(next_map2.len() > 100);
@@ -1086,44 +1133,33 @@ fn test_character_class_handling() {
 #[test]
 fn test_interaction_with_start_end_line_and_current_states() {
     // Create states
-    let start_line = State::start_of_line();
-    let end_line = State::end_of_line();
-    let match_state = State::match_(0);
+    let mut nfa = Nfa::new();
+    let start_line = nfa.push(State::start_of_line());
+    let end_line = nfa.push(State::end_of_line());
+    let match_state = nfa.push(State::match_(0));
     
-    start_line.borrow().start_of_line_out().unwrap().replace(match_state.clone());
-    end_line.borrow().end_of_line_out().unwrap().replace(match_state.clone());
+    connect(&mut nfa, start_line, match_state);
+    connect(&mut nfa, end_line, match_state);
     
     // Create current states with both start and end of line
     let mut current_states = StateList::new();
-    current_states.add_state(&start_line);
-    current_states.add_state(&end_line);
+    current_states.add_state(&nfa, start_line);
+    current_states.add_state(&nfa, end_line);
     
     // Call find_next on the start_line state
-    let (next_map1, _) = DfaState::find_next(&start_line, &current_states);
+    let (next_map1, _) = DfaState::find_next(&nfa, start_line, &current_states);
     
     // Start of line should only include non-end-of-line states from current_states
     let start_list = next_map1.get(&InputCondition::StartOfLine).unwrap();
-    let mut includes_end = false;
-    for state in start_list {
-        if State::is_end_of_line_ptr(state) {
-            includes_end = true;
-            break;
-        }
-    }
+    let includes_end = start_list.iter().any(|state| nfa[state].is_end_of_line());
     assert!(!includes_end, "StartOfLine should not include EndOfLine states");
     
     // Call find_next on the end_line state
-    let (next_map2, _) = DfaState::find_next(&end_line, &current_states);
+    let (next_map2, _) = DfaState::find_next(&nfa, end_line, &current_states);
     
     // End of line should only include non-start-of-line states from current_states
     let end_list = next_map2.get(&InputCondition::EndOfLine).unwrap();
-    let mut includes_start = false;
-    for state in end_list {
-        if State::is_start_of_line_ptr(state) {
-            includes_start = true;
-            break;
-        }
-    }
+    let includes_start = end_list.iter().any(|state| nfa[state].is_start_of_line());
     assert!(!includes_start, "EndOfLine should not include StartOfLine states");
 }
 
@@ -1134,12 +1170,13 @@ fn test_interaction_with_start_end_line_and_current_states() {
 #[test]
 fn test_iterative_create_with_simple_state_list() {
     // Create a simple state: a -> match
-    let state = create_basic_to_match('a', 0);
+    let mut nfa = Nfa::new();
+    let state = create_basic_to_match(&mut nfa, 'a', 0);
     
     let mut list = StateList::new();
-    list.add_state(&state);
+    list.add_state(&nfa, state);
     
-    let (result, mem) = DfaState::iterative_create(list.clone());
+    let (result, mem) = DfaState::iterative_create(&nfa, list.clone());
     
     // Check that the result is a valid DfaState
     assert!(result.borrow().next.contains_key(&InputCondition::Char('a')));
@@ -1151,15 +1188,55 @@ fn test_iterative_create_with_simple_state_list() {
     assert!(mem.contains_key(&list));
 }
 
+#[test]
+fn test_iterative_create_all_shares_memory() {
+    let mut nfa = Nfa::new();
+    let state_a = create_basic_to_match(&mut nfa, 'a', 1);
+    let state_b = create_basic_to_match(&mut nfa, 'b', 2);
+
+    let list_a = create_state_list(&nfa, vec![state_a]);
+    let list_ab = create_state_list(&nfa, vec![state_a, state_b]);
+
+    let (starts, mem) = DfaState::iterative_create_all(&nfa, vec![list_a.clone(), list_ab.clone(), list_a.clone()]);
+
+    assert_eq!(starts.len(), 3);
+
+    // The start states have the lowest ids, identical lists share their state
+    assert_eq!(starts[0].borrow().id, 0);
+    assert_eq!(starts[1].borrow().id, 1);
+    assert!(Rc::ptr_eq(&starts[0], &starts[2]));
+
+    assert!(starts[1].borrow().next.contains_key(&InputCondition::Char('b')));
+    assert!(!starts[0].borrow().next.contains_key(&InputCondition::Char('b')));
+
+    // The state after 'a' is shared by both start states
+    assert!(Rc::ptr_eq(
+        &mem[&starts[0].borrow().next[&InputCondition::Char('a')]],
+        &mem[&starts[1].borrow().next[&InputCondition::Char('a')]]
+    ));
+}
+
+#[test]
+fn test_dfa_with_starts() {
+    let nfa_a = post2nfa(into_postfix("a+"), 1).unwrap();
+    let nfa_b = post2nfa(into_postfix("b+"), 2).unwrap();
+
+    let dfa = Dfa::with_starts(&[&nfa_a, &nfa_b], vec![vec![0], vec![0, 1], vec![]]);
+
+    assert_eq!(dfa.starts.len(), 3);
+    assert!(Rc::ptr_eq(&dfa.start, &dfa.starts[0]));
+    assert!(dfa.starts[2].borrow().next.is_empty());
+}
+
 #[test]
 fn test_iterative_create_with_complex_state_graph() {
     // Create a more complex pattern: a(b|c)*d
     let nfa = post2nfa(into_postfix("a(b|c)*d"), 0).unwrap();
     
     let mut list = StateList::new();
-    list.add_state(&nfa);
+    list.add_state(&nfa, nfa.start);
     
-    let (result, mem) = DfaState::iterative_create(list);
+    let (result, mem) = DfaState::iterative_create(&nfa, list);
     
     // Check that the result is a valid DfaState
     assert!(result.borrow().next.contains_key(&InputCondition::Char('a')));
@@ -1171,14 +1248,15 @@ fn test_iterative_create_with_complex_state_graph() {
 #[test]
 fn test_iterative_create_with_cyclic_state_references() {
     // Create a cycle: a -> b -> c -> a
-    let cycle_states = create_cycle();
+    let mut nfa = Nfa::new();
+    let cycle_states = create_cycle(&mut nfa);
     
     let mut list = StateList::new();
     for state in &cycle_states {
-        list.add_state(state);
+        list.add_state(&nfa, *state);
     }
     
-    let (result, mem) = DfaState::iterative_create(list);
+    let (result, mem) = DfaState::iterative_create(&nfa, list);
     
     // Memory should contain at least 3 entries (a, b, c)
     assert!(mem.len() >= 3);
@@ -1186,12 +1264,12 @@ fn test_iterative_create_with_cyclic_state_references() {
     // Check that cyclic references are handled correctly
 	let borrow = result.borrow();
     let next_a = borrow.next.get(&InputCondition::Char('a')).unwrap();
-    let next_dfa_a = DfaState::new(0, next_a.clone());
+    let next_dfa_a = DfaState::new(0, next_a.clone(), &nfa);
     assert!(next_dfa_a.next.is_empty());
     
     // Create transitions for the next state
-    let mut next_dfa_a_mut = DfaState::new(0, next_a.clone());
-    next_dfa_a_mut.compute_next();
+    let mut next_dfa_a_mut = DfaState::new(0, next_a.clone(), &nfa);
+    next_dfa_a_mut.compute_next(&nfa);
     
     // Should have a transition for 'b'
     assert!(next_dfa_a_mut.next.contains_key(&InputCondition::Char('b')));
@@ -1200,19 +1278,20 @@ fn test_iterative_create_with_cyclic_state_references() {
 #[test]
 fn test_iterative_create_with_large_state_list() {
     // Create a large number of states (100)
+    let mut nfa = Nfa::new();
     let mut states = Vec::with_capacity(100);
     for i in 0..100 {
         let c = (i % 26 + 'a' as usize) as u8 as char;
-        states.push(create_basic_to_match(c, i));
+        states.push(create_basic_to_match(&mut nfa, c, i));
     }
     
     let mut list = StateList::new();
     for state in &states {
-        list.add_state(state);
+        list.add_state(&nfa, *state);
     }
     
     let start_time = Instant::now();
-    let (_result, mem) = DfaState::iterative_create(list);
+    let (_result, mem) = DfaState::iterative_create(&nfa, list);
     let duration = start_time.elapsed();
     
     // Memory should contain many entries
@@ -1226,22 +1305,21 @@ fn test_iterative_create_with_large_state_list() {
 #[allow(deprecated)]
 fn test_compare_recursive_and_iterative_create() {
     // Create a simple pattern: a -> b -> match
-    let state_a = create_basic_state('a');
-    let state_b = create_basic_state('b');
-    let match_state = State::match_(0);
+    let mut nfa = Nfa::new();
+    let state_a = create_basic_state(&mut nfa, 'a');
+    let state_b = create_basic_to_match(&mut nfa, 'b', 0);
     
-    state_b.borrow_mut().into_basic().unwrap().out.replace(match_state);
-    state_a.borrow_mut().into_basic().unwrap().out.replace(state_b);
+    connect(&mut nfa, state_a, state_b);
     
     let mut list = StateList::new();
-    list.add_state(&state_a);
+    list.add_state(&nfa, state_a);
     
     // Create with recursive method
     let mut memory1 = HashMap::new();
-    let result1 = DfaState::recursive_create(list.clone(), &mut memory1);
+    let result1 = DfaState::recursive_create(&nfa, list.clone(), &mut memory1);
     
     // Create with iterative method
-    let (result2, memory2) = DfaState::iterative_create(list);
+    let (result2, memory2) = DfaState::iterative_create(&nfa, list);
     
     // Both should produce same number of states in memory
     assert_eq!(memory1.len(), memory2.len());
@@ -1260,16 +1338,17 @@ fn test_compare_recursive_and_iterative_create() {
 #[test]
 fn test_memory_caching_during_state_creation() {
     // Create a simple state: a -> match
-    let state = create_basic_to_match('a', 0);
+    let mut nfa = Nfa::new();
+    let state = create_basic_to_match(&mut nfa, 'a', 0);
 
     let mut list = StateList::new();
-    list.add_state(&state);
+    list.add_state(&nfa, state);
 
     // First creation
-    let (result1, memory1) = DfaState::iterative_create(list.clone());
+    let (result1, memory1) = DfaState::iterative_create(&nfa, list.clone());
 
     // Second creation with same list
-    let (result2, memory2) = DfaState::iterative_create(list.clone());
+    let (result2, memory2) = DfaState::iterative_create(&nfa, list.clone());
 
     // Both results should be the same DfaState (cached)
     assert_eq!(result1.borrow().states, result2.borrow().states);
@@ -1287,9 +1366,9 @@ fn test_work_queue_processing_handles_all_transitions() {
     let nfa = post2nfa(into_postfix("a(b|c|d)*e"), 0).unwrap();
     
     let mut list = StateList::new();
-    list.add_state(&nfa);
+    list.add_state(&nfa, nfa.start);
     
-    let (result, mem) = DfaState::iterative_create(list);
+    let (result, mem) = DfaState::iterative_create(&nfa, list);
     
     // First transition should be 'a'
     assert!(result.borrow().next.contains_key(&InputCondition::Char('a')));
@@ -1318,12 +1397,13 @@ fn test_work_queue_processing_handles_all_transitions() {
 #[test]
 fn test_is_match_with_match_state() {
     // Create a state list with a match state
-    let match_state = State::match_(0);
+    let mut nfa = Nfa::new();
+    let match_state = nfa.push(State::match_(0));
     
     let mut list = StateList::new();
-    list.add_state(&match_state);
+    list.add_state(&nfa, match_state);
     
-    let dfa_state = DfaState::new(1, list);
+    let dfa_state = DfaState::new(1, list, &nfa);
     
     // Should be a match state
     assert!(dfa_state.is_match());
@@ -1332,12 +1412,13 @@ fn test_is_match_with_match_state() {
 #[test]
 fn test_is_match_with_no_match_state() {
     // Create a state list with no match state
-    let basic = create_basic_state('a');
+    let mut nfa = Nfa::new();
+    let basic = create_basic_state(&mut nfa, 'a');
     
     let mut list = StateList::new();
-    list.add_state(&basic);
+    list.add_state(&nfa, basic);
     
-    let dfa_state = DfaState::new(1, list);
+    let dfa_state = DfaState::new(1, list, &nfa);
     
     // Should not be a match state
     assert!(!dfa_state.is_match());
@@ -1346,47 +1427,50 @@ fn test_is_match_with_no_match_state() {
 #[test]
 fn test_match_id_returns_correct_id_for_single_match() {
     // Create a state list with a single match state
-    let match_state = State::match_(42);
+    let mut nfa = Nfa::new();
+    let match_state = nfa.push(State::match_(42));
     
     let mut list = StateList::new();
-    list.add_state(&match_state);
+    list.add_state(&nfa, match_state);
     
-    let dfa_state = DfaState::new(1, list);
+    let dfa_state = DfaState::new(1, list, &nfa);
     
     // Should return the correct match ID
-    assert_eq!(dfa_state.match_id(), Some(42));
+    assert_eq!(dfa_state.match_id(&nfa), Some(42));
 }
 
 #[test]
 fn test_match_id_returns_lowest_id_for_multiple_matches() {
     // Create a state list with multiple match states
-    let match1 = State::match_(10);
-    let match2 = State::match_(5);
-    let match3 = State::match_(15);
+    let mut nfa = Nfa::new();
+    let match1 = nfa.push(State::match_(10));
+    let match2 = nfa.push(State::match_(5));
+    let match3 = nfa.push(State::match_(15));
     
     let mut list = StateList::new();
-    list.add_state(&match1);
-    list.add_state(&match2);
-    list.add_state(&match3);
+    list.add_state(&nfa, match1);
+    list.add_state(&nfa, match2);
+    list.add_state(&nfa, match3);
 
-    let dfa_state = DfaState::new(1, list);
+    let dfa_state = DfaState::new(1, list, &nfa);
     
     // Should return the lowest match ID
-    assert_eq!(dfa_state.match_id(), Some(5));
+    assert_eq!(dfa_state.match_id(&nfa), Some(5));
 }
 
 #[test]
 fn test_match_id_returns_none_for_no_matches() {
     // Create a state list with no match states
-    let basic = create_basic_state('a');
+    let mut nfa = Nfa::new();
+    let basic = create_basic_state(&mut nfa, 'a');
     
     let mut list = StateList::new();
-    list.add_state(&basic);
+    list.add_state(&nfa, basic);
     
-    let dfa_state = DfaState::new(1, list);
+    let dfa_state = DfaState::new(1, list, &nfa);
     
     // Should return None
-    assert_eq!(dfa_state.match_id(), None);
+    assert_eq!(dfa_state.match_id(&nfa), None);
 }
 
 #[test]
@@ -1394,11 +1478,8 @@ fn test_complex_match_patterns() {
     // Create a complex pattern with nested alternatives
     let nfa = post2nfa(into_postfix("(a|b)(c|d)"), 7).unwrap();
     
-    let mut list = StateList::new();
-    list.add_state(&nfa);
-    
     // Create a DFA
-    let dfa = Dfa::new(vec![nfa]);
+    let dfa = Dfa::new(&[&nfa]);
     
     // Check that we can follow transitions to match states
     let start = dfa.start.borrow();
@@ -1421,7 +1502,7 @@ fn test_complex_match_patterns() {
     
     // This state should be a match state with the correct ID
     assert!(c_state.borrow().is_match());
-    assert_eq!(c_state.borrow().match_id(), Some(7));
+    assert_eq!(c_state.borrow().match_id(&dfa.nfa), Some(7));
 }
 
 // ==============================
@@ -1430,48 +1511,51 @@ fn test_complex_match_patterns() {
 
 #[test]
 fn test_adding_states_to_state_list() {
+    let mut nfa = Nfa::new();
+
     // Create a fresh state list
     let mut list = StateList::new();
     assert_eq!(list.len(), 0);
     
     // Add a basic state
-    let state_a = create_basic_state('a');
-    list.add_state(&state_a);
+    let state_a = create_basic_state(&mut nfa, 'a');
+    list.add_state(&nfa, state_a);
     assert_eq!(list.len(), 1);
     
     // Add a match state
-    let state_match = State::match_(1);
-    list.add_state(&state_match);
+    let state_match = nfa.push(State::match_(1));
+    list.add_state(&nfa, state_match);
     assert_eq!(list.len(), 2);
     
     // Add a split state (should be flattened)
-    let split = State::split(
-        create_basic_state('b'),
-        create_basic_state('c')
-    );
-    list.add_state(&split);
+    let state_b = create_basic_state(&mut nfa, 'b');
+    let state_c = create_basic_state(&mut nfa, 'c');
+    let split = nfa.push(State::split(Some(state_b), Some(state_c)));
+    list.add_state(&nfa, split);
     
     // The split state should add its two out states
-    assert!(list.len() > 2);
+    assert_eq!(list.len(), 4);
+    assert!(!list.contains(split));
 }
 
 #[test]
 fn test_removing_matchs_from_state_list() {
     // Create a state list with various states
-    let basic = create_basic_state('a');
-    let match1 = State::match_(1);
-    let match2 = State::match_(2);
+    let mut nfa = Nfa::new();
+    let basic = create_basic_state(&mut nfa, 'a');
+    let match1 = nfa.push(State::match_(1));
+    let match2 = nfa.push(State::match_(2));
     
     let mut list = StateList::new();
-    list.add_state(&basic);
-    list.add_state(&match1);
-    list.add_state(&match2);
+    list.add_state(&nfa, basic);
+    list.add_state(&nfa, match1);
+    list.add_state(&nfa, match2);
     
     // There should be 3 states now
     assert_eq!(list.len(), 3);
     
     // Remove matches
-    let matches = list.remove_matchs();
+    let matches = list.remove_matchs(&nfa);
     
     // There should be 1 state left
     assert_eq!(list.len(), 1);
@@ -1481,21 +1565,27 @@ fn test_removing_matchs_from_state_list() {
     
     // Verify that all removed states are match states
     for state in &matches {
-        assert!(State::is_match_ptr(state));
+        assert!(nfa[*state].is_match());
     }
 }
 
 #[test]
 fn test_merging_two_state_lists() {
+    let mut nfa = Nfa::new();
+    let state_a = create_basic_state(&mut nfa, 'a');
+    let state_b = create_basic_state(&mut nfa, 'b');
+    let state_c = create_basic_state(&mut nfa, 'c');
+    let state_match = nfa.push(State::match_(1));
+
     // Create first list
     let mut list1 = StateList::new();
-    list1.add_state(&create_basic_state('a'));
-    list1.add_state(&create_basic_state('b'));
+    list1.add_state(&nfa, state_a);
+    list1.add_state(&nfa, state_b);
     
     // Create second list
     let mut list2 = StateList::new();
-    list2.add_state(&create_basic_state('c'));
-    list2.add_state(&State::match_(1));
+    list2.add_state(&nfa, state_c);
+    list2.add_state(&nfa, state_match);
     
     // Remember initial counts
     let list1_len = list1.len();
@@ -1510,19 +1600,19 @@ fn test_merging_two_state_lists() {
 
 #[test]
 fn test_state_list_with_duplicate_states() {
-    // Create a state and its clone
-    let state = create_basic_state('a');
+    let mut nfa = Nfa::new();
+    let state = create_basic_state(&mut nfa, 'a');
     
     // Add the same state twice
     let mut list = StateList::new();
-    list.add_state(&state);
-    list.add_state(&state);  // Should not add duplicate
+    list.add_state(&nfa, state);
+    list.add_state(&nfa, state);  // Should not add duplicate
     
     // The list should only contain one instance
     assert_eq!(list.len(), 1);
     
-    // Add the same state again with a different instance but same pointer
-    list.add_state(&state.clone());
+    // Push the same state again
+    list.push(state);
     
     // Should still have just one state
     assert_eq!(list.len(), 1);
@@ -1530,24 +1620,29 @@ fn test_state_list_with_duplicate_states() {
 
 #[test]
 fn test_state_list_equality_comparison() {
+    let mut nfa = Nfa::new();
+
     // Create two identical lists
     let mut list1 = StateList::new();
     let mut list2 = StateList::new();
     
-    let state_a = create_basic_state('a');
-    let state_b = create_basic_state('b');
+    let state_a = create_basic_state(&mut nfa, 'a');
+    let state_b = create_basic_state(&mut nfa, 'b');
+    let state_c = create_basic_state(&mut nfa, 'c');
     
-    list1.add_state(&state_a);
-    list1.add_state(&state_b);
+    list1.add_state(&nfa, state_a);
+    list1.add_state(&nfa, state_b);
     
-    list2.add_state(&state_a);
-    list2.add_state(&state_b);
+    // In another order
+    list2.add_state(&nfa, state_b);
+    list2.add_state(&nfa, state_a);
     
     // The lists should be equal
     assert_eq!(list1, list2);
+    assert_eq!(list1.hash_code(), list2.hash_code());
     
     // Add another state to list2
-    list2.add_state(&create_basic_state('c'));
+    list2.add_state(&nfa, state_c);
     
     // The lists should now be different
     assert_ne!(list1, list2);
@@ -1557,19 +1652,20 @@ fn test_state_list_equality_comparison() {
 fn test_state_list_with_large_number_of_states() {
     // Create a large number of states
     const NUM_STATES: usize = 1000;
+    let mut nfa = Nfa::new();
     let mut list = StateList::new();
     let mut states = Vec::with_capacity(NUM_STATES);
     
     // Add states to the vector
     for i in 0..NUM_STATES {
         let c = (i % 26 + 'a' as usize) as u8 as char;
-        states.push(create_basic_state(c));
+        states.push(create_basic_state(&mut nfa, c));
     }
     
     // Measure time to add all states
     let start = Instant::now();
     for state in &states {
-        list.add_state(state);
+        list.add_state(&nfa, *state);
     }
     let duration = start.elapsed();
     
@@ -1589,7 +1685,7 @@ fn pattern_matches(pattern: &str, input: &str) -> bool {
     let nfa = post2nfa(into_postfix(pattern), 0).unwrap();
     
     // Convert to DFA
-    let dfa = Dfa::new(vec![nfa]);
+    let dfa = Dfa::new(&[&nfa]);
     
     // For a full simulation, we'd need to trace through the DFA transitions
     // based on each character in the input string, but we'll simplify for tests
@@ -1607,7 +1703,7 @@ fn pattern_matches(pattern: &str, input: &str) -> bool {
     
     // Check if there's a transition for the first character
     if let Some(next_states) = start.next.get(&InputCondition::Char(first_char)) {
-        return next_states.is_matched();
+        return next_states.is_matched(&dfa.nfa);
     }
     
     false
@@ -1618,7 +1714,7 @@ fn test_full_regex_to_dfa_pipeline() {
     // Simple pattern "abc"
     let pattern = "abc";
     let nfa = post2nfa(into_postfix(pattern), 0).unwrap();
-    let dfa = Dfa::new(vec![nfa]);
+    let dfa = Dfa::new(&[&nfa]);
     
     // Check the DFA structure
     let start = dfa.start.borrow();
@@ -1645,7 +1741,7 @@ fn test_with_start_of_line_anchor() {
     // Pattern "^abc" - starts with abc
     let pattern = "^abc";
     let nfa = post2nfa(into_postfix(pattern), 0).unwrap();
-    let dfa = Dfa::new(vec![nfa]);
+    let dfa = Dfa::new(&[&nfa]);
     
     // Check the DFA structure
     let start = dfa.start.borrow();
@@ -1666,7 +1762,7 @@ fn test_with_end_of_line_anchor() {
     // Pattern "abc$" - ends with abc
     let pattern = "abc$";
     let nfa = post2nfa(into_postfix(pattern), 0).unwrap();
-    let dfa = Dfa::new(vec![nfa]);
+    let dfa = Dfa::new(&[&nfa]);
     
     // Trace through the DFA until we reach the $ transition
     let start = dfa.start.borrow();
@@ -1696,7 +1792,7 @@ fn test_with_both_start_and_end_anchors() {
     // Pattern "^abc$" - exactly abc
     let pattern = "^abc$";
     let nfa = post2nfa(into_postfix(pattern), 0).unwrap();
-    let dfa = Dfa::new(vec![nfa]);
+    let dfa = Dfa::new(&[&nfa]);
     
     // Check the DFA structure - should start with StartOfLine
     let start = dfa.start.borrow();
@@ -1725,7 +1821,7 @@ fn test_with_character_classes() {
     // Pattern "[abc]" - one of a, b, or c
     let pattern = "[abc]";
     let nfa = post2nfa(into_postfix(pattern), 0).unwrap();
-    let dfa = Dfa::new(vec![nfa]);
+    let dfa = Dfa::new(&[&nfa]);
     
     // The start state should have transitions for a, b, and c
     let start = dfa.start.borrow();
@@ -1746,7 +1842,7 @@ fn test_with_repetition_quantifiers() {
     // Test with * (zero or more)
     let pattern1 = "a*";
     let nfa1 = post2nfa(into_postfix(pattern1), 0).unwrap();
-    let dfa1 = Dfa::new(vec![nfa1]);
+    let dfa1 = Dfa::new(&[&nfa1]);
     
     // The start state should be a match state (for zero occurrences)
     assert!(dfa1.start.borrow().is_match());
@@ -1758,7 +1854,7 @@ fn test_with_repetition_quantifiers() {
     // Test with + (one or more)
     let pattern2 = "a+";
     let nfa2 = post2nfa(into_postfix(pattern2), 0).unwrap();
-    let dfa2 = Dfa::new(vec![nfa2]);
+    let dfa2 = Dfa::new(&[&nfa2]);
     
     // The start state should NOT be a match state
     assert!(!dfa2.start.borrow().is_match());
@@ -1774,7 +1870,7 @@ fn test_with_repetition_quantifiers() {
     // Test with ? (zero or one)
     let pattern3 = "a?";
     let nfa3 = post2nfa(into_postfix(pattern3), 0).unwrap();
-    let dfa3 = Dfa::new(vec![nfa3]);
+    let dfa3 = Dfa::new(&[&nfa3]);
     
     // The start state should be a match state
     assert!(dfa3.start.borrow().is_match());
@@ -1793,7 +1889,7 @@ fn test_with_alternation() {
     // Pattern "a|b" - either a or b
     let pattern = "a|b";
     let nfa = post2nfa(into_postfix(pattern), 0).unwrap();
-    let dfa = Dfa::new(vec![nfa]);
+    let dfa = Dfa::new(&[&nfa]);
     
     // The start state should have transitions for both 'a' and 'b'
     let start = dfa.start.borrow();
@@ -1813,7 +1909,7 @@ fn test_with_nested_groups() {
     // Pattern "(a(b|c)d)" - abd or acd
     let pattern = "(a(b|c)d)";
     let nfa = post2nfa(into_postfix(pattern), 0).unwrap();
-    let dfa = Dfa::new(vec![nfa]);
+    let dfa = Dfa::new(&[&nfa]);
     
     // Trace through the DFA for 'abd'
     let start = dfa.start.borrow();
@@ -1847,7 +1943,7 @@ fn test_with_complex_patterns() {
     // Email pattern (simplified)
     let email_pattern = "[a-zA-Z0-9]+@[a-zA-Z0-9]+\\.[a-zA-Z]{2,}";
     let nfa = post2nfa(into_postfix(email_pattern), 0).unwrap();
-    let dfa = Dfa::new(vec![nfa]);
+    let dfa = Dfa::new(&[&nfa]);
     
     // Just verify that DFA creation succeeds
     assert!(dfa.memory.len() > 0);
@@ -1855,7 +1951,7 @@ fn test_with_complex_patterns() {
     // URL pattern (simplified)
     let url_pattern = "https?://[a-zA-Z0-9]+(\\.[a-zA-Z0-9]+)+(/[a-zA-Z0-9]*)*";
     let nfa2 = post2nfa(into_postfix(url_pattern), 0).unwrap();
-    let dfa2 = Dfa::new(vec![nfa2]);
+    let dfa2 = Dfa::new(&[&nfa2]);
     
     // Verify DFA creation succeeds
     assert!(dfa2.memory.len() > 0);
@@ -1863,7 +1959,7 @@ fn test_with_complex_patterns() {
     // IPv4 address pattern
     let ip_pattern = "(25[0-5]|2[0-4][0-9]|[01]?[0-9][0-9]?)\\.(25[0-5]|2[0-4][0-9]|[01]?[0-9][0-9]?)\\.(25[0-5]|2[0-4][0-9]|[01]?[0-9][0-9]?)\\.(25[0-5]|2[0-4][0-9]|[01]?[0-9][0-9]?)";
     let nfa3 = post2nfa(into_postfix(ip_pattern), 0).unwrap();
-    let dfa3 = Dfa::new(vec![nfa3]);
+    let dfa3 = Dfa::new(&[&nfa3]);
     
     // Verify DFA creation succeeds
     assert!(dfa3.memory.len() > 0);
@@ -1873,16 +1969,18 @@ fn test_with_complex_patterns() {
 // 11. Error Cases
 // ==============================
 
+
 #[test]
 fn test_iterative_create_with_invalid_state_list() {
-    // Create an invalid state list with a None state
-    let none_state = State::none();
+    // Create an invalid state list with an unconnected state
+    let mut nfa = Nfa::new();
+    let unconnected_state = create_basic_state(&mut nfa, 'a');
     
     let mut list = StateList::new();
-    list.add_state(&none_state);
+    list.add_state(&nfa, unconnected_state);
     
-    // Create a DFA - should work even with None state
-    let (result, mem) = DfaState::iterative_create(list);
+    // Create a DFA - should work even with an unconnected state
+    let (result, mem) = DfaState::iterative_create(&nfa, list);
     
     // The result should have no transitions
     assert_eq!(result.borrow().next.len(), 0);
@@ -1893,20 +1991,21 @@ fn test_iterative_create_with_invalid_state_list() {
 
 #[test]
 fn test_finding_next_states_for_invalid_types() {
-    // Test with None state
-    let none_state = State::none();
+    // Test with an unconnected state
+    let mut nfa = Nfa::new();
+    let unconnected_state = create_basic_state(&mut nfa, 'a');
     let current_states = StateList::new();
     
-    let (next_map, match_list) = DfaState::find_next(&none_state, &current_states);
+    let (next_map, match_list) = DfaState::find_next(&nfa, unconnected_state, &current_states);
     
-    // None state should have no transitions and no matches
+    // An unconnected state should have no transitions and no matches
     assert_eq!(next_map.len(), 0);
     assert_eq!(match_list.len(), 0);
     
     // Test with NoMatch state
-    let nomatch_state = State::no_match();
+    let nomatch_state = nfa.push(State::no_match());
     
-    let (next_map, match_list) = DfaState::find_next(&nomatch_state, &current_states);
+    let (next_map, match_list) = DfaState::find_next(&nfa, nomatch_state, &current_states);
     
     // NoMatch state should have no transitions and no matches
     assert_eq!(next_map.len(), 0);
@@ -1916,68 +2015,90 @@ fn test_finding_next_states_for_invalid_types() {
 #[test]
 fn test_match_id_with_corrupted_match_states() {
     // Create a state list with match states
-    let match1 = State::match_(1);
-    let match2 = State::match_(2);
+    let mut nfa = Nfa::new();
+    let match1 = nfa.push(State::match_(1));
+    let match2 = nfa.push(State::match_(2));
     
     // Create a DFA state with these match states
     let mut list = StateList::new();
-    list.add_state(&match1);
-    list.add_state(&match2);
+    list.add_state(&nfa, match1);
+    list.add_state(&nfa, match2);
     
-    let dfa_state = DfaState::new(0, list);
+    let dfa_state = DfaState::new(0, list, &nfa);
     
     // Get the match ID - should be the minimum (1)
-    let id = dfa_state.match_id();
+    let id = dfa_state.match_id(&nfa);
     assert_eq!(id, Some(1));
     
     // Test with a match state that has a corrupted ID (usize::MAX)
-    let max_match = State::match_(usize::MAX);
+    let max_match = nfa.push(State::match_(usize::MAX));
     
     let mut list = StateList::new();
-    list.add_state(&max_match);
+    list.add_state(&nfa, max_match);
     
-    let dfa_state = DfaState::new(0, list);
+    let dfa_state = DfaState::new(0, list, &nfa);
     
     // Get the match ID - should still work
-    let id = dfa_state.match_id();
+    let id = dfa_state.match_id(&nfa);
     assert_eq!(id, Some(usize::MAX));
 }
 
 #[test]
 fn test_compute_next_with_circular_references() {
     // Create a state that references itself
-    let state = create_basic_state('a');
-    state.borrow_mut().into_basic().unwrap().out.replace(state.clone());
+    let mut nfa = Nfa::new();
+    let state = create_basic_state(&mut nfa, 'a');
+    connect(&mut nfa, state, state);
     
     let mut list = StateList::new();
-    list.add_state(&state);
+    list.add_state(&nfa, state);
     
-    let mut dfa_state = DfaState::new(0, list);
+    let mut dfa_state = DfaState::new(0, list, &nfa);
     
     // This should not cause an infinite loop
-    dfa_state.compute_next();
+    dfa_state.compute_next(&nfa);
     
     // The result should have one transition for 'a'
     assert_eq!(dfa_state.next.len(), 1);
     assert!(dfa_state.next.contains_key(&InputCondition::Char('a')));
     
     // Create a more complex cycle: a -> b -> c -> a
-    let cycle_states = create_cycle();
+    let cycle_states = create_cycle(&mut nfa);
     
     let mut list = StateList::new();
     for state in &cycle_states {
-        list.add_state(state);
+        list.add_state(&nfa, *state);
     }
     
-    let mut dfa_state = DfaState::new(0, list);
+    let mut dfa_state = DfaState::new(0, list, &nfa);
     
     // This should not cause an infinite loop
-    dfa_state.compute_next();
+    dfa_state.compute_next(&nfa);
     
     // The result should have three transitions
     assert_eq!(dfa_state.next.len(), 3);
 }
 
+#[test]
+fn test_split_cycles_are_followed_once() {
+    // Two splits pointing at each other: (a*)*
+    let mut nfa = Nfa::new();
+    let a = create_basic_state(&mut nfa, 'a');
+    let split1 = nfa.push(State::split(Some(a), None));
+    let split2 = nfa.push(State::split(Some(split1), None));
+    let match_state = nfa.push(State::match_(1));
+
+    nfa.patch(OutPtr::second(split1), split2);
+    nfa.patch(OutPtr::second(split2), match_state);
+    connect(&mut nfa, a, split1);
+
+    let list = StateList::from(&nfa, split1);
+
+    // The closure is made of the 'a' and match states only
+    assert_eq!(list.len(), 2);
+    assert!(list.contains(a));
+    assert!(list.contains(match_state));
+}
 #[test]
 fn test_memory_handling_with_large_machines() {
     // Create a pattern that will generate a very large state machine
@@ -1998,7 +2119,7 @@ fn test_memory_handling_with_large_machines() {
     let nfa = post2nfa(into_postfix(&pattern), 0).unwrap();
     
     // Create the DFA - this should handle the large machine gracefully
-    let dfa = Dfa::new(vec![nfa]);
+    let dfa = Dfa::new(&[&nfa]);
     
     // The DFA should have at least one state
     assert!(dfa.memory.len() > 0);
@@ -2019,7 +2140,7 @@ fn test_with_minimum_valid_input() {
     // Simplest valid pattern: a single character
     let pattern = "a";
     let nfa = post2nfa(into_postfix(pattern), 0).unwrap();
-    let dfa = Dfa::new(vec![nfa]);
+    let dfa = Dfa::new(&[&nfa]);
     
     // The DFA should have a start state with a transition for 'a'
     let start = dfa.start.borrow();
@@ -2039,7 +2160,7 @@ fn test_with_empty_regex_pattern() {
     // We can use a pattern like "a*" which can match empty string
     let pattern = "a*";
     let nfa = post2nfa(into_postfix(pattern), 0).unwrap();
-    let dfa = Dfa::new(vec![nfa]);
+    let dfa = Dfa::new(&[&nfa]);
     
     // The start state should be a match state
     assert!(dfa.start.borrow().is_match());
@@ -2057,7 +2178,7 @@ fn test_with_very_large_regex_pattern() {
     let nfa = post2nfa(into_postfix(&pattern), 0).unwrap();
     
     // Create the DFA - should handle the large pattern without issues
-    let dfa = Dfa::new(vec![nfa]);
+    let dfa = Dfa::new(&[&nfa]);
     
     // The DFA should have a valid structure
     assert!(dfa.memory.len() > 0);
@@ -2077,7 +2198,7 @@ fn test_with_only_character_classes() {
     // Pattern with only character classes: [a-z][0-9]
     let pattern = "[a-z][0-9]";
     let nfa = post2nfa(into_postfix(pattern), 0).unwrap();
-    let dfa = Dfa::new(vec![nfa]);
+    let dfa = Dfa::new(&[&nfa]);
     
     // The DFA start state should have transitions for all lowercase letters
     let start = dfa.start.borrow();
@@ -2112,7 +2233,7 @@ fn test_states_with_maximum_transitions() {
     let nfa = post2nfa(into_postfix(&pattern), 0).unwrap();
     
     // Create the DFA
-    let dfa = Dfa::new(vec![nfa]);
+    let dfa = Dfa::new(&[&nfa]);
     
     // The start state should have a large number of transitions
     let start = dfa.start.borrow();
@@ -2135,7 +2256,7 @@ fn test_complex_cyclical_state_machines() {
     let nfa = post2nfa(into_postfix(pattern), 0).unwrap();
     
     // Create the DFA
-    let dfa = Dfa::new(vec![nfa]);
+    let dfa = Dfa::new(&[&nfa]);
     
     // Trace through a basic path
     let start = dfa.start.borrow();
@@ -2179,7 +2300,7 @@ fn test_dfa_creation_time_simple_patterns() {
         let nfa = post2nfa(into_postfix(pattern), 0).unwrap();
         
         let start = Instant::now();
-        let dfa = Dfa::new(vec![nfa]);
+        let dfa = Dfa::new(&[&nfa]);
         let duration = start.elapsed();
         
         println!("Pattern '{}' - DFA creation took: {:?}, states: {}", 
@@ -2205,7 +2326,7 @@ fn test_dfa_creation_time_complex_patterns() {
         let nfa = post2nfa(into_postfix(pattern), 0).unwrap();
         
         let start = Instant::now();
-        let dfa = Dfa::new(vec![nfa]);
+        let dfa = Dfa::new(&[&nfa]);
         let duration = start.elapsed();
         
         println!("Complex pattern '{}' - DFA creation took: {:?}, states: {}", 
@@ -2225,7 +2346,7 @@ fn test_memory_usage_for_large_machines() {
     let nfa1 = post2nfa(into_postfix(pattern1), 0).unwrap();
     
     let start = Instant::now();
-    let dfa1 = Dfa::new(vec![nfa1]);
+    let dfa1 = Dfa::new(&[&nfa1]);
     let duration1 = start.elapsed();
     
     println!("Pattern '{}' - DFA creation took: {:?}, states: {}", 
@@ -2236,7 +2357,7 @@ fn test_memory_usage_for_large_machines() {
     let nfa2 = post2nfa(into_postfix(pattern2), 0).unwrap();
     
     let start = Instant::now();
-    let dfa2 = Dfa::new(vec![nfa2]);
+    let dfa2 = Dfa::new(&[&nfa2]);
     let duration2 = start.elapsed();
     
     println!("Pattern '{}' - DFA creation took: {:?}, states: {}", 
@@ -2247,7 +2368,7 @@ fn test_memory_usage_for_large_machines() {
     let nfa3 = post2nfa(into_postfix(pattern3), 0).unwrap();
     
     let start = Instant::now();
-    let dfa3 = Dfa::new(vec![nfa3]);
+    let dfa3 = Dfa::new(&[&nfa3]);
     let duration3 = start.elapsed();
     
     println!("Pattern '{}' - DFA creation took: {:?}, states: {}", 
@@ -2270,17 +2391,17 @@ fn test_compare_recursive_vs_iterative_creation() {
         let nfa = post2nfa(into_postfix(pattern), 0).unwrap();
         
         let mut list = StateList::new();
-        list.add_state(&nfa);
+        list.add_state(&nfa, nfa.start);
         
         // Measure recursive creation time
         let start = Instant::now();
         let mut memory1 = HashMap::new();
-        let _result1 = DfaState::recursive_create(list.clone(), &mut memory1);
+        let _result1 = DfaState::recursive_create(&nfa, list.clone(), &mut memory1);
         let recursive_time = start.elapsed();
         
         // Measure iterative creation time
         let start = Instant::now();
-        let (_result2, memory2) = DfaState::iterative_create(list);
+        let (_result2, memory2) = DfaState::iterative_create(&nfa, list);
         let iterative_time = start.elapsed();
         
         println!("Pattern '{}' - Recursive: {:?}, Iterative: {:?}, States: {}",
@@ -2294,18 +2415,20 @@ fn test_compare_recursive_vs_iterative_creation() {
 #[test]
 fn test_state_transition_computation_performance() {
     // Create states with varying numbers of transitions
+    let mut nfa = Nfa::new();
     
     // First, test with a small number of transitions
     let mut small_list = StateList::new();
     for i in 0..10 {
         let c = (i as u8 + b'a') as char;
-        small_list.add_state(&create_basic_to_match(c, i));
+        let state = create_basic_to_match(&mut nfa, c, i);
+        small_list.add_state(&nfa, state);
     }
     
-    let mut dfa_small = DfaState::new(0, small_list);
+    let mut dfa_small = DfaState::new(0, small_list, &nfa);
     
     let start = Instant::now();
-    dfa_small.compute_next();
+    dfa_small.compute_next(&nfa);
     let small_time = start.elapsed();
     
     println!("10 transitions - compute_next took: {:?}", small_time);
@@ -2314,13 +2437,14 @@ fn test_state_transition_computation_performance() {
     let mut medium_list = StateList::new();
     for i in 0..50 {
         let c = ((i % 26) as u8 + b'a') as char;
-        medium_list.add_state(&create_basic_to_match(c, i));
+        let state = create_basic_to_match(&mut nfa, c, i);
+        medium_list.add_state(&nfa, state);
     }
     
-    let mut dfa_medium = DfaState::new(0, medium_list);
+    let mut dfa_medium = DfaState::new(0, medium_list, &nfa);
     
     let start = Instant::now();
-    dfa_medium.compute_next();
+    dfa_medium.compute_next(&nfa);
     let medium_time = start.elapsed();
     
     println!("50 transitions - compute_next took: {:?}", medium_time);
@@ -2329,14 +2453,75 @@ fn test_state_transition_computation_performance() {
     let mut large_list = StateList::new();
     for i in 0..100 {
         let c = ((i % 26) as u8 + b'a') as char;
-        large_list.add_state(&create_basic_to_match(c, i));
+        let state = create_basic_to_match(&mut nfa, c, i);
+        large_list.add_state(&nfa, state);
     }
     
-    let mut dfa_large = DfaState::new(0, large_list);
+    let mut dfa_large = DfaState::new(0, large_list, &nfa);
     
     let start = Instant::now();
-    dfa_large.compute_next();
+    dfa_large.compute_next(&nfa);
     let large_time = start.elapsed();
     
     println!("100 transitions - compute_next took: {:?}", large_time);
 }
+
+// ==============================
+// 14. State Limit Tests
+// ==============================
+
+fn regex_nfas(patterns: &[&str]) -> Vec<Nfa> {
+	patterns.iter()
+		.enumerate()
+		.map(|(index, pattern)| Regex::new(pattern.to_string(), index + 1).unwrap())
+		.collect()
+}
+
+fn with_state_limit(nfas: &[Nfa], limit: usize) -> Result<Dfa, StateLimitExceeded> {
+	let refs: Vec<&Nfa> = nfas.iter().collect();
+	let classes = EquivalenceClasses::from_nfas(&refs);
+
+	Dfa::with_state_limit(&refs, vec![(0..nfas.len()).collect()], classes, limit)
+}
+
+#[test]
+fn test_state_limit_not_reached() {
+	let nfas = regex_nfas(&["if", "[a-z]+", "[0-9]+"]);
+
+	let dfa = with_state_limit(&nfas, 1000).unwrap();
+	let unlimited = Dfa::new(&nfas.iter().collect::<Vec<_>>());
+
+	assert_eq!(dfa.memory.len(), unlimited.memory.len());
+}
+
+#[test]
+fn test_state_limit_is_inclusive() {
+	let nfas = regex_nfas(&["if", "[a-z]+"]);
+	let len = with_state_limit(&nfas, usize::MAX).unwrap().memory.len();
+
+	assert!(with_state_limit(&nfas, len).is_ok());
+	assert_eq!(
+		with_state_limit(&nfas, len - 1).unwrap_err().limit,
+		len - 1
+	);
+}
+
+#[test]
+fn test_state_limit_names_the_exploding_nfa() {
+	// (a|b)*a(a|b){12} needs 2^13 states
+	let nfas = regex_nfas(&["if", "[a-z]+", "(a|b)*a(a|b){12}", "[0-9]+"]);
+
+	let start = Instant::now();
+	let exceeded = with_state_limit(&nfas, 500).unwrap_err();
+	println!("State limit reached in {:?}", start.elapsed());
+
+	assert_eq!(exceeded, StateLimitExceeded { limit: 500, nfa: 2 });
+}
+
+#[test]
+fn test_state_limit_with_several_exploding_nfas() {
+	// the second one explodes faster
+	let nfas = regex_nfas(&["(a|b)*a(a|b){6}", "(c|d)*c(c|d){12}"]);
+
+	assert_eq!(with_state_limit(&nfas, 300).unwrap_err().nfa, 1);
+}
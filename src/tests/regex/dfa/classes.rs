@@ -0,0 +1,217 @@
+use crate::regex::*;
+use crate::regex::dfa::*;
+use crate::regex::dfa::normalizer::*;
+
+fn nfas(patterns: &[&str]) -> Vec<Nfa> {
+	patterns.iter()
+		.enumerate()
+		.map(|(index, pattern)| Regex::new(pattern.to_string(), index + 1).unwrap())
+		.collect()
+}
+
+fn refs(nfas: &[Nfa]) -> Vec<&Nfa> {
+	nfas.iter().collect()
+}
+
+/// The start of the DFA of all the NFAs
+fn all(nfas: &[Nfa]) -> Vec<Vec<usize>> {
+	vec![(0..nfas.len()).collect()]
+}
+
+fn classes(patterns: &[&str]) -> EquivalenceClasses {
+	EquivalenceClasses::from_nfas(&refs(&nfas(patterns)))
+}
+
+/// Normalized DFA of the patterns, with transitions on their equivalence classes
+fn normalized(patterns: &[&str]) -> NormalizedDfa {
+	let nfas = nfas(patterns);
+	let classes = EquivalenceClasses::from_nfas(&refs(&nfas));
+
+	NormalizedDfa::from(&mut Dfa::with_classes(&refs(&nfas), all(&nfas), classes))
+}
+
+#[test]
+fn test_classes_of_literals() {
+	let classes = classes(&["ab", "ba"]);
+
+	assert_eq!(classes.len(), 2);
+	assert_eq!(classes.chars(0), &['a']);
+	assert_eq!(classes.chars(1), &['b']);
+}
+
+#[test]
+fn test_classes_merge_the_chars_never_told_apart() {
+	let classes = classes(&["[a-c]x", "[a-c]+"]);
+
+	assert_eq!(classes.len(), 2);
+	assert_eq!(classes.class_of('a'), classes.class_of('b'));
+	assert_eq!(classes.class_of('a'), classes.class_of('c'));
+	assert_ne!(classes.class_of('a'), classes.class_of('x'));
+}
+
+#[test]
+fn test_classes_split_overlapping_sets() {
+	let classes = classes(&["[a-f]", "[d-z]", "e"]);
+
+	let class_of = |c| classes.class_of(c).unwrap();
+
+	assert_eq!(class_of('a'), class_of('c'));
+	assert_ne!(class_of('c'), class_of('d'));
+	assert_ne!(class_of('d'), class_of('e'));
+	assert_eq!(class_of('d'), class_of('f'));
+	assert_ne!(class_of('f'), class_of('g'));
+	assert_eq!(class_of('g'), class_of('z'));
+	assert_eq!(classes.len(), 4);
+}
+
+#[test]
+fn test_classes_of_any_char() {
+	// the newline is only excluded by `.`
+	let classes = classes(&[".", "a"]);
+
+	assert_eq!(classes.len(), 2);
+	assert_eq!(classes.class_of('\n'), None);
+	assert_eq!(classes.chars(classes.class_of('a').unwrap()), &['a']);
+	assert_eq!(classes.chars(classes.class_of('b').unwrap()).len(), 126);
+}
+
+#[test]
+fn test_classes_of_unused_chars() {
+	let classes = classes(&["abc"]);
+
+	assert_eq!(classes.class_of('d'), None);
+	assert_eq!(classes.class_of('é'), None);
+	assert_eq!(classes.classes_of(&['d', 'a', 'c', 'a']), vec![0, 2]);
+}
+
+#[test]
+fn test_classes_are_sorted_by_first_char() {
+	let classes = classes(&["z", "[^a-y]", "m"]);
+
+	let firsts: Vec<char> = (0..classes.len())
+		.map(|class| classes.chars(class)[0])
+		.collect();
+
+	let mut sorted = firsts.clone();
+	sorted.sort();
+
+	assert_eq!(firsts, sorted);
+	assert_eq!(classes.chars(classes.class_of('z').unwrap()), &['z']);
+}
+
+#[test]
+fn test_classes_of_anchored_and_repeated_rules() {
+	let classes = classes(&["^a+$", "[bc]*d"]);
+
+	assert_eq!(classes.len(), 3);
+	assert_eq!(classes.class_of('b'), classes.class_of('c'));
+	assert!(classes.class_of('a').is_some());
+	assert!(classes.class_of('d').is_some());
+}
+
+#[test]
+fn test_classes_without_rules() {
+	let classes = classes(&[]);
+
+	assert!(classes.is_empty());
+	assert_eq!(classes.class_of('a'), None);
+}
+
+#[test]
+fn test_classes_of_states() {
+	let nfas = nfas(&["[a-z]+", "if"]);
+	let classes = EquivalenceClasses::from_nfas(&refs(&nfas));
+	let (merged, _) = Nfa::merge(&refs(&nfas));
+
+	// the classes of each state of the merged NFAs, the other states reading none
+	for (state, nfa_state) in merged.states.iter().enumerate() {
+		let expected = match nfa_state.into_basic().map(|basic| &basic.c) {
+			Some(RegexType::CharacterClass(class)) => classes.classes_of(&class.chars()),
+			Some(c) => vec![classes.class_of(c.char().unwrap()).unwrap()],
+			None => vec![],
+		};
+
+		assert_eq!(classes.of_state(state), expected.as_slice());
+	}
+
+	// [a-z]: 'i', 'f' and the other letters
+	assert!(merged.states.iter().enumerate().any(|(state, _)| classes.of_state(state).len() == 3));
+}
+
+#[test]
+fn test_dfa_transitions_on_classes() {
+	let nfas = nfas(&["[a-z]+", "if"]);
+	let classes = EquivalenceClasses::from_nfas(&refs(&nfas));
+	let dfa = Dfa::with_classes(&refs(&nfas), all(&nfas), classes.clone());
+
+	let start = dfa.start.borrow();
+
+	// 'i', 'f' and the other letters
+	assert_eq!(start.next.len(), 3);
+	assert!(start.next.keys().all(|condition| matches!(condition, InputCondition::Class(_))));
+	assert!(start.next.contains_key(&InputCondition::Class(classes.class_of('i').unwrap())));
+	assert_eq!(dfa.classes, Some(classes));
+}
+
+#[test]
+fn test_dfa_any_char_has_one_transition() {
+	let nfas = nfas(&[".*"]);
+	let classes = EquivalenceClasses::from_nfas(&refs(&nfas));
+	let dfa = Dfa::with_classes(&refs(&nfas), all(&nfas), classes);
+
+	assert_eq!(dfa.start.borrow().next.len(), 1);
+
+	let char_dfa = Dfa::new(&refs(&self::nfas(&[".*"])));
+
+	assert_eq!(char_dfa.start.borrow().next.len(), 127);
+}
+
+#[test]
+fn test_dfa_with_classes_has_the_same_states() {
+	let patterns = ["[a-z]+", "[0-9]+", "if|else", "[^a-z0-9 ]"];
+
+	let with_classes = normalized(&patterns);
+	let with_chars = NormalizedDfa::from(&mut Dfa::new(&refs(&nfas(&patterns))));
+
+	assert_eq!(with_classes.states.len(), with_chars.states.len());
+}
+
+#[test]
+fn test_simulate_with_classes() {
+	let dfa = normalized(&["if", "[a-z]+", "[^a-z\\n]"]);
+
+	let token = |input: &str| simulate(input, &dfa).map(|match_| (match_.id(), match_.length()));
+
+	assert_eq!(token("if"), Some((1, 2)));
+	assert_eq!(token("iffy"), Some((2, 4)));
+	assert_eq!(token("x1"), Some((2, 1)));
+	assert_eq!(token("%%"), Some((3, 1)));
+	assert_eq!(token("\n"), None);
+
+	// read by no rule
+	assert_eq!(token("é"), None);
+	assert_eq!(token("ab\u{e9}"), Some((2, 2)));
+}
+
+#[test]
+fn test_input_of() {
+	let dfa = normalized(&["a", "[b-z]"]);
+
+	assert_eq!(dfa.input_of('a'), Some(InputCondition::Class(0)));
+	assert_eq!(dfa.input_of('q'), Some(InputCondition::Class(1)));
+	assert_eq!(dfa.input_of('A'), None);
+
+	let char_dfa = NormalizedDfa::from(&mut Dfa::new(&refs(&nfas(&["a"]))));
+
+	assert_eq!(char_dfa.input_of('A'), Some(InputCondition::Char('A')));
+}
+
+#[test]
+fn test_minimize_keeps_classes() {
+	let dfa = normalized(&["ac|bc", "[d-f]"]);
+	let minimized = dfa.minimize();
+
+	assert_eq!(minimized.classes, dfa.classes);
+	assert_eq!(simulate("bc", &minimized).map(|match_| match_.id()), Some(1));
+	assert_eq!(simulate("e", &minimized).map(|match_| match_.id()), Some(2));
+}
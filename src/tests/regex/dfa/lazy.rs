@@ -0,0 +1,111 @@
+use std::io::Cursor;
+
+use crate::parsing::{Parsing, Reader};
+use crate::regex::*;
+use crate::regex::dfa::lazy::*;
+use crate::regex::dfa::normalizer::*;
+
+fn parse(spec: &str) -> Parsing {
+	let mut parsing = Parsing::new().unwrap();
+	let mut reader = Reader::new(Cursor::new(spec.as_bytes().to_vec()), "<test>").unwrap();
+
+	let _ = parsing.parse_sections(&mut reader);
+
+	assert!(parsing.errors.iter().all(|err| err.is_warning()), "invalid test specification: {:?}", parsing.errors);
+
+	parsing
+}
+
+/// (rule, length) of every match of the prefixes of `input`, from the best one
+fn found(matches: Vec<Match>) -> Vec<(usize, usize)> {
+	matches.into_iter()
+		.map(|match_| (match_.id(), match_.length()))
+		.collect()
+}
+
+/// Checks that the lazy and the full automatons match the same prefixes of the inputs,
+/// in each start condition
+fn assert_same_matches(spec: &str, inputs: &[&str]) {
+	let parsing = parse(spec);
+
+	let mut dfa = Dfa::from_rules(&parsing.rules, &parsing.definitions);
+	let dfa = NormalizedDfa::from(&mut dfa);
+
+	let mut lazy = LazyDfa::from_rules(&parsing.rules, &parsing.definitions);
+
+	assert_eq!(lazy.dfa().starts.len(), dfa.starts.len());
+
+	for condition in 0..dfa.starts.len() {
+		for input in inputs {
+			for at_bol in [true, false] {
+				let start = lazy.dfa().starts[condition];
+
+				assert_eq!(
+					found(lazy.matches_from(input, start, at_bol)),
+					found(matches_from(input, &dfa, dfa.starts[condition], at_bol)),
+					"input {input:?} in the condition {condition} (at_bol: {at_bol})"
+				);
+			}
+		}
+	}
+}
+
+#[test]
+fn test_lazy_dfa_matches_like_the_full_dfa() {
+	assert_same_matches(
+		"%%\nif|int\tkeyword();\n[a-z]+\tword();\n[0-9]+(\\.[0-9]+)?\tnumber();\n.|\\n\t;\n",
+		&["if", "int", "into", "12.5", "12.", "x y", "", "+"],
+	);
+}
+
+#[test]
+fn test_lazy_dfa_anchors() {
+	assert_same_matches(
+		"%%\n^begin\tone();\nend$\ttwo();\n[a-z]+\tthree();\n",
+		&["begin", "begin\n", "end\n", "end", "endless\n"],
+	);
+}
+
+#[test]
+fn test_lazy_dfa_start_conditions() {
+	assert_same_matches(
+		"%x STRING\n%s OTHER\n%%\n\\\"\tBEGIN STRING;\n<STRING>[^\"]*\tstring();\n<STRING>\\\"\tBEGIN 0;\n<OTHER>x\tx();\n[a-z]+\tword();\n",
+		&["\"abc\"", "abc", "x", "xyz\""],
+	);
+}
+
+#[test]
+fn test_lazy_dfa_trailing_contexts() {
+	assert_same_matches(
+		"%%\nab/cd\tone();\n[a-z]+/[0-9]+\ttwo();\na*/b*c\tthree();\n",
+		&["abcd", "abc12", "aaabbc", "ab"],
+	);
+}
+
+#[test]
+fn test_lazy_dfa_builds_the_reached_states_only() {
+	let parsing = parse("%%\n(a|b)*a(a|b){20}\tone();\nc+\ttwo();\n");
+	let mut lazy = LazyDfa::from_rules(&parsing.rules, &parsing.definitions);
+
+	assert!(lazy.is_empty());
+
+	let start = lazy.dfa().starts[0];
+
+	let matches = found(lazy.matches_from("ccc", start, true));
+	assert_eq!(matches, vec![(2, 3), (2, 2), (2, 1)]);
+
+	// the start state, its start of line state and one state per 'c'
+	assert!(lazy.len() <= 5, "{} states built", lazy.len());
+
+	let input = "ab".repeat(15);
+	let matches = found(lazy.matches_from(&input, start, true));
+
+	// the prefixes whose 21st character from the end is an 'a'
+	assert_eq!(matches, vec![(1, 29), (1, 27), (1, 25), (1, 23), (1, 21)]);
+	assert!(lazy.len() <= 5 + 2 + input.len(), "{} states built", lazy.len());
+
+	// the states are cached
+	let len = lazy.len();
+	lazy.matches_from(&input, start, true);
+	assert_eq!(lazy.len(), len);
+}
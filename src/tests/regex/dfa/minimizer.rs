@@ -0,0 +1,144 @@
+use std::collections::VecDeque;
+
+use crate::regex::*;
+use crate::regex::dfa::*;
+use crate::regex::dfa::normalizer::*;
+use crate::regex::post2nfa::*;
+
+fn into_postfix(str: &str) -> VecDeque<TokenType> {
+	re2post(Regex::add_concatenation(Regex::tokens(str).unwrap())).unwrap()
+}
+
+/// Normalized DFA of the patterns, with the ids 1, 2, ...
+fn normalized(patterns: &[&str]) -> NormalizedDfa {
+	let nfas: Vec<Nfa> = patterns.iter()
+		.enumerate()
+		.map(|(index, pattern)| post2nfa(into_postfix(pattern), index + 1).unwrap())
+		.collect();
+
+	NormalizedDfa::from(&mut Dfa::new(&nfas.iter().collect::<Vec<_>>()))
+}
+
+/// Rules matched after reading the whole input (None if the automaton is stuck)
+fn run(dfa: &NormalizedDfa, start: usize, input: &str) -> Option<Vec<usize>> {
+	let mut id = start;
+
+	for c in input.chars() {
+		id = *dfa.states[&id].next.get(&InputCondition::Char(c))?;
+	}
+
+	let mut matchs: Vec<usize> = dfa.states[&id].matchs.iter().copied().collect();
+	matchs.sort();
+
+	Some(matchs)
+}
+
+#[test]
+fn test_minimize_merges_equivalent_states() {
+	// after 'a' and after 'b', the same 'c' is expected
+	let dfa = normalized(&["ac|bc"]);
+	let minimized = dfa.minimize();
+
+	assert_eq!(dfa.states.len(), 4);
+	assert_eq!(minimized.states.len(), 3);
+
+	assert_eq!(run(&minimized, minimized.start_id, "ac"), Some(vec![1]));
+	assert_eq!(run(&minimized, minimized.start_id, "bc"), Some(vec![1]));
+	assert_eq!(run(&minimized, minimized.start_id, "cc"), None);
+}
+
+#[test]
+fn test_minimize_keeps_rules_apart() {
+	// same shape, but different rules
+	let dfa = normalized(&["ac", "bc"]);
+	let minimized = dfa.minimize();
+
+	assert_eq!(minimized.states.len(), 5);
+
+	assert_eq!(run(&minimized, minimized.start_id, "ac"), Some(vec![1]));
+	assert_eq!(run(&minimized, minimized.start_id, "bc"), Some(vec![2]));
+}
+
+#[test]
+fn test_minimize_keywords() {
+	let keywords = ["\"if\"", "\"in\"", "\"int\"", "\"for\"", "\"float\"", "[a-z]+"];
+
+	let dfa = normalized(&keywords);
+	let minimized = dfa.minimize();
+
+	assert!(minimized.states.len() <= dfa.states.len());
+
+	for input in ["i", "if", "in", "int", "inte", "for", "fo", "float", "floats", "x", "iff", "IF"] {
+		assert_eq!(
+			run(&minimized, minimized.start_id, input),
+			run(&dfa, dfa.start_id, input),
+			"input {input:?}"
+		);
+	}
+}
+
+#[test]
+fn test_minimize_repetitions() {
+	// (a|aa)+ is a+: a start state and a single accepting state
+	let dfa = normalized(&["(a|aa)+"]);
+	let minimized = dfa.minimize();
+
+	assert_eq!(minimized.states.len(), 2);
+	assert_eq!(run(&minimized, minimized.start_id, "aaaaa"), Some(vec![1]));
+}
+
+#[test]
+fn test_minimize_removes_dead_states() {
+	// the states from which nothing can be matched are removed
+	let minimized = normalized(&["ab", "a[0-9]c"]).minimize();
+
+	for state in minimized.states.values() {
+		assert!(!state.matchs.is_empty() || !state.next.is_empty());
+	}
+}
+
+#[test]
+fn test_minimize_keeps_start_states() {
+	let a = post2nfa(into_postfix("a+"), 1).unwrap();
+	let b = post2nfa(into_postfix("b+"), 2).unwrap();
+
+	let mut dfa = Dfa::with_starts(&[&a, &b], vec![vec![0, 1], vec![0], vec![]]);
+	let minimized = NormalizedDfa::from(&mut dfa).minimize();
+
+	assert_eq!(minimized.starts.len(), 3);
+
+	// a start state without rules is kept
+	let empty = &minimized.states[&minimized.starts[2]];
+	assert!(empty.next.is_empty() && empty.matchs.is_empty());
+
+	assert_eq!(run(&minimized, minimized.starts[0], "bb"), Some(vec![2]));
+	assert_eq!(run(&minimized, minimized.starts[1], "bb"), None);
+	assert_eq!(run(&minimized, minimized.starts[1], "aa"), Some(vec![1]));
+}
+
+#[test]
+fn test_minimize_is_idempotent() {
+	let once = normalized(&["[a-z]+", "[0-9]+(\\.[0-9]+)?", "x(yz)*"]).minimize();
+	let twice = once.minimize();
+
+	assert_eq!(once.states.len(), twice.states.len());
+}
+
+#[test]
+fn test_minimize_agrees_with_simulate() {
+	let patterns = ["[a-z]+", "[0-9]+(\\.[0-9]+)?", "\"if\"", "a|ab|abc", "x(yz)*", "^begin", "end$", "(ab|cd)*e"];
+	let inputs = ["abc def", "12.5x", "if", "abcd", "xyzyzy", "begin", "end\n", "end", "9.", "ababcde", "cdcde"];
+
+	for pattern in patterns {
+		let dfa = normalized(&[pattern]);
+		let minimized = dfa.minimize();
+
+		for input in inputs {
+			assert_eq!(
+				simulate(input, &minimized).map(|match_| match_.length()),
+				simulate(input, &dfa).map(|match_| match_.length()),
+				"pattern `{pattern}` on input {input:?}"
+			);
+		}
+	}
+}
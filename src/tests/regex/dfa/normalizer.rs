@@ -1,5 +1,4 @@
 use std::collections::{HashMap, HashSet, VecDeque};
-use std::rc::Rc;
 
 use crate::regex::*;
 use crate::regex::dfa::*;
@@ -15,7 +14,7 @@ fn into_postfix(str: &str) -> VecDeque<TokenType> {
 fn create_test_dfa() -> Dfa {
     // Create a simple pattern "a(b|c)" which should give us a DFA with multiple states
     let nfa = post2nfa(into_postfix("a(b|c)"), 0).unwrap();
-    Dfa::new(vec![nfa])
+    Dfa::new(&[&nfa])
 }
 
 // Helper function to create a DFA with multiple match states
@@ -25,7 +24,7 @@ fn create_test_dfa_with_multiple_matches() -> Dfa {
     let nfa2 = post2nfa(into_postfix("b"), 2).unwrap();
     
     // Combine them into a single DFA
-    Dfa::new(vec![nfa1, nfa2])
+    Dfa::new(&[&nfa1, &nfa2])
 }
 
 #[test]
@@ -79,8 +78,8 @@ fn test_normalized_dfa_with_multiple_matches() {
     assert!(normalized_dfa.matchs.contains_key(&2));
     
     // Each of the states in the matchs map should be a match state with the correct ID
-    assert_eq!(normalized_dfa.matchs[&1].borrow().match_id(), Some(1));
-    assert_eq!(normalized_dfa.matchs[&2].borrow().match_id(), Some(2));
+    assert_eq!(dfa.nfa[normalized_dfa.matchs[&1]].match_id(), Some(1));
+    assert_eq!(dfa.nfa[normalized_dfa.matchs[&2]].match_id(), Some(2));
 }
 
 #[test]
@@ -93,7 +92,7 @@ fn test_normalize_state() {
     let mut match_memory = HashMap::new();
     
     // Normalize the state
-    let normalized_state = NormalizedDfa::normalize_state(&state_ptr, &dfa.memory, &mut match_memory);
+    let normalized_state = NormalizedDfa::normalize_state(&state_ptr, &dfa.memory, &dfa.nfa, &mut match_memory);
     
     // Check that the ID matches
     assert_eq!(normalized_state.id, state_ptr.borrow().id);
@@ -151,7 +150,7 @@ fn test_normalize_statelist() {
     
     // Now test with a StateList that's definitely not in memory
     let mut unknown_list = StateList::new();
-    unknown_list.add_state(&State::match_(999));
+    unknown_list.push(dfa.nfa.len());
     
     let id_option = NormalizedDfa::normalize_statelist(&unknown_list, &dfa.memory);
     assert!(id_option.is_none());
@@ -197,7 +196,7 @@ fn test_match_states_in_normalized_dfa() {
     // Create a simple DFA that ends with a match
     let pattern = "abc";
     let nfa = post2nfa(into_postfix(pattern), 42).unwrap();
-    let mut dfa = Dfa::new(vec![nfa]);
+    let mut dfa = Dfa::new(&[&nfa]);
     
     // Convert to normalized DFA
     let normalized_dfa = NormalizedDfa::from(&mut dfa);
@@ -239,7 +238,7 @@ fn test_match_states_in_normalized_dfa() {
 #[test]
 fn test_empty_dfa_normalization() {
     // Create an empty DFA
-    let mut dfa = Dfa::new(vec![]);
+    let mut dfa = Dfa::new(&[]);
     
     // Convert to normalized DFA
     let normalized_dfa = NormalizedDfa::from(&mut dfa);
@@ -251,7 +250,7 @@ fn test_empty_dfa_normalization() {
 #[test]
 fn test_dfa_with_special_transitions() {
     // Create a DFA with StartOfLine and EndOfLine transitions
-    let mut dfa = Dfa::new(vec![post2nfa(into_postfix("^a$"), 0).unwrap()]);
+    let mut dfa = Dfa::new(&[&post2nfa(into_postfix("^a$"), 0).unwrap()]);
     
     // Convert to normalized DFA
     let normalized_dfa = NormalizedDfa::from(&mut dfa);
@@ -294,7 +293,7 @@ fn test_complex_dfa_normalization() {
         nfas.push(post2nfa(into_postfix(pattern), i).unwrap());
     }
     
-    let mut dfa = Dfa::new(nfas);
+    let mut dfa = Dfa::new(&nfas.iter().collect::<Vec<_>>());
     
     // Convert to normalized DFA
     let normalized_dfa = NormalizedDfa::from(&mut dfa);
@@ -313,7 +312,7 @@ fn test_simulate_basic_matching() {
     // Create a simple DFA for pattern "abc"
     let pattern = "abc";
     let nfa = post2nfa(into_postfix(pattern), 1).unwrap();
-    let mut dfa = Dfa::new(vec![nfa]);
+    let mut dfa = Dfa::new(&[&nfa]);
     let normalized_dfa = NormalizedDfa::from(&mut dfa);
     
     // Test exact match
@@ -336,7 +335,7 @@ fn test_simulate_with_anchors() {
     // Create a DFA for pattern "^abc$"
     let pattern = "^abc$";
     let nfa = post2nfa(into_postfix(pattern), 1).unwrap();
-    let mut dfa = Dfa::new(vec![nfa]);
+    let mut dfa = Dfa::new(&[&nfa]);
     let normalized_dfa = NormalizedDfa::from(&mut dfa);
     
     // Test exact match with anchors
@@ -358,7 +357,7 @@ fn test_simulate_multiple_matches() {
     // Create a DFA with multiple patterns: "abc" and "ab"
     let nfa1 = post2nfa(into_postfix("abc"), 1).unwrap();
     let nfa2 = post2nfa(into_postfix("ab"), 2).unwrap();
-    let mut dfa = Dfa::new(vec![nfa1, nfa2]);
+    let mut dfa = Dfa::new(&[&nfa1, &nfa2]);
     let normalized_dfa = NormalizedDfa::from(&mut dfa);
     
     // Test with "abc" - should match the longer pattern
@@ -374,12 +373,38 @@ fn test_simulate_multiple_matches() {
     assert_eq!(match_result.length(), 2);
 }
 
+#[test]
+fn test_matches_from_best_to_worst() {
+    let nfa1 = post2nfa(into_postfix("ab"), 1).unwrap();
+    let nfa2 = post2nfa(into_postfix("[a-z]+"), 2).unwrap();
+    let nfa3 = post2nfa(into_postfix("a"), 3).unwrap();
+    let mut dfa = Dfa::new(&[&nfa1, &nfa2, &nfa3]);
+    let normalized_dfa = NormalizedDfa::from(&mut dfa);
+
+    let matches: Vec<(usize, usize)> = matches_from("abc", &normalized_dfa, normalized_dfa.start_id, true)
+        .iter()
+        .map(|match_| (match_.id(), match_.length()))
+        .collect();
+
+    // longest first, then the lowest rule id
+    assert_eq!(matches, vec![(2, 3), (1, 2), (2, 2), (2, 1), (3, 1)]);
+}
+
+#[test]
+fn test_matches_from_without_match() {
+    let nfa = post2nfa(into_postfix("ab"), 1).unwrap();
+    let mut dfa = Dfa::new(&[&nfa]);
+    let normalized_dfa = NormalizedDfa::from(&mut dfa);
+
+    assert!(matches_from("xyz", &normalized_dfa, normalized_dfa.start_id, true).is_empty());
+}
+
 #[test]
 fn test_simulate_with_newlines() {
     // Create a DFA for pattern "a\nb"
     let pattern = "a\nb";
     let nfa = post2nfa(into_postfix(pattern), 1).unwrap();
-    let mut dfa = Dfa::new(vec![nfa]);
+    let mut dfa = Dfa::new(&[&nfa]);
     let normalized_dfa = NormalizedDfa::from(&mut dfa);
     
     // Test with newline
@@ -398,7 +423,7 @@ fn test_simulate_edge_cases() {
     // Create a DFA for pattern "a*"
     let pattern = "a*";
     let nfa = post2nfa(into_postfix(pattern), 1).unwrap();
-    let mut dfa = Dfa::new(vec![nfa]);
+    let mut dfa = Dfa::new(&[&nfa]);
     let normalized_dfa = NormalizedDfa::from(&mut dfa);
 
     // Test empty string
@@ -420,7 +445,7 @@ fn test_simulate_with_special_characters() {
     // Create a DFA for pattern "[a-z]+"
     let pattern = "[a-z]+";
     let nfa = post2nfa(into_postfix(pattern), 1).unwrap();
-    let mut dfa = Dfa::new(vec![nfa]);
+    let mut dfa = Dfa::new(&[&nfa]);
     let normalized_dfa = NormalizedDfa::from(&mut dfa);
     
     // Test with lowercase letters
@@ -445,7 +470,7 @@ fn test_simulate_with_nested_patterns() {
     // Create a DFA for pattern "a(b|c)d"
     let pattern = "a(b|c)d";
     let nfa = post2nfa(into_postfix(pattern), 1).unwrap();
-    let mut dfa = Dfa::new(vec![nfa]);
+    let mut dfa = Dfa::new(&[&nfa]);
     let normalized_dfa = NormalizedDfa::from(&mut dfa);
     
     // Test valid matches
@@ -463,7 +488,7 @@ fn test_simulate_with_alternations() {
     // Create a DFA with multiple alternative patterns of different lengths
     let pattern = "a|abc|abcde";
     let nfa = post2nfa(into_postfix(pattern), 1).unwrap();
-    let mut dfa = Dfa::new(vec![nfa]);
+    let mut dfa = Dfa::new(&[&nfa]);
     let normalized_dfa = NormalizedDfa::from(&mut dfa);
     
     // Test all valid matches - should match the longest possible
@@ -490,7 +515,7 @@ fn test_simulate_with_complex_repetition() {
     // Create a DFA with complex repetition
     let pattern = "a{2,4}";  // matches "aa", "aaa", or "aaaa"
     let nfa = post2nfa(into_postfix(pattern), 1).unwrap();
-    let mut dfa = Dfa::new(vec![nfa]);
+    let mut dfa = Dfa::new(&[&nfa]);
     let normalized_dfa = NormalizedDfa::from(&mut dfa);
     
     // Test valid matches
@@ -510,7 +535,7 @@ fn test_simulate_with_complex_anchors() {
     // Create a DFA with complex anchored pattern
     let pattern = "^a.*b$";
     let nfa = post2nfa(into_postfix(pattern), 1).unwrap();
-    let mut dfa = Dfa::new(vec![nfa]);
+    let mut dfa = Dfa::new(&[&nfa]);
     let normalized_dfa = NormalizedDfa::from(&mut dfa);
     
     // Test valid matches
@@ -529,7 +554,7 @@ fn test_simulate_with_multiline() {
     // Test pattern with both start and end anchors
     let pattern = "^a$";
     let nfa = post2nfa(into_postfix(pattern), 1).unwrap();
-    let mut dfa = Dfa::new(vec![nfa]);
+    let mut dfa = Dfa::new(&[&nfa]);
     let normalized_dfa = NormalizedDfa::from(&mut dfa);
     
     // Test with multiline text
@@ -540,7 +565,7 @@ fn test_simulate_with_multiline() {
     // Test another multiline pattern
     let pattern = "^b$";
     let nfa = post2nfa(into_postfix(pattern), 1).unwrap();
-    let mut dfa = Dfa::new(vec![nfa]);
+    let mut dfa = Dfa::new(&[&nfa]);
     let normalized_dfa = NormalizedDfa::from(&mut dfa);
     
     let result = simulate("b\nc", &normalized_dfa);
@@ -553,7 +578,7 @@ fn test_simulate_with_overlapping_patterns() {
     // Create a DFA with overlapping patterns: "ab" and "abc"
     let nfa1 = post2nfa(into_postfix("ab"), 1).unwrap();
     let nfa2 = post2nfa(into_postfix("abc"), 2).unwrap();
-    let mut dfa = Dfa::new(vec![nfa1, nfa2]);
+    let mut dfa = Dfa::new(&[&nfa1, &nfa2]);
     let normalized_dfa = NormalizedDfa::from(&mut dfa);
     
     // Test with "abc" - should match the longer pattern (id 2)
@@ -574,7 +599,7 @@ fn test_simulate_with_zero_width_patterns() {
     // Create a DFA with a pattern that can match zero-width: "a*"
     let pattern = "a*";
     let nfa = post2nfa(into_postfix(pattern), 1).unwrap();
-    let mut dfa = Dfa::new(vec![nfa]);
+    let mut dfa = Dfa::new(&[&nfa]);
     let normalized_dfa = NormalizedDfa::from(&mut dfa);
     
     // Test with empty string (should match)
@@ -593,7 +618,7 @@ fn test_simulate_with_complex_character_classes() {
     // Create a DFA with a complex character class: [a-zA-Z0-9_]+
     let pattern = "[a-zA-Z0-9_]+";
     let nfa = post2nfa(into_postfix(pattern), 1).unwrap();
-    let mut dfa = Dfa::new(vec![nfa]);
+    let mut dfa = Dfa::new(&[&nfa]);
     let normalized_dfa = NormalizedDfa::from(&mut dfa);
     
     // Test with various valid inputs
@@ -612,10 +637,38 @@ fn test_simulate_with_complex_character_classes() {
 }
 
 
+#[test]
+fn test_simulate_match_id() {
+    let mut dfa = create_test_dfa_with_multiple_matches();
+    let normalized_dfa = NormalizedDfa::from(&mut dfa);
+
+    assert_eq!(simulate("a", &normalized_dfa).unwrap().id(), 1);
+    assert_eq!(simulate("b", &normalized_dfa).unwrap().id(), 2);
+}
+
+#[test]
+fn test_simulate_from_start_states() {
+    let a = post2nfa(into_postfix("a+"), 1).unwrap();
+    let b = post2nfa(into_postfix("^b"), 2).unwrap();
+
+    let mut dfa = Dfa::with_starts(&[&a, &b], vec![vec![0, 1], vec![0]]);
+    let normalized_dfa = NormalizedDfa::from(&mut dfa);
+
+    let (all, only_a) = (normalized_dfa.starts[0], normalized_dfa.starts[1]);
+
+    assert_eq!(simulate_from("aa", &normalized_dfa, only_a, true).unwrap().length(), 2);
+    assert!(simulate_from("b", &normalized_dfa, only_a, true).is_none());
+
+    // `^b` only matches at the beginning of a line
+    assert_eq!(simulate_from("b", &normalized_dfa, all, true).unwrap().id(), 2);
+    assert!(simulate_from("b", &normalized_dfa, all, false).is_none());
+}
+
+
 fn test_simulate(pattern: &str, valid_matches: Vec<&str>, invalid_matches: Vec<&str>) {
     // Create a DFA with complex anchored pattern
     let nfa = post2nfa(into_postfix(pattern), 1).unwrap();
-    let mut dfa = Dfa::new(vec![nfa]);
+    let mut dfa = Dfa::new(&[&nfa]);
     let normalized_dfa = NormalizedDfa::from(&mut dfa);
 
     // Test valid matches
@@ -0,0 +1,262 @@
+use std::io::Cursor;
+
+use crate::parsing::{Parsing, Reader};
+use crate::regex::*;
+use crate::regex::dfa::normalizer::*;
+
+// ==============================
+// Test Utilities
+// ==============================
+
+/// Xorshift generator, so the fuzzed inputs are the same on every run
+struct Prng(u64);
+
+impl Prng {
+	fn new(seed: u64) -> Self {
+		Prng(seed.max(1))
+	}
+
+	fn next(&mut self) -> u64 {
+		self.0 ^= self.0 << 13;
+		self.0 ^= self.0 >> 7;
+		self.0 ^= self.0 << 17;
+		self.0
+	}
+
+	/// Number in `0..bound`
+	fn below(&mut self, bound: usize) -> usize {
+		(self.next() % bound as u64) as usize
+	}
+
+	fn pick<'a, T>(&mut self, items: &'a [T]) -> &'a T {
+		&items[self.below(items.len())]
+	}
+}
+
+fn parse(spec: &str) -> Parsing {
+	let mut parsing = Parsing::new().unwrap();
+	let mut reader = Reader::new(Cursor::new(spec.as_bytes().to_vec()), "<test>").unwrap();
+
+	let _ = parsing.parse_sections(&mut reader);
+
+	assert!(parsing.errors.iter().all(|err| err.is_warning()), "invalid test specification {spec:?}: {:?}", parsing.errors);
+
+	parsing
+}
+
+/// (rule, length) of every match of the prefixes of `input`, from the best one
+fn found(matches: Vec<Match>) -> Vec<(usize, usize)> {
+	matches.into_iter()
+		.map(|match_| (match_.id(), match_.length()))
+		.collect()
+}
+
+/// Checks that `matcher` finds the same matches as `reference` on every input,
+/// in each start condition
+fn assert_agree(reference: &impl Matcher, matcher: &impl Matcher, context: &str, inputs: &[String]) {
+	assert_eq!(matcher.conditions(), reference.conditions(), "{context}");
+
+	for condition in 0..reference.conditions() {
+		for input in inputs {
+			for at_bol in [true, false] {
+				assert_eq!(
+					found(matcher.matches(input, condition, at_bol)),
+					found(reference.matches(input, condition, at_bol)),
+					"{context}: input {input:?} in the condition {condition} (at_bol: {at_bol})"
+				);
+			}
+		}
+	}
+}
+
+/// Checks that the DFA of the rules of `spec` and their NFA simulation agree on every input
+fn assert_spec_agrees(spec: &str, inputs: &[String]) {
+	let parsing = parse(spec);
+
+	let simulation = NfaSimulation::from_rules(&parsing.rules, &parsing.definitions);
+
+	let mut dfa = Dfa::from_rules(&parsing.rules, &parsing.definitions);
+	let dfa = NormalizedDfa::from(&mut dfa);
+
+	assert_agree(&simulation, &dfa, spec, inputs);
+}
+
+/// Checks that the DFA of `patterns` (rules 1, 2...) and their NFA simulation agree on every input,
+/// with and without equivalence classes
+fn assert_patterns_agree(patterns: &[&str], inputs: &[String]) {
+	let nfas: Vec<Nfa> = patterns.iter()
+		.enumerate()
+		.map(|(index, pattern)| Regex::new(pattern.to_string(), index + 1).unwrap())
+		.collect();
+	let nfas: Vec<&Nfa> = nfas.iter().collect();
+
+	let simulation = NfaSimulation::new(&nfas);
+	let context = format!("{patterns:?}");
+
+	let dfa = NormalizedDfa::from(&mut Dfa::new(&nfas));
+	assert_agree(&simulation, &dfa, &context, inputs);
+
+	let classes = EquivalenceClasses::from_nfas(&nfas);
+	let dfa = NormalizedDfa::from(&mut Dfa::with_classes(&nfas, vec![(0..nfas.len()).collect()], classes));
+	assert_agree(&simulation, &dfa, &context, inputs);
+}
+
+fn strings(inputs: &[&str]) -> Vec<String> {
+	inputs.iter().map(|input| input.to_string()).collect()
+}
+
+/// Every string of `alphabet` up to `max_length` characters
+fn all_strings(alphabet: &[char], max_length: usize) -> Vec<String> {
+	let mut strings = vec![String::new()];
+	let mut last = vec![String::new()];
+
+	for _ in 0..max_length {
+		last = last.iter()
+			.flat_map(|prefix| alphabet.iter().map(move |c| format!("{prefix}{c}")))
+			.collect();
+
+		strings.extend(last.iter().cloned());
+	}
+
+	strings
+}
+
+// ==============================
+// Differential Tests
+// ==============================
+
+#[test]
+fn test_engines_agree_on_literals_and_repetitions() {
+	let inputs = all_strings(&['a', 'b', 'c'], 5);
+
+	assert_patterns_agree(&["abc", "ab", "a"], &inputs);
+	assert_patterns_agree(&["a*", "b+", "c?"], &inputs);
+	assert_patterns_agree(&["(ab)*c", "a(b|c)*", "(a|b)*a(a|b)"], &inputs);
+	assert_patterns_agree(&["a{2}", "a{1,3}b", "(ab){2,}"], &inputs);
+	assert_patterns_agree(&["(a*)*b", "(a|b?)+c", "((a|b)c?)*"], &inputs);
+}
+
+#[test]
+fn test_engines_agree_on_character_classes() {
+	let inputs = all_strings(&['a', 'b', 'z', '0', '\n', ' '], 3);
+
+	assert_patterns_agree(&["[a-z]+", "[0-9]", "[^a-z]"], &inputs);
+	assert_patterns_agree(&[".", ".*", "[ab]*z"], &inputs);
+	assert_patterns_agree(&["[^\\n]+", "\\n", "[ ]+"], &inputs);
+}
+
+#[test]
+fn test_engines_agree_on_anchors() {
+	let inputs = all_strings(&['a', 'b', '\n'], 5);
+
+	assert_patterns_agree(&["^a", "a$", "a"], &inputs);
+	assert_patterns_agree(&["^a*$", "b*", "^\\n"], &inputs);
+	assert_patterns_agree(&["^(ab)+", "(a|b)+$", "\\n"], &inputs);
+	assert_spec_agrees("%%\n^ab$\tone();\n^a\ttwo();\nb$\tthree();\n.|\\n\t;\n", &inputs);
+}
+
+#[test]
+fn test_engines_agree_on_trailing_contexts() {
+	let inputs = all_strings(&['a', 'b', 'c'], 5);
+
+	assert_spec_agrees("%%\nab/cd\tone();\n[a-z]+/[0-9]+\ttwo();\na*/b*c\tthree();\n", &inputs);
+	assert_spec_agrees("%%\na+/b\tone();\n(a|b)/b*$\ttwo();\nc\tthree();\n", &inputs);
+}
+
+#[test]
+fn test_engines_agree_on_start_conditions() {
+	assert_spec_agrees(
+		"%x STRING\n%s OTHER\n%%\n\\\"\tBEGIN STRING;\n<STRING>[^\"]*\tstring();\n<STRING>\\\"\tBEGIN 0;\n<OTHER>x\tx();\n<INITIAL,OTHER>^[a-z]+\tword();\n",
+		&strings(&["\"abc\"", "abc", "x", "xyz\"", "", "\n\"", "ab\ncd"]),
+	);
+}
+
+#[test]
+fn test_engines_agree_on_the_examples() {
+	let inputs = strings(&["if", "int", "into", "12.5", "12.", "x y", "", "+", "auto", "owner", "automobile", "42", " \t\n"]);
+
+	assert_spec_agrees(
+		"%%\nif|int\tkeyword();\n[a-z]+\tword();\n[0-9]+(\\.[0-9]+)?\tnumber();\n.|\\n\t;\n",
+		&inputs,
+	);
+	assert_spec_agrees(include_str!("../../../examples/keywords.lex"), &inputs);
+}
+
+// ==============================
+// Fuzzing
+// ==============================
+
+/// Random pattern over the characters `a`, `b` and `c`, of at most `depth` nested operators
+fn random_pattern(prng: &mut Prng, depth: usize) -> String {
+	if depth == 0 {
+		return prng.pick(&["a", "b", "c", "[ab]", "[^a]", "."]).to_string();
+	}
+
+	let pattern = random_pattern(prng, depth - 1);
+	let other_depth = prng.below(depth);
+
+	match prng.below(8) {
+		0 => format!("{pattern}*"),
+		1 => format!("{pattern}+"),
+		2 => format!("{pattern}?"),
+		3 => format!("({pattern}){{1,2}}"),
+		4 => format!("({pattern}|{})", random_pattern(prng, other_depth)),
+		_ => format!("{pattern}{}", random_pattern(prng, other_depth)),
+	}
+}
+
+/// Random rule: a pattern, anchored or followed by a trailing context sometimes
+fn random_rule(prng: &mut Prng) -> String {
+	let depth = 1 + prng.below(3);
+	let mut rule = random_pattern(prng, depth);
+
+	match prng.below(8) {
+		0 => rule = format!("^{rule}"),
+		1 => rule = format!("{rule}$"),
+		2 => rule = format!("{rule}/{}", random_pattern(prng, 1)),
+		_ => {},
+	}
+
+	rule
+}
+
+fn random_input(prng: &mut Prng, max_length: usize) -> String {
+	let length = prng.below(max_length + 1);
+
+	(0..length)
+		.map(|_| *prng.pick(&['a', 'b', 'c', 'd', '\n']))
+		.collect()
+}
+
+#[test]
+fn test_fuzz_engines_agree() {
+	let mut prng = Prng::new(0x5eed_f00d);
+
+	for _ in 0..100 {
+		let rules: Vec<String> = (0..1 + prng.below(4))
+			.map(|_| random_rule(&mut prng))
+			.collect();
+
+		let mut spec = String::from("%%\n");
+
+		for (index, rule) in rules.iter().enumerate() {
+			spec.push_str(&format!("{rule}\trule{index}();\n"));
+		}
+
+		let inputs: Vec<String> = (0..20)
+			.map(|_| random_input(&mut prng, 8))
+			.collect();
+
+		assert_spec_agrees(&spec, &inputs);
+
+		// the same patterns, without the rules
+		let patterns: Vec<&str> = rules.iter()
+			.filter(|rule| !rule.contains('/'))
+			.map(String::as_str)
+			.collect();
+
+		if !patterns.is_empty() {
+			assert_patterns_agree(&patterns, &inputs);
+		}
+	}
+}
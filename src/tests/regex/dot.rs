@@ -0,0 +1,166 @@
+use crate::regex::*;
+use crate::regex::dfa::normalizer::*;
+
+fn nfa(pattern: &str, id: usize) -> Nfa {
+	Regex::new(pattern.to_string(), id).unwrap()
+}
+
+fn normalized(patterns: &[&str]) -> NormalizedDfa {
+	let nfas: Vec<Nfa> = patterns.iter()
+		.enumerate()
+		.map(|(index, pattern)| nfa(pattern, index + 1))
+		.collect();
+
+	NormalizedDfa::from(&mut Dfa::new(&nfas.iter().collect::<Vec<_>>()))
+}
+
+fn edges(dot: &str) -> Vec<&str> {
+	dot.lines()
+		.map(str::trim)
+		.filter(|line| line.contains("->") && !line.starts_with("start"))
+		.collect()
+}
+
+#[test]
+fn test_class_label_single_chars() {
+	assert_eq!(class_label(&['a']), "a");
+	assert_eq!(class_label(&['\n']), "\\n");
+	assert_eq!(class_label(&[' ']), "' '");
+	assert_eq!(class_label(&['-']), "-");
+}
+
+#[test]
+fn test_class_label_ranges() {
+	assert_eq!(class_label(&['c', 'a', 'b']), "[a-c]");
+	assert_eq!(class_label(&['a', 'b']), "[ab]");
+	assert_eq!(class_label(&['x', '0', '1', '2', '3', 'a', 'b', 'c', 'c']), "[0-3a-cx]");
+}
+
+#[test]
+fn test_class_label_escapes() {
+	assert_eq!(class_label(&['-', 'a']), "[\\-a]");
+	assert_eq!(class_label(&[']', '^']), "[\\]\\^]");
+	assert_eq!(class_label(&['\t', '\x01']), "[\\x01\\t]");
+}
+
+#[test]
+fn test_class_label_complement() {
+	// all the ascii chars but '\n'
+	let chars: Vec<char> = (0..=127_u8).map(char::from).filter(|c| *c != '\n').collect();
+
+	assert_eq!(class_label(&chars), "[^\\n]");
+
+	let all: Vec<char> = (0..=127_u8).map(char::from).collect();
+
+	assert_eq!(class_label(&all), "[\\x00-\\x7f]");
+}
+
+#[test]
+fn test_nfa_to_dot() {
+	let dot = nfa_to_dot(&[&nfa("ab", 1), &nfa("[0-9]", 2)]);
+
+	assert!(dot.starts_with("digraph nfa {"));
+	assert!(dot.ends_with("}\n"));
+
+	assert_eq!(dot.matches("start ->").count(), 2);
+	assert!(dot.contains("label=\"rule 1\""));
+	assert!(dot.contains("label=\"rule 2\""));
+
+	assert!(dot.contains("[label=\"a\"]"));
+	assert!(dot.contains("[label=\"b\"]"));
+	assert!(dot.contains("[label=\"[0-9]\"]"));
+}
+
+#[test]
+fn test_nfa_to_dot_shared_states() {
+	// a loop is written once
+	let dot = nfa_to_dot(&[&nfa("a*", 1)]);
+
+	assert_eq!(edges(&dot).iter().filter(|edge| edge.contains("label=\"a\"")).count(), 1);
+	assert_eq!(edges(&dot).iter().filter(|edge| edge.contains("ε")).count(), 2);
+}
+
+#[test]
+fn test_nfa_to_dot_line_conditions() {
+	let dot = nfa_to_dot(&[&nfa("^a$", 1)]);
+
+	assert!(dot.contains("[label=\"^\", style=dashed]"));
+	assert!(dot.contains("[label=\"$\", style=dashed]"));
+}
+
+#[test]
+fn test_nfa_to_dot_escapes() {
+	let dot = nfa_to_dot(&[&nfa("\"\\\"\"\\\\", 1)]);
+
+	assert!(dot.contains("[label=\"\\\"\"]"));
+	assert!(dot.contains("[label=\"\\\\\"]"));
+}
+
+#[test]
+fn test_dfa_to_dot() {
+	let dfa = normalized(&["[a-z]+", "[0-9]"]);
+	let dot = dfa_to_dot(&dfa, &["INITIAL".to_string()]);
+
+	assert!(dot.starts_with("digraph dfa {"));
+	assert!(dot.contains("start0 [shape=plaintext, label=\"INITIAL\"];"));
+
+	// one edge per next state, with the chars collapsed into ranges
+	let edges = edges(&dot);
+
+	assert_eq!(edges.len(), 3);
+	assert_eq!(edges.iter().filter(|edge| edge.contains("[a-z]")).count(), 2);
+	assert_eq!(edges.iter().filter(|edge| edge.contains("[0-9]")).count(), 1);
+
+	assert!(dot.contains("\\nrule 1\"]"));
+	assert!(dot.contains("\\nrule 2\"]"));
+}
+
+#[test]
+fn test_dfa_to_dot_lowest_rule() {
+	let dfa = normalized(&["\"if\"", "[a-z]+"]);
+	let dot = dfa_to_dot(&dfa, &[]);
+
+	let if_state = dot.lines()
+		.filter(|line| line.contains("shape=doublecircle"))
+		.filter(|line| line.contains("rule 1"))
+		.count();
+
+	assert_eq!(if_state, 1);
+}
+
+#[test]
+fn test_dfa_to_dot_start_names() {
+	let a = nfa("a", 1);
+
+	let mut dfa = Dfa::with_starts(&[&a], vec![vec![0], vec![0], vec![]]);
+	let dot = dfa_to_dot(&NormalizedDfa::from(&mut dfa), &["INITIAL".to_string(), "COMMENT".to_string()]);
+
+	assert!(dot.contains("label=\"INITIAL\""));
+	assert!(dot.contains("label=\"COMMENT\""));
+
+	// no name: the index of the start state
+	assert!(dot.contains("start2 [shape=plaintext, label=\"2\"];"));
+}
+
+#[test]
+fn test_dfa_to_dot_line_conditions() {
+	let dfa = normalized(&["^a", "b$"]);
+	let dot = dfa_to_dot(&dfa, &[]);
+
+	assert!(dot.contains("[label=\"^\", style=dashed]"));
+	assert!(dot.contains("[label=\"$\", style=dashed]"));
+}
+
+#[test]
+fn test_dfa_to_dot_with_classes() {
+	let nfas = [&nfa("[a-z]+", 1), &nfa("if", 2)];
+	let classes = EquivalenceClasses::from_nfas(&nfas);
+
+	let dfa = NormalizedDfa::from(&mut Dfa::with_classes(&nfas, vec![vec![0, 1]], classes));
+	let dot = dfa_to_dot(&dfa, &[]);
+
+	// the classes are written with their chars
+	assert!(dot.contains("[label=\"i\"]"));
+	assert!(dot.contains("[label=\"[a-hj-z]\"]"));
+	assert!(!dot.contains("Class"));
+}
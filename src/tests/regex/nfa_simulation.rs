@@ -1,13 +1,13 @@
 use crate::regex::*;
 
 #[cfg(test)]
-mod add_state_with_memo_iterative_tests {
+mod add_state_with_memo_tests {
     use super::*;
-    use std::{collections::HashSet, rc::Rc};
+    use std::collections::HashSet;
 
     // Helper function to create a deep linear chain of states
-    fn create_deep_chain(depth: usize) -> StatePtr {
-        let match_state = State::match_(0);
+    fn create_deep_chain(nfa: &mut Nfa, depth: usize) -> StateId {
+        let match_state = nfa.push(State::match_(0));
         if depth == 0 {
             return match_state;
         }
@@ -15,8 +15,8 @@ mod add_state_with_memo_iterative_tests {
         let mut current = match_state;
         for i in 0..depth {
             let char_val = (b'a' + (i % 26) as u8) as char;
-            let basic = State::basic(RegexType::Char(char_val));
-            basic.borrow_mut().into_basic().unwrap().out.replace(current);
+            let basic = nfa.push(State::basic(RegexType::Char(char_val)));
+            nfa.patch(OutPtr::first(basic), current);
             current = basic;
         }
         
@@ -24,15 +24,23 @@ mod add_state_with_memo_iterative_tests {
     }
     
     // Helper to create a deeply nested split tree
-    fn create_split_tree(depth: usize) -> StatePtr {
+    fn create_split_tree(nfa: &mut Nfa, depth: usize) -> StateId {
         if depth == 0 {
-            return State::match_(0);
+            return nfa.push(State::match_(0));
         }
         
-        let left = State::basic(RegexType::Char('a'));
-        let right = create_split_tree(depth - 1);
+        let left = nfa.push(State::basic(RegexType::Char('a')));
+        let right = create_split_tree(nfa, depth - 1);
         
-        State::split(left, right)
+        nfa.push(State::split(Some(left), Some(right)))
+    }
+
+    // Helper to create a split state between two new basic states
+    fn create_split(nfa: &mut Nfa, c1: char, c2: char) -> StateId {
+        let out1 = nfa.push(State::basic(RegexType::Char(c1)));
+        let out2 = nfa.push(State::basic(RegexType::Char(c2)));
+
+        nfa.push(State::split(Some(out1), Some(out2)))
     }
     
     // Helper to count states in a StateList
@@ -41,9 +49,9 @@ mod add_state_with_memo_iterative_tests {
     }
     
     // Helper to check if StateList contains specific character states
-    fn contains_char(list: &StateList, c: char) -> bool {
+    fn contains_char(nfa: &Nfa, list: &StateList, c: char) -> bool {
         list.iter().any(|state| {
-            if let Some(basic) = state.borrow().into_basic() {
+            if let Some(basic) = nfa[state].into_basic() {
                 if let Some(ch) = basic.c.char() {
                     return ch == c;
                 }
@@ -53,145 +61,152 @@ mod add_state_with_memo_iterative_tests {
     }
 
     // Helper to add all states in a chain to a list
-    fn add_all_states_in_chain(list: &mut StateList, state: &StatePtr, visited: &mut HashSet<*const State>) {
-        let mut current = Rc::clone(state);
+    fn add_all_states_in_chain(nfa: &Nfa, list: &mut StateList, state: StateId, visited: &mut HashSet<StateId>) {
+        let mut current = Some(state);
         
-        while !State::is_none_ptr(&current) && !State::is_match_ptr(&current) {
-            list.add_state_with_memo_iterative(&current, visited);
+        while let Some(state) = current {
+            list.add_state_with_memo(nfa, state, visited);
             
-			let tmp = current;
-			let borrow = tmp.borrow();
-            if let Some(basic) = &borrow.into_basic() {
-                current = Rc::clone(&basic.out.borrow());
-            } else {
-				drop(borrow);
-				current = tmp;
-                break;
-            }
+            current = nfa[state].out();
         }
-        
-        if State::is_match_ptr(&current) {
-            list.add_state_with_memo_iterative(&current, visited);
+    }
+
+    // Helper to add all the states reached from `state`, one by one
+    fn add_all_states(nfa: &Nfa, list: &mut StateList, state: StateId, visited: &mut HashSet<StateId>) {
+        let mut work_stack = vec![state];
+        let mut seen = HashSet::new();
+
+        while let Some(current) = work_stack.pop() {
+            if !seen.insert(current) {
+                continue;
+            }
+
+            list.add_state_with_memo(nfa, current, visited);
+
+            work_stack.extend(nfa[current].outs());
         }
     }
 
     #[test]
     fn test_basic_state_add() {
         // Create a basic state
-        let basic = State::basic(RegexType::Char('x'));
+        let mut nfa = Nfa::new();
+        let basic = nfa.push(State::basic(RegexType::Char('x')));
         
-        // Add to list using iterative method
+        // Add to list
         let mut list = StateList::new();
-        list.add_state_with_memo_iterative(&basic, &mut HashSet::new());
+        list.add_state_with_memo(&nfa, basic, &mut HashSet::new());
         
         // Verify list has exactly one state
         assert_eq!(count_states(&list), 1);
-        assert!(contains_char(&list, 'x'));
+        assert!(contains_char(&nfa, &list, 'x'));
     }
     
     #[test]
     fn test_match_state_add() {
         // Create a match state
-        let match_state = State::match_(0);
+        let mut nfa = Nfa::new();
+        let match_state = nfa.push(State::match_(0));
         
-        // Add to list using iterative method
+        // Add to list
         let mut list = StateList::new();
-        list.add_state_with_memo_iterative(&match_state, &mut HashSet::new());
+        list.add_state_with_memo(&nfa, match_state, &mut HashSet::new());
         
         // Verify list has exactly one state
         assert_eq!(count_states(&list), 1);
-        assert!(list.is_matched());
+        assert!(list.is_matched(&nfa));
     }
 
     #[test]
     fn test_simple_split_state() {
         // Create a split with two basic states
-        let a = State::basic(RegexType::Char('a'));
-        let b = State::basic(RegexType::Char('b'));
-        let split = State::split(a, b);
+        let mut nfa = Nfa::new();
+        let split = create_split(&mut nfa, 'a', 'b');
         
-        // Add to list using iterative method
+        // Add to list
         let mut list = StateList::new();
         let mut visited = HashSet::new();
         
         // Add split state - this should add out1 and out2 states
-        list.add_state_with_memo_iterative(&split, &mut visited);
+        list.add_state_with_memo(&nfa, split, &mut visited);
         
         // Verify list has exactly two states (a, b) but not the split itself
         assert_eq!(count_states(&list), 2);
-        assert!(contains_char(&list, 'a'));
-        assert!(contains_char(&list, 'b'));
+        assert!(contains_char(&nfa, &list, 'a'));
+        assert!(contains_char(&nfa, &list, 'b'));
+        assert!(!list.contains(split));
     }
     
     #[test]
     fn test_nested_split_state() {
         // Create a nested split: split(a, split(b, c))
-        let a = State::basic(RegexType::Char('a'));
-        let b = State::basic(RegexType::Char('b'));
-        let c = State::basic(RegexType::Char('c'));
-        let inner_split = State::split(b, c);
-        let outer_split = State::split(a, inner_split);
+        let mut nfa = Nfa::new();
+        let a = nfa.push(State::basic(RegexType::Char('a')));
+        let inner_split = create_split(&mut nfa, 'b', 'c');
+        let outer_split = nfa.push(State::split(Some(a), Some(inner_split)));
         
-        // Add to list using iterative method
+        // Add to list
         let mut list = StateList::new();
         let mut visited = HashSet::new();
         
         // Add outer split - this should add 'a' and process inner_split
-        list.add_state_with_memo_iterative(&outer_split, &mut visited);
+        list.add_state_with_memo(&nfa, outer_split, &mut visited);
         
         // Verify list has exactly three states (a, b, c) but not the splits
         assert_eq!(count_states(&list), 3);
-        assert!(contains_char(&list, 'a'));
-        assert!(contains_char(&list, 'b'));
-        assert!(contains_char(&list, 'c'));
+        assert!(contains_char(&nfa, &list, 'a'));
+        assert!(contains_char(&nfa, &list, 'b'));
+        assert!(contains_char(&nfa, &list, 'c'));
     }
     
     #[test]
     fn test_already_visited_state() {
         // Create a state that would be visited multiple times
-        let shared = State::basic(RegexType::Char('x'));
+        let mut nfa = Nfa::new();
+        let shared = nfa.push(State::basic(RegexType::Char('x')));
         
         // Create a structure that visits the shared state twice
-        let a = State::basic(RegexType::Char('a'));
-        a.borrow_mut().into_basic().unwrap().out.replace(shared.clone());
+        let a = nfa.push(State::basic(RegexType::Char('a')));
+        nfa.patch(OutPtr::first(a), shared);
         
-        let b = State::basic(RegexType::Char('b'));
-        b.borrow_mut().into_basic().unwrap().out.replace(shared.clone());
+        let b = nfa.push(State::basic(RegexType::Char('b')));
+        nfa.patch(OutPtr::first(b), shared);
         
-        let split = State::split(Rc::clone(&a), b);
+        let split = nfa.push(State::split(Some(a), Some(b)));
         
         // First try without the visited set to confirm duplicate detection
         let mut list1 = StateList::new();
         let mut visited = HashSet::new();
-        list1.add_state_with_memo_iterative(&split, &mut visited);
+        list1.add_state_with_memo(&nfa, split, &mut visited);
 
         // Verify shared state is only added once - we should have 2 states: a, b
         assert_eq!(count_states(&list1), 2);
-        assert!(contains_char(&list1, 'a'));
-        assert!(contains_char(&list1, 'b'));
+        assert!(contains_char(&nfa, &list1, 'a'));
+        assert!(contains_char(&nfa, &list1, 'b'));
 
         // Adding 'a' and 'b' states out (x). This should add 'x' state one time
-		let borrow = a.borrow();
-        list1.add_state_with_memo_iterative(&borrow.into_basic().unwrap().out.borrow(), &mut visited);
+        list1.add_state_with_memo(&nfa, nfa[a].out().unwrap(), &mut visited);
+        list1.add_state_with_memo(&nfa, nfa[b].out().unwrap(), &mut visited);
         
         // Verify 'x' is now added
         assert_eq!(count_states(&list1), 3);
-        assert!(contains_char(&list1, 'a'));
-        assert!(contains_char(&list1, 'b'));
-        assert!(contains_char(&list1, 'x'));
+        assert!(contains_char(&nfa, &list1, 'a'));
+        assert!(contains_char(&nfa, &list1, 'b'));
+        assert!(contains_char(&nfa, &list1, 'x'));
     }
     
     #[test]
     fn test_contains_check() {
         // Create a state
-        let a = State::basic(RegexType::Char('a'));
+        let mut nfa = Nfa::new();
+        let a = nfa.push(State::basic(RegexType::Char('a')));
         
         // Add it to the list first
         let mut list = StateList::new();
-        list.push(&a);
+        list.push(a);
 
-        // Now try to add it again with the iterative method
-        list.add_state_with_memo_iterative(&a, &mut HashSet::new());
+        // Now try to add it again
+        list.add_state_with_memo(&nfa, a, &mut HashSet::new());
         
         // Verify it's still only in the list once
         assert_eq!(count_states(&list), 1);
@@ -200,14 +215,15 @@ mod add_state_with_memo_iterative_tests {
     #[test]
     fn test_deep_structure() {
         // Create a deep structure that would likely cause stack overflow with recursion
-        let deep_chain = create_deep_chain(1000);
+        let mut nfa = Nfa::new();
+        let deep_chain = create_deep_chain(&mut nfa, 1000);
 
-        // Add using iterative method - but we need to manually traverse the chain
+        // Add the states - but we need to manually traverse the chain
         let mut list = StateList::new();
         let mut visited = HashSet::new();
         
         // Add each state in the chain one by one
-        add_all_states_in_chain(&mut list, &deep_chain, &mut visited);
+        add_all_states_in_chain(&nfa, &mut list, deep_chain, &mut visited);
         
         // Verify correct number of states (should have the whole chain)
         assert_eq!(count_states(&list), 1001); // 1000 basic states + 1 match state
@@ -216,101 +232,69 @@ mod add_state_with_memo_iterative_tests {
     #[test]
     fn test_deep_split_tree() {
         // Create a deeply nested split tree
-        let split_tree = create_split_tree(100);
-        
-        // Add using iterative method
-        let mut list = StateList::new();
-        let mut visited = HashSet::new();
+        let mut nfa = Nfa::new();
+        let split_tree = create_split_tree(&mut nfa, 100);
         
-        // Need to traverse the split tree manually
-        let mut work_stack = vec![Rc::clone(&split_tree)];
-        
-        while let Some(current) = work_stack.pop() {
-            list.add_state_with_memo_iterative(&current, &mut visited);
-            
-			let tmp = current;
-            if State::is_split_ptr(&tmp) {
-				let borrow = tmp.borrow();
-                let split = borrow.into_split().unwrap();
-                work_stack.push(Rc::clone(&split.out1.borrow()));
-                work_stack.push(Rc::clone(&split.out2.borrow()));
-            } else if let Some(basic) = tmp.borrow().into_basic() {
-                if !State::is_none_ptr(&basic.out.borrow()) {
-                    work_stack.push(Rc::clone(&basic.out.borrow()));
-                }
-            }
-        }
+        // A single call follows all the splits
+        let list = StateList::from(&nfa, split_tree);
         
         // Should have 100 basic states ('a') + 1 match state
         assert_eq!(count_states(&list), 101);
     }
+
+    #[test]
+    fn test_deep_split_chain() {
+        // split(a, split(a, split(a, ... match))), deep enough to overflow a recursive closure
+        let mut nfa = Nfa::new();
+        let mut split_tree = nfa.push(State::match_(0));
+
+        for _ in 0..100_000 {
+            let left = nfa.push(State::basic(RegexType::Char('a')));
+            split_tree = nfa.push(State::split(Some(left), Some(split_tree)));
+        }
+
+        let list = StateList::from(&nfa, split_tree);
+
+        assert_eq!(count_states(&list), 100_001);
+        assert!(list.is_matched(&nfa));
+    }
     
     #[test]
-    fn test_compare_with_recursive() {
-        // Test with structures small enough not to overflow the recursive version
+    fn test_compare_with_single_call() {
+        let mut nfa = Nfa::new();
+
+        // Test with structures small enough to be checked by hand
         let test_cases = vec![
-            State::basic(RegexType::Char('a')),
-            State::match_(0),
-            State::split(
-                State::basic(RegexType::Char('a')), 
-                State::basic(RegexType::Char('b'))
-            ),
-            create_deep_chain(10),
-            create_split_tree(5)
+            nfa.push(State::basic(RegexType::Char('a'))),
+            nfa.push(State::match_(0)),
+            create_split(&mut nfa, 'a', 'b'),
+            create_deep_chain(&mut nfa, 10),
+            create_split_tree(&mut nfa, 5)
         ];
         
         for test_case in test_cases {
-            // Create two identical lists
-            let mut recursive_list = StateList::new();
-            let mut iterative_list = StateList::new();
-            let mut visited_recursive = HashSet::new();
-            let mut visited_iterative = HashSet::new();
-            
-            // Fill recursive list using recursive method
-            
-            // For iterative list, we need to manually traverse the structure
-            let mut work_stack = vec![Rc::clone(&test_case)];
-            
-            while let Some(current) = work_stack.pop() {
-				recursive_list.add_state_with_memo(&current, &mut visited_recursive);
-                iterative_list.add_state_with_memo_iterative(&current, &mut visited_iterative);
-                
-                if State::is_split_ptr(&current) {
-					let tmp = current;
-					let borrow = tmp.borrow();
-                    let split = borrow.into_split().unwrap();
-                    work_stack.push(Rc::clone(&split.out1.borrow()));
-                    work_stack.push(Rc::clone(&split.out2.borrow()));
-                } else if let Some(basic) = current.borrow().into_basic() {
-                    if !State::is_none_ptr(&basic.out.borrow()) {
-                        work_stack.push(Rc::clone(&basic.out.borrow()));
-                    }
-                }
-            }
+            // Fill the first list with a single call: the epsilon closure of the state
+            let closure = StateList::from(&nfa, test_case);
+
+            // Fill the second list with every state reached, one by one
+            let mut all_states = StateList::new();
+            add_all_states(&nfa, &mut all_states, test_case, &mut HashSet::new());
             
-            // Compare results
-            assert_eq!(
-                count_states(&recursive_list), 
-                count_states(&iterative_list),
-                "State count should be identical"
+            // The closure is a part of all the states reached
+            assert!(
+                count_states(&closure) <= count_states(&all_states),
+                "The closure should not have more states"
             );
             
             // Verify same character states
-            for state in &recursive_list {
-                if let Some(basic) = state.borrow().into_basic() {
-                    if let Some(ch) = basic.c.char() {
-                        assert!(
-                            contains_char(&iterative_list, ch),
-                            "Iterative list should contain char '{}'", ch
-                        );
-                    }
-                }
+            for state in &closure {
+                assert!(all_states.contains(state), "All the states should contain the state {}", state);
             }
             
             // Check match states
             assert_eq!(
-                recursive_list.is_matched(),
-                iterative_list.is_matched(),
+                closure.is_matched(&nfa),
+                nfa[test_case].is_match() || nfa[test_case].is_split() && all_states.is_matched(&nfa),
                 "Match state presence should be identical"
             );
         }
@@ -319,27 +303,289 @@ mod add_state_with_memo_iterative_tests {
     #[test]
     fn test_processing_order() {
         // Create a split with character states a,b,c in a specific order
-        let a = State::basic(RegexType::Char('a'));
-        let b = State::basic(RegexType::Char('b'));
-        let c = State::basic(RegexType::Char('c'));
-        
-        let split1 = State::split(a, b);
-        let split2 = State::split(Rc::clone(&split1), c);
+        let mut nfa = Nfa::new();
+        let split1 = create_split(&mut nfa, 'a', 'b');
+        let c = nfa.push(State::basic(RegexType::Char('c')));
+        let split2 = nfa.push(State::split(Some(split1), Some(c)));
         
         // Add to list
         let mut list = StateList::new();
         let mut visited = HashSet::new();
         
-        // Need to manually process all splits
-        list.add_state_with_memo_iterative(&split2, &mut visited);
+        // The splits are all followed
+        list.add_state_with_memo(&nfa, split2, &mut visited);
         
-        // Process split1 from split2's out1
-        list.add_state_with_memo_iterative(&split1, &mut visited);
+        // Adding split1 again adds nothing
+        list.add_state_with_memo(&nfa, split1, &mut visited);
         
         // Verify all three basic states are present
         assert_eq!(count_states(&list), 3);
-        assert!(contains_char(&list, 'a'));
-        assert!(contains_char(&list, 'b'));
-        assert!(contains_char(&list, 'c'));
+        assert!(contains_char(&nfa, &list, 'a'));
+        assert!(contains_char(&nfa, &list, 'b'));
+        assert!(contains_char(&nfa, &list, 'c'));
+
+        // The list is sorted by state id
+        assert!(list.iter().zip(list.iter().skip(1)).all(|(a, b)| a < b));
+    }
+}
+
+#[cfg(test)]
+mod state_list_tests {
+    use super::*;
+    use std::collections::{HashSet, VecDeque};
+
+    fn into_postfix(str: &str) -> VecDeque<TokenType> {
+        re2post(Regex::add_concatenation(Regex::tokens(str).unwrap())).unwrap()
+    }
+
+    fn list_of(states: &[StateId]) -> StateList {
+        let mut list = StateList::new();
+
+        for state in states {
+            list.push(*state);
+        }
+
+        list
+    }
+
+    #[test]
+    fn test_iteration_order() {
+        let list = list_of(&[300, 2, 64, 0, 63, 130]);
+
+        assert_eq!(list.iter().collect::<Vec<_>>(), vec![0, 2, 63, 64, 130, 300]);
+        assert_eq!(list.len(), 6);
+        assert_eq!(list.to_string(), "StateList[0, 2, 63, 64, 130, 300]");
+    }
+
+    #[test]
+    fn test_contains() {
+        let list = list_of(&[70, 200]);
+
+        assert!(list.contains(70));
+        assert!(list.contains(200));
+
+        // before, between and after the stored words
+        assert!(!list.contains(0));
+        assert!(!list.contains(71));
+        assert!(!list.contains(199));
+        assert!(!list.contains(10_000));
+    }
+
+    #[test]
+    fn test_equality_does_not_depend_on_insertion_order() {
+        let a = list_of(&[5, 500, 1000]);
+        let b = list_of(&[1000, 5, 500]);
+
+        assert_eq!(a, b);
+        assert_eq!(a.hash_code(), b.hash_code());
+        assert_ne!(a, list_of(&[5, 500]));
+    }
+
+    #[test]
+    fn test_equality_after_removing_states() {
+        let mut nfa = Nfa::new();
+        let mut states: Vec<StateId> = (0..200).map(|_| nfa.push(State::basic(RegexType::Char('a')))).collect();
+
+        // match states at both ends of the list
+        states.insert(0, nfa.push(State::match_(1)));
+        let first_match = states[0];
+        let high_match = nfa.push(State::match_(2));
+
+        let mut list = list_of(&states[100..]);
+        list.push(first_match);
+        list.push(high_match);
+
+        let mut matchs = list.remove_matchs(&nfa);
+        matchs.sort();
+
+        assert_eq!(matchs, vec![first_match, high_match]);
+
+        // the empty words left by the removed states are not kept
+        let expected = list_of(&states[100..]);
+        assert_eq!(list, expected);
+        assert_eq!(list.hash_code(), expected.hash_code());
+    }
+
+    #[test]
+    fn test_remove_all_states() {
+        let mut nfa = Nfa::new();
+        let match_ = nfa.push(State::match_(1));
+
+        let mut list = list_of(&[match_]);
+        list.remove_matchs(&nfa);
+
+        assert!(list.is_empty());
+        assert_eq!(list, StateList::new());
+    }
+
+    #[test]
+    fn test_union() {
+        let mut list = list_of(&[200, 300]);
+
+        // before, overlapping and after the states of the list
+        list.union(&list_of(&[1, 250]));
+        list.union(&list_of(&[300, 1000]));
+        list.union(&StateList::new());
+
+        assert_eq!(list, list_of(&[1, 200, 250, 300, 1000]));
+
+        let mut empty = StateList::new();
+        empty.union(&list);
+        assert_eq!(empty, list);
+    }
+
+    #[test]
+    fn test_merge_and_clear() {
+        let mut list = StateList::new();
+
+        list.merge(list_of(&[64]));
+        list.merge(list_of(&[0]));
+        assert_eq!(list, list_of(&[0, 64]));
+
+        list.clear();
+        assert!(list.is_empty());
+        assert_eq!(list, StateList::new());
+    }
+
+    #[test]
+    fn test_epsilon_closures() {
+        // (a|b)c
+        let nfa = post2nfa(into_postfix("(a|b)c"), 1).unwrap();
+        let closures = EpsilonClosures::new(&nfa);
+
+        let closure = closures.of(nfa.start);
+        assert_eq!(closure, &StateList::from(&nfa, nfa.start));
+        assert_eq!(closure.len(), 2);
+
+        // computed once, the same list is returned
+        assert!(std::ptr::eq(closures.of(nfa.start), closure));
+
+        let mut list = StateList::new();
+        closures.add_to(&mut list, nfa.start);
+        closures.add_to(&mut list, nfa.start);
+        assert_eq!(&list, closure);
+    }
+
+    #[test]
+    fn test_epsilon_closures_match_add_state() {
+        let nfa = post2nfa(into_postfix("(ab|c*)*d?(e|f)"), 1).unwrap();
+        let closures = EpsilonClosures::new(&nfa);
+
+        for state in 0..nfa.len() {
+            let mut expected = StateList::new();
+            expected.add_state_with_memo(&nfa, state, &mut HashSet::new());
+
+            assert_eq!(closures.of(state), &expected, "closure of state {state}");
+        }
+    }
+}
+
+mod simulation_tests {
+    use super::*;
+    use std::io::Cursor;
+
+    use crate::parsing::{Parsing, Reader};
+    use crate::regex::dfa::normalizer::Match;
+
+    fn simulation(patterns: &[&str]) -> NfaSimulation {
+        let nfas: Vec<Nfa> = patterns.iter()
+            .enumerate()
+            .map(|(index, pattern)| Regex::new(pattern.to_string(), index + 1).unwrap())
+            .collect();
+
+        NfaSimulation::new(&nfas.iter().collect::<Vec<_>>())
+    }
+
+    fn rules_simulation(spec: &str) -> NfaSimulation {
+        let mut parsing = Parsing::new().unwrap();
+        let mut reader = Reader::new(Cursor::new(spec.as_bytes().to_vec()), "<test>").unwrap();
+
+        let _ = parsing.parse_sections(&mut reader);
+
+        assert!(parsing.errors.iter().all(|err| err.is_warning()), "invalid test specification: {:?}", parsing.errors);
+
+        NfaSimulation::from_rules(&parsing.rules, &parsing.definitions)
+    }
+
+    /// (rule, length) of every match, from the best one
+    fn found(matches: Vec<Match>) -> Vec<(usize, usize)> {
+        matches.into_iter()
+            .map(|match_| (match_.id(), match_.length()))
+            .collect()
+    }
+
+    fn best(simulation: &NfaSimulation, input: &str) -> Option<(usize, usize)> {
+        simulation.longest_match(input, 0, true).map(|match_| (match_.id(), match_.length()))
+    }
+
+    #[test]
+    fn test_simulation_literal() {
+        let simulation = simulation(&["abc"]);
+
+        assert_eq!(best(&simulation, "abc"), Some((1, 3)));
+        assert_eq!(best(&simulation, "abcd"), Some((1, 3)));
+        assert_eq!(best(&simulation, "ab"), None);
+        assert_eq!(best(&simulation, "xabc"), None);
+        assert_eq!(best(&simulation, ""), None);
+    }
+
+    #[test]
+    fn test_simulation_longest_match() {
+        let simulation = simulation(&["a", "a+", "ab*"]);
+
+        assert_eq!(best(&simulation, "aaa"), Some((2, 3)));
+        assert_eq!(best(&simulation, "abbb"), Some((3, 4)));
+
+        // the lowest rule first at the same length
+        assert_eq!(best(&simulation, "a"), Some((1, 1)));
+        assert_eq!(found(simulation.matches_from("ab", 0, true)), vec![(3, 2), (1, 1), (2, 1), (3, 1)]);
+    }
+
+    #[test]
+    fn test_simulation_empty_match() {
+        let simulation = simulation(&["a*"]);
+
+        assert_eq!(found(simulation.matches_from("aab", 0, true)), vec![(1, 2), (1, 1), (1, 0)]);
+        assert_eq!(best(&simulation, "b"), Some((1, 0)));
+    }
+
+    #[test]
+    fn test_simulation_character_classes() {
+        let simulation = simulation(&["[a-c]+", "[^a-c]", "."]);
+
+        assert_eq!(best(&simulation, "abcd"), Some((1, 3)));
+        assert_eq!(best(&simulation, "d"), Some((2, 1)));
+        assert_eq!(best(&simulation, "\n"), Some((2, 1)));
+        assert_eq!(best(&simulation, "é"), None);
+    }
+
+    #[test]
+    fn test_simulation_anchors() {
+        let simulation = simulation(&["^ab", "ab$", "[a-z]+"]);
+
+        assert_eq!(best(&simulation, "ab"), Some((1, 2)));
+        assert_eq!(simulation.longest_match("ab", 0, false).map(|match_| match_.id()), Some(3));
+        assert_eq!(simulation.longest_match("ab\n", 0, false).map(|match_| match_.id()), Some(2));
+        assert_eq!(best(&simulation, "abc"), Some((3, 3)));
+    }
+
+    #[test]
+    fn test_simulation_trailing_context() {
+        let simulation = rules_simulation("%%\nab/cd\tone();\n[a-z]+\ttwo();\n");
+
+        // the whole `abcd` is matched, only its head is kept
+        assert_eq!(best(&simulation, "abcd"), Some((1, 2)));
+        assert_eq!(best(&simulation, "abce"), Some((2, 4)));
+    }
+
+    #[test]
+    fn test_simulation_start_conditions() {
+        let simulation = rules_simulation("%x STRING\n%%\n\\\"\tBEGIN STRING;\n<STRING>[^\"]*\tstring();\n[a-z]+\tword();\n");
+
+        assert_eq!(simulation.conditions(), 2);
+
+        assert_eq!(best(&simulation, "abc"), Some((3, 3)));
+        assert_eq!(simulation.longest_match("abc", 1, true).map(|match_| (match_.id(), match_.length())), Some((2, 3)));
+        assert_eq!(simulation.longest_match("abc", 2, true).map(|match_| match_.id()), None);
     }
-}
\ No newline at end of file
+}
@@ -1,5 +1,5 @@
 use crate::regex::parsing::{RegexType, TokenType, CharacterClass, Quantifier};
-use crate::{Regex, Utils, ParsingError};
+use crate::{Regex, Utils, ParsingError, ParsingResult};
 use std::collections::{HashSet, VecDeque};
 
 // ==============================================
@@ -234,6 +234,36 @@ fn test_character_class_from_negated() {
 	assert_eq!(negated.len(), 127);
 }
 
+#[test]
+fn test_character_class_eight_bit() {
+    let class = CharacterClass::from_single('a').negated().eight_bit();
+    assert!(!class.negated);
+    assert_eq!(class.len(), 255);
+    assert!(class.contains(&'\u{e9}'));
+    assert!(class.contains(&'\u{ff}'));
+    assert!(!class.contains(&'a'));
+
+    // only the negated classes change
+    let class = CharacterClass::from_single('a').eight_bit();
+    assert_eq!(class.chars, vec!['a']);
+}
+
+#[test]
+fn test_eight_bit_tokens() {
+    let flags = crate::regex::RegexFlags { eight_bit: true, ..Default::default() };
+    let tokens = Regex::tokens_with_flags(".[^x]", flags).unwrap();
+
+    let classes: Vec<&CharacterClass> = tokens.iter().filter_map(RegexType::class).collect();
+    assert_eq!(classes.len(), 2);
+    assert!(classes.iter().all(|class| class.contains(&'\u{80}') && !class.negated));
+    assert!(!classes[0].contains(&'\n'));
+    assert!(!classes[1].contains(&'x'));
+
+    // without the flag, the negated classes only match ASCII
+    let tokens = Regex::tokens_with_flags(".", Default::default()).unwrap();
+    assert!(!tokens[0].match_(&'\u{80}'));
+}
+
 #[test]
 fn test_character_class_predefined_digit() {
     let class = CharacterClass::digit();
@@ -432,6 +462,138 @@ fn test_character_class_parse_escape_at_end() {
     assert!(err.message().contains("Escape sequence at end of character class"));
 }
 
+fn parse_class(input: &str) -> ParsingResult<CharacterClass> {
+    CharacterClass::parse(&mut input.chars().peekable())
+}
+
+#[test]
+fn test_character_class_posix_classes() {
+    type Predicate = fn(&char) -> bool;
+
+    let expected: [(&str, Predicate); 12] = [
+        ("alnum", char::is_ascii_alphanumeric),
+        ("alpha", char::is_ascii_alphabetic),
+        ("blank", |c| matches!(c, ' ' | '\t')),
+        ("cntrl", |c| (*c as u32) < 32 || *c == '\x7f'),
+        ("digit", |c| c.is_ascii_digit()),
+        ("graph", |c| ('!'..='~').contains(c)),
+        ("lower", |c| c.is_ascii_lowercase()),
+        ("print", |c| (' '..='~').contains(c)),
+        ("punct", |c| c.is_ascii_graphic() && !c.is_ascii_alphanumeric()),
+        ("space", |c| matches!(c, ' ' | '\t' | '\n' | '\x0b' | '\x0c' | '\r')),
+        ("upper", |c| c.is_ascii_uppercase()),
+        ("xdigit", |c| c.is_ascii_hexdigit()),
+    ];
+
+    for (name, predicate) in expected {
+        let class = parse_class(&format!("[:{name}:]]")).unwrap();
+
+        for c in (0..=127_u8).map(char::from) {
+            assert_eq!(class.contains(&c), predicate(&c), "{c:?} in [:{name}:]");
+        }
+
+        assert_eq!(CharacterClass::posix(name).unwrap(), class);
+    }
+}
+
+#[test]
+fn test_character_class_posix_with_other_chars() {
+    // [[:alpha:]_]
+    let class = parse_class("[:alpha:]_]").unwrap();
+    assert_eq!(class.len(), 53);
+    assert!(class.contains(&'_'));
+    assert!(class.contains(&'Z'));
+    assert!(!class.contains(&'0'));
+
+    // [[:digit:]a-f[:space:]]
+    let class = parse_class("[:digit:]a-f[:space:]]").unwrap();
+    assert_eq!(class.len(), 10 + 6 + 6);
+    assert!(class.contains(&'e'));
+    assert!(class.contains(&'\n'));
+    assert!(!class.contains(&'g'));
+
+    // a class does not start a range: the '-' is a literal
+    let class = parse_class("[:digit:]-z]").unwrap();
+    assert_eq!(class.len(), 12);
+    assert!(class.contains(&'-'));
+    assert!(!class.contains(&'y'));
+}
+
+#[test]
+fn test_character_class_posix_negated() {
+    let class = parse_class("^[:alnum:][:space:]]").unwrap();
+
+    assert!(class.negated);
+    assert_eq!(class.len(), 128 - 62 - 6);
+    assert!(class.contains(&'+'));
+    assert!(!class.contains(&'a'));
+    assert!(!class.contains(&' '));
+}
+
+#[test]
+fn test_character_class_posix_unknown() {
+    let err = parse_class("[:letter:]]").unwrap_err();
+    assert!(err.message().contains("Unknown POSIX class `[:letter:]`"));
+
+    let err = parse_class("[:alpha]").unwrap_err();
+    assert!(err.message().contains("Unclosed `[:` in character class"));
+}
+
+#[test]
+fn test_character_class_bracket_without_element() {
+    // `[` is a literal when it does not open an element
+    let class = parse_class("[a]").unwrap();
+    assert_eq!(class.len(), 2);
+    assert!(class.contains(&'['));
+    assert!(class.contains(&'a'));
+}
+
+#[test]
+fn test_character_class_equivalence_class() {
+    let class = parse_class("[=a=]b]").unwrap();
+    assert_eq!(class.len(), 2);
+    assert!(class.contains(&'a'));
+    assert!(class.contains(&'b'));
+
+    // an equivalence class does not start a range
+    let class = parse_class("[=a=]-c]").unwrap();
+    assert_eq!(class.len(), 3);
+    assert!(class.contains(&'-'));
+    assert!(!class.contains(&'b'));
+
+    let err = parse_class("[=ch=]]").unwrap_err();
+    assert!(err.message().contains("unsupported collating element `[=ch=]`"));
+}
+
+#[test]
+fn test_character_class_collating_symbol() {
+    let class = parse_class("[.-.]]").unwrap();
+    assert_eq!(class.len(), 1);
+    assert!(class.contains(&'-'));
+
+    // collating symbols as range bounds
+    let class = parse_class("[.a.]-[.c.]]").unwrap();
+    assert_eq!(class.len(), 3);
+    assert!(class.contains(&'b'));
+
+    let class = parse_class("Z-[.].]]").unwrap();
+    assert_eq!(class.len(), 4);
+    assert!(class.contains(&']'));
+
+    let err = parse_class("[.space.]]").unwrap_err();
+    assert!(err.message().contains("unsupported collating element `[.space.]`"));
+}
+
+#[test]
+fn test_tokens_with_posix_classes() {
+    let tokens = Regex::tokens("[[:alpha:]_][[:alnum:]_]*").unwrap();
+
+    assert_eq!(tokens.len(), 3);
+    assert_eq!(tokens[0].class().unwrap().len(), 53);
+    assert_eq!(tokens[1].class().unwrap().len(), 63);
+    assert_eq!(tokens[2], RegexType::Quant(Quantifier::AtLeast(0)));
+}
+
 // ==============================================
 // 4. QUANTIFIER TESTS
 // ==============================================
@@ -722,6 +884,59 @@ fn test_tokens_with_escaped_characters() {
     assert_eq!(result[7], RegexType::Char('$'));
 }
 
+#[test]
+fn test_tokens_with_numeric_escapes() {
+    let result = Regex::tokens("\\101\\x42\\0\\12\\x7\\cA\\cz\\c?\\t").unwrap();
+
+    let expected = ['A', 'B', '\0', '\n', '\x07', '\x01', '\x1a', '\x7f', '\t'];
+    assert_eq!(result, expected.map(RegexType::Char).into_iter().collect::<VecDeque<_>>());
+
+    // at most 3 octal or 2 hexadecimal digits
+    let result = Regex::tokens("\\1234\\x414\\8").unwrap();
+
+    let expected = ['S', '4', 'A', '4', '8'];
+    assert_eq!(result, expected.map(RegexType::Char).into_iter().collect::<VecDeque<_>>());
+
+    // the highest byte
+    assert_eq!(Regex::tokens("\\377").unwrap()[0], RegexType::Char('\u{ff}'));
+    assert_eq!(Regex::tokens("\\xFf").unwrap()[0], RegexType::Char('\u{ff}'));
+}
+
+#[test]
+fn test_tokens_with_numeric_escapes_in_strings_and_classes() {
+    let result = Regex::tokens("\"\\x61\\142\\cC\"").unwrap();
+
+    let expected = [RegexType::OpenParenthesis, RegexType::Char('a'), RegexType::Char('b'), RegexType::Char('\x03'), RegexType::CloseParenthesis];
+    assert_eq!(result, expected.into_iter().collect::<VecDeque<_>>());
+
+    // ranges between escapes
+    let result = Regex::tokens("[\\x30-\\71\\x5F]").unwrap();
+    let class = result[0].class().unwrap();
+
+    assert_eq!(class.len(), 11);
+    assert!(class.contains(&'5'));
+    assert!(class.contains(&'_'));
+
+    let result = Regex::tokens("[^\\0-\\x1f]").unwrap();
+    let class = result[0].class().unwrap();
+
+    assert_eq!(class.len(), 128 - 32);
+    assert!(!class.contains(&'\x05'));
+    assert!(class.contains(&' '));
+}
+
+#[test]
+fn test_tokens_with_invalid_escapes() {
+    let message = |regex: &str| Regex::tokens(regex).unwrap_err().to_string();
+
+    assert!(message("a\\400").contains("invalid escape sequence `\\400`: out of range"));
+    assert!(message("\"\\777\"").contains("invalid escape sequence `\\777`"));
+    assert!(message("[\\xz]").contains("invalid escape sequence `\\x`: no hexadecimal digit after `\\x`"));
+    assert!(message("[a-\\x]").contains("invalid escape sequence `\\x`"));
+    assert!(message("\\c1").contains("invalid escape sequence `\\c1`"));
+    assert!(message("\\c").contains("invalid escape sequence `\\c`: no character after `\\c`"));
+}
+
 #[test]
 fn test_tokens_with_backslash_shorthand_classes() {
     let result = Regex::tokens("\\d\\w\\s").unwrap();
@@ -852,7 +1067,7 @@ fn test_add_backslash() {
     let mut tokens = VecDeque::new();
     let mut chars = "d".chars().peekable();
     
-    Regex::add_backslash(&mut tokens, &mut chars);
+    Regex::add_backslash(&mut tokens, &mut chars).unwrap();
 
     assert_eq!(tokens.len(), 1);
 
@@ -0,0 +1,132 @@
+use std::collections::VecDeque;
+
+use crate::regex::*;
+use crate::regex::dfa::normalizer::*;
+
+fn into_postfix(str: &str) -> VecDeque<TokenType> {
+	re2post(Regex::add_concatenation(Regex::tokens(str).unwrap())).unwrap()
+}
+
+fn length(str: &str) -> Option<usize> {
+	Regex::length(&into_postfix(str))
+}
+
+fn chars(str: &str) -> Vec<char> {
+	str.chars().collect()
+}
+
+/// DFA of a single `r/s` rule of id 1
+fn trailing_dfa(expr: &str, following: &str) -> NormalizedDfa {
+	let (nfa, trailing_context) = Regex::with_trailing_context(expr.to_string(), following.to_string(), 1).unwrap();
+
+	let mut dfa = Dfa::new(&[&nfa]);
+	dfa.trailing_contexts.insert(1, trailing_context);
+
+	NormalizedDfa::from(&mut dfa)
+}
+
+#[test]
+fn test_length_fixed() {
+	assert_eq!(length("a"), Some(1));
+	assert_eq!(length("abc"), Some(3));
+	assert_eq!(length("[a-z][0-9]"), Some(2));
+	assert_eq!(length("\"if\""), Some(2));
+	assert_eq!(length("a{3}"), Some(3));
+	assert_eq!(length("(ab){2,2}"), Some(4));
+	assert_eq!(length("ab|cd"), Some(2));
+	assert_eq!(length("^ab$"), Some(2));
+}
+
+#[test]
+fn test_length_variable() {
+	assert_eq!(length("a*"), None);
+	assert_eq!(length("a+"), None);
+	assert_eq!(length("a?"), None);
+	assert_eq!(length("a{1,3}"), None);
+	assert_eq!(length("a|bc"), None);
+	assert_eq!(length("x(ab|c)y"), None);
+}
+
+#[test]
+fn test_trailing_context_lengths() {
+	let (_, fixed_head) = Regex::with_trailing_context("ab".to_string(), "c*".to_string(), 1).unwrap();
+	assert_eq!(fixed_head.head_length, Some(2));
+	assert_eq!(fixed_head.tail_length, None);
+	assert!(!fixed_head.is_variable());
+
+	let (_, fixed_tail) = Regex::with_trailing_context("[a-z]+".to_string(), "\"!\"".to_string(), 1).unwrap();
+	assert_eq!(fixed_tail.head_length, None);
+	assert_eq!(fixed_tail.tail_length, Some(1));
+	assert!(!fixed_tail.is_variable());
+
+	let (_, variable) = Regex::with_trailing_context("x+".to_string(), "y+z".to_string(), 1).unwrap();
+	assert!(variable.is_variable());
+}
+
+#[test]
+fn test_trailing_context_anchors() {
+	// '^' and '$' are not part of the lengths
+	let (_, trailing_context) = Regex::with_trailing_context("^ab".to_string(), "c$".to_string(), 1).unwrap();
+
+	assert_eq!(trailing_context.head_length, Some(2));
+	assert_eq!(trailing_context.tail_length, Some(1));
+
+	// but misplaced anchors are still errors
+	assert!(Regex::with_trailing_context("a$".to_string(), "b".to_string(), 1).is_err());
+	assert!(Regex::with_trailing_context("a".to_string(), "^b".to_string(), 1).is_err());
+}
+
+#[test]
+fn test_trailing_context_empty_part() {
+	assert!(Regex::with_trailing_context("^".to_string(), "a".to_string(), 1).is_err());
+	assert!(Regex::with_trailing_context("a".to_string(), "$".to_string(), 1).is_err());
+}
+
+#[test]
+fn test_head_length_in() {
+	let (_, fixed_head) = Regex::with_trailing_context("ab".to_string(), "c*".to_string(), 1).unwrap();
+	assert_eq!(fixed_head.head_length_in(&chars("abccc")), 2);
+
+	let (_, fixed_tail) = Regex::with_trailing_context("[a-z]+".to_string(), "[0-9]".to_string(), 1).unwrap();
+	assert_eq!(fixed_tail.head_length_in(&chars("hello1")), 5);
+
+	let (_, variable) = Regex::with_trailing_context("x+".to_string(), "x*y".to_string(), 1).unwrap();
+	// the longest head followed by a valid tail
+	assert_eq!(variable.head_length_in(&chars("xxxy")), 3);
+
+	let (_, variable) = Regex::with_trailing_context("(ab)+".to_string(), "(ab)*c".to_string(), 1).unwrap();
+	assert_eq!(variable.head_length_in(&chars("ababc")), 4);
+}
+
+#[test]
+fn test_simulate_fixed_head() {
+	let dfa = trailing_dfa("ab", "cd");
+
+	assert_eq!(simulate("abcd", &dfa).map(|match_| match_.length()), Some(2));
+	assert_eq!(simulate("abcdef", &dfa).map(|match_| match_.length()), Some(2));
+	assert!(simulate("abc", &dfa).is_none());
+}
+
+#[test]
+fn test_simulate_fixed_tail() {
+	let dfa = trailing_dfa("[a-z]+", "\";\"");
+
+	assert_eq!(simulate("hello;", &dfa).map(|match_| match_.length()), Some(5));
+	assert!(simulate("hello", &dfa).is_none());
+}
+
+#[test]
+fn test_simulate_variable() {
+	let dfa = trailing_dfa("a+", "a*b");
+
+	assert_eq!(simulate("aaab", &dfa).map(|match_| match_.length()), Some(3));
+	assert_eq!(simulate("ab", &dfa).map(|match_| match_.length()), Some(1));
+}
+
+#[test]
+fn test_simulate_trailing_context_end_of_line() {
+	let dfa = trailing_dfa("ab", "c$");
+
+	assert_eq!(simulate("abc\n", &dfa).map(|match_| match_.length()), Some(2));
+	assert!(simulate("abcd", &dfa).is_none());
+}
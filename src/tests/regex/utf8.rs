@@ -0,0 +1,198 @@
+use std::collections::VecDeque;
+
+use crate::regex::*;
+use crate::regex::dfa::normalizer::*;
+
+/// The bytes of `text`, a char per byte (the input of the automatons in UTF-8 mode)
+fn bytes_of(text: &str) -> String {
+	text.bytes().map(|byte| byte as char).collect()
+}
+
+/// Checks if the encoding of `c` is matched by one of the sequences
+fn matched_by(sequences: &[Vec<(u8, u8)>], c: char) -> bool {
+	let mut buffer = [0; 4];
+	let bytes = c.encode_utf8(&mut buffer).as_bytes();
+
+	sequences.iter().any(|sequence| {
+		sequence.len() == bytes.len()
+			&& sequence.iter().zip(bytes).all(|((start, end), byte)| (start..=end).contains(&byte))
+	})
+}
+
+/// Some scalar values of each encoding length, and around the bounds of the ranges
+fn samples(ranges: &[(u32, u32)]) -> Vec<char> {
+	let mut values: Vec<u32> = vec![0, 0x7f, 0x80, 0x7ff, 0x800, 0xd7ff, 0xe000, 0xffff, 0x10000, 0x10ffff];
+
+	for (start, end) in ranges {
+		values.extend([start.saturating_sub(1), *start, start + 1, end - 1, *end, end + 1]);
+	}
+
+	values.extend((0..0x110000).step_by(0x3f1));
+
+	values.into_iter().filter_map(char::from_u32).collect()
+}
+
+fn utf8_dfa(patterns: &[&str]) -> NormalizedDfa {
+	let nfas: Vec<Nfa> = patterns.iter()
+		.enumerate()
+		.map(|(index, pattern)| Regex::with_flags(pattern.to_string(), index + 1, RegexFlags { utf8: true, ..Default::default() }).unwrap())
+		.collect();
+
+	NormalizedDfa::from(&mut Dfa::new(&nfas.iter().collect::<Vec<_>>()))
+}
+
+/// (rule, length in chars) of the best match of `text`
+fn best(dfa: &NormalizedDfa, text: &str) -> Option<(usize, usize)> {
+	let match_ = simulate(&bytes_of(text), dfa)?;
+
+	let length = text.char_indices()
+		.map(|(index, c)| index + c.len_utf8())
+		.position(|end| end == match_.length())
+		.map_or(0, |index| index + 1);
+
+	Some((match_.id(), length))
+}
+
+// ==============================
+// Byte Sequences Tests
+// ==============================
+
+#[test]
+fn test_utf8_sequences_of_ascii() {
+	assert_eq!(utf8_sequences(&[(0x61, 0x7a)]), vec![vec![(0x61, 0x7a)]]);
+}
+
+#[test]
+fn test_utf8_sequences_of_a_2_bytes_range() {
+	// α-ω
+	assert_eq!(utf8_sequences(&[(0x3b1, 0x3c9)]), vec![
+		vec![(0xce, 0xce), (0xb1, 0xbf)],
+		vec![(0xcf, 0xcf), (0x80, 0x89)],
+	]);
+}
+
+#[test]
+fn test_utf8_sequences_of_all_the_scalar_values() {
+	let sequences = utf8_sequences(&[(0, 0xd7ff), (0xe000, 0x10ffff)]);
+
+	assert_eq!(sequences, vec![
+		vec![(0x00, 0x7f)],
+		vec![(0xc2, 0xdf), (0x80, 0xbf)],
+		vec![(0xe0, 0xe0), (0xa0, 0xbf), (0x80, 0xbf)],
+		vec![(0xe1, 0xec), (0x80, 0xbf), (0x80, 0xbf)],
+		vec![(0xed, 0xed), (0x80, 0x9f), (0x80, 0xbf)],
+		vec![(0xee, 0xef), (0x80, 0xbf), (0x80, 0xbf)],
+		vec![(0xf0, 0xf0), (0x90, 0xbf), (0x80, 0xbf), (0x80, 0xbf)],
+		vec![(0xf1, 0xf3), (0x80, 0xbf), (0x80, 0xbf), (0x80, 0xbf)],
+		vec![(0xf4, 0xf4), (0x80, 0x8f), (0x80, 0xbf), (0x80, 0xbf)],
+	]);
+}
+
+#[test]
+fn test_utf8_sequences_match_their_ranges() {
+	let cases: [&[(u32, u32)]; 4] = [
+		&[(0x41, 0x3b1)],
+		&[(0x7f, 0x800), (0x1000, 0x1fff)],
+		&[(0x3b1, 0x3c9), (0x4e00, 0x9fff), (0x1f600, 0x1f64f)],
+		&[(0xfffe, 0x10001), (0x10fff0, 0x10ffff)],
+	];
+
+	for ranges in cases {
+		let sequences = utf8_sequences(ranges);
+
+		for c in samples(ranges) {
+			let expected = ranges.iter().any(|(start, end)| (*start..=*end).contains(&(c as u32)));
+
+			assert_eq!(matched_by(&sequences, c), expected, "{c:?} ({:#x}) in {ranges:x?}", c as u32);
+		}
+	}
+}
+
+// ==============================
+// Lowering Tests
+// ==============================
+
+#[test]
+fn test_lower_utf8_keeps_ascii() {
+	let tokens = Regex::tokens("[a-z]+\"if\"x").unwrap();
+
+	assert_eq!(Regex::lower_utf8(tokens.clone()), tokens);
+}
+
+#[test]
+fn test_lower_utf8_chars() {
+	let tokens = Regex::tokens_with_flags("é+", RegexFlags { utf8: true, ..Default::default() }).unwrap();
+
+	assert_eq!(tokens, VecDeque::from([
+		RegexType::OpenParenthesis,
+		RegexType::Char('\u{c3}'),
+		RegexType::Char('\u{a9}'),
+		RegexType::CloseParenthesis,
+		RegexType::Quant(Quantifier::AtLeast(1)),
+	]));
+}
+
+#[test]
+fn test_lower_utf8_classes() {
+	let tokens = Regex::tokens_with_flags("[a€]", RegexFlags { utf8: true, ..Default::default() }).unwrap();
+
+	// (a|€)
+	assert_eq!(tokens.len(), 7);
+	assert_eq!(tokens[0], RegexType::OpenParenthesis);
+	assert_eq!(tokens[1], RegexType::CharacterClass(CharacterClass::from_single('a')));
+	assert_eq!(tokens[2], RegexType::Or);
+	assert_eq!(tokens[3], RegexType::Char('\u{e2}'));
+	assert_eq!(tokens[6], RegexType::CloseParenthesis);
+}
+
+// ==============================
+// Matching Tests
+// ==============================
+
+#[test]
+fn test_utf8_literals() {
+	let dfa = utf8_dfa(&["café", "caf[eé]s?"]);
+
+	assert_eq!(best(&dfa, "café"), Some((1, 4)));
+	assert_eq!(best(&dfa, "cafés"), Some((2, 5)));
+	assert_eq!(best(&dfa, "cafe"), Some((2, 4)));
+	assert_eq!(best(&dfa, "cafè"), None);
+}
+
+#[test]
+fn test_utf8_multibyte_identifiers() {
+	let dfa = utf8_dfa(&["[α-ωΑ-Ω_][α-ωΑ-Ω0-9_]*", "[a-z]+", "[一-鿿]+", "[😀-🙏]"]);
+
+	assert_eq!(best(&dfa, "λx"), Some((1, 1)));
+	assert_eq!(best(&dfa, "αβγ_1 δ"), Some((1, 5)));
+	assert_eq!(best(&dfa, "Ωmega"), Some((1, 1)));
+	assert_eq!(best(&dfa, "abc"), Some((2, 3)));
+	assert_eq!(best(&dfa, "漢字テスト"), Some((3, 2)));
+	assert_eq!(best(&dfa, "😎😀"), Some((4, 1)));
+	assert_eq!(best(&dfa, "ß"), None);
+}
+
+#[test]
+fn test_utf8_negated_unicode_ranges() {
+	let dfa = utf8_dfa(&["[^α-ω]+", "[α-ω]"]);
+
+	assert_eq!(best(&dfa, "abc€😀α"), Some((1, 5)));
+	assert_eq!(best(&dfa, "ω"), Some((2, 1)));
+
+	// the chars around the range
+	assert_eq!(best(&dfa, "ΰ"), Some((1, 1)));
+	assert_eq!(best(&dfa, "ϊ"), Some((1, 1)));
+	assert_eq!(best(&dfa, "\u{10ffff}\u{ffff}\u{7f}\n"), Some((1, 4)));
+}
+
+#[test]
+fn test_utf8_dot_matches_a_whole_code_point() {
+	let dfa = utf8_dfa(&[".", "..", "\\n"]);
+
+	assert_eq!(best(&dfa, "é"), Some((1, 1)));
+	assert_eq!(best(&dfa, "€😀"), Some((2, 2)));
+	assert_eq!(best(&dfa, "\n"), Some((3, 1)));
+
+	// a byte alone is not a code point
+	assert_eq!(simulate("\u{c3}", &dfa).map(|match_| match_.id()), None);
+}
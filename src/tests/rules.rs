@@ -3,7 +3,7 @@ use crate::parsing::definitions::{
 };
 use crate::parsing::error::ParsingResult;
 use crate::parsing::reader::Reader;
-use crate::parsing::{RuleAction, Rules, DEFAULT_STATE};
+use crate::parsing::{EndOfFileRule, RuleAction, Rules, DEFAULT_STATE};
 use crate::parsing::LineType;
 use std::collections::HashMap;
 use std::io::Cursor;
@@ -386,6 +386,33 @@ fn test_get_regular_expression_with_quantifiers() {
     assert_eq!(result.1, None);
 }
 
+#[test]
+fn test_get_regular_expression_with_interval() {
+    let mut reader = reader_from_str("a{2,3}(b|c){4} ");
+
+    let result = Rules::get_regular_expression(&HashMap::new(), &mut reader).unwrap();
+
+    assert_eq!(result.0, "a{2,3}(b|c){4}");
+    assert_eq!(result.1, None);
+}
+
+#[test]
+fn test_get_regular_expression_with_posix_classes() {
+    let mut reader = reader_from_str("[[:alpha:]_][[:alnum:]_]*[[:space:]] ");
+
+    let result = Rules::get_regular_expression(&HashMap::new(), &mut reader).unwrap();
+
+    assert_eq!(result.0, "[[:alpha:]_][[:alnum:]_]*[[:space:]]");
+    assert_eq!(result.1, None);
+
+    // `]` and spaces inside the elements
+    let mut reader = reader_from_str("[[.].][= =]a] ");
+
+    let result = Rules::get_regular_expression(&HashMap::new(), &mut reader).unwrap();
+
+    assert_eq!(result.0, "[[.].][= =]a]");
+}
+
 #[test]
 fn test_get_regular_expression_with_groups() {
     let mut reader = reader_from_str("(ab|cd)+ ");
@@ -470,6 +497,16 @@ fn test_get_regular_expression_with_section() {
     assert_eq!(result.1, Some("second".to_string()));
 }
 
+#[test]
+fn test_get_regular_expression_with_quoted_slash() {
+    let mut reader = reader_from_str("\"*/\"/[/] ");
+
+    let result = Rules::get_regular_expression(&HashMap::new(), &mut reader).unwrap();
+
+    assert_eq!(result.0, "\"*/\"");
+    assert_eq!(result.1, Some("[/]".to_string()));
+}
+
 #[test]
 fn test_get_regular_expression_with_duplicate_slash() {
     let mut reader = reader_from_str("first/second/");
@@ -641,6 +678,24 @@ fn test_line_type_with_default_condition() {
     assert!(matches!(result.unwrap(), LineType::Rule(_)));
 }
 
+#[test]
+fn test_line_type_rule_position() {
+    let mut definitions = Definitions::default();
+    definitions.substitutes.insert("D".to_string(), "[0-9]".to_string());
+
+    let mut reader = reader_from_str("\n{D}+/x {\n\tnumber();\n}\n");
+
+    assert!(matches!(Rules::line_type(&mut reader, &definitions).unwrap(), LineType::Empty));
+
+    let LineType::Rule(rule) = Rules::line_type(&mut reader, &definitions).unwrap() else {
+        panic!("expected a rule");
+    };
+
+    assert_eq!(rule.pattern, "([0-9])+/x");
+    assert_eq!(rule.file, "<test>");
+    assert_eq!(rule.line_index, 1);
+}
+
 #[test]
 fn test_line_type_with_custom_condition() {
     // Mock a definitions object with states
@@ -719,6 +774,58 @@ fn test_line_type_with_following_regex() {
     assert!(matches!(result.unwrap(), LineType::Rule(_)));
 }
 
+#[test]
+fn test_line_type_end_of_file_rule() {
+	let mut definitions = Definitions::default();
+	definitions.states.insert(DEFAULT_STATE.to_string(), StateType::Inclusive);
+
+	let mut reader = reader_from_str("<<EOF>>\treturn 0;\n");
+
+	let LineType::EndOfFile(rule) = Rules::line_type(&mut reader, &definitions).unwrap() else {
+		panic!("expected an <<EOF>> rule");
+	};
+
+	assert!(!rule.custom_conditions);
+	assert!(matches!(rule.action, RuleAction::Statement(action) if action.trim() == "return 0;"));
+}
+
+#[test]
+fn test_line_type_end_of_file_rule_with_condition() {
+	let mut definitions = Definitions::default();
+	definitions.states.insert("STR".to_string(), StateType::Exclusive);
+
+	let mut reader = reader_from_str("<STR><<EOF>>\t|\n");
+
+	let LineType::EndOfFile(rule) = Rules::line_type(&mut reader, &definitions).unwrap() else {
+		panic!("expected an <<EOF>> rule");
+	};
+
+	assert!(rule.custom_conditions);
+	assert_eq!(rule.start_conditions, vec!["STR".to_string()]);
+	assert!(matches!(rule.action, RuleAction::Or));
+}
+
+#[test]
+fn test_line_type_end_of_file_rule_with_undeclared_condition() {
+	let definitions = Definitions::default();
+
+	let mut reader = reader_from_str("<STR><<EOF>>\treturn 0;\n");
+
+	let err = Rules::line_type(&mut reader, &definitions).unwrap_err();
+
+	assert!(err.message().contains("undeclared start condition: `STR`"));
+}
+
+#[test]
+fn test_line_type_regex_starting_with_chevrons() {
+	let mut definitions = Definitions::default();
+	definitions.states.insert(DEFAULT_STATE.to_string(), StateType::Inclusive);
+
+	let mut reader = reader_from_str("\"<<\"\treturn 0;\n");
+
+	assert!(matches!(Rules::line_type(&mut reader, &definitions).unwrap(), LineType::Rule(_)));
+}
+
 #[test]
 fn test_line_type_with_whitespace() {
     let mut reader = reader_from_str("   \t  \n");
@@ -736,7 +843,7 @@ fn test_parse_rules_empty() {
     let definitions = Definitions::default();
     
 	let mut result = vec![];
-    let _ = Rules::parse_rules(&mut result, &mut reader, &definitions).unwrap();
+    let _ = Rules::parse_rules(&mut result, &mut vec![], &mut reader, &definitions).unwrap();
     
     assert_eq!(result.len(), 0);
 }
@@ -749,7 +856,7 @@ fn test_parse_rules_single_rule() {
     let mut reader = reader_from_str("[a-z]+ {action1;}\n%%");
     
 	let mut result = vec![];
-    let _ = Rules::parse_rules(&mut result, &mut reader, &definitions).unwrap();
+    let _ = Rules::parse_rules(&mut result, &mut vec![], &mut reader, &definitions).unwrap();
     
     assert_eq!(result.len(), 1);
 }
@@ -764,7 +871,7 @@ fn test_parse_rules_multiple_rules() {
     );
     
 	let mut result = vec![];
-    let _ = Rules::parse_rules(&mut result, &mut reader, &definitions).unwrap();
+    let _ = Rules::parse_rules(&mut result, &mut vec![], &mut reader, &definitions).unwrap();
     
     assert_eq!(result.len(), 3);
 }
@@ -779,7 +886,7 @@ fn test_parse_rules_with_empty_lines() {
     );
 
 	let mut result = vec![];
-    let _ = Rules::parse_rules(&mut result, &mut reader, &definitions).unwrap();
+    let _ = Rules::parse_rules(&mut result, &mut vec![], &mut reader, &definitions).unwrap();
     
     assert_eq!(result.len(), 3);
 }
@@ -796,7 +903,7 @@ fn test_parse_rules_with_different_start_conditions() {
     );
     
 	let mut result = vec![];
-    let _ = Rules::parse_rules(&mut result, &mut reader, &definitions).unwrap();
+    let _ = Rules::parse_rules(&mut result, &mut vec![], &mut reader, &definitions).unwrap();
     
     assert_eq!(result.len(), 3);
     
@@ -813,6 +920,127 @@ fn test_parse_rules_with_different_start_conditions() {
     assert_eq!(result[2].start_conditions[0], "STATE2");
 }
 
+#[test]
+fn test_parse_rules_end_of_file_rules() {
+	let mut definitions = Definitions::default();
+	definitions.states.insert(DEFAULT_STATE.to_string(), StateType::Inclusive);
+	definitions.states.insert("STR".to_string(), StateType::Exclusive);
+
+	let mut reader = reader_from_str("a\t{action1;}\n<STR><<EOF>>\t{action2;}\n<<EOF>>\t{action3;}\n%%");
+
+	let mut result = vec![];
+	let mut end_of_file_rules = vec![];
+	let _ = Rules::parse_rules(&mut result, &mut end_of_file_rules, &mut reader, &definitions).unwrap();
+
+	// <<EOF>> rules have no rule id
+	assert_eq!(result.len(), 1);
+	assert_eq!(end_of_file_rules.len(), 2);
+}
+
+#[test]
+fn test_parse_rules_duplicate_end_of_file_rules() {
+	let mut definitions = Definitions::default();
+	definitions.states.insert(DEFAULT_STATE.to_string(), StateType::Inclusive);
+	definitions.states.insert("STR".to_string(), StateType::Exclusive);
+
+	let mut reader = reader_from_str("<STR><<EOF>>\t{action1;}\n<STR,INITIAL><<EOF>>\t{action2;}\n%%");
+
+	let mut result = vec![];
+	let mut end_of_file_rules = vec![];
+	let err = Rules::parse_rules(&mut result, &mut end_of_file_rules, &mut reader, &definitions).unwrap_err();
+
+	assert!(err.is_warning());
+	assert!(err.message().contains("multiple <<EOF>> rules for the same start condition"));
+
+	// the rule is kept anyway
+	assert_eq!(end_of_file_rules.len(), 2);
+}
+
+#[test]
+fn test_end_of_file_rules_by_condition() {
+	let mut definitions = Definitions::default();
+	definitions.states.insert(DEFAULT_STATE.to_string(), StateType::Inclusive);
+	definitions.states.insert("CMT".to_string(), StateType::Exclusive);
+	definitions.states.insert("STR".to_string(), StateType::Exclusive);
+
+	let mut reader = reader_from_str("<<EOF>>\t{action1;}\n<STR><<EOF>>\t{action2;}\n%%");
+
+	let mut end_of_file_rules = vec![];
+	let _ = Rules::parse_rules(&mut vec![], &mut end_of_file_rules, &mut reader, &definitions).unwrap();
+
+	// INITIAL, CMT, STR: the unprefixed rule covers the exclusive conditions too
+	assert_eq!(EndOfFileRule::by_condition(&end_of_file_rules, &definitions), vec![Some(0), Some(0), Some(1)]);
+}
+
+#[test]
+fn test_end_of_file_rules_by_condition_without_default() {
+	let mut definitions = Definitions::default();
+	definitions.states.insert(DEFAULT_STATE.to_string(), StateType::Inclusive);
+	definitions.states.insert("STR".to_string(), StateType::Exclusive);
+
+	let mut reader = reader_from_str("<STR><<EOF>>\t{action1;}\n%%");
+
+	let mut end_of_file_rules = vec![];
+	let _ = Rules::parse_rules(&mut vec![], &mut end_of_file_rules, &mut reader, &definitions).unwrap();
+
+	assert_eq!(EndOfFileRule::by_condition(&end_of_file_rules, &definitions), vec![None, Some(0)]);
+}
+
+#[test]
+fn test_action_rejects() {
+	assert!(RuleAction::Statement("{ count++; REJECT; }".to_string()).rejects());
+	assert!(RuleAction::Statement("REJECT!();".to_string()).rejects());
+
+	assert!(!RuleAction::Statement("NOT_REJECT;".to_string()).rejects());
+	assert!(!RuleAction::Statement("REJECTED = 1;".to_string()).rejects());
+	assert!(!RuleAction::Or.rejects());
+}
+
+#[test]
+fn test_use_reject() {
+	let mut definitions = Definitions::default();
+	definitions.states.insert(DEFAULT_STATE.to_string(), StateType::Inclusive);
+
+	let mut reader = reader_from_str("a\t{action1;}\nb\t|\nc\tREJECT;\n%%");
+
+	let mut result = vec![];
+	let _ = Rules::parse_rules(&mut result, &mut vec![], &mut reader, &definitions).unwrap();
+
+	assert!(Rules::use_reject(&result));
+	assert!(!Rules::use_reject(&result[..2]));
+}
+
+#[test]
+fn test_rules_active_in_start_conditions() {
+    let mut definitions = Definitions::default();
+    definitions.states.insert(DEFAULT_STATE.to_string(), StateType::Inclusive);
+    definitions.states.insert("INC".to_string(), StateType::Inclusive);
+    definitions.states.insert("EXC".to_string(), StateType::Exclusive);
+
+    let mut reader = reader_from_str(
+        "a {action1;}\n<EXC>b {action2;}\n<INITIAL>c {action3;}\n%%"
+    );
+
+	let mut result = vec![];
+    let _ = Rules::parse_rules(&mut result, &mut vec![], &mut reader, &definitions).unwrap();
+
+    assert_eq!(result.len(), 3);
+
+    // Without prefix: all the inclusive conditions
+    assert!(!result[0].custom_conditions);
+    assert!(result[0].is_active_in(DEFAULT_STATE, StateType::Inclusive));
+    assert!(result[0].is_active_in("INC", StateType::Inclusive));
+    assert!(!result[0].is_active_in("EXC", StateType::Exclusive));
+
+    // With a prefix: only the listed conditions
+    assert!(result[1].custom_conditions);
+    assert!(result[1].is_active_in("EXC", StateType::Exclusive));
+    assert!(!result[1].is_active_in(DEFAULT_STATE, StateType::Inclusive));
+
+    assert!(result[2].is_active_in(DEFAULT_STATE, StateType::Inclusive));
+    assert!(!result[2].is_active_in("INC", StateType::Inclusive));
+}
+
 #[test]
 fn test_parse_rules_with_multiple_start_conditions() {
     let mut definitions = Definitions::default();
@@ -825,7 +1053,7 @@ fn test_parse_rules_with_multiple_start_conditions() {
     );
     
 	let mut result = vec![];
-    let _ = Rules::parse_rules(&mut result, &mut reader, &definitions).unwrap();
+    let _ = Rules::parse_rules(&mut result, &mut vec![], &mut reader, &definitions).unwrap();
     
     assert_eq!(result.len(), 1);
     assert_eq!(result[0].start_conditions.len(), 2);
@@ -843,7 +1071,7 @@ fn test_parse_rules_with_or_actions() {
     );
     
 	let mut result = vec![];
-    let _ = Rules::parse_rules(&mut result, &mut reader, &definitions).unwrap();
+    let _ = Rules::parse_rules(&mut result, &mut vec![], &mut reader, &definitions).unwrap();
     
     assert_eq!(result.len(), 3);
     
@@ -865,10 +1093,32 @@ fn test_parse_rules_with_following_regex() {
     );
     
 	let mut result = vec![];
-	let _ = Rules::parse_rules(&mut result, &mut reader, &definitions).unwrap();
+	let _ = Rules::parse_rules(&mut result, &mut vec![], &mut reader, &definitions).unwrap();
     
     assert_eq!(result.len(), 1);
-    assert!(result[0].following_regex_nfa.is_some());
+    assert!(result[0].trailing_context.is_some());
+}
+
+#[test]
+fn test_parse_rules_with_dangerous_trailing_context() {
+    let mut definitions = Definitions::default();
+    definitions.states.insert(DEFAULT_STATE.to_string(), StateType::Inclusive);
+
+    let mut reader = reader_from_str(
+        "a+/b* {action1;}\nc {action2;}\n%%"
+    );
+
+	let mut result = vec![];
+	let err = Rules::parse_rules(&mut result, &mut vec![], &mut reader, &definitions).unwrap_err();
+
+    // the rule is kept, with a warning
+    assert!(err.is_warning());
+    assert!(err.message().contains("dangerous trailing context"));
+    assert_eq!(result.len(), 1);
+
+    // and the parsing goes on
+    let _ = Rules::parse_rules(&mut result, &mut vec![], &mut reader, &definitions).unwrap();
+    assert_eq!(result.len(), 2);
 }
 
 #[test]
@@ -884,7 +1134,7 @@ fn test_parse_rules_with_error() {
     );
 
 	let mut tmp = vec![];
-	let result = Rules::parse_rules(&mut tmp, &mut reader, &definitions);
+	let result = Rules::parse_rules(&mut tmp, &mut vec![], &mut reader, &definitions);
 
     assert!(result.is_err());
 	let err = result.unwrap_err();